@@ -0,0 +1,26 @@
+use std::process::Command;
+
+// Exposes the git commit being built as the GIT_COMMIT environment variable.
+//
+// A GIT_COMMIT set in the build environment wins, for builds from a source
+// archive without the .git directory. Otherwise the commit is read from git,
+// and left unset when git is unavailable.
+fn main() {
+    println!("cargo:rerun-if-env-changed=GIT_COMMIT");
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+
+    let commit = std::env::var("GIT_COMMIT").ok().or_else(|| {
+        let output = Command::new("git")
+            .args(["rev-parse", "--short", "HEAD"])
+            .output()
+            .ok()?;
+        output
+            .status
+            .success()
+            .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+    });
+    if let Some(commit) = commit.filter(|commit| !commit.is_empty()) {
+        println!("cargo:rustc-env=GIT_COMMIT={}", commit);
+    }
+}
@@ -0,0 +1,325 @@
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, Request, State},
+    middleware::Next,
+    response::Response,
+};
+use chrono::{DateTime, Utc};
+use futures_util::StreamExt;
+use serde_json::{json, Map, Value};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{error, warn};
+
+use crate::config::{AccessLogConfig, AccessLogFormat, AccessLogOutput, ConfigError};
+use crate::metrics::Stage;
+use crate::request_id::RequestId;
+
+// Lines buffered for the writer thread before new ones are dropped.
+const QUEUE_CAPACITY: usize = 4096;
+
+#[derive(Debug, Default)]
+struct AccessFields {
+    user: Option<String>,
+    model: Option<String>,
+    verdicts: Vec<(String, String)>,
+    stages: Vec<(Stage, Duration)>,
+}
+
+// Details of a request gathered by the handlers for its access log line.
+//
+// Inserted into the request extensions by `log_access` and reached through the
+// `ScanContext`. Cheap to clone; all clones share the same fields.
+#[derive(Debug, Clone, Default)]
+pub struct AccessRecord {
+    fields: Arc<Mutex<AccessFields>>,
+}
+
+impl AccessRecord {
+    pub fn set_user(&self, user: &str) {
+        self.fields.lock().unwrap().user = Some(user.to_string());
+    }
+
+    pub fn set_model(&self, model: &str) {
+        self.fields.lock().unwrap().model = Some(model.to_string());
+    }
+
+    // Adds the category and action of one scan.
+    pub fn add_verdict(&self, category: &str, action: &str) {
+        self.fields
+            .lock()
+            .unwrap()
+            .verdicts
+            .push((category.to_string(), action.to_string()));
+    }
+
+    pub fn add_stage(&self, stage: Stage, duration: Duration) {
+        self.fields.lock().unwrap().stages.push((stage, duration));
+    }
+}
+
+// Writes one line per request to stdout or a size-rotated file.
+//
+// Lines are handed to a dedicated writer thread so requests never wait on
+// disk I/O; if it falls behind, lines are dropped with a warning.
+#[derive(Clone)]
+pub struct AccessLogger {
+    format: AccessLogFormat,
+    sender: SyncSender<String>,
+}
+
+impl AccessLogger {
+    // Starts the writer thread for the configured output.
+    //
+    // # Returns
+    //
+    // * `Ok(AccessLogger)` - The logger, ready to be used by `log_access`
+    // * `Err(ConfigError)` - If the log file cannot be opened
+    pub fn new(config: &AccessLogConfig) -> Result<Self, ConfigError> {
+        let mut sink = match config.output {
+            AccessLogOutput::Stdout => Sink::Stdout,
+            AccessLogOutput::File => Sink::File(RotatingFile::open(
+                PathBuf::from(config.path.clone().unwrap_or_default()),
+                config.max_bytes,
+                config.max_files,
+            )?),
+        };
+
+        let (sender, receiver) = mpsc::sync_channel::<String>(QUEUE_CAPACITY);
+        std::thread::Builder::new()
+            .name("access-log".into())
+            .spawn(move || {
+                for line in receiver {
+                    if let Err(e) = sink.write_line(&line) {
+                        error!("Failed to write access log: {}", e);
+                    }
+                }
+            })
+            .map_err(ConfigError::IoError)?;
+
+        Ok(Self {
+            format: config.format,
+            sender,
+        })
+    }
+
+    fn log(&self, line: String) {
+        if let Err(TrySendError::Full(_)) = self.sender.try_send(line) {
+            warn!("Access log queue is full, dropping a line");
+        }
+    }
+}
+
+enum Sink {
+    Stdout,
+    File(RotatingFile),
+}
+
+impl Sink {
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        match self {
+            Sink::Stdout => writeln!(io::stdout().lock(), "{}", line),
+            Sink::File(file) => file.write_line(line),
+        }
+    }
+}
+
+// Log file renamed to `<path>.1`, `<path>.2`, ... once it exceeds `max_bytes`.
+struct RotatingFile {
+    path: PathBuf,
+    max_bytes: u64,
+    max_files: usize,
+    file: File,
+    written: u64,
+}
+
+impl RotatingFile {
+    fn open(path: PathBuf, max_bytes: u64, max_files: usize) -> Result<Self, ConfigError> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let written = file.metadata()?.len();
+        Ok(Self {
+            path,
+            max_bytes,
+            max_files,
+            file,
+            written,
+        })
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        if self.written > 0 && self.written + line.len() as u64 + 1 > self.max_bytes {
+            self.rotate()?;
+        }
+        writeln!(self.file, "{}", line)?;
+        self.written += line.len() as u64 + 1;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        let numbered = |n: usize| PathBuf::from(format!("{}.{}", self.path.display(), n));
+        if self.max_files == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            for n in (1..self.max_files).rev() {
+                if numbered(n).exists() {
+                    fs::rename(numbered(n), numbered(n + 1))?;
+                }
+            }
+            fs::rename(&self.path, numbered(1))?;
+        }
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.written = 0;
+        Ok(())
+    }
+}
+
+// Middleware writing one access log line per request.
+//
+// Handlers add the model, the caller, scan verdicts and stage durations to the
+// `AccessRecord` placed in the request extensions. The line is written once the
+// response body has been sent or dropped, so streamed responses are logged with
+// their full duration, size and the verdicts of their chunks.
+pub async fn log_access(
+    State(logger): State<AccessLogger>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let started = Instant::now();
+    let time = Utc::now();
+    let method = request.method().to_string();
+    let path = request.uri().path().to_string();
+    let version = format!("{:?}", request.version());
+    let client = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|info| info.0.ip().to_string());
+    let request_id = request
+        .extensions()
+        .get::<RequestId>()
+        .map(|id| id.0.clone());
+    let record = AccessRecord::default();
+    request.extensions_mut().insert(record.clone());
+
+    let response = next.run(request).await;
+
+    let mut pending = PendingEntry {
+        logger,
+        record,
+        started,
+        entry: Entry {
+            time,
+            client,
+            request_id,
+            method,
+            path,
+            version,
+            status: response.status().as_u16(),
+            bytes: 0,
+            duration: Duration::ZERO,
+            fields: AccessFields::default(),
+        },
+    };
+    let (parts, body) = response.into_parts();
+    let body = body.into_data_stream().map(move |chunk| {
+        if let Ok(chunk) = &chunk {
+            pending.add_bytes(chunk.len());
+        }
+        chunk
+    });
+    Response::from_parts(parts, Body::from_stream(body))
+}
+
+// An access log line written when the response body is dropped, after its
+// last chunk or when the client goes away.
+struct PendingEntry {
+    logger: AccessLogger,
+    record: AccessRecord,
+    started: Instant,
+    entry: Entry,
+}
+
+impl PendingEntry {
+    fn add_bytes(&mut self, bytes: usize) {
+        self.entry.bytes += bytes as u64;
+    }
+}
+
+impl Drop for PendingEntry {
+    fn drop(&mut self) {
+        self.entry.duration = self.started.elapsed();
+        self.entry.fields = std::mem::take(&mut *self.record.fields.lock().unwrap());
+        self.logger.log(match self.logger.format {
+            AccessLogFormat::Json => self.entry.to_json(),
+            AccessLogFormat::Common => self.entry.to_common(),
+        });
+    }
+}
+
+struct Entry {
+    time: DateTime<Utc>,
+    client: Option<String>,
+    request_id: Option<String>,
+    method: String,
+    path: String,
+    version: String,
+    status: u16,
+    bytes: u64,
+    duration: Duration,
+    fields: AccessFields,
+}
+
+impl Entry {
+    fn to_json(&self) -> String {
+        let verdicts: Vec<Value> = self
+            .fields
+            .verdicts
+            .iter()
+            .map(|(category, action)| json!({ "category": category, "action": action }))
+            .collect();
+        let stages: Map<String, Value> = self
+            .fields
+            .stages
+            .iter()
+            .map(|(stage, duration)| (stage.as_str().to_string(), json!(duration.as_millis())))
+            .collect();
+        json!({
+            "time": self.time.to_rfc3339(),
+            "request_id": self.request_id,
+            "client": self.client,
+            "method": self.method,
+            "path": self.path,
+            "status": self.status,
+            "bytes": self.bytes,
+            "duration_ms": self.duration.as_millis(),
+            "user": self.fields.user,
+            "model": self.fields.model,
+            "verdicts": verdicts,
+            "stages_ms": stages,
+        })
+        .to_string()
+    }
+
+    // Common Log Format: `host ident user [time] "request" status bytes`.
+    fn to_common(&self) -> String {
+        let or_dash = |value: Option<&str>| value.unwrap_or("-").to_string();
+        format!(
+            "{} - {} [{}] \"{} {} {}\" {} {}",
+            or_dash(self.client.as_deref()),
+            or_dash(self.fields.user.as_deref()),
+            self.time.format("%d/%b/%Y:%H:%M:%S %z"),
+            self.method,
+            self.path,
+            self.version,
+            self.status,
+            self.bytes,
+        )
+    }
+}
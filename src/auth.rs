@@ -0,0 +1,376 @@
+use crate::config::{AdminConfig, ConfigError, JwtConfig, TenantConfig};
+use crate::handlers::ApiError;
+use crate::http;
+use crate::policy::ModelPolicy;
+use crate::rate_limit::RateLimiter;
+use axum::{
+    extract::{Request, State},
+    http::header::AUTHORIZATION,
+    middleware::Next,
+    response::Response,
+};
+use jsonwebtoken::{decode, decode_header, jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::sync::{Mutex, RwLock};
+use tracing::{debug, warn};
+
+// Represents errors that can occur while authenticating incoming requests.
+#[derive(Debug, Error)]
+pub enum AuthError {
+    #[error("Missing bearer token")]
+    MissingToken,
+
+    #[error("Invalid token: {0}")]
+    InvalidToken(#[from] jsonwebtoken::errors::Error),
+
+    #[error("Unsupported token algorithm: {0:?}")]
+    UnsupportedAlgorithm(Algorithm),
+
+    #[error("Unknown signing key: {0}")]
+    UnknownKey(String),
+
+    #[error("Failed to fetch JWKS: {0}")]
+    JwksError(#[from] reqwest::Error),
+
+    #[error("Invalid API key")]
+    InvalidApiKey,
+}
+
+// Identity of the caller extracted from a validated JWT.
+//
+// Inserted into the request extensions by `require_jwt` so handlers can use it
+// for per-user policy decisions and PANW attribution.
+//
+// # Fields
+//
+// * `subject` - The `sub` claim of the token, forwarded to PANW as `app_user`
+// * `claims` - All remaining claims of the token
+#[derive(Debug, Clone)]
+pub struct AuthenticatedUser {
+    pub subject: String,
+    pub claims: Map<String, Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Claims {
+    sub: String,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+// Shortest time between two JWKS fetches triggered by unknown `kid`s.
+const JWKS_MIN_REFETCH_INTERVAL: Duration = Duration::from_secs(30);
+
+// Validates bearer tokens against the issuer's JWKS.
+//
+// The key set is fetched lazily and cached for `jwks_refresh_secs`. An unknown
+// `kid` forces a refresh so key rotation on the IdP side is picked up immediately,
+// at most once per `JWKS_MIN_REFETCH_INTERVAL` so forged `kid`s cannot flood the
+// IdP. Concurrent refreshes are coalesced into a single fetch.
+#[derive(Clone)]
+pub struct JwtValidator {
+    client: Client,
+    config: JwtConfig,
+    jwks: Arc<RwLock<Option<(JwkSet, Instant)>>>,
+    // Time of the last fetch attempt; held while fetching
+    last_fetch: Arc<Mutex<Option<Instant>>>,
+}
+
+impl JwtValidator {
+    // Creates a validator fetching the JWKS with the configured timeouts and TLS settings.
+    //
+    // # Returns
+    //
+    // * `Ok(JwtValidator)` - The validator; no key set is fetched yet
+    // * `Err(ConfigError)` - If a certificate or key cannot be read or parsed
+    pub fn new(config: &JwtConfig) -> Result<Self, ConfigError> {
+        let client = http::build_client(
+            Duration::from_secs(config.connect_timeout_secs),
+            Some(Duration::from_secs(config.timeout_secs)),
+            &config.tls,
+            &config.connection,
+        )?;
+        Ok(Self {
+            client,
+            config: config.clone(),
+            jwks: Arc::new(RwLock::new(None)),
+            last_fetch: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    async fn fetch_jwks(&self) -> Result<JwkSet, AuthError> {
+        debug!("Fetching JWKS from {}", self.config.jwks_url);
+        let jwks = self
+            .client
+            .get(&self.config.jwks_url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<JwkSet>()
+            .await?;
+        Ok(jwks)
+    }
+
+    // Looks up the decoding key for `kid`, refreshing the cached JWKS when it is
+    // stale or does not contain the requested key.
+    async fn decoding_key(&self, kid: &str) -> Result<DecodingKey, AuthError> {
+        if let Some(key) = self.cached_key(kid, true).await? {
+            return Ok(key);
+        }
+
+        // Callers waiting here reuse the key set fetched by the first one
+        let mut last_fetch = self.last_fetch.lock().await;
+        if let Some(key) = self.cached_key(kid, true).await? {
+            return Ok(key);
+        }
+        if last_fetch.is_some_and(|at| at.elapsed() < JWKS_MIN_REFETCH_INTERVAL) {
+            debug!("JWKS fetched recently, not refetching for kid {}", kid);
+            return self
+                .cached_key(kid, false)
+                .await?
+                .ok_or_else(|| AuthError::UnknownKey(kid.to_string()));
+        }
+        *last_fetch = Some(Instant::now());
+
+        let jwks = self.fetch_jwks().await?;
+        let key = jwks.find(kid).map(DecodingKey::from_jwk).transpose()?;
+        *self.jwks.write().await = Some((jwks, Instant::now()));
+        key.ok_or_else(|| AuthError::UnknownKey(kid.to_string()))
+    }
+
+    // Looks up `kid` in the cached JWKS, ignoring a stale set when `fresh_only` is set.
+    async fn cached_key(
+        &self,
+        kid: &str,
+        fresh_only: bool,
+    ) -> Result<Option<DecodingKey>, AuthError> {
+        let max_age = Duration::from_secs(self.config.jwks_refresh_secs);
+        let cached = self.jwks.read().await;
+        let Some((jwks, fetched_at)) = cached.as_ref() else {
+            return Ok(None);
+        };
+        if fresh_only && fetched_at.elapsed() >= max_age {
+            return Ok(None);
+        }
+        Ok(jwks.find(kid).map(DecodingKey::from_jwk).transpose()?)
+    }
+
+    // Validates the token signature, issuer, audience and expiry.
+    //
+    // # Arguments
+    //
+    // * `token` - The raw JWT taken from the `Authorization` header
+    //
+    // # Returns
+    //
+    // * `Ok(AuthenticatedUser)` - The identity carried by the token
+    // * `Err(AuthError)` - If the token is malformed, expired or not signed by the issuer
+    pub async fn validate(&self, token: &str) -> Result<AuthenticatedUser, AuthError> {
+        let header = decode_header(token)?;
+
+        // Only asymmetric algorithms make sense with a public JWKS
+        if matches!(
+            header.alg,
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+        ) {
+            return Err(AuthError::UnsupportedAlgorithm(header.alg));
+        }
+
+        let kid = header
+            .kid
+            .ok_or_else(|| AuthError::UnknownKey("<missing kid>".to_string()))?;
+        let key = self.decoding_key(&kid).await?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&self.config.issuer]);
+        validation.set_audience(&[&self.config.audience]);
+        validation.leeway = self.config.leeway_secs;
+
+        let data = decode::<Claims>(token, &key, &validation)?;
+        Ok(AuthenticatedUser {
+            subject: data.claims.sub,
+            claims: data.claims.extra,
+        })
+    }
+}
+
+// Middleware rejecting requests without a valid bearer token.
+//
+// On success the caller's `AuthenticatedUser` is added to the request extensions.
+pub async fn require_jwt(
+    State(validator): State<JwtValidator>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let token = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::to_owned)
+        .ok_or(AuthError::MissingToken)?;
+
+    let user = validator.validate(&token).await.map_err(|e| {
+        warn!("Rejected request with invalid token: {}", e);
+        e
+    })?;
+
+    debug!("Authenticated request for subject: {}", user.subject);
+    request.extensions_mut().insert(user);
+    Ok(next.run(request).await)
+}
+
+// Token guarding the admin API.
+#[derive(Clone)]
+pub struct AdminToken(Arc<str>);
+
+impl AdminToken {
+    pub fn new(config: &AdminConfig) -> Self {
+        Self(Arc::from(config.token.as_str()))
+    }
+
+    fn matches(&self, candidate: &str) -> bool {
+        constant_time_eq(&self.0, candidate)
+    }
+}
+
+// Compares secrets in constant time so they cannot be guessed byte by byte.
+fn constant_time_eq(expected: &str, candidate: &str) -> bool {
+    let expected = expected.as_bytes();
+    let candidate = candidate.as_bytes();
+    expected.len() == candidate.len()
+        && expected
+            .iter()
+            .zip(candidate)
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+// Middleware rejecting admin requests without the configured bearer token.
+pub async fn require_admin_token(
+    State(token): State<AdminToken>,
+    request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let authorized = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|candidate| token.matches(candidate))
+        .unwrap_or(false);
+
+    if !authorized {
+        warn!("Rejected admin request without a valid token");
+        return Err(ApiError::Unauthorized("invalid admin token".into()));
+    }
+    Ok(next.run(request).await)
+}
+
+// Header accepted as an alternative to `Authorization: Bearer` for API keys.
+pub const API_KEY_HEADER: &str = "X-API-Key";
+
+// A client authenticated by API key, with its PANW metadata and limits.
+//
+// Inserted into the request extensions by `require_api_key`; the scan context
+// uses it to attribute assessments to the tenant.
+//
+// # Fields
+//
+// * `name` - Tenant name, used in logs
+// * `app_name` - PANW `app_name` for this tenant, overriding the global value
+// * `app_user` - PANW `app_user` for this tenant, overriding the global value
+// * `profile_name` - PANW AI security profile for this tenant
+// * `models` - Models this tenant may use
+// * `allowed_profiles` - Profiles this tenant may select with `X-PANW-Profile`
+#[derive(Debug)]
+pub struct Tenant {
+    pub name: String,
+    pub app_name: Option<String>,
+    pub app_user: Option<String>,
+    pub profile_name: Option<String>,
+    pub models: ModelPolicy,
+    pub allowed_profiles: Vec<String>,
+    key: String,
+    limiter: Option<RateLimiter>,
+}
+
+impl Tenant {
+    pub fn new(config: &TenantConfig) -> Self {
+        Self {
+            name: config.name.clone(),
+            app_name: config.app_name.clone(),
+            app_user: config.app_user.clone(),
+            profile_name: config.profile_name.clone(),
+            models: ModelPolicy::allowing(&config.allowed_models),
+            allowed_profiles: config.allowed_profiles.clone(),
+            key: config.key.clone(),
+            limiter: config.requests_per_minute.map(RateLimiter::per_minute),
+        }
+    }
+}
+
+// The tenants known to the gateway, looked up by API key.
+#[derive(Clone)]
+pub struct TenantRegistry {
+    tenants: Arc<Vec<Arc<Tenant>>>,
+}
+
+impl TenantRegistry {
+    pub fn new(configs: &[TenantConfig]) -> Self {
+        Self {
+            tenants: Arc::new(configs.iter().map(|c| Arc::new(Tenant::new(c))).collect()),
+        }
+    }
+
+    fn find(&self, key: &str) -> Option<Arc<Tenant>> {
+        self.tenants
+            .iter()
+            .find(|tenant| constant_time_eq(&tenant.key, key))
+            .cloned()
+    }
+}
+
+// Middleware rejecting requests without a known API key.
+//
+// The key is read from `Authorization: Bearer` or `X-API-Key`. Requests above
+// the tenant's rate limit get `429`; otherwise the `Tenant` is added to the
+// request extensions.
+pub async fn require_api_key(
+    State(registry): State<TenantRegistry>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let headers = request.headers();
+    let key = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .or_else(|| {
+            headers
+                .get(API_KEY_HEADER)
+                .and_then(|value| value.to_str().ok())
+        })
+        .ok_or(AuthError::MissingToken)?;
+
+    let tenant = registry.find(key).ok_or_else(|| {
+        warn!("Rejected request with unknown API key");
+        AuthError::InvalidApiKey
+    })?;
+
+    if let Some(limiter) = &tenant.limiter {
+        if let Err(retry_after) = limiter.try_acquire() {
+            warn!("Rate limit exceeded for tenant {}", tenant.name);
+            return Err(ApiError::TooManyRequests(retry_after));
+        }
+    }
+
+    debug!("Authenticated request for tenant: {}", tenant.name);
+    request.extensions_mut().insert(tenant);
+    Ok(next.run(request).await)
+}
@@ -0,0 +1,166 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::config::AutoBanConfig;
+use crate::security::ScanContext;
+
+#[derive(Debug, Default)]
+struct Principal {
+    blocks: VecDeque<Instant>,
+    last_request_id: Option<String>,
+    banned_until: Option<(Instant, DateTime<Utc>)>,
+}
+
+// Active ban as reported by `/admin/bans`.
+#[derive(Debug, Clone, Serialize)]
+pub struct BanEntry {
+    pub principal: String,
+    pub banned_until: DateTime<Utc>,
+    pub remaining_secs: u64,
+}
+
+// Temporary bans of callers whose requests keep getting blocked.
+//
+// Callers are identified by their tenant (API key) or `app_user`; anonymous
+// callers share no identity and are never banned. Each request counts at most
+// once, however many of its scans are blocked. Cheap to clone; all clones
+// share the same state.
+#[derive(Debug, Clone)]
+pub struct BanList {
+    threshold: usize,
+    window: Duration,
+    cooldown: Duration,
+    principals: Arc<Mutex<HashMap<String, Principal>>>,
+}
+
+impl BanList {
+    pub fn new(config: &AutoBanConfig) -> Self {
+        Self {
+            threshold: config.threshold,
+            window: Duration::from_secs(config.window_secs),
+            cooldown: Duration::from_secs(config.cooldown_secs),
+            principals: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    // Returns the identity bans apply to, `None` for anonymous callers.
+    pub fn principal(ctx: &ScanContext) -> Option<String> {
+        ctx.tenant
+            .as_ref()
+            .map(|tenant| tenant.name.clone())
+            .or_else(|| ctx.app_user.clone())
+    }
+
+    // Checks whether the caller is currently banned.
+    //
+    // # Returns
+    //
+    // * `Ok(())` - The caller may proceed
+    // * `Err(Duration)` - Time until the ban is lifted
+    pub fn check(&self, ctx: &ScanContext) -> Result<(), Duration> {
+        let Some(principal) = Self::principal(ctx) else {
+            return Ok(());
+        };
+        let mut principals = self.principals.lock().unwrap();
+        let Some(entry) = principals.get_mut(&principal) else {
+            return Ok(());
+        };
+        match entry.banned_until {
+            Some((until, _)) if until > Instant::now() => Err(until - Instant::now()),
+            Some(_) => {
+                entry.banned_until = None;
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    // Counts a blocked request and bans the caller once the threshold is reached.
+    pub fn record_block(&self, ctx: &ScanContext) {
+        let Some(principal) = Self::principal(ctx) else {
+            return;
+        };
+        let now = Instant::now();
+        let mut principals = self.principals.lock().unwrap();
+        principals.retain(|_, entry| {
+            entry.banned_until.is_some_and(|(until, _)| until > now)
+                || entry
+                    .blocks
+                    .back()
+                    .is_some_and(|last| now.duration_since(*last) < self.window)
+        });
+
+        let entry = principals.entry(principal.clone()).or_default();
+        if ctx.request_id.is_some() && entry.last_request_id == ctx.request_id {
+            return;
+        }
+        entry.last_request_id = ctx.request_id.clone();
+        while entry
+            .blocks
+            .front()
+            .is_some_and(|first| now.duration_since(*first) >= self.window)
+        {
+            entry.blocks.pop_front();
+        }
+        entry.blocks.push_back(now);
+
+        if entry.blocks.len() >= self.threshold {
+            entry.blocks.clear();
+            let until = Utc::now()
+                + chrono::Duration::from_std(self.cooldown).unwrap_or(chrono::Duration::zero());
+            entry.banned_until = Some((now + self.cooldown, until));
+            warn!(
+                "Banning {} until {} after {} blocked requests",
+                principal,
+                until.to_rfc3339(),
+                self.threshold
+            );
+            info!(
+                target: "audit",
+                principal = %principal,
+                banned_until = %until.to_rfc3339(),
+                "Caller temporarily banned after repeated blocks"
+            );
+        }
+    }
+
+    // Lists the active bans.
+    pub fn list(&self) -> Vec<BanEntry> {
+        let now = Instant::now();
+        let principals = self.principals.lock().unwrap();
+        let mut bans: Vec<BanEntry> = principals
+            .iter()
+            .filter_map(|(principal, entry)| {
+                let (until, banned_until) = entry.banned_until?;
+                (until > now).then(|| BanEntry {
+                    principal: principal.clone(),
+                    banned_until,
+                    remaining_secs: (until - now).as_secs(),
+                })
+            })
+            .collect();
+        bans.sort_by(|a, b| a.principal.cmp(&b.principal));
+        bans
+    }
+
+    // Lifts the ban of a caller and resets their block count.
+    //
+    // # Returns
+    //
+    // `true` if the caller was banned
+    pub fn lift(&self, principal: &str) -> bool {
+        let mut principals = self.principals.lock().unwrap();
+        let was_banned = principals
+            .remove(principal)
+            .and_then(|entry| entry.banned_until)
+            .is_some_and(|(until, _)| until > Instant::now());
+        if was_banned {
+            info!(target: "audit", principal, "Ban lifted by an administrator");
+        }
+        was_banned
+    }
+}
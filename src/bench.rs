@@ -0,0 +1,373 @@
+use reqwest::Client;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+use crate::mock::{self, MockOllama, MockPanw, MockServer};
+use crate::{build_router, build_state, BoxError, Config};
+
+// Endpoint the benchmark sends its requests to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BenchEndpoint {
+    Chat,
+    Generate,
+}
+
+impl BenchEndpoint {
+    fn path(self) -> &'static str {
+        match self {
+            BenchEndpoint::Chat => "/api/chat",
+            BenchEndpoint::Generate => "/api/generate",
+        }
+    }
+
+    fn body(self, model: &str, prompt: &str, stream: bool) -> Value {
+        match self {
+            BenchEndpoint::Chat => json!({
+                "model": model,
+                "messages": [{ "role": "user", "content": prompt }],
+                "stream": stream,
+            }),
+            BenchEndpoint::Generate => json!({
+                "model": model,
+                "prompt": prompt,
+                "stream": stream,
+            }),
+        }
+    }
+}
+
+impl FromStr for BenchEndpoint {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "chat" => Ok(BenchEndpoint::Chat),
+            "generate" => Ok(BenchEndpoint::Generate),
+            other => Err(format!(
+                "unknown endpoint {}, expected chat or generate",
+                other
+            )),
+        }
+    }
+}
+
+// Stub upstreams started for the benchmark instead of the configured ones.
+//
+// # Fields
+//
+// * `ollama_latency` - Delay of the stub Ollama before each answer
+// * `panw_latency` - Delay of the stub PANW API before each verdict
+#[derive(Debug, Clone)]
+pub struct MockOptions {
+    pub ollama_latency: Duration,
+    pub panw_latency: Duration,
+}
+
+// What the benchmark sends and where.
+//
+// # Fields
+//
+// * `target` - Gateway URL; defaults to the configured listener
+// * `baseline` - Ollama URL called directly to measure the gateway overhead
+// * `endpoint` - Chat or generate
+// * `model` - Model named in every request
+// * `prompt` - Prompt sent in every request
+// * `stream` - Request streamed responses, read to the end
+// * `concurrency` - Requests in flight at once
+// * `requests` - Requests sent to each URL
+// * `api_key` - Bearer token sent to the gateway, if it requires one
+// * `mock` - Run an in-process gateway against stub upstreams instead
+#[derive(Debug, Clone)]
+pub struct BenchOptions {
+    pub target: Option<String>,
+    pub baseline: Option<String>,
+    pub endpoint: BenchEndpoint,
+    pub model: String,
+    pub prompt: String,
+    pub stream: bool,
+    pub concurrency: usize,
+    pub requests: usize,
+    pub api_key: Option<String>,
+    pub mock: Option<MockOptions>,
+}
+
+// Latency percentiles of the successful requests of a run, in milliseconds.
+#[derive(Debug, Clone, Serialize)]
+pub struct LatencySummary {
+    pub mean: f64,
+    pub p50: f64,
+    pub p95: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+impl LatencySummary {
+    fn from_samples(samples: &mut [Duration]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        samples.sort_unstable();
+        let millis = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        let percentile =
+            |p: f64| millis(samples[((samples.len() - 1) as f64 * p).round() as usize]);
+        let total: Duration = samples.iter().sum();
+        Some(Self {
+            mean: millis(total) / samples.len() as f64,
+            p50: percentile(0.50),
+            p95: percentile(0.95),
+            p99: percentile(0.99),
+            max: millis(samples[samples.len() - 1]),
+        })
+    }
+
+    // Latency the gateway adds on top of a direct call, per percentile.
+    fn overhead(&self, baseline: &LatencySummary) -> LatencySummary {
+        LatencySummary {
+            mean: self.mean - baseline.mean,
+            p50: self.p50 - baseline.p50,
+            p95: self.p95 - baseline.p95,
+            p99: self.p99 - baseline.p99,
+            max: self.max - baseline.max,
+        }
+    }
+}
+
+// Outcome of sending the benchmark traffic to one URL.
+//
+// # Fields
+//
+// * `url` - Where the requests were sent
+// * `requests` - Requests sent
+// * `failures` - Requests that failed or got a non-2xx status
+// * `duration_secs` - Wall time of the run
+// * `throughput` - Successful requests per second
+// * `latency` - Latency of the successful requests, if any
+#[derive(Debug, Clone, Serialize)]
+pub struct RunReport {
+    pub url: String,
+    pub requests: usize,
+    pub failures: usize,
+    pub duration_secs: f64,
+    pub throughput: f64,
+    pub latency: Option<LatencySummary>,
+}
+
+// Result of a benchmark: the gateway run and, with a baseline, the overhead.
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchReport {
+    pub endpoint: BenchEndpoint,
+    pub stream: bool,
+    pub concurrency: usize,
+    pub gateway: RunReport,
+    pub baseline: Option<RunReport>,
+    pub overhead: Option<LatencySummary>,
+}
+
+// Drives concurrent traffic through the gateway and reports throughput and latency.
+//
+// With `mock`, stub Ollama and PANW servers are started on local ports and an
+// in-process gateway built from `config` is pointed at them, so the reported
+// overhead is the cost of the gateway and its scans alone. The same traffic is
+// then sent straight to the stub Ollama as a baseline.
+//
+// # Arguments
+//
+// * `config` - The loaded configuration, for the listener address or the in-process gateway
+// * `options` - Traffic shape and targets
+//
+// # Returns
+//
+// * `Ok(BenchReport)` - Measurements of the gateway run and the optional baseline
+// * `Err(BoxError)` - If the stub servers or the in-process gateway cannot be started
+pub async fn run(config: &Config, options: &BenchOptions) -> Result<BenchReport, BoxError> {
+    // Held until the runs are over; dropping them stops the servers
+    let mut servers = Vec::new();
+    let (target, baseline) = match &options.mock {
+        Some(mock) => {
+            let ollama = MockOllama::start().await?;
+            ollama.set_latency(mock.ollama_latency);
+            let panw = MockPanw::start().await?;
+            panw.set_latency(mock.panw_latency);
+            let gateway = spawn_gateway(config, ollama.url(), panw.url()).await?;
+            let urls = (gateway.url.clone(), Some(ollama.url().to_string()));
+            servers.push((ollama, panw, gateway));
+            urls
+        }
+        None => (
+            options
+                .target
+                .clone()
+                .unwrap_or_else(|| listener_url(config)),
+            options.baseline.clone(),
+        ),
+    };
+
+    let client = Client::new();
+    info!("Benchmarking {} with {} requests", target, options.requests);
+    let gateway = drive(&client, &target, options, options.api_key.as_deref()).await;
+    let baseline = match baseline {
+        Some(url) => {
+            info!("Benchmarking baseline {}", url);
+            Some(drive(&client, &url, options, None).await)
+        }
+        None => None,
+    };
+    let overhead = baseline.as_ref().and_then(|baseline| {
+        Some(
+            gateway
+                .latency
+                .as_ref()?
+                .overhead(baseline.latency.as_ref()?),
+        )
+    });
+
+    Ok(BenchReport {
+        endpoint: options.endpoint,
+        stream: options.stream,
+        concurrency: options.concurrency,
+        gateway,
+        baseline,
+        overhead,
+    })
+}
+
+// URL of the listener described by the server configuration.
+fn listener_url(config: &Config) -> String {
+    let scheme = if config.server.tls.is_some() {
+        "https"
+    } else {
+        "http"
+    };
+    let host = match config.server.host.as_str() {
+        "0.0.0.0" | "::" => "127.0.0.1",
+        host => host,
+    };
+    format!("{}://{}:{}", scheme, host, config.server.port)
+}
+
+// Starts the gateway in-process with its upstreams replaced by the stub servers.
+async fn spawn_gateway(
+    config: &Config,
+    ollama_url: &str,
+    panw_url: &str,
+) -> Result<MockServer, BoxError> {
+    let mut config = config.clone();
+    config.ollama.base_url = vec![ollama_url.to_string()];
+    config.ollama.routes.clear();
+    config.ollama.fallback_url = None;
+    config.security.base_url = panw_url.to_string();
+    config.security.region = None;
+    config.security.api_key_file = None;
+    config.security.secret_source = None;
+    if config.security.api_key.is_empty() {
+        config.security.api_key = "bench".to_string();
+    }
+
+    let state = build_state(&config).await?;
+    Ok(mock::serve(build_router(state, &config)).await?)
+}
+
+// Sends the benchmark requests to one URL with the configured concurrency.
+async fn drive(
+    client: &Client,
+    base_url: &str,
+    options: &BenchOptions,
+    api_key: Option<&str>,
+) -> RunReport {
+    let url = format!(
+        "{}{}",
+        base_url.trim_end_matches('/'),
+        options.endpoint.path()
+    );
+    let body = Arc::new(
+        options
+            .endpoint
+            .body(&options.model, &options.prompt, options.stream),
+    );
+    let next = Arc::new(AtomicUsize::new(0));
+
+    let started = Instant::now();
+    let workers: Vec<_> = (0..options.concurrency.max(1))
+        .map(|_| {
+            let client = client.clone();
+            let url = url.clone();
+            let body = body.clone();
+            let next = next.clone();
+            let api_key = api_key.map(str::to_string);
+            let requests = options.requests;
+            tokio::spawn(async move {
+                let mut samples = Vec::new();
+                let mut failures = 0;
+                while next.fetch_add(1, Ordering::Relaxed) < requests {
+                    match send(&client, &url, &body, api_key.as_deref()).await {
+                        Ok(latency) => samples.push(latency),
+                        Err(_) => failures += 1,
+                    }
+                }
+                (samples, failures)
+            })
+        })
+        .collect();
+
+    let mut samples = Vec::with_capacity(options.requests);
+    let mut failures = 0;
+    for worker in workers {
+        if let Ok((worker_samples, worker_failures)) = worker.await {
+            samples.extend(worker_samples);
+            failures += worker_failures;
+        }
+    }
+    let duration = started.elapsed();
+
+    RunReport {
+        url,
+        requests: options.requests,
+        failures,
+        duration_secs: duration.as_secs_f64(),
+        throughput: samples.len() as f64 / duration.as_secs_f64().max(f64::EPSILON),
+        latency: LatencySummary::from_samples(&mut samples),
+    }
+}
+
+// Sends one request and reads the whole response, returning its latency.
+//
+// Blocked or failed streams still have a 2xx status, so a stream ending with
+// an error object counts as a failure as well.
+async fn send(
+    client: &Client,
+    url: &str,
+    body: &Value,
+    api_key: Option<&str>,
+) -> Result<Duration, String> {
+    let started = Instant::now();
+    let mut request = client.post(url).json(body);
+    if let Some(api_key) = api_key {
+        request = request.bearer_auth(api_key);
+    }
+    let bytes = request
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| e.to_string())?
+        .bytes()
+        .await
+        .map_err(|e| e.to_string())?;
+    let latency = started.elapsed();
+
+    let last_line = bytes
+        .split(|&byte| byte == b'\n')
+        .rfind(|line| !line.iter().all(u8::is_ascii_whitespace))
+        .unwrap_or_default();
+    if let Ok(Value::Object(last)) = serde_json::from_slice(last_line) {
+        if let Some(error) = last.get("error") {
+            return Err(error.to_string());
+        }
+    }
+    Ok(latency)
+}
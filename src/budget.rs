@@ -0,0 +1,167 @@
+use chrono::{Duration as ChronoDuration, NaiveDate, Utc};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::security::ScanContext;
+
+// Key used for callers without a tenant or user identity.
+const ANONYMOUS_USER: &str = "anonymous";
+
+#[derive(Debug, Clone, Copy)]
+struct DailyUsage {
+    day: NaiveDate,
+    tokens: u64,
+}
+
+// Per-user daily token budget.
+//
+// Usage is tracked from the `prompt_eval_count` and `eval_count` values relayed
+// from Ollama and resets at midnight UTC. Users are identified by their tenant,
+// their `app_user`, or share the `anonymous` budget. Cheap to clone; all clones
+// share the same counters.
+#[derive(Debug, Clone)]
+pub struct TokenBudget {
+    limit: u64,
+    usage: Arc<Mutex<HashMap<String, DailyUsage>>>,
+}
+
+impl TokenBudget {
+    pub fn new(limit: u64) -> Self {
+        Self {
+            limit,
+            usage: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    // Returns the budget key of the caller.
+    pub fn user_key(ctx: &ScanContext) -> String {
+        ctx.tenant
+            .as_ref()
+            .map(|tenant| tenant.name.clone())
+            .or_else(|| ctx.app_user.clone())
+            .unwrap_or_else(|| ANONYMOUS_USER.to_string())
+    }
+
+    // Returns the tokens a user may still consume today.
+    //
+    // # Arguments
+    //
+    // * `user` - The key returned by `user_key`
+    //
+    // # Returns
+    //
+    // * `Ok(u64)` - The remaining tokens, never zero
+    // * `Err(Duration)` - Time until the budget resets if it is exhausted
+    pub fn remaining(&self, user: &str) -> Result<u64, Duration> {
+        let today = Utc::now().date_naive();
+        let used = self
+            .usage
+            .lock()
+            .unwrap()
+            .get(user)
+            .filter(|usage| usage.day == today)
+            .map(|usage| usage.tokens)
+            .unwrap_or(0);
+
+        match self.limit.saturating_sub(used) {
+            0 => Err(until_reset()),
+            remaining => Ok(remaining),
+        }
+    }
+
+    // Adds consumed tokens to a user's usage of the current day.
+    pub fn record(&self, user: &str, tokens: u64) {
+        if tokens == 0 {
+            return;
+        }
+        let today = Utc::now().date_naive();
+        let mut usage = self.usage.lock().unwrap();
+        let entry = usage.entry(user.to_string()).or_insert(DailyUsage {
+            day: today,
+            tokens: 0,
+        });
+        if entry.day != today {
+            *entry = DailyUsage {
+                day: today,
+                tokens: 0,
+            };
+        }
+        entry.tokens = entry.tokens.saturating_add(tokens);
+    }
+
+    // Returns today's usage of every user as a JSON object.
+    pub fn snapshot(&self) -> Value {
+        let today = Utc::now().date_naive();
+        let mut usage = self.usage.lock().unwrap();
+        usage.retain(|_, usage| usage.day == today);
+
+        let mut users: Vec<Value> = usage
+            .iter()
+            .map(|(user, usage)| {
+                json!({
+                    "user": user,
+                    "tokens": usage.tokens,
+                    "remaining": self.limit.saturating_sub(usage.tokens),
+                })
+            })
+            .collect();
+        users.sort_by(|a, b| a["user"].as_str().cmp(&b["user"].as_str()));
+
+        json!({
+            "day": today.to_string(),
+            "daily_token_budget": self.limit,
+            "resets_in_secs": until_reset().as_secs(),
+            "users": users,
+        })
+    }
+}
+
+// Time until the next midnight UTC.
+fn until_reset() -> Duration {
+    let now = Utc::now();
+    let midnight = (now.date_naive() + ChronoDuration::days(1))
+        .and_hms_opt(0, 0, 0)
+        .map(|time| time.and_utc());
+    midnight
+        .and_then(|midnight| (midnight - now).to_std().ok())
+        .unwrap_or(Duration::from_secs(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_usage_per_user() {
+        let budget = TokenBudget::new(100);
+        budget.record("alice", 40);
+        assert_eq!(budget.remaining("alice"), Ok(60));
+        assert_eq!(budget.remaining("bob"), Ok(100));
+    }
+
+    #[test]
+    fn exhausted_budget_reports_time_until_reset() {
+        let budget = TokenBudget::new(100);
+        budget.record("alice", 150);
+        let wait = budget.remaining("alice").unwrap_err();
+        assert!(wait <= Duration::from_secs(24 * 60 * 60));
+    }
+
+    #[test]
+    fn previous_day_usage_is_reset() {
+        let budget = TokenBudget::new(100);
+        let yesterday = Utc::now().date_naive() - ChronoDuration::days(1);
+        budget.usage.lock().unwrap().insert(
+            "alice".to_string(),
+            DailyUsage {
+                day: yesterday,
+                tokens: 100,
+            },
+        );
+        assert_eq!(budget.remaining("alice"), Ok(100));
+        budget.record("alice", 10);
+        assert_eq!(budget.remaining("alice"), Ok(90));
+    }
+}
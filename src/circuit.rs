@@ -0,0 +1,300 @@
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+// State of the circuit breaker guarding the PANW AI Runtime API.
+//
+// * `Closed` - Scans flow normally
+// * `Open` - Scans are short-circuited until the cooldown elapses
+// * `HalfOpen` - A single probe scan is allowed through to test recovery
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+struct BreakerInner {
+    state: CircuitState,
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    probe_in_flight: bool,
+}
+
+// Circuit breaker shared by all clones of the security client.
+//
+// Opens after `failure_threshold` consecutive failures, rejects calls for `cooldown`,
+// then lets one probe through. A successful probe closes the circuit again,
+// a failed one reopens it for another cooldown period.
+#[derive(Clone)]
+pub struct CircuitBreaker {
+    inner: Arc<Mutex<BreakerInner>>,
+    failure_threshold: u32,
+    cooldown: Duration,
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(BreakerInner {
+                state: CircuitState::Closed,
+                consecutive_failures: 0,
+                opened_at: None,
+                probe_in_flight: false,
+            })),
+            failure_threshold,
+            cooldown,
+        }
+    }
+
+    // Returns the current breaker state without changing it.
+    pub fn state(&self) -> CircuitState {
+        self.inner.lock().unwrap().state
+    }
+
+    // Returns how long callers should wait before the breaker lets a probe through.
+    //
+    // This is the remaining cooldown while open and the full cooldown while a
+    // half-open probe is in flight.
+    pub fn retry_after(&self) -> Duration {
+        let inner = self.inner.lock().unwrap();
+        match (inner.state, inner.opened_at) {
+            (CircuitState::Closed, _) => Duration::ZERO,
+            (CircuitState::Open, Some(opened_at)) => {
+                self.cooldown.saturating_sub(opened_at.elapsed())
+            }
+            _ => self.cooldown,
+        }
+    }
+
+    // Checks whether a call may proceed, moving an expired open circuit to half-open.
+    //
+    // # Returns
+    //
+    // * `Some(BreakerPermit)` - The call may proceed; report its outcome through the permit
+    // * `None` - The call must be short-circuited
+    pub fn try_acquire(&self) -> Option<BreakerPermit> {
+        let mut inner = self.inner.lock().unwrap();
+        let probe = match inner.state {
+            CircuitState::Closed => false,
+            CircuitState::Open => {
+                let cooled_down = inner
+                    .opened_at
+                    .map(|opened_at| opened_at.elapsed() >= self.cooldown)
+                    .unwrap_or(true);
+                if !cooled_down {
+                    return None;
+                }
+                info!("PANW circuit breaker half-open, probing API");
+                inner.state = CircuitState::HalfOpen;
+                inner.probe_in_flight = true;
+                true
+            }
+            CircuitState::HalfOpen => {
+                if inner.probe_in_flight {
+                    return None;
+                }
+                inner.probe_in_flight = true;
+                true
+            }
+        };
+        Some(BreakerPermit {
+            breaker: self.clone(),
+            probe,
+            settled: false,
+        })
+    }
+
+    // Records a successful call, closing the circuit.
+    //
+    // Only the half-open probe may close the circuit. Outcomes of permits taken
+    // while the circuit was closed are ignored once it has left that state, so
+    // a late answer cannot cut a cooldown short or free the probe slot.
+    fn record_success(&self, probe: bool) {
+        let mut inner = self.inner.lock().unwrap();
+        if !probe && inner.state != CircuitState::Closed {
+            return;
+        }
+        if inner.state != CircuitState::Closed {
+            info!("PANW circuit breaker closed");
+        }
+        inner.state = CircuitState::Closed;
+        inner.consecutive_failures = 0;
+        inner.opened_at = None;
+        inner.probe_in_flight = false;
+    }
+
+    // Records a failed call, opening the circuit once the threshold is reached.
+    //
+    // Like `record_success`, only the probe counts outside the closed state.
+    fn record_failure(&self, probe: bool) {
+        let mut inner = self.inner.lock().unwrap();
+        if !probe && inner.state != CircuitState::Closed {
+            return;
+        }
+        inner.consecutive_failures = inner.consecutive_failures.saturating_add(1);
+        inner.probe_in_flight = false;
+
+        if inner.state == CircuitState::HalfOpen
+            || inner.consecutive_failures >= self.failure_threshold
+        {
+            if inner.state != CircuitState::Open {
+                warn!(
+                    "PANW circuit breaker opened after {} consecutive failures",
+                    inner.consecutive_failures
+                );
+            }
+            inner.state = CircuitState::Open;
+            inner.opened_at = Some(Instant::now());
+        }
+    }
+
+    // Frees the probe slot without changing the state.
+    fn release_probe(&self) {
+        self.inner.lock().unwrap().probe_in_flight = false;
+    }
+}
+
+// A call allowed through the circuit breaker.
+//
+// The outcome is reported with `success`, `failure` or `release`. A half-open
+// probe dropped without an outcome, e.g. because the request future was
+// cancelled, counts as a failure so the breaker never stays stuck half-open.
+pub struct BreakerPermit {
+    breaker: CircuitBreaker,
+    probe: bool,
+    settled: bool,
+}
+
+impl BreakerPermit {
+    // Records that the API answered, closing the circuit.
+    pub fn success(mut self) {
+        self.settled = true;
+        self.breaker.record_success(self.probe);
+    }
+
+    // Records an outage of the API: a transport error, a timeout or a 5xx status.
+    pub fn failure(mut self) {
+        self.settled = true;
+        self.breaker.record_failure(self.probe);
+    }
+
+    // Ends the call without an outcome, e.g. when no scan slot freed up in time.
+    pub fn release(mut self) {
+        self.settled = true;
+        if self.probe {
+            self.breaker.release_probe();
+        }
+    }
+}
+
+impl Drop for BreakerPermit {
+    fn drop(&mut self) {
+        if !self.settled && self.probe {
+            warn!("PANW circuit breaker probe abandoned, counting it as a failure");
+            self.breaker.record_failure(true);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn opened_breaker(cooldown: Duration) -> CircuitBreaker {
+        let breaker = CircuitBreaker::new(2, cooldown);
+        breaker.try_acquire().unwrap().failure();
+        breaker.try_acquire().unwrap().failure();
+        breaker
+    }
+
+    #[test]
+    fn opens_after_threshold_failures() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(60));
+        breaker.try_acquire().unwrap().failure();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        breaker.try_acquire().unwrap().failure();
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(breaker.try_acquire().is_none());
+        let retry_after = breaker.retry_after();
+        assert!(retry_after > Duration::ZERO && retry_after <= Duration::from_secs(60));
+    }
+
+    #[test]
+    fn success_resets_failure_count() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(60));
+        breaker.try_acquire().unwrap().failure();
+        breaker.try_acquire().unwrap().success();
+        breaker.try_acquire().unwrap().failure();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[test]
+    fn half_open_allows_a_single_probe() {
+        let breaker = opened_breaker(Duration::ZERO);
+        let probe = breaker.try_acquire().unwrap();
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        assert!(breaker.try_acquire().is_none());
+        probe.success();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert!(breaker.try_acquire().is_some());
+    }
+
+    #[test]
+    fn failed_probe_reopens() {
+        let breaker = opened_breaker(Duration::ZERO);
+        breaker.try_acquire().unwrap().failure();
+        assert_eq!(breaker.state(), CircuitState::Open);
+    }
+
+    #[test]
+    fn dropped_probe_counts_as_failure() {
+        let breaker = opened_breaker(Duration::ZERO);
+        drop(breaker.try_acquire().unwrap());
+        assert_eq!(breaker.state(), CircuitState::Open);
+    }
+
+    #[test]
+    fn released_probe_frees_the_slot() {
+        let breaker = opened_breaker(Duration::ZERO);
+        breaker.try_acquire().unwrap().release();
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        assert!(breaker.try_acquire().is_some());
+    }
+
+    #[test]
+    fn dropped_closed_permit_changes_nothing() {
+        let breaker = CircuitBreaker::new(1, Duration::from_secs(60));
+        drop(breaker.try_acquire().unwrap());
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[test]
+    fn stale_closed_permit_success_keeps_circuit_open() {
+        let breaker = CircuitBreaker::new(1, Duration::from_secs(60));
+        let stale = breaker.try_acquire().unwrap();
+        breaker.try_acquire().unwrap().failure();
+        assert_eq!(breaker.state(), CircuitState::Open);
+        stale.success();
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(breaker.try_acquire().is_none());
+    }
+
+    #[test]
+    fn stale_closed_permit_outcome_keeps_probe_slot() {
+        let breaker = CircuitBreaker::new(1, Duration::ZERO);
+        let early = breaker.try_acquire().unwrap();
+        let late = breaker.try_acquire().unwrap();
+        breaker.try_acquire().unwrap().failure();
+        let probe = breaker.try_acquire().unwrap();
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        early.success();
+        late.failure();
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        assert!(breaker.try_acquire().is_none());
+        probe.success();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+}
@@ -1,54 +1,1725 @@
-use serde::Deserialize;
+use serde::de::value::{MapDeserializer, SeqDeserializer};
+use serde::de::{Deserializer, IntoDeserializer, Visitor};
+use serde::{forward_to_deserialize_any, Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use std::env;
 use std::fs;
+use std::path::Path;
 use thiserror::Error;
+use tracing::warn;
+
+// Prefix of environment variables overriding config values,
+// e.g. `PANW_OLLAMA__SECURITY__API_KEY` overrides `security.api_key`.
+const ENV_PREFIX: &str = "PANW_OLLAMA__";
 
 #[derive(Debug, Error)]
 pub enum ConfigError {
     #[error("Failed to read config file: {0}")]
     IoError(#[from] std::io::Error),
 
-    #[error("Failed to parse config file: {0}")]
-    ParseError(#[from] serde_yaml::Error),
+    #[error("Failed to parse config file: {0}")]
+    ParseError(#[from] serde_yaml::Error),
+
+    #[error("Validation error: {0}")]
+    ValidationError(String),
+
+    #[error("TLS configuration error: {0}")]
+    TlsError(String),
+
+    #[error("Failed to load secret: {0}")]
+    SecretError(String),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub ollama: OllamaConfig,
+    pub security: SecurityConfig,
+    #[serde(default)]
+    pub auth: AuthConfig,
+    #[serde(default)]
+    pub scanner: ScannerConfig,
+    #[serde(default)]
+    pub policy: PolicyConfig,
+    #[serde(default)]
+    pub quarantine: Option<QuarantineConfig>,
+    #[serde(default)]
+    pub history: Option<HistoryConfig>,
+    #[serde(default)]
+    pub events: Option<EventsConfig>,
+    #[serde(default)]
+    pub hooks: Vec<HookConfig>,
+    #[serde(default)]
+    pub guardrail: Option<GuardrailConfig>,
+    #[serde(default)]
+    pub logging: LoggingConfig,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct LoggingConfig {
+    #[serde(default)]
+    pub access_log: AccessLogConfig,
+    // Keep prompt and response text out of logs and error bodies, logging only hashes and lengths
+    #[serde(default)]
+    pub privacy_mode: bool,
+}
+
+// One structured line per request, written to stdout or a rotating file.
+//
+// With `output: file`, `path` is renamed to `path.1` once it exceeds `max_bytes`,
+// keeping at most `max_files` rotated files.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AccessLogConfig {
+    pub enabled: bool,
+    pub format: AccessLogFormat,
+    pub output: AccessLogOutput,
+    pub path: Option<String>,
+    pub max_bytes: u64,
+    pub max_files: usize,
+}
+
+impl Default for AccessLogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            format: AccessLogFormat::Json,
+            output: AccessLogOutput::Stdout,
+            path: None,
+            max_bytes: 100 * 1024 * 1024,
+            max_files: 5,
+        }
+    }
+}
+
+// * `Json` - All fields including model, scan verdicts and stage durations
+// * `Common` - The Common Log Format understood by most log tooling
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccessLogFormat {
+    #[default]
+    Json,
+    Common,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccessLogOutput {
+    #[default]
+    Stdout,
+    File,
+}
+
+// Safety preamble added as system prompt to every chat and generate request.
+//
+// `{{model}}` in a prompt is replaced by the requested model name. The first
+// entry of `models` matching the model overrides `system_prompt`; an empty
+// prompt exempts the model.
+#[derive(Debug, Clone, Deserialize)]
+pub struct GuardrailConfig {
+    #[serde(default)]
+    pub mode: GuardrailMode,
+    pub system_prompt: String,
+    #[serde(default)]
+    pub models: Vec<ModelGuardrail>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ModelGuardrail {
+    pub model: String,
+    pub system_prompt: String,
+}
+
+// How the guardrail combines with the client's own system prompt.
+//
+// * `Prepend` - Put the guardrail before the client's system prompt
+// * `Replace` - Drop the client's system prompt
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GuardrailMode {
+    #[default]
+    Prepend,
+    Replace,
+}
+
+// A WebAssembly module transforming requests and responses.
+//
+// Requires building with the `wasm` feature.
+#[derive(Debug, Clone, Deserialize)]
+pub struct HookConfig {
+    pub name: String,
+    pub path: String,
+    // Upper bound of work units per call, stopping runaway modules
+    #[serde(default = "default_hook_max_fuel")]
+    pub max_fuel: u64,
+    // Upper bound of the linear memory of an instance
+    #[serde(default = "default_hook_max_memory_bytes")]
+    pub max_memory_bytes: usize,
+}
+
+fn default_hook_max_fuel() -> u64 {
+    1_000_000_000
+}
+
+fn default_hook_max_memory_bytes() -> usize {
+    64 * 1024 * 1024
+}
+
+// Encrypted store of blocked exchanges for later review.
+//
+// The key is 32 bytes, base64-encoded, given inline or in `key_file`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct QuarantineConfig {
+    pub directory: String,
+    #[serde(default)]
+    pub key: Option<String>,
+    #[serde(default)]
+    pub key_file: Option<String>,
+    // Days records are kept; 0 keeps them forever
+    #[serde(default)]
+    pub retention_days: u64,
+}
+
+// Persistent record of every scan verdict, queried through `/admin/assessments`.
+//
+// Verdicts are stored in the SQLite database at `database`, without the scanned
+// content. Records older than `retention_days` are removed; 0 keeps them forever.
+#[derive(Debug, Clone, Deserialize)]
+pub struct HistoryConfig {
+    #[serde(default = "default_history_database")]
+    pub database: String,
+    #[serde(default)]
+    pub retention_days: u64,
+    // Verdicts waiting to be written; newer ones are dropped when it is full
+    #[serde(default = "default_history_buffer_size")]
+    pub buffer_size: usize,
+}
+
+fn default_history_database() -> String {
+    "assessments.db".to_string()
+}
+
+fn default_history_buffer_size() -> usize {
+    10_000
+}
+
+// Publishing of verdict and usage events to a message broker.
+//
+// Events are buffered in memory (`buffer_size` events at most) and sent in
+// batches of up to `batch_size`; failed batches are retried with exponential
+// backoff capped at `max_retry_delay_secs`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct EventsConfig {
+    pub backend: EventBackendConfig,
+    #[serde(default = "default_verdict_topic")]
+    pub verdict_topic: String,
+    #[serde(default = "default_usage_topic")]
+    pub usage_topic: String,
+    #[serde(default = "default_event_buffer_size")]
+    pub buffer_size: usize,
+    #[serde(default = "default_event_batch_size")]
+    pub batch_size: usize,
+    #[serde(default = "default_max_retry_delay_secs")]
+    pub max_retry_delay_secs: u64,
+    // Retries of a failed batch before its events are dropped
+    #[serde(default = "default_max_event_retries")]
+    pub max_retries: u32,
+}
+
+// Message broker receiving gateway events.
+//
+// Each broker is only available when the crate is built with the matching
+// feature (`kafka` or `nats`).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+#[cfg_attr(not(all(feature = "kafka", feature = "nats")), allow(dead_code))]
+pub enum EventBackendConfig {
+    Kafka {
+        brokers: Vec<String>,
+        #[serde(default)]
+        partition: i32,
+    },
+    Nats {
+        url: String,
+        // Publish through JetStream and wait for its acknowledgements
+        #[serde(default = "default_true")]
+        jetstream: bool,
+    },
+}
+
+fn default_verdict_topic() -> String {
+    "panw-api-ollama.verdicts".to_string()
+}
+
+fn default_usage_topic() -> String {
+    "panw-api-ollama.usage".to_string()
+}
+
+fn default_event_buffer_size() -> usize {
+    10000
+}
+
+fn default_event_batch_size() -> usize {
+    100
+}
+
+fn default_max_retry_delay_secs() -> u64 {
+    30
+}
+
+fn default_max_event_retries() -> u32 {
+    10
+}
+
+// Access policy applied to incoming requests.
+//
+// Model lists hold wildcard patterns such as `llama3*`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PolicyConfig {
+    #[serde(default)]
+    pub allowed_models: Vec<String>,
+    #[serde(default)]
+    pub blocked_models: Vec<String>,
+    #[serde(default)]
+    pub conversation_scope: ConversationScope,
+    #[serde(default = "default_scan_window")]
+    pub scan_window: usize,
+    // Lets clients pick the scope per request with the `X-Scan-Scope` header
+    #[serde(default)]
+    pub allow_scope_header: bool,
+    // Chat roles whose messages are never scanned as prompts, e.g. trusted `system` prompts
+    #[serde(default)]
+    pub skip_roles: Vec<String>,
+    // Send attached images to the scanner; they are passed through unscanned otherwise
+    #[serde(default)]
+    pub scan_images: bool,
+    #[serde(default)]
+    pub option_limits: OptionLimitsConfig,
+    #[serde(default)]
+    pub prompt_limits: PromptLimitsConfig,
+    #[serde(default)]
+    pub embeddings: EmbeddingsScanConfig,
+    // Tokens (prompt plus generated) each user may consume per UTC day
+    #[serde(default)]
+    pub daily_token_budget: Option<u64>,
+    // Scan recent user turns of a conversation together to catch multi-turn attacks
+    #[serde(default)]
+    pub session_scan: Option<SessionScanConfig>,
+    // Temporarily reject callers whose requests are blocked repeatedly
+    #[serde(default)]
+    pub auto_ban: Option<AutoBanConfig>,
+    // Daily and monthly request and token quotas per user, persisted in SQLite
+    #[serde(default)]
+    pub quotas: Option<QuotaConfig>,
+    // Policy engine deciding on scan verdicts per caller, model and endpoint
+    #[serde(default)]
+    pub engine: Option<PolicyEngineConfig>,
+}
+
+// Policy engine evaluated after each scan.
+//
+// The engine may allow, block or mask content regardless of the scanner
+// verdict. With `flagged_only`, only flagged content is sent to it.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PolicyEngineConfig {
+    pub backend: PolicyEngineBackendConfig,
+    #[serde(default = "default_true")]
+    pub flagged_only: bool,
+    // Replacement of masked content when the engine does not provide one
+    #[serde(default = "default_mask_text")]
+    pub mask_text: String,
+    #[serde(default = "default_policy_engine_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+// Engine evaluating the policy.
+//
+// * `Opa` - An Open Policy Agent data API URL, with the TLS and pool settings to reach it
+// * `Cedar` - A Cedar policy file, requires the `cedar` feature
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+#[cfg_attr(not(feature = "cedar"), allow(dead_code))]
+pub enum PolicyEngineBackendConfig {
+    Opa {
+        url: String,
+        #[serde(default)]
+        tls: UpstreamTlsConfig,
+        #[serde(default)]
+        connection: ConnectionConfig,
+    },
+    Cedar {
+        policy_file: String,
+    },
+}
+
+fn default_mask_text() -> String {
+    "[REDACTED]".to_string()
+}
+
+fn default_policy_engine_timeout_ms() -> u64 {
+    500
+}
+
+// Per-user request and token quotas.
+//
+// Usage is stored in the SQLite database at `database` so it survives restarts.
+// Callers over a quota are rejected with `429` until the period resets (UTC).
+#[derive(Debug, Clone, Deserialize)]
+pub struct QuotaConfig {
+    #[serde(default = "default_quota_database")]
+    pub database: String,
+    #[serde(default)]
+    pub daily: QuotaLimitsConfig,
+    #[serde(default)]
+    pub monthly: QuotaLimitsConfig,
+}
+
+// Limits for one quota period; unset limits are not enforced.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct QuotaLimitsConfig {
+    #[serde(default)]
+    pub requests: Option<u64>,
+    // Generated tokens, as reported by Ollama's `eval_count`
+    #[serde(default)]
+    pub tokens: Option<u64>,
+}
+
+fn default_quota_database() -> String {
+    "quotas.db".to_string()
+}
+
+// Temporary ban after repeated blocks.
+//
+// A caller with `threshold` blocked requests within `window_secs` is rejected
+// with `429` for `cooldown_secs`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AutoBanConfig {
+    #[serde(default = "default_ban_threshold")]
+    pub threshold: usize,
+    #[serde(default = "default_ban_window_secs")]
+    pub window_secs: u64,
+    #[serde(default = "default_ban_cooldown_secs")]
+    pub cooldown_secs: u64,
+}
+
+fn default_ban_threshold() -> usize {
+    5
+}
+
+fn default_ban_window_secs() -> u64 {
+    600
+}
+
+fn default_ban_cooldown_secs() -> u64 {
+    900
+}
+
+// Cumulative scanning of the recent turns of a conversation.
+//
+// Every `scan_every_turns` new user turns, the last `window_turns` turns are
+// joined and scanned as one prompt. Chat conversations without the session
+// header are recognized by their first user message.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SessionScanConfig {
+    #[serde(default = "default_session_header")]
+    pub header: String,
+    #[serde(default = "default_session_window_turns")]
+    pub window_turns: usize,
+    #[serde(default = "default_session_scan_every_turns")]
+    pub scan_every_turns: usize,
+    #[serde(default = "default_session_idle_timeout_secs")]
+    pub idle_timeout_secs: u64,
+    #[serde(default = "default_max_sessions")]
+    pub max_sessions: usize,
+}
+
+fn default_session_header() -> String {
+    "X-Session-Id".to_string()
+}
+
+fn default_session_window_turns() -> usize {
+    8
+}
+
+fn default_session_scan_every_turns() -> usize {
+    3
+}
+
+fn default_session_idle_timeout_secs() -> u64 {
+    1800
+}
+
+fn default_max_sessions() -> usize {
+    10000
+}
+
+// Upper bounds for model options sent by clients.
+//
+// With `mode: clamp` values above a limit are lowered to it, with `mode: reject`
+// such requests fail with `400`. An unset `num_predict` is capped in both modes.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct OptionLimitsConfig {
+    #[serde(default)]
+    pub max_num_ctx: Option<u32>,
+    #[serde(default)]
+    pub max_num_predict: Option<u32>,
+    #[serde(default)]
+    pub mode: LimitMode,
+}
+
+// How option values above the configured limits are handled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LimitMode {
+    #[default]
+    Clamp,
+    Reject,
+}
+
+// Upper bounds for the size of prompts sent by clients.
+//
+// `max_tokens` is approximated as four characters per token. With `mode: truncate`
+// the oldest chat messages are dropped and the remaining text is cut to the limit,
+// with `mode: reject` such requests fail with `400`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PromptLimitsConfig {
+    #[serde(default)]
+    pub max_chars: Option<usize>,
+    #[serde(default)]
+    pub max_tokens: Option<usize>,
+    #[serde(default)]
+    pub mode: PromptLimitMode,
+}
+
+// How prompts above the configured size are handled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PromptLimitMode {
+    #[default]
+    Reject,
+    Truncate,
+}
+
+// Scanning of `/api/embeddings` inputs.
+//
+// With `mode: hash` inputs are not sent to the scanner; their SHA-256 is written
+// to the audit log instead, and a `sample_rate` fraction of them is still scanned.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct EmbeddingsScanConfig {
+    #[serde(default)]
+    pub mode: EmbeddingsScanMode,
+    #[serde(default)]
+    pub sample_rate: f64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmbeddingsScanMode {
+    #[default]
+    Scan,
+    Hash,
+}
+
+impl Default for PolicyConfig {
+    fn default() -> Self {
+        Self {
+            allowed_models: Vec::new(),
+            blocked_models: Vec::new(),
+            conversation_scope: ConversationScope::default(),
+            scan_window: default_scan_window(),
+            allow_scope_header: false,
+            skip_roles: Vec::new(),
+            scan_images: false,
+            option_limits: OptionLimitsConfig::default(),
+            prompt_limits: PromptLimitsConfig::default(),
+            embeddings: EmbeddingsScanConfig::default(),
+            daily_token_budget: None,
+            session_scan: None,
+            auto_ban: None,
+            quotas: None,
+            engine: None,
+        }
+    }
+}
+
+// Which part of a chat conversation is scanned on each turn.
+//
+// * `All` - Every message of the history, one scan each
+// * `Latest` - Only the newest user message and the tool results after it
+// * `Window` - The last `scan_window` messages, concatenated into one scan
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConversationScope {
+    #[default]
+    All,
+    Latest,
+    Window,
+}
+
+fn default_scan_window() -> usize {
+    4
+}
+
+// Selection of the content security backend.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ScannerConfig {
+    #[serde(default)]
+    pub backend: ScannerBackend,
+    #[serde(default)]
+    pub llama_guard: Option<LlamaGuardConfig>,
+    #[serde(default)]
+    pub chain: Vec<ChainStageConfig>,
+    #[serde(default)]
+    pub local: LocalScannerConfig,
+    // Replace PANW with the local scanner when no PANW API key is configured
+    #[serde(default)]
+    pub offline: bool,
+}
+
+// Rules of the built-in local pre-filter scanner.
+#[derive(Debug, Clone, Deserialize)]
+pub struct LocalScannerConfig {
+    #[serde(default)]
+    pub regex: Vec<RegexRule>,
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    #[serde(default = "default_true")]
+    pub detect_credit_cards: bool,
+    // Block long random-looking tokens such as API keys and passwords
+    #[serde(default)]
+    pub detect_secrets: bool,
+    #[serde(default = "default_secret_min_length")]
+    pub secret_min_length: usize,
+    // Shannon entropy in bits per character above which a token counts as a secret
+    #[serde(default = "default_secret_min_entropy")]
+    pub secret_min_entropy: f64,
+}
+
+impl Default for LocalScannerConfig {
+    fn default() -> Self {
+        Self {
+            regex: Vec::new(),
+            keywords: Vec::new(),
+            detect_credit_cards: true,
+            detect_secrets: false,
+            secret_min_length: default_secret_min_length(),
+            secret_min_entropy: default_secret_min_entropy(),
+        }
+    }
+}
+
+fn default_secret_min_length() -> usize {
+    20
+}
+
+fn default_secret_min_entropy() -> f64 {
+    4.0
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RegexRule {
+    pub name: String,
+    pub pattern: String,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl ScannerConfig {
+    // Whether the PANW AI Runtime API is used, directly or as a chain stage.
+    pub fn uses_panw(&self) -> bool {
+        match self.backend {
+            ScannerBackend::Chain => self
+                .chain
+                .iter()
+                .any(|stage| stage.backend == ScannerBackend::Panw),
+            backend => backend == ScannerBackend::Panw,
+        }
+    }
+
+    // Drops the PANW backend, using the local scanner in its place.
+    //
+    // A chain keeps its other stages and falls back to the local scanner alone
+    // if PANW was its only stage.
+    fn without_panw(&mut self) {
+        match self.backend {
+            ScannerBackend::Panw => self.backend = ScannerBackend::Local,
+            ScannerBackend::Chain => {
+                self.chain
+                    .retain(|stage| stage.backend != ScannerBackend::Panw);
+                if self.chain.is_empty() {
+                    self.backend = ScannerBackend::Local;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// One stage of the `chain` scanner backend.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChainStageConfig {
+    pub backend: ScannerBackend,
+    #[serde(default)]
+    pub on_block: OnBlock,
+    #[serde(default)]
+    pub on_error: OnError,
+}
+
+// What a chain does when a stage blocks content.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OnBlock {
+    // Stop and block immediately
+    #[default]
+    ShortCircuit,
+    // Keep running later stages, then block
+    Continue,
+}
+
+// What a chain does when a stage fails to produce a verdict.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OnError {
+    // Fail the assessment
+    #[default]
+    Fail,
+    // Ignore the stage and continue with the next one
+    Skip,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScannerBackend {
+    #[default]
+    Panw,
+    LlamaGuard,
+    Local,
+    Chain,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LlamaGuardConfig {
+    #[serde(default = "default_llama_guard_model")]
+    pub model: String,
+    #[serde(default)]
+    pub base_url: Option<String>,
+}
+
+impl Default for LlamaGuardConfig {
+    fn default() -> Self {
+        Self {
+            model: default_llama_guard_model(),
+            base_url: None,
+        }
+    }
+}
+
+fn default_llama_guard_model() -> String {
+    "llama-guard3".to_string()
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ServerConfig {
+    pub host: String,
+    pub port: u16,
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    #[serde(default = "default_shutdown_timeout_secs")]
+    pub shutdown_timeout_secs: u64,
+    #[serde(default)]
+    pub body_limits: BodyLimitConfig,
+    // Report the time spent per stage in the `X-Proxy-Timing` response header
+    #[serde(default)]
+    pub timing_header: bool,
+    // Compress responses and accept compressed request bodies (gzip, deflate, br); opt-in
+    #[serde(default)]
+    pub compression: bool,
+    #[serde(default)]
+    pub streaming: StreamingConfig,
+}
+
+// Buffering of streamed Ollama responses while their chunks are scanned.
+#[derive(Debug, Clone, Deserialize)]
+pub struct StreamingConfig {
+    // Chunks read ahead from Ollama before reading pauses
+    #[serde(default = "default_stream_buffer_chunks")]
+    pub buffer_chunks: usize,
+    // Chunks of one stream scanned concurrently; order is preserved
+    #[serde(default = "default_max_scans_in_flight")]
+    pub max_scans_in_flight: usize,
+    // Idle seconds after which a keep-alive frame is sent to the client; 0 disables them.
+    // NDJSON streams get an empty, non-final chunk so line-by-line JSON clients parse it
+    #[serde(default)]
+    pub keep_alive_secs: u64,
+}
+
+impl Default for StreamingConfig {
+    fn default() -> Self {
+        Self {
+            buffer_chunks: default_stream_buffer_chunks(),
+            max_scans_in_flight: default_max_scans_in_flight(),
+            keep_alive_secs: 0,
+        }
+    }
+}
+
+fn default_stream_buffer_chunks() -> usize {
+    64
+}
+
+fn default_max_scans_in_flight() -> usize {
+    4
+}
+
+fn default_shutdown_timeout_secs() -> u64 {
+    30
+}
+
+// Maximum accepted request body sizes in bytes.
+//
+// Chat and generate allow larger bodies since they may carry base64 images.
+// Larger requests are rejected with `413` before being deserialized.
+#[derive(Debug, Clone, Deserialize)]
+pub struct BodyLimitConfig {
+    #[serde(default = "default_body_limit_bytes")]
+    pub default_bytes: usize,
+    #[serde(default = "default_generation_body_limit_bytes")]
+    pub chat_bytes: usize,
+    #[serde(default = "default_generation_body_limit_bytes")]
+    pub generate_bytes: usize,
+    #[serde(default = "default_embeddings_body_limit_bytes")]
+    pub embeddings_bytes: usize,
+}
+
+impl Default for BodyLimitConfig {
+    fn default() -> Self {
+        Self {
+            default_bytes: default_body_limit_bytes(),
+            chat_bytes: default_generation_body_limit_bytes(),
+            generate_bytes: default_generation_body_limit_bytes(),
+            embeddings_bytes: default_embeddings_body_limit_bytes(),
+        }
+    }
+}
+
+fn default_body_limit_bytes() -> usize {
+    1024 * 1024
+}
+
+fn default_generation_body_limit_bytes() -> usize {
+    32 * 1024 * 1024
+}
+
+fn default_embeddings_body_limit_bytes() -> usize {
+    8 * 1024 * 1024
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TlsConfig {
+    pub cert_path: String,
+    pub key_path: String,
+    #[serde(default = "default_tls_reload_interval_secs")]
+    pub reload_interval_secs: u64,
+}
+
+fn default_tls_reload_interval_secs() -> u64 {
+    30
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct OllamaConfig {
+    // A single URL or a list of URLs forming a load-balanced pool
+    #[serde(deserialize_with = "one_or_many")]
+    pub base_url: Vec<String>,
+    #[serde(default)]
+    pub load_balancing: LoadBalancing,
+    #[serde(default)]
+    pub health_check: HealthCheckConfig,
+    // Model name patterns routed to dedicated upstreams, first match wins
+    #[serde(default)]
+    pub routes: Vec<ModelRoute>,
+    // Backend retried when the selected one fails on an idempotent endpoint
+    #[serde(default)]
+    pub fallback_url: Option<String>,
+    // How long `/api/tags` and `/api/show` responses are cached, 0 disables caching
+    #[serde(default = "default_model_cache_ttl_secs")]
+    pub model_cache_ttl_secs: u64,
+    // Most `/api/show` and `/api/tags` responses cached at once
+    #[serde(default = "default_model_cache_max_entries")]
+    pub model_cache_max_entries: usize,
+    #[serde(default)]
+    pub response_cache: ResponseCacheConfig,
+    #[serde(default = "default_ollama_timeout_secs")]
+    pub timeout_secs: u64,
+    #[serde(default = "default_connect_timeout_secs")]
+    pub connect_timeout_secs: u64,
+    #[serde(default)]
+    pub tls: UpstreamTlsConfig,
+    #[serde(default)]
+    pub connection: ConnectionConfig,
+}
+
+// Sends requests for models matching `model` to a dedicated pool.
+//
+// `model` is a wildcard pattern such as `llama3*` or `*-embed`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ModelRoute {
+    pub model: String,
+    #[serde(deserialize_with = "one_or_many")]
+    pub base_url: Vec<String>,
+}
+
+// Strategy used to pick an upstream from the Ollama pool.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoadBalancing {
+    #[default]
+    RoundRobin,
+    LeastConnections,
+}
+
+// Periodic health probing of the Ollama upstreams.
+//
+// Unhealthy upstreams are skipped until a probe succeeds again.
+#[derive(Debug, Clone, Deserialize)]
+pub struct HealthCheckConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    #[serde(default = "default_health_check_interval_secs")]
+    pub interval_secs: u64,
+    #[serde(default = "default_health_check_path")]
+    pub path: String,
+}
+
+impl Default for HealthCheckConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_secs: default_health_check_interval_secs(),
+            path: default_health_check_path(),
+        }
+    }
+}
+
+fn default_model_cache_ttl_secs() -> u64 {
+    30
+}
+
+fn default_model_cache_max_entries() -> usize {
+    1000
+}
+
+fn default_health_check_interval_secs() -> u64 {
+    10
+}
+
+fn default_health_check_path() -> String {
+    "/api/version".to_string()
+}
+
+// Accepts either a single string or a list of strings.
+fn one_or_many<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(url) => vec![url],
+        OneOrMany::Many(urls) => urls,
+    })
+}
+
+// TLS settings for connections to an upstream service.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct UpstreamTlsConfig {
+    #[serde(default)]
+    pub ca_bundle_path: Option<String>,
+    #[serde(default)]
+    pub client_cert_path: Option<String>,
+    #[serde(default)]
+    pub client_key_path: Option<String>,
+    #[serde(default)]
+    pub insecure_skip_verify: bool,
+}
+
+// Connection reuse settings for an upstream HTTP client.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ConnectionConfig {
+    // Idle connections kept open per host for reuse
+    #[serde(default = "default_pool_max_idle_per_host")]
+    pub pool_max_idle_per_host: usize,
+    // Time after which an idle pooled connection is closed
+    #[serde(default = "default_pool_idle_timeout_secs")]
+    pub pool_idle_timeout_secs: u64,
+    // TCP keepalive probe interval, 0 disables
+    #[serde(default = "default_tcp_keepalive_secs")]
+    pub tcp_keepalive_secs: u64,
+    #[serde(default)]
+    pub http_version: HttpVersion,
+    // HTTP/2 PING interval keeping idle connections alive, 0 disables
+    #[serde(default = "default_http2_keep_alive_interval_secs")]
+    pub http2_keep_alive_interval_secs: u64,
+}
+
+impl Default for ConnectionConfig {
+    fn default() -> Self {
+        Self {
+            pool_max_idle_per_host: default_pool_max_idle_per_host(),
+            pool_idle_timeout_secs: default_pool_idle_timeout_secs(),
+            tcp_keepalive_secs: default_tcp_keepalive_secs(),
+            http_version: HttpVersion::default(),
+            http2_keep_alive_interval_secs: default_http2_keep_alive_interval_secs(),
+        }
+    }
+}
+
+fn default_pool_max_idle_per_host() -> usize {
+    32
+}
+
+fn default_pool_idle_timeout_secs() -> u64 {
+    90
+}
+
+fn default_tcp_keepalive_secs() -> u64 {
+    60
+}
+
+fn default_http2_keep_alive_interval_secs() -> u64 {
+    30
+}
+
+// HTTP version used for upstream requests.
+//
+// * `Auto` - HTTP/2 when negotiated through TLS ALPN, HTTP/1.1 otherwise
+// * `Http1` - Always HTTP/1.1
+// * `Http2` - Always HTTP/2, also over plain HTTP (prior knowledge)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HttpVersion {
+    #[default]
+    Auto,
+    Http1,
+    Http2,
+}
+
+fn default_ollama_timeout_secs() -> u64 {
+    300
+}
+
+fn default_connect_timeout_secs() -> u64 {
+    10
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SecurityConfig {
+    // Explicit API endpoint; leave empty to use `region`
+    #[serde(default)]
+    pub base_url: String,
+    #[serde(default)]
+    pub region: Option<PanwRegion>,
+    // Prepended to the scan path, e.g. when PANW is reached through an API gateway
+    #[serde(default)]
+    pub path_prefix: String,
+    #[serde(default = "default_panw_api_version")]
+    pub api_version: String,
+    #[serde(default)]
+    pub api_key: String,
+    #[serde(default)]
+    pub api_key_file: Option<String>,
+    #[serde(default)]
+    pub secret_source: Option<SecretSource>,
+    // Profile and application settings are only required by the PANW backend
+    #[serde(default)]
+    pub profile_name: String,
+    #[serde(default)]
+    pub app_name: String,
+    #[serde(default)]
+    pub app_user: String,
+    #[serde(default = "default_security_timeout_secs")]
+    pub timeout_secs: u64,
+    #[serde(default = "default_connect_timeout_secs")]
+    pub connect_timeout_secs: u64,
+    #[serde(default)]
+    pub tls: UpstreamTlsConfig,
+    #[serde(default)]
+    pub connection: ConnectionConfig,
+    #[serde(default = "default_max_concurrent_scans")]
+    pub max_concurrent_scans: usize,
+    #[serde(default = "default_scan_queue_timeout_secs")]
+    pub scan_queue_timeout_secs: u64,
+    #[serde(default)]
+    pub retry: RetryConfig,
+    #[serde(default)]
+    pub circuit_breaker: CircuitBreakerConfig,
+    #[serde(default)]
+    pub fail_open: bool,
+    #[serde(default)]
+    pub content_limit: ContentLimitConfig,
+    // Expose scan id, report id, category and action as `X-PANW-*` response headers
+    #[serde(default)]
+    pub verdict_headers: bool,
+    #[serde(default)]
+    pub mode: EnforcementMode,
+    // Profiles clients may select with the `X-PANW-Profile` header; empty disables the header
+    #[serde(default)]
+    pub allowed_profiles: Vec<String>,
+    #[serde(default)]
+    pub scan_cache: ScanCacheConfig,
+    #[serde(default)]
+    pub credential_check: CredentialCheckConfig,
+}
+
+impl SecurityConfig {
+    // Whether a PANW API key is configured, inline, as a file or in a secret manager.
+    pub fn has_api_key(&self) -> bool {
+        !self.api_key.is_empty() || self.api_key_file.is_some() || self.secret_source.is_some()
+    }
+
+    // Base URL of the PANW AI Runtime API, from `base_url` or else `region`.
+    pub fn resolved_base_url(&self) -> String {
+        match (&self.region, self.base_url.is_empty()) {
+            (Some(region), true) => region.base_url().to_string(),
+            _ => self.base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    // Path of the synchronous scan endpoint, e.g. `/v1/scan/sync/request`.
+    pub fn scan_path(&self) -> String {
+        let prefix = self.path_prefix.trim_matches('/');
+        let version = self.api_version.trim_matches('/');
+        if prefix.is_empty() {
+            format!("/{}/scan/sync/request", version)
+        } else {
+            format!("/{}/{}/scan/sync/request", prefix, version)
+        }
+    }
+}
+
+// Regional PANW AI Runtime API deployment.
+//
+// * `Us` - United States
+// * `Eu` - European Union (Germany)
+// * `In` - India
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PanwRegion {
+    Us,
+    Eu,
+    In,
+}
+
+impl PanwRegion {
+    pub fn base_url(self) -> &'static str {
+        match self {
+            PanwRegion::Us => "https://service.api.aisecurity.paloaltonetworks.com",
+            PanwRegion::Eu => "https://service-de.api.aisecurity.paloaltonetworks.com",
+            PanwRegion::In => "https://service-in.api.aisecurity.paloaltonetworks.com",
+        }
+    }
+}
+
+fn default_panw_api_version() -> String {
+    "v1".to_string()
+}
+
+// Startup and periodic verification of the PANW API key and profile.
+//
+// On by default whenever PANW scans are configured.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CredentialCheckConfig {
+    pub enabled: bool,
+    pub interval_secs: u64,
+}
+
+impl Default for CredentialCheckConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_secs: 300,
+        }
+    }
+}
+
+// Cache of scan verdicts for content seen before.
+//
+// Disabled with the default `ttl_secs` of 0.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ScanCacheConfig {
+    pub ttl_secs: u64,
+    pub max_entries: usize,
+}
+
+impl Default for ScanCacheConfig {
+    fn default() -> Self {
+        Self {
+            ttl_secs: 0,
+            max_entries: 10_000,
+        }
+    }
+}
+
+// Cache of allowed non-streaming generate and chat responses.
+//
+// Disabled with the default `ttl_secs` of 0. With `deterministic_only`, only
+// requests with `temperature: 0` or a fixed `seed` are cached.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ResponseCacheConfig {
+    pub ttl_secs: u64,
+    pub max_entries: usize,
+    pub max_entry_bytes: usize,
+    pub deterministic_only: bool,
+}
+
+impl Default for ResponseCacheConfig {
+    fn default() -> Self {
+        Self {
+            ttl_secs: 0,
+            max_entries: 1000,
+            max_entry_bytes: 1024 * 1024,
+            deterministic_only: true,
+        }
+    }
+}
+
+// What happens to content the scanner flags.
+//
+// * `Enforce` - Block the request or response
+// * `Monitor` - Let it through and only log the verdict to the `audit` target
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EnforcementMode {
+    #[default]
+    Enforce,
+    Monitor,
+}
 
-    #[error("Validation error: {0}")]
-    ValidationError(String),
+// How content larger than the PANW scan API accepts is handled.
+//
+// * `Chunk` - Split the content into several `contents` entries of one scan request
+// * `HeadTail` - Scan only the beginning and the end of the content
+// * `Truncate` - Scan only the beginning of the content and log a warning
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OversizeStrategy {
+    #[default]
+    Chunk,
+    HeadTail,
+    Truncate,
 }
 
+// Size limit applied to each piece of content sent to PANW.
 #[derive(Debug, Clone, Deserialize)]
-pub struct Config {
-    pub server: ServerConfig,
-    pub ollama: OllamaConfig,
-    pub security: SecurityConfig,
+#[serde(default)]
+pub struct ContentLimitConfig {
+    pub max_bytes: usize,
+    pub strategy: OversizeStrategy,
+}
+
+impl Default for ContentLimitConfig {
+    fn default() -> Self {
+        Self {
+            max_bytes: 1024 * 1024,
+            strategy: OversizeStrategy::Chunk,
+        }
+    }
 }
 
+// Circuit breaker settings for the PANW AI Runtime API.
 #[derive(Debug, Clone, Deserialize)]
-pub struct ServerConfig {
-    pub host: String,
-    pub port: u16,
+#[serde(default)]
+pub struct CircuitBreakerConfig {
+    pub failure_threshold: u32,
+    pub cooldown_secs: u64,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            cooldown_secs: 30,
+        }
+    }
 }
 
+// External secret manager holding the PANW API token.
+//
+// Each backend is only available when the crate is built with the matching feature
+// (`vault` or `aws-secrets`).
 #[derive(Debug, Clone, Deserialize)]
-pub struct OllamaConfig {
-    pub base_url: String,
+#[serde(tag = "type", rename_all = "snake_case")]
+#[cfg_attr(not(all(feature = "vault", feature = "aws-secrets")), allow(dead_code))]
+pub enum SecretSource {
+    Vault {
+        address: String,
+        token: String,
+        #[serde(default = "default_vault_mount")]
+        mount: String,
+        path: String,
+        #[serde(default = "default_secret_field")]
+        field: String,
+        // Same settings as `ollama.tls` and `ollama.connection`, e.g. for a private CA
+        #[serde(default)]
+        tls: UpstreamTlsConfig,
+        #[serde(default)]
+        connection: ConnectionConfig,
+    },
+    AwsSecretsManager {
+        secret_id: String,
+        #[serde(default)]
+        field: Option<String>,
+    },
+}
+
+fn default_vault_mount() -> String {
+    "secret".to_string()
+}
+
+fn default_secret_field() -> String {
+    "api_key".to_string()
 }
 
+// Retry policy for transient PANW AI Runtime API failures.
 #[derive(Debug, Clone, Deserialize)]
-pub struct SecurityConfig {
-    pub base_url: String,
-    pub api_key: String,
-    pub profile_name: String,
-    pub app_name: String,
-    pub app_user: String,
+#[serde(default)]
+pub struct RetryConfig {
+    pub max_attempts: u32,
+    pub base_delay_ms: u64,
+    pub max_delay_ms: u64,
+    pub jitter: bool,
+    pub retry_on: Vec<u16>,
+    pub retry_on_network_errors: bool,
 }
 
-pub fn load_config(path: &str) -> Result<Config, ConfigError> {
-    let content = fs::read_to_string(path)?;
-    let config: Config = serde_yaml::from_str(&content)?;
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay_ms: 200,
+            max_delay_ms: 5000,
+            jitter: true,
+            retry_on: vec![429, 500, 502, 503, 504],
+            retry_on_network_errors: true,
+        }
+    }
+}
+
+fn default_security_timeout_secs() -> u64 {
+    30
+}
+
+fn default_max_concurrent_scans() -> usize {
+    16
+}
+
+fn default_scan_queue_timeout_secs() -> u64 {
+    30
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AuthConfig {
+    #[serde(default)]
+    pub jwt: Option<JwtConfig>,
+    #[serde(default)]
+    pub admin: Option<AdminConfig>,
+    // Client API keys, each mapped to its own tenant settings
+    #[serde(default)]
+    pub api_keys: Vec<TenantConfig>,
+}
+
+// A client API key and the PANW metadata and limits of its tenant.
+//
+// Unset PANW fields fall back to the global `security` values.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TenantConfig {
+    pub name: String,
+    pub key: String,
+    #[serde(default)]
+    pub app_name: Option<String>,
+    #[serde(default)]
+    pub app_user: Option<String>,
+    #[serde(default)]
+    pub profile_name: Option<String>,
+    // Wildcard patterns of the models this tenant may use; empty allows all
+    #[serde(default)]
+    pub allowed_models: Vec<String>,
+    #[serde(default)]
+    pub requests_per_minute: Option<u32>,
+    // Profiles this tenant may select with `X-PANW-Profile`; empty keeps it on `profile_name`
+    #[serde(default)]
+    pub allowed_profiles: Vec<String>,
+}
+
+// Access to the `/admin` API, which is only mounted when this section is set.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AdminConfig {
+    // Bearer token required on every admin request
+    pub token: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct JwtConfig {
+    pub issuer: String,
+    pub audience: String,
+    pub jwks_url: String,
+    #[serde(default = "default_jwks_refresh_secs")]
+    pub jwks_refresh_secs: u64,
+    #[serde(default)]
+    pub leeway_secs: u64,
+    // Claim listing the profiles a caller may select with `X-PANW-Profile`, as an
+    // array or a space-separated string; without it JWT callers cannot select one
+    #[serde(default)]
+    pub profiles_claim: Option<String>,
+    // Bounds on fetching the JWKS, so a hanging IdP cannot stall authentication
+    #[serde(default = "default_jwks_timeout_secs")]
+    pub timeout_secs: u64,
+    #[serde(default = "default_connect_timeout_secs")]
+    pub connect_timeout_secs: u64,
+    #[serde(default)]
+    pub tls: UpstreamTlsConfig,
+    #[serde(default)]
+    pub connection: ConnectionConfig,
+}
+
+fn default_jwks_refresh_secs() -> u64 {
+    3600
+}
+
+fn default_jwks_timeout_secs() -> u64 {
+    10
+}
+
+// Loads the configuration file, applying an optional profile overlay and environment variables.
+//
+// When a profile is given, `config.<profile>.yaml` next to the base file is deep-merged
+// on top of it, so environments only need to override what differs.
+// `${VAR}` and `${VAR:-default}` references inside the YAML are expanded first,
+// then any `PANW_OLLAMA__SECTION__KEY` variable overrides the matching value,
+// so env always takes precedence over the files.
+//
+// # Arguments
+//
+// * `path` - Path to the base configuration file
+// * `profile` - Optional profile name selecting the overlay file
+pub fn load_config(path: &str, profile: Option<&str>) -> Result<Config, ConfigError> {
+    let mut value = read_yaml(path)?;
+    if let Some(profile) = profile {
+        let overlay_path = profile_path(path, profile);
+        merge_values(&mut value, read_yaml(&overlay_path)?);
+    }
+    apply_env_overrides(&mut value, env::vars());
+    config_from_value(value)
+}
+
+// Deserializes and validates a configuration from its merged YAML value.
+pub(crate) fn config_from_value(value: Value) -> Result<Config, ConfigError> {
+    let mut config = Config::deserialize(LenientValue(value))?;
+    config.apply_offline_mode();
     config.validate()?;
     Ok(config)
 }
 
+// Reads and parses a YAML file after expanding environment references.
+fn read_yaml(path: &str) -> Result<Value, ConfigError> {
+    let content = fs::read_to_string(path)?;
+    let mut value: Value = serde_yaml::from_str(&content)?;
+    interpolate_env(&mut value)?;
+    Ok(value)
+}
+
+// Derives the overlay file path for a profile, e.g. `config.yaml` + `prod` -> `config.prod.yaml`.
+fn profile_path(path: &str, profile: &str) -> String {
+    let base = Path::new(path);
+    let stem = base
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "config".to_string());
+    let file_name = match base.extension() {
+        Some(ext) => format!("{}.{}.{}", stem, profile, ext.to_string_lossy()),
+        None => format!("{}.{}", stem, profile),
+    };
+    base.with_file_name(file_name)
+        .to_string_lossy()
+        .into_owned()
+}
+
+// Deep-merges `overlay` into `base`: mappings are merged key by key,
+// any other value in the overlay replaces the base value.
+pub(crate) fn merge_values(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Mapping(base_map), Value::Mapping(overlay_map)) => {
+            for (key, overlay_value) in overlay_map {
+                match base_map.get_mut(&key) {
+                    Some(base_value) => merge_values(base_value, overlay_value),
+                    None => {
+                        base_map.insert(key, overlay_value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+// Expands `${VAR}` and `${VAR:-default}` references in the string scalars of a parsed tree.
+//
+// Working on parsed values rather than raw text keeps comments untouched and lets
+// values hold any character, including `#`, `: ` and newlines.
+fn interpolate_env(value: &mut Value) -> Result<(), ConfigError> {
+    match value {
+        Value::String(text) if text.contains("${") => *text = expand_env(text)?,
+        Value::Sequence(items) => {
+            for item in items {
+                interpolate_env(item)?;
+            }
+        }
+        Value::Mapping(map) => {
+            for (_, item) in map.iter_mut() {
+                interpolate_env(item)?;
+            }
+        }
+        Value::Tagged(tagged) => interpolate_env(&mut tagged.value)?,
+        _ => {}
+    }
+    Ok(())
+}
+
+// Expands the environment references of a single string.
+fn expand_env(text: &str) -> Result<String, ConfigError> {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after.find('}').ok_or_else(|| {
+            ConfigError::ValidationError(format!("Unterminated ${{...}} in: {}", text.trim()))
+        })?;
+
+        let expr = &after[..end];
+        let (name, default) = match expr.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (expr, None),
+        };
+
+        match (env::var(name), default) {
+            (Ok(value), _) => output.push_str(&value),
+            (Err(_), Some(default)) => output.push_str(default),
+            (Err(_), None) => {
+                return Err(ConfigError::ValidationError(format!(
+                    "Environment variable {} referenced in config is not set",
+                    name
+                )))
+            }
+        }
+        rest = &after[end + 1..];
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+// Applies `PANW_OLLAMA__`-prefixed variables to the parsed YAML tree.
+fn apply_env_overrides(root: &mut Value, vars: impl Iterator<Item = (String, String)>) {
+    for (key, raw) in vars {
+        let Some(path) = key.strip_prefix(ENV_PREFIX) else {
+            continue;
+        };
+
+        let segments: Vec<String> = path.split("__").map(|s| s.to_lowercase()).collect();
+        if segments.iter().any(|s| s.is_empty()) {
+            continue;
+        }
+
+        set_path(root, &segments, raw);
+    }
+}
+
+// Sets a nested value, creating intermediate mappings as needed.
+//
+// Values are always inserted as strings, so API keys made of digits or values such
+// as `~` keep their text; numeric and boolean settings are converted when the
+// configuration is deserialized (see `LenientValue`).
+fn set_path(node: &mut Value, segments: &[String], raw: String) {
+    let Some((first, rest)) = segments.split_first() else {
+        return;
+    };
+
+    if !node.is_mapping() {
+        *node = Value::Mapping(Mapping::new());
+    }
+    let Some(map) = node.as_mapping_mut() else {
+        return;
+    };
+    let key = Value::String(first.clone());
+
+    if rest.is_empty() {
+        map.insert(key, Value::String(raw));
+        return;
+    }
+
+    if !map.contains_key(&key) {
+        map.insert(key.clone(), Value::Mapping(Mapping::new()));
+    }
+    if let Some(child) = map.get_mut(&key) {
+        set_path(child, rest, raw);
+    }
+}
+
+// A YAML tree deserialized with strings accepted where numbers or booleans are
+// expected, and numbers or booleans accepted where strings are expected.
+//
+// Environment references and `PANW_OLLAMA__` overrides always yield strings, so
+// this lets `port: ${PORT}` reach a numeric field. Fields of internally tagged
+// sections (`type: ...`) are buffered by serde and keep strict types.
+struct LenientValue(Value);
+
+impl LenientValue {
+    fn parse<T: std::str::FromStr>(&self) -> Option<T> {
+        match &self.0 {
+            Value::String(text) => text.trim().parse().ok(),
+            _ => None,
+        }
+    }
+}
+
+impl IntoDeserializer<'_, serde_yaml::Error> for LenientValue {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident($ty:ty)),* $(,)?) => {$(
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+            match self.parse::<$ty>() {
+                Some(parsed) => visitor.$visit(parsed),
+                None => self.0.$method(visitor),
+            }
+        }
+    )*};
+}
+
+impl<'de> Deserializer<'de> for LenientValue {
+    type Error = serde_yaml::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            Value::Sequence(items) => {
+                SeqDeserializer::new(items.into_iter().map(LenientValue)).deserialize_any(visitor)
+            }
+            Value::Mapping(map) => MapDeserializer::new(
+                map.into_iter()
+                    .map(|(key, value)| (LenientValue(key), LenientValue(value))),
+            )
+            .deserialize_any(visitor),
+            value => value.deserialize_any(visitor),
+        }
+    }
+
+    deserialize_parsed! {
+        deserialize_bool => visit_bool(bool),
+        deserialize_i8 => visit_i8(i8),
+        deserialize_i16 => visit_i16(i16),
+        deserialize_i32 => visit_i32(i32),
+        deserialize_i64 => visit_i64(i64),
+        deserialize_u8 => visit_u8(u8),
+        deserialize_u16 => visit_u16(u16),
+        deserialize_u32 => visit_u32(u32),
+        deserialize_u64 => visit_u64(u64),
+        deserialize_f32 => visit_f32(f32),
+        deserialize_f64 => visit_f64(f64),
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            Value::Number(number) => visitor.visit_string(number.to_string()),
+            Value::Bool(flag) => visitor.visit_string(flag.to_string()),
+            value => LenientValue(value).deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            // An empty section, e.g. `models:` with every entry commented out
+            Value::Null => LenientValue(Value::Sequence(Vec::new())).deserialize_any(visitor),
+            value => LenientValue(value).deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            Value::Null => LenientValue(Value::Mapping(Mapping::new())).deserialize_any(visitor),
+            value => LenientValue(value).deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            Value::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.0.deserialize_enum(name, variants, visitor)
+    }
+
+    forward_to_deserialize_any! {
+        char bytes byte_buf unit unit_struct tuple tuple_struct identifier ignored_any
+    }
+}
+
 impl Config {
+    // Switches to local scanning when offline mode is on and no PANW API key is set.
+    //
+    // This lets the gateway be evaluated, or keep basic guardrails, without an
+    // AI Runtime Security subscription. With a key configured, nothing changes.
+    pub fn apply_offline_mode(&mut self) {
+        if !self.scanner.offline || self.security.has_api_key() || !self.scanner.uses_panw() {
+            return;
+        }
+        warn!("No PANW API key configured, running offline with the local scanner only");
+        self.scanner.without_panw();
+    }
+
     // Validate configuration values
     pub fn validate(&self) -> Result<(), ConfigError> {
         // Validate server config
@@ -58,30 +1729,393 @@ impl Config {
             ));
         }
 
+        if let Some(events) = &self.events {
+            if events.buffer_size == 0 || events.batch_size == 0 {
+                return Err(ConfigError::ValidationError(
+                    "events.buffer_size and batch_size must be greater than 0".into(),
+                ));
+            }
+            if matches!(&events.backend, EventBackendConfig::Kafka { brokers, .. } if brokers.is_empty())
+            {
+                return Err(ConfigError::ValidationError(
+                    "events.backend.brokers must list at least one Kafka broker".into(),
+                ));
+            }
+        }
+
+        if self.server.streaming.buffer_chunks == 0
+            || self.server.streaming.max_scans_in_flight == 0
+        {
+            return Err(ConfigError::ValidationError(
+                "server.streaming.buffer_chunks and max_scans_in_flight must be greater than 0"
+                    .into(),
+            ));
+        }
+
+        if let Some(tls) = &self.server.tls {
+            if tls.cert_path.is_empty() || tls.key_path.is_empty() {
+                return Err(ConfigError::ValidationError(
+                    "TLS cert_path and key_path are required".into(),
+                ));
+            }
+        }
+
         // Validate ollama config
-        if self.ollama.base_url.is_empty() {
+        if self.ollama.base_url.is_empty() || self.ollama.base_url.iter().any(String::is_empty) {
             return Err(ConfigError::ValidationError(
                 "Ollama base URL cannot be empty".into(),
             ));
         }
 
-        // Validate security config
-        if self.security.base_url.is_empty() || self.security.api_key.is_empty() {
+        for route in &self.ollama.routes {
+            if route.model.is_empty()
+                || route.base_url.is_empty()
+                || route.base_url.iter().any(String::is_empty)
+            {
+                return Err(ConfigError::ValidationError(
+                    "Ollama routes require a model pattern and a base URL".into(),
+                ));
+            }
+        }
+
+        if self.ollama.health_check.enabled && self.ollama.health_check.interval_secs == 0 {
+            return Err(ConfigError::ValidationError(
+                "Ollama health_check.interval_secs must be greater than 0".into(),
+            ));
+        }
+
+        // Validate security config (PANW credentials are only needed by the PANW backend)
+        if self.scanner.uses_panw()
+            && ((self.security.base_url.is_empty() && self.security.region.is_none())
+                || !self.security.has_api_key())
+        {
             return Err(ConfigError::ValidationError(
                 "Security credentials missing".into(),
             ));
         }
+        if !self.security.base_url.is_empty() && self.security.region.is_some() {
+            return Err(ConfigError::ValidationError(
+                "Set either security.base_url or security.region, not both".into(),
+            ));
+        }
+        if self.security.api_version.trim_matches('/').is_empty() {
+            return Err(ConfigError::ValidationError(
+                "security.api_version must not be empty".into(),
+            ));
+        }
 
         // Validate PANW AI AI profile config
-        if self.security.profile_name.is_empty()
-            || self.security.app_name.is_empty()
-            || self.security.app_user.is_empty()
+        if self.scanner.uses_panw()
+            && (self.security.profile_name.is_empty()
+                || self.security.app_name.is_empty()
+                || self.security.app_user.is_empty())
         {
             return Err(ConfigError::ValidationError(
                 "AI Profile settings missing".into(),
             ));
         }
 
+        if self
+            .security
+            .allowed_profiles
+            .iter()
+            .any(|p| p.trim().is_empty())
+        {
+            return Err(ConfigError::ValidationError(
+                "security.allowed_profiles cannot contain empty names".into(),
+            ));
+        }
+
+        if self.security.max_concurrent_scans == 0 {
+            return Err(ConfigError::ValidationError(
+                "security.max_concurrent_scans must be greater than zero".into(),
+            ));
+        }
+
+        if self.policy.conversation_scope == ConversationScope::Window
+            && self.policy.scan_window == 0
+        {
+            return Err(ConfigError::ValidationError(
+                "policy.scan_window must be greater than 0".into(),
+            ));
+        }
+
+        if let Some(role) = self
+            .policy
+            .skip_roles
+            .iter()
+            .find(|role| matches!(role.as_str(), "user" | "tool"))
+        {
+            return Err(ConfigError::ValidationError(format!(
+                "policy.skip_roles cannot contain '{}': user and tool messages are always scanned",
+                role
+            )));
+        }
+
+        let prompt_limits = &self.policy.prompt_limits;
+        if prompt_limits.max_chars == Some(0) || prompt_limits.max_tokens == Some(0) {
+            return Err(ConfigError::ValidationError(
+                "policy.prompt_limits values must be greater than 0".into(),
+            ));
+        }
+
+        if !(0.0..=1.0).contains(&self.policy.embeddings.sample_rate) {
+            return Err(ConfigError::ValidationError(
+                "policy.embeddings.sample_rate must be between 0 and 1".into(),
+            ));
+        }
+
+        if self.policy.daily_token_budget == Some(0) {
+            return Err(ConfigError::ValidationError(
+                "policy.daily_token_budget must be greater than 0".into(),
+            ));
+        }
+
+        if let Some(quotas) = &self.policy.quotas {
+            let limits = [&quotas.daily, &quotas.monthly];
+            if limits
+                .iter()
+                .any(|limit| limit.requests == Some(0) || limit.tokens == Some(0))
+            {
+                return Err(ConfigError::ValidationError(
+                    "policy.quotas limits must be greater than 0".into(),
+                ));
+            }
+        }
+
+        if let Some(ban) = &self.policy.auto_ban {
+            if ban.threshold == 0 || ban.window_secs == 0 || ban.cooldown_secs == 0 {
+                return Err(ConfigError::ValidationError(
+                    "policy.auto_ban threshold, window_secs and cooldown_secs must be greater than 0"
+                        .into(),
+                ));
+            }
+        }
+
+        if let Some(session) = &self.policy.session_scan {
+            if session.header.is_empty() {
+                return Err(ConfigError::ValidationError(
+                    "policy.session_scan.header cannot be empty".into(),
+                ));
+            }
+            if session.window_turns < 2 || session.scan_every_turns == 0 {
+                return Err(ConfigError::ValidationError(
+                    "policy.session_scan.window_turns must be at least 2 and scan_every_turns greater than 0".into(),
+                ));
+            }
+        }
+
+        if self.security.content_limit.max_bytes < 1024 {
+            return Err(ConfigError::ValidationError(
+                "security.content_limit.max_bytes must be at least 1024".into(),
+            ));
+        }
+
+        if self.security.credential_check.enabled
+            && self.security.credential_check.interval_secs == 0
+        {
+            return Err(ConfigError::ValidationError(
+                "security.credential_check.interval_secs must be greater than 0".into(),
+            ));
+        }
+
+        let access_log = &self.logging.access_log;
+        if access_log.enabled && access_log.output == AccessLogOutput::File {
+            if access_log.path.as_deref().unwrap_or_default().is_empty() {
+                return Err(ConfigError::ValidationError(
+                    "logging.access_log.path is required with output: file".into(),
+                ));
+            }
+            if access_log.max_bytes == 0 {
+                return Err(ConfigError::ValidationError(
+                    "logging.access_log.max_bytes must be greater than 0".into(),
+                ));
+            }
+        }
+
+        if self.security.retry.max_attempts == 0 {
+            return Err(ConfigError::ValidationError(
+                "security.retry.max_attempts must be at least 1".into(),
+            ));
+        }
+
+        if self.security.circuit_breaker.failure_threshold == 0 {
+            return Err(ConfigError::ValidationError(
+                "security.circuit_breaker.failure_threshold must be at least 1".into(),
+            ));
+        }
+
+        // Validate scanner chain
+        if self.scanner.backend == ScannerBackend::Chain {
+            if self.scanner.chain.is_empty() {
+                return Err(ConfigError::ValidationError(
+                    "scanner.chain must list at least one stage".into(),
+                ));
+            }
+            if self
+                .scanner
+                .chain
+                .iter()
+                .any(|stage| stage.backend == ScannerBackend::Chain)
+            {
+                return Err(ConfigError::ValidationError(
+                    "scanner.chain stages cannot be chains themselves".into(),
+                ));
+            }
+        }
+
+        // Validate JWT auth config
+        if let Some(jwt) = &self.auth.jwt {
+            if jwt.issuer.is_empty() || jwt.audience.is_empty() || jwt.jwks_url.is_empty() {
+                return Err(ConfigError::ValidationError(
+                    "JWT issuer, audience and jwks_url are required".into(),
+                ));
+            }
+        }
+
+        if let Some(hook) = self
+            .hooks
+            .iter()
+            .find(|hook| hook.name.is_empty() || hook.path.is_empty() || hook.max_fuel == 0)
+        {
+            return Err(ConfigError::ValidationError(format!(
+                "hook '{}' requires a name, a path and a positive max_fuel",
+                hook.name
+            )));
+        }
+
+        if let Some(guardrail) = &self.guardrail {
+            if guardrail.models.iter().any(|entry| entry.model.is_empty()) {
+                return Err(ConfigError::ValidationError(
+                    "guardrail.models entries require a model pattern".into(),
+                ));
+            }
+        }
+
+        if let Some(quarantine) = &self.quarantine {
+            if quarantine.directory.is_empty()
+                || (quarantine.key.is_none() && quarantine.key_file.is_none())
+            {
+                return Err(ConfigError::ValidationError(
+                    "quarantine requires a directory and a key or key_file".into(),
+                ));
+            }
+        }
+
+        if self.auth.jwt.is_some() && !self.auth.api_keys.is_empty() {
+            return Err(ConfigError::ValidationError(
+                "auth.jwt and auth.api_keys cannot be combined".into(),
+            ));
+        }
+
+        for (index, tenant) in self.auth.api_keys.iter().enumerate() {
+            if tenant.name.is_empty() || tenant.key.len() < 16 {
+                return Err(ConfigError::ValidationError(format!(
+                    "auth.api_keys[{}] requires a name and a key of at least 16 characters",
+                    index
+                )));
+            }
+            if tenant.requests_per_minute == Some(0) {
+                return Err(ConfigError::ValidationError(format!(
+                    "auth.api_keys[{}].requests_per_minute must be greater than 0",
+                    index
+                )));
+            }
+            if self.auth.api_keys[..index]
+                .iter()
+                .any(|other| other.key == tenant.key)
+            {
+                return Err(ConfigError::ValidationError(format!(
+                    "auth.api_keys[{}] reuses the key of another tenant",
+                    index
+                )));
+            }
+            if let Some(profile) = tenant
+                .allowed_profiles
+                .iter()
+                .find(|p| !self.security.allowed_profiles.contains(p))
+            {
+                return Err(ConfigError::ValidationError(format!(
+                    "auth.api_keys[{}].allowed_profiles contains {}, which is not in security.allowed_profiles",
+                    index, profile
+                )));
+            }
+        }
+
+        if let Some(admin) = &self.auth.admin {
+            if admin.token.len() < 16 {
+                return Err(ConfigError::ValidationError(
+                    "auth.admin.token must be at least 16 characters".into(),
+                ));
+            }
+        }
+
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(yaml: &str) -> Value {
+        let mut value: Value = serde_yaml::from_str(yaml).unwrap();
+        interpolate_env(&mut value).unwrap();
+        value
+    }
+
+    #[test]
+    fn interpolate_env_keeps_special_characters() {
+        env::set_var("PANW_TEST_INTERPOLATE_KEY", "a#b: c\nd");
+        let value = parse("key: \"${PANW_TEST_INTERPOLATE_KEY}\"\n");
+        assert_eq!(value["key"].as_str(), Some("a#b: c\nd"));
+    }
+
+    #[test]
+    fn interpolate_env_uses_defaults() {
+        let value = parse("items:\n  - \"x-${PANW_TEST_INTERPOLATE_UNSET:-fallback}-y\"\n");
+        assert_eq!(value["items"][0].as_str(), Some("x-fallback-y"));
+    }
+
+    #[test]
+    fn interpolate_env_rejects_missing_variables() {
+        let mut value: Value =
+            serde_yaml::from_str("key: ${PANW_TEST_INTERPOLATE_MISSING}").unwrap();
+        assert!(interpolate_env(&mut value).is_err());
+    }
+
+    #[test]
+    fn interpolate_env_ignores_comments() {
+        let value = parse("# ${PANW_TEST_INTERPOLATE_MISSING}\nkey: value\n");
+        assert_eq!(value["key"].as_str(), Some("value"));
+    }
+
+    #[test]
+    fn set_path_creates_nested_string_values() {
+        let mut root = Value::Null;
+        let segments = vec!["security".to_string(), "api_key".to_string()];
+        set_path(&mut root, &segments, "0123".to_string());
+        assert_eq!(root["security"]["api_key"].as_str(), Some("0123"));
+
+        set_path(&mut root, &segments, "~".to_string());
+        assert_eq!(root["security"]["api_key"].as_str(), Some("~"));
+    }
+
+    #[test]
+    fn set_path_replaces_scalars_on_the_way() {
+        let mut root: Value = serde_yaml::from_str("server: localhost").unwrap();
+        let segments = vec!["server".to_string(), "port".to_string()];
+        set_path(&mut root, &segments, "8080".to_string());
+        assert_eq!(root["server"]["port"].as_str(), Some("8080"));
+    }
+
+    #[test]
+    fn lenient_value_parses_numeric_strings() {
+        let value = Value::String("8080".to_string());
+        let port = u16::deserialize(LenientValue(value)).unwrap();
+        assert_eq!(port, 8080);
+
+        let value = Value::String("true".to_string());
+        assert!(bool::deserialize(LenientValue(value)).unwrap());
+    }
+}
@@ -0,0 +1,151 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+use crate::metrics::Metrics;
+use crate::security::{ScanContext, SecurityClient, SecurityError};
+use crate::types::ScanResponse;
+
+// Harmless prompt sent to verify the PANW API key and profile.
+const TEST_PROMPT: &str = "Hello, this is a connectivity check.";
+
+// Model name reported in the metadata of test scans.
+const TEST_MODEL: &str = "panw-api-ollama-healthcheck";
+
+// Outcome of the latest credential check.
+//
+// * `Unknown` - No check has completed yet
+// * `Valid` - The test scan succeeded
+// * `Invalid` - PANW rejected the API key or the profile
+// * `Unreachable` - The API could not be reached; says nothing about the credentials
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CredentialStatus {
+    Unknown,
+    Valid,
+    Invalid,
+    Unreachable,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CredentialState {
+    pub status: CredentialStatus,
+    pub checked_at: Option<DateTime<Utc>>,
+    pub message: Option<String>,
+}
+
+// Latest result of the periodic PANW credential check, shared with `/readyz`.
+#[derive(Clone)]
+pub struct CredentialHealth {
+    state: Arc<Mutex<CredentialState>>,
+}
+
+impl Default for CredentialHealth {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CredentialHealth {
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(CredentialState {
+                status: CredentialStatus::Unknown,
+                checked_at: None,
+                message: None,
+            })),
+        }
+    }
+
+    pub fn state(&self) -> CredentialState {
+        self.state.lock().unwrap().clone()
+    }
+
+    // Returns `true` once a check has completed without PANW rejecting the credentials.
+    //
+    // `Unknown` is not usable, so `/readyz` only reports ready after the startup check.
+    pub fn is_usable(&self) -> bool {
+        matches!(
+            self.state.lock().unwrap().status,
+            CredentialStatus::Valid | CredentialStatus::Unreachable
+        )
+    }
+
+    fn update(&self, status: CredentialStatus, message: Option<String>) {
+        *self.state.lock().unwrap() = CredentialState {
+            status,
+            checked_at: Some(Utc::now()),
+            message,
+        };
+    }
+}
+
+// Sends a benign test scan with the client's API key and profile.
+pub async fn probe(client: &SecurityClient) -> Result<ScanResponse, SecurityError> {
+    client
+        .scan_exchange(Some(TEST_PROMPT), None, TEST_MODEL, &ScanContext::default())
+        .await
+}
+
+// Returns `true` if an error means PANW refused the API key or the profile.
+pub fn is_credential_error(error: &SecurityError) -> bool {
+    matches!(
+        error,
+        SecurityError::AssessmentError(message)
+            if ["400", "401", "403"].iter().any(|code| message.starts_with(code))
+    )
+}
+
+// Checks the PANW credentials right away and then at a fixed interval.
+//
+// Rejected credentials are logged at error level on every check, mark the proxy
+// as not ready and are counted in the metrics, so revoked or expired keys are
+// noticed before traffic fails.
+//
+// # Arguments
+//
+// * `client` - The client whose API key and profile are checked
+// * `health` - Receives the result of each check
+// * `metrics` - Registry counting checks and failures
+// * `interval` - Time between two checks
+pub fn spawn_credential_checker(
+    client: SecurityClient,
+    health: CredentialHealth,
+    metrics: Metrics,
+    interval: Duration,
+) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+
+            let result = probe(&client).await;
+            metrics.record_credential_check(!matches!(&result, Err(e) if is_credential_error(e)));
+            match result {
+                Ok(_) => {
+                    if health.state().status != CredentialStatus::Valid {
+                        info!(
+                            "PANW credentials verified for profile {}",
+                            client.profile_name()
+                        );
+                    }
+                    health.update(CredentialStatus::Valid, None);
+                }
+                Err(e) if is_credential_error(&e) => {
+                    error!(
+                        "PANW REJECTED THE API KEY OR PROFILE '{}': {}. All scans will fail until the credentials are fixed",
+                        client.profile_name(),
+                        e
+                    );
+                    health.update(CredentialStatus::Invalid, Some(e.to_string()));
+                }
+                Err(e) => {
+                    warn!("PANW credential check could not reach the API: {}", e);
+                    health.update(CredentialStatus::Unreachable, Some(e.to_string()));
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,74 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use rskafka::client::partition::{Compression, PartitionClient, UnknownTopicHandling};
+use rskafka::client::{Client, ClientBuilder};
+use rskafka::record::Record;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::config::EventBackendConfig;
+use crate::events::{EventError, EventMessage, EventPublisher};
+
+// Publishes events to Kafka topics.
+//
+// Each topic is written to the configured partition; a produce request
+// succeeds once the partition leader has acknowledged the batch.
+pub struct KafkaPublisher {
+    client: Client,
+    partition: i32,
+    partitions: Mutex<HashMap<String, Arc<PartitionClient>>>,
+}
+
+impl KafkaPublisher {
+    pub async fn connect(backend: &EventBackendConfig) -> Result<Self, EventError> {
+        let EventBackendConfig::Kafka { brokers, partition } = backend else {
+            unreachable!("KafkaPublisher::connect called with a non-Kafka backend");
+        };
+        let client = ClientBuilder::new(brokers.clone())
+            .build()
+            .await
+            .map_err(|e| EventError::ConnectError(e.to_string()))?;
+        Ok(Self {
+            client,
+            partition: *partition,
+            partitions: Mutex::new(HashMap::new()),
+        })
+    }
+
+    async fn partition_client(&self, topic: &str) -> Result<Arc<PartitionClient>, EventError> {
+        let mut partitions = self.partitions.lock().await;
+        if let Some(client) = partitions.get(topic) {
+            return Ok(client.clone());
+        }
+        let client = Arc::new(
+            self.client
+                .partition_client(topic, self.partition, UnknownTopicHandling::Retry)
+                .await
+                .map_err(|e| EventError::ConnectError(e.to_string()))?,
+        );
+        partitions.insert(topic.to_string(), client.clone());
+        Ok(client)
+    }
+}
+
+#[async_trait]
+impl EventPublisher for KafkaPublisher {
+    async fn publish(&self, topic: &str, messages: &[EventMessage]) -> Result<(), EventError> {
+        let records = messages
+            .iter()
+            .map(|message| Record {
+                key: Some(message.key.as_bytes().to_vec()),
+                value: Some(message.payload.to_vec()),
+                headers: BTreeMap::new(),
+                timestamp: Utc::now(),
+            })
+            .collect();
+        self.partition_client(topic)
+            .await?
+            .produce(records, Compression::NoCompression)
+            .await
+            .map_err(|e| EventError::PublishError(e.to_string()))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,406 @@
+// Kafka producer, available with the `kafka` feature.
+#[cfg(feature = "kafka")]
+pub mod kafka;
+
+// NATS and JetStream publisher, available with the `nats` feature.
+#[cfg(feature = "nats")]
+pub mod nats;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::{mpsc, Mutex};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, warn};
+use uuid::Uuid;
+
+use crate::budget::TokenBudget;
+use crate::config::{ConfigError, EventBackendConfig, EventsConfig};
+use crate::security::ScanContext;
+
+// Delay before the first retry of a failed publish; doubled on each further failure.
+const INITIAL_RETRY_DELAY: Duration = Duration::from_millis(200);
+
+#[derive(Debug, Error)]
+pub enum EventError {
+    #[error("Failed to connect to the event broker: {0}")]
+    ConnectError(String),
+
+    #[error("Failed to publish events: {0}")]
+    PublishError(String),
+}
+
+// Gateway activity published to the event broker, one JSON message each.
+//
+// Every event carries a unique `id` so consumers can drop the duplicates that
+// at-least-once delivery may produce.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GatewayEvent {
+    // A scan verdict, whether the content was allowed or blocked
+    Verdict {
+        id: Uuid,
+        timestamp: DateTime<Utc>,
+        request_id: Option<String>,
+        user: String,
+        endpoint: Option<String>,
+        model: String,
+        is_prompt: bool,
+        is_safe: bool,
+        category: String,
+        action: String,
+        scanner: String,
+        report_id: String,
+    },
+    // Tokens counted once a response completes
+    Usage {
+        id: Uuid,
+        timestamp: DateTime<Utc>,
+        request_id: Option<String>,
+        user: String,
+        endpoint: Option<String>,
+        model: String,
+        prompt_tokens: u64,
+        completion_tokens: u64,
+    },
+}
+
+impl GatewayEvent {
+    // Creates a usage event for the caller of a request.
+    pub fn usage(
+        ctx: &ScanContext,
+        model: &str,
+        prompt_tokens: u64,
+        completion_tokens: u64,
+    ) -> Self {
+        GatewayEvent::Usage {
+            id: Uuid::new_v4(),
+            timestamp: Utc::now(),
+            request_id: ctx.request_id.clone(),
+            user: TokenBudget::user_key(ctx),
+            endpoint: ctx.endpoint.clone(),
+            model: model.to_string(),
+            prompt_tokens,
+            completion_tokens,
+        }
+    }
+
+    // Message key identifying the caller.
+    //
+    // Kafka writes every event to the configured partition, so events keep
+    // their order within a topic regardless of the key.
+    fn key(&self) -> &str {
+        match self {
+            GatewayEvent::Verdict { user, .. } | GatewayEvent::Usage { user, .. } => user,
+        }
+    }
+}
+
+// A serialized event waiting to be published.
+#[derive(Debug, Clone)]
+pub struct EventMessage {
+    pub key: String,
+    pub payload: Bytes,
+}
+
+// A broker client able to publish a batch of messages to one topic.
+//
+// Implementations return only once the broker has acknowledged every message
+// of the batch, so a failed batch is retried as a whole.
+#[async_trait]
+pub trait EventPublisher: Send + Sync {
+    async fn publish(&self, topic: &str, messages: &[EventMessage]) -> Result<(), EventError>;
+}
+
+// Buffered, at-least-once publishing of gateway events.
+//
+// Events are queued in memory and published in batches by a background task,
+// which retries failed batches with exponential backoff. A batch still failing
+// after `max_retries` retries is dropped with an error, so a batch the broker
+// rejects for good does not hold back every later event. Requests never wait
+// for the broker: when the buffer is full, new events are dropped with a
+// warning. Cheap to clone; all clones feed the same queue.
+#[derive(Clone)]
+pub struct EventSink {
+    sender: mpsc::Sender<(Arc<str>, EventMessage)>,
+    verdict_topic: Arc<str>,
+    usage_topic: Arc<str>,
+    closing: CancellationToken,
+    task: Arc<Mutex<Option<JoinHandle<()>>>>,
+}
+
+impl EventSink {
+    // Connects to the configured broker and starts the publishing task.
+    //
+    // # Returns
+    //
+    // * `Ok(EventSink)` - The sink, ready to queue events
+    // * `Err(ConfigError)` - If the broker is unreachable or its support is not built in
+    pub async fn connect(config: &EventsConfig) -> Result<Self, ConfigError> {
+        let publisher = connect_publisher(&config.backend)
+            .await
+            .map_err(|e| ConfigError::ValidationError(e.to_string()))?;
+        Ok(Self::with_publisher(config, publisher))
+    }
+
+    // Starts the publishing task for an existing publisher.
+    //
+    // # Arguments
+    //
+    // * `config` - Topics, buffer and batch sizes
+    // * `publisher` - The broker client events are handed to
+    pub fn with_publisher(config: &EventsConfig, publisher: Arc<dyn EventPublisher>) -> Self {
+        let (sender, receiver) = mpsc::channel(config.buffer_size);
+        let closing = CancellationToken::new();
+        let task = tokio::spawn(run_publisher(
+            receiver,
+            publisher,
+            closing.clone(),
+            config.batch_size,
+            config.max_retries,
+            Duration::from_secs(config.max_retry_delay_secs),
+        ));
+        Self {
+            sender,
+            verdict_topic: Arc::from(config.verdict_topic.as_str()),
+            usage_topic: Arc::from(config.usage_topic.as_str()),
+            closing,
+            task: Arc::new(Mutex::new(Some(task))),
+        }
+    }
+
+    // Stops accepting events and publishes the ones still queued.
+    //
+    // Call once the server has stopped; events queued afterwards are dropped.
+    //
+    // # Arguments
+    //
+    // * `timeout` - How long to wait for the broker before giving up on the queue
+    //
+    // # Returns
+    //
+    // `true` if every queued event was handed to the broker (or dropped after
+    // its retries), `false` if the timeout elapsed first
+    pub async fn flush(&self, timeout: Duration) -> bool {
+        self.closing.cancel();
+        let Some(task) = self.task.lock().await.take() else {
+            return true;
+        };
+        tokio::time::timeout(timeout, task).await.is_ok()
+    }
+
+    // Queues an event for publishing to its topic.
+    pub fn publish(&self, event: GatewayEvent) {
+        let topic = match event {
+            GatewayEvent::Verdict { .. } => self.verdict_topic.clone(),
+            GatewayEvent::Usage { .. } => self.usage_topic.clone(),
+        };
+        let payload = match serde_json::to_vec(&event) {
+            Ok(payload) => Bytes::from(payload),
+            Err(e) => {
+                warn!("Failed to serialize gateway event: {}", e);
+                return;
+            }
+        };
+        let message = EventMessage {
+            key: event.key().to_string(),
+            payload,
+        };
+        if self.sender.try_send((topic, message)).is_err() {
+            warn!("Event buffer is full, dropping a gateway event");
+        }
+    }
+}
+
+async fn connect_publisher(
+    backend: &EventBackendConfig,
+) -> Result<Arc<dyn EventPublisher>, EventError> {
+    match backend {
+        EventBackendConfig::Kafka { .. } => connect_kafka(backend).await,
+        EventBackendConfig::Nats { .. } => connect_nats(backend).await,
+    }
+}
+
+#[cfg(feature = "kafka")]
+async fn connect_kafka(
+    backend: &EventBackendConfig,
+) -> Result<Arc<dyn EventPublisher>, EventError> {
+    Ok(Arc::new(kafka::KafkaPublisher::connect(backend).await?))
+}
+
+#[cfg(not(feature = "kafka"))]
+async fn connect_kafka(
+    _backend: &EventBackendConfig,
+) -> Result<Arc<dyn EventPublisher>, EventError> {
+    Err(EventError::ConnectError(
+        "Kafka support requires building with the `kafka` feature".into(),
+    ))
+}
+
+#[cfg(feature = "nats")]
+async fn connect_nats(backend: &EventBackendConfig) -> Result<Arc<dyn EventPublisher>, EventError> {
+    Ok(Arc::new(nats::NatsPublisher::connect(backend).await?))
+}
+
+#[cfg(not(feature = "nats"))]
+async fn connect_nats(
+    _backend: &EventBackendConfig,
+) -> Result<Arc<dyn EventPublisher>, EventError> {
+    Err(EventError::ConnectError(
+        "NATS support requires building with the `nats` feature".into(),
+    ))
+}
+
+// Publishes queued events in batches until every sink is dropped, or until
+// `closing` is cancelled and the queue is drained.
+async fn run_publisher(
+    mut receiver: mpsc::Receiver<(Arc<str>, EventMessage)>,
+    publisher: Arc<dyn EventPublisher>,
+    closing: CancellationToken,
+    batch_size: usize,
+    max_retries: u32,
+    max_retry_delay: Duration,
+) {
+    let mut batch = Vec::with_capacity(batch_size);
+    let mut closed = false;
+    loop {
+        let received = tokio::select! {
+            received = receiver.recv_many(&mut batch, batch_size) => received,
+            _ = closing.cancelled(), if !closed => {
+                // Refuse new events but keep receiving the queued ones
+                receiver.close();
+                closed = true;
+                continue;
+            }
+        };
+        if received == 0 {
+            break;
+        }
+
+        // Group by topic, keeping the order of events within each topic
+        let mut topics: Vec<Arc<str>> = Vec::new();
+        let mut messages: HashMap<Arc<str>, Vec<EventMessage>> = HashMap::new();
+        for (topic, message) in batch.drain(..) {
+            if !messages.contains_key(&topic) {
+                topics.push(topic.clone());
+            }
+            messages.entry(topic).or_default().push(message);
+        }
+
+        for topic in topics {
+            let messages = &messages[&topic];
+            let mut delay = INITIAL_RETRY_DELAY;
+            let mut retries = 0;
+            loop {
+                match publisher.publish(&topic, messages).await {
+                    Ok(()) => {
+                        debug!("Published {} events to {}", messages.len(), topic);
+                        break;
+                    }
+                    Err(e) if retries >= max_retries => {
+                        error!(
+                            "Dropping {} events for {} after {} retries: {}",
+                            messages.len(),
+                            topic,
+                            retries,
+                            e
+                        );
+                        break;
+                    }
+                    Err(e) => {
+                        warn!(
+                            "Failed to publish {} events to {}, retrying in {:?}: {}",
+                            messages.len(),
+                            topic,
+                            delay,
+                            e
+                        );
+                        tokio::time::sleep(delay).await;
+                        delay = (delay * 2).min(max_retry_delay);
+                        retries += 1;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // Publisher failing its first `failures` calls, counting every attempt and
+    // the events it accepted.
+    struct FlakyPublisher {
+        failures: usize,
+        attempts: AtomicUsize,
+        published: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl EventPublisher for FlakyPublisher {
+        async fn publish(&self, _topic: &str, messages: &[EventMessage]) -> Result<(), EventError> {
+            if self.attempts.fetch_add(1, Ordering::SeqCst) < self.failures {
+                return Err(EventError::PublishError("rejected".into()));
+            }
+            self.published.fetch_add(messages.len(), Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    fn sink(publisher: Arc<FlakyPublisher>, max_retries: u32) -> EventSink {
+        let config: EventsConfig = serde_yaml::from_str(&format!(
+            "backend: {{type: nats, url: 'nats://localhost'}}\n\
+             max_retry_delay_secs: 0\n\
+             max_retries: {max_retries}"
+        ))
+        .unwrap();
+        EventSink::with_publisher(&config, publisher)
+    }
+
+    fn usage() -> GatewayEvent {
+        GatewayEvent::usage(&ScanContext::default(), "llama3", 1, 1)
+    }
+
+    #[tokio::test]
+    async fn rejected_batches_are_dropped_after_max_retries() {
+        let publisher = Arc::new(FlakyPublisher {
+            failures: usize::MAX,
+            attempts: AtomicUsize::new(0),
+            published: AtomicUsize::new(0),
+        });
+        let events = sink(publisher.clone(), 2);
+        events.publish(usage());
+
+        assert!(events.flush(Duration::from_secs(5)).await);
+        assert_eq!(publisher.attempts.load(Ordering::SeqCst), 3);
+        assert_eq!(publisher.published.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn flush_publishes_queued_events() {
+        let publisher = Arc::new(FlakyPublisher {
+            failures: 1,
+            attempts: AtomicUsize::new(0),
+            published: AtomicUsize::new(0),
+        });
+        let events = sink(publisher.clone(), 10);
+        for _ in 0..3 {
+            events.publish(usage());
+        }
+
+        assert!(events.flush(Duration::from_secs(5)).await);
+        assert_eq!(publisher.published.load(Ordering::SeqCst), 3);
+
+        // Events queued after the flush are refused
+        events.publish(usage());
+        assert_eq!(publisher.published.load(Ordering::SeqCst), 3);
+    }
+}
@@ -0,0 +1,64 @@
+use async_nats::jetstream;
+use async_trait::async_trait;
+
+use crate::config::EventBackendConfig;
+use crate::events::{EventError, EventMessage, EventPublisher};
+
+// Publishes events to NATS subjects.
+//
+// With JetStream, a batch succeeds once the stream has acknowledged every
+// message. Core NATS only confirms that the server received them.
+pub struct NatsPublisher {
+    client: async_nats::Client,
+    jetstream: Option<jetstream::Context>,
+}
+
+impl NatsPublisher {
+    pub async fn connect(backend: &EventBackendConfig) -> Result<Self, EventError> {
+        let EventBackendConfig::Nats {
+            url,
+            jetstream: use_jetstream,
+        } = backend
+        else {
+            unreachable!("NatsPublisher::connect called with a non-NATS backend");
+        };
+        let client = async_nats::connect(url.as_str())
+            .await
+            .map_err(|e| EventError::ConnectError(e.to_string()))?;
+        let jetstream = use_jetstream.then(|| jetstream::new(client.clone()));
+        Ok(Self { client, jetstream })
+    }
+}
+
+#[async_trait]
+impl EventPublisher for NatsPublisher {
+    async fn publish(&self, topic: &str, messages: &[EventMessage]) -> Result<(), EventError> {
+        let publish_error = |e: &dyn std::fmt::Display| EventError::PublishError(e.to_string());
+        match &self.jetstream {
+            Some(context) => {
+                let mut acks = Vec::with_capacity(messages.len());
+                for message in messages {
+                    acks.push(
+                        context
+                            .publish(topic.to_string(), message.payload.clone())
+                            .await
+                            .map_err(|e| publish_error(&e))?,
+                    );
+                }
+                for ack in acks {
+                    ack.await.map_err(|e| publish_error(&e))?;
+                }
+            }
+            None => {
+                for message in messages {
+                    self.client
+                        .publish(topic.to_string(), message.payload.clone())
+                        .await
+                        .map_err(|e| publish_error(&e))?;
+                }
+                self.client.flush().await.map_err(|e| publish_error(&e))?;
+            }
+        }
+        Ok(())
+    }
+}
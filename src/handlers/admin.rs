@@ -0,0 +1,247 @@
+use axum::{
+    extract::{Path, Query, State},
+    response::IntoResponse,
+    Json,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::ban::BanList;
+use crate::config::EnforcementMode;
+use crate::handlers::ApiError;
+use crate::history::{AssessmentHistory, AssessmentPage, AssessmentQuery};
+use crate::quarantine::{QuarantinePage, QuarantineRecord, QuarantineStore};
+use crate::AppState;
+
+// Partial update of the runtime policy; omitted fields are left unchanged.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PolicyUpdate {
+    pub mode: Option<EnforcementMode>,
+    pub profile_name: Option<String>,
+    pub scanning: Option<ScanningUpdate>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScanningUpdate {
+    pub chat: Option<bool>,
+    pub generate: Option<bool>,
+    pub embeddings: Option<bool>,
+}
+
+// Default and maximum number of records returned by the quarantine listing.
+const DEFAULT_QUARANTINE_LIMIT: usize = 100;
+const MAX_QUARANTINE_LIMIT: usize = 1000;
+
+// Default and maximum page size of the assessment history.
+const DEFAULT_ASSESSMENTS_LIMIT: usize = 100;
+const MAX_ASSESSMENTS_LIMIT: usize = 1000;
+
+#[derive(Debug, Deserialize)]
+pub struct QuarantineQuery {
+    pub limit: Option<usize>,
+    pub cursor: Option<String>,
+}
+
+// Runtime statistics since start (GET /admin/stats).
+//
+// Reports request counts, blocks by category, PANW latency percentiles,
+// the model cache hit rate and upstream error counts.
+pub async fn handle_stats(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.metrics.snapshot())
+}
+
+// Current runtime policy (GET /admin/policy).
+pub async fn handle_get_policy(State(state): State<AppState>) -> Json<Value> {
+    Json(policy_view(&state))
+}
+
+// Changes the runtime policy without a restart (PATCH /admin/policy).
+//
+// Switches between enforcement and monitor mode, selects another PANW
+// profile and toggles scanning per endpoint. Every change is written to
+// the `audit` log target.
+pub async fn handle_update_policy(
+    State(state): State<AppState>,
+    Json(update): Json<PolicyUpdate>,
+) -> Result<Json<Value>, ApiError> {
+    if let Some(profile_name) = &update.profile_name {
+        if profile_name.trim().is_empty() {
+            return Err(ApiError::BadRequest("profile_name cannot be empty".into()));
+        }
+    }
+
+    let mut policy = (**state.runtime_policy.load()).clone();
+    if let Some(mode) = update.mode {
+        policy.mode = mode;
+    }
+    if let Some(scanning) = update.scanning {
+        policy.scanning.chat = scanning.chat.unwrap_or(policy.scanning.chat);
+        policy.scanning.generate = scanning.generate.unwrap_or(policy.scanning.generate);
+        policy.scanning.embeddings = scanning.embeddings.unwrap_or(policy.scanning.embeddings);
+    }
+    info!(
+        target: "audit",
+        mode = ?policy.mode,
+        scan_chat = policy.scanning.chat,
+        scan_generate = policy.scanning.generate,
+        scan_embeddings = policy.scanning.embeddings,
+        "Runtime policy updated"
+    );
+    state.runtime_policy.store(Arc::new(policy));
+
+    if let Some(profile_name) = &update.profile_name {
+        info!(target: "audit", profile_name = %profile_name, "Active PANW profile changed");
+        state.security_client.set_profile_name(profile_name);
+        // Verdicts of the previous profile no longer apply
+        if let Some(cache) = &state.scan_cache {
+            cache.invalidate();
+        }
+    }
+
+    // Cached responses were allowed under the previous policy
+    if let Some(cache) = &state.response_cache {
+        cache.invalidate();
+    }
+
+    Ok(Json(policy_view(&state)))
+}
+
+// Drops all cached upstream responses (POST /admin/policy/flush-caches).
+pub async fn handle_flush_caches(State(state): State<AppState>) -> Json<Value> {
+    state.model_cache.invalidate();
+    let mut flushed = vec!["model_cache"];
+    if let Some(cache) = &state.scan_cache {
+        cache.invalidate();
+        flushed.push("scan_cache");
+    }
+    if let Some(cache) = &state.response_cache {
+        cache.invalidate();
+        flushed.push("response_cache");
+    }
+    info!(target: "audit", "Caches flushed");
+    Json(json!({ "flushed": flushed }))
+}
+
+// Lists quarantined exchanges, newest first (GET /admin/quarantine).
+//
+// Only metadata is returned; fetch a record by ID to see its content and findings.
+// Pages hold `limit` records; pass the returned `next_cursor` as `cursor` to
+// fetch the next one.
+pub async fn handle_list_quarantine(
+    State(state): State<AppState>,
+    Query(query): Query<QuarantineQuery>,
+) -> Result<Json<QuarantinePage>, ApiError> {
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_QUARANTINE_LIMIT)
+        .clamp(1, MAX_QUARANTINE_LIMIT);
+    let page = quarantine_store(&state)?
+        .list(limit, query.cursor.as_deref())
+        .await
+        .map_err(|e| ApiError::InternalError(e.to_string()))?;
+    Ok(Json(page))
+}
+
+// Returns one quarantined exchange with its content and findings (GET /admin/quarantine/{id}).
+pub async fn handle_get_quarantine(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<QuarantineRecord>, ApiError> {
+    let record = quarantine_store(&state)?
+        .get(id)
+        .await
+        .map_err(|e| ApiError::InternalError(e.to_string()))?
+        .ok_or_else(|| ApiError::NotFound(format!("quarantine record {}", id)))?;
+    info!(target: "audit", record = %id, "Quarantine record retrieved");
+    Ok(Json(record))
+}
+
+// Recorded scan verdicts, newest first (GET /admin/assessments).
+//
+// Filters by `user`, `category` and `since` (RFC 3339). Pages hold `limit`
+// verdicts; pass the returned `next_cursor` as `cursor` to fetch the next one.
+// Returns `404` when the assessment history is not configured.
+pub async fn handle_list_assessments(
+    State(state): State<AppState>,
+    Query(query): Query<AssessmentQuery>,
+) -> Result<Json<AssessmentPage>, ApiError> {
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_ASSESSMENTS_LIMIT)
+        .clamp(1, MAX_ASSESSMENTS_LIMIT);
+    let page = assessment_history(&state)?
+        .query(query, limit)
+        .await
+        .map_err(|e| ApiError::InternalError(e.to_string()))?;
+    Ok(Json(page))
+}
+
+// Today's token usage per user (GET /admin/usage).
+//
+// Returns `404` when no daily token budget is configured.
+pub async fn handle_usage(State(state): State<AppState>) -> Result<Json<Value>, ApiError> {
+    let budget = state
+        .budget
+        .as_ref()
+        .ok_or_else(|| ApiError::NotFound("token budget is not enabled".into()))?;
+    Ok(Json(budget.snapshot()))
+}
+
+// Active temporary bans (GET /admin/bans).
+//
+// Returns `404` when automatic bans are not configured.
+pub async fn handle_list_bans(State(state): State<AppState>) -> Result<Json<Value>, ApiError> {
+    let bans = ban_list(&state)?;
+    Ok(Json(json!({ "bans": bans.list() })))
+}
+
+// Lifts the ban of a caller (DELETE /admin/bans/:principal).
+pub async fn handle_lift_ban(
+    State(state): State<AppState>,
+    Path(principal): Path<String>,
+) -> Result<Json<Value>, ApiError> {
+    let bans = ban_list(&state)?;
+    if !bans.lift(&principal) {
+        return Err(ApiError::NotFound(format!(
+            "no active ban for {}",
+            principal
+        )));
+    }
+    info!("Ban of {} lifted through the admin API", principal);
+    Ok(Json(json!({ "principal": principal, "lifted": true })))
+}
+
+fn ban_list(state: &AppState) -> Result<&BanList, ApiError> {
+    state
+        .bans
+        .as_ref()
+        .ok_or_else(|| ApiError::NotFound("automatic bans are not enabled".into()))
+}
+
+fn assessment_history(state: &AppState) -> Result<&AssessmentHistory, ApiError> {
+    state
+        .history
+        .as_ref()
+        .ok_or_else(|| ApiError::NotFound("assessment history is not enabled".into()))
+}
+
+fn quarantine_store(state: &AppState) -> Result<&QuarantineStore, ApiError> {
+    state
+        .quarantine
+        .as_ref()
+        .ok_or_else(|| ApiError::NotFound("quarantine is not enabled".into()))
+}
+
+fn policy_view(state: &AppState) -> Value {
+    let policy = state.runtime_policy.load();
+    json!({
+        "mode": policy.mode,
+        "profile_name": state.security_client.profile_name(),
+        "scanning": policy.scanning,
+    })
+}
@@ -1,8 +1,20 @@
-use axum::{extract::State, response::Response, Json};
+use axum::{extract::State, http::HeaderMap, response::Response, Json};
+use futures_util::future::try_join_all;
+use std::time::Instant;
 use tracing::{debug, error, info};
 
-use crate::handlers::utils::{build_json_response, handle_streaming_request};
+use crate::handlers::utils::{
+    apply_request_hooks, apply_response_hooks, build_json_response, cached_response,
+    enforce_options, enforce_quota, enforce_token_budget, ensure_model_allowed, ensure_not_banned,
+    handle_streaming_request, mask_response_body, record_token_usage, response_cache_key,
+    scan_session_window, store_response, with_backend_header, with_timing_header,
+    with_verdict_headers,
+};
 use crate::handlers::ApiError;
+use crate::metrics::{Stage, StageTimings};
+use crate::ollama::backend_origin;
+use crate::privacy;
+use crate::security::ScanContext;
 use crate::stream::SecurityAssessable;
 use crate::types::ChatRequest;
 use crate::AppState;
@@ -11,18 +23,90 @@ impl SecurityAssessable for crate::types::ChatResponse {
     fn get_content_for_assessment(&self) -> Option<(&str, &str)> {
         Some((&self.message.content, "chat_response"))
     }
+
+    fn replace_content(&mut self, content: String) {
+        self.message.content = content;
+    }
+
+    fn is_done(&self) -> bool {
+        self.done
+    }
+
+    fn keep_alive(model: &str) -> Self {
+        crate::types::ChatResponse {
+            model: model.to_string(),
+            created_at: chrono::Utc::now().to_rfc3339(),
+            message: crate::types::Message {
+                role: "assistant".to_string(),
+                content: String::new(),
+                images: None,
+                tool_calls: None,
+                extra: Default::default(),
+            },
+            done: false,
+            done_reason: None,
+            total_duration: None,
+            load_duration: None,
+            prompt_eval_count: None,
+            prompt_eval_duration: None,
+            eval_count: None,
+            eval_duration: None,
+            extra: Default::default(),
+        }
+    }
 }
 
+// Header letting clients choose the conversation scan scope, when enabled.
+const SCAN_SCOPE_HEADER: &str = "x-scan-scope";
+
 pub async fn handle_chat(
     State(state): State<AppState>,
-    Json(request): Json<ChatRequest>,
+    ctx: ScanContext,
+    headers: HeaderMap,
+    Json(mut request): Json<ChatRequest>,
 ) -> Result<Response, ApiError> {
     debug!("Received chat request for model: {}", request.model);
+    ensure_not_banned(&state, &ctx)?;
+    ensure_model_allowed(&state, &ctx, &request.model)?;
+    enforce_quota(&state, &ctx).await?;
+    enforce_options(&state, &mut request.options)?;
+    enforce_token_budget(&state, &ctx, &mut request.options)?;
+    let mut request = apply_request_hooks(&state, "/api/chat", request, &ctx).await?;
+    state
+        .policy
+        .enforce_messages(&mut request.messages)
+        .map_err(ApiError::BadRequest)?;
+
+    let cache_key = response_cache_key(
+        &state,
+        "/api/chat",
+        &request,
+        request.options.as_ref(),
+        request.stream.unwrap_or(false),
+        &ctx,
+    );
+    if let Some(response) = cached_response(&state, cache_key.as_ref())? {
+        return Ok(response);
+    }
 
-    for message in &request.messages {
+    let scope_header = headers
+        .get(SCAN_SCOPE_HEADER)
+        .map(|value| value.to_str().unwrap_or_default());
+    let scope = state
+        .scan_policy
+        .scope_for(scope_header)
+        .map_err(ApiError::BadRequest)?;
+    debug!("Scanning conversation with scope {:?}", scope);
+
+    // Messages are assessed concurrently, bounded by the scanner's concurrency limit;
+    // the first blocked message cancels the remaining scans
+    let mut timings = StageTimings::new(state.metrics(), ctx.access.clone());
+    let started = Instant::now();
+    let prompts = state.scan_policy.prompts_to_scan(&request.messages, scope);
+    let mut assessments = try_join_all(prompts.iter().map(|prompt| async {
         let assessment = state
-            .security_client
-            .assess_content(&message.content, &request.model, true)
+            .scanner
+            .assess(prompt, &request.model, true, &ctx)
             .await?;
 
         if !assessment.is_safe {
@@ -35,32 +119,101 @@ pub async fn handle_chat(
                 assessment.category, assessment.action
             )));
         }
+        Ok(assessment)
+    }))
+    .await?;
+
+    // Masked prompts replace the messages they were taken from; a masked window
+    // of several messages cannot be mapped back and is blocked instead
+    let masks: Vec<(String, String)> = prompts
+        .iter()
+        .zip(assessments.iter_mut())
+        .filter_map(|(prompt, assessment)| {
+            Some((prompt.to_string(), assessment.masked_content.take()?))
+        })
+        .collect();
+    for (prompt, masked) in masks {
+        let mut matched = false;
+        for message in request.messages.iter_mut().filter(|m| m.content == prompt) {
+            message.content = masked.clone();
+            matched = true;
+        }
+        if !matched {
+            return Err(ApiError::SecurityIssue(
+                "Message content violates security policy. Action: mask".to_string(),
+            ));
+        }
+        info!("Chat message masked by the policy engine");
+    }
+    let mut unscanned = assessments.iter().any(|assessment| assessment.unscanned);
+    let verdict = assessments.into_iter().last();
+    let mut user_turns = request
+        .messages
+        .iter()
+        .filter(|message| message.role == "user")
+        .map(|message| message.content.as_str());
+    let first_turn = user_turns.next();
+    if let Some(turn) = user_turns.next_back().or(first_turn) {
+        unscanned |=
+            scan_session_window(&state, &ctx, &headers, &request.model, first_turn, turn).await?;
+    }
+    timings.record(Stage::PromptScan, started);
+
+    // Enforce the guardrail after scanning so the trusted preamble is not sent to the scanner
+    if let Some(guardrail) = &state.guardrail {
+        guardrail.apply_to_chat(&mut request);
     }
 
     // Handle streaming requests
     if request.stream.unwrap_or(false) {
         debug!("Handling streaming chat request");
-        return handle_streaming_chat(State(state), Json(request)).await;
+        let verdict_state = state.clone();
+        let started = Instant::now();
+        let response = handle_streaming_chat(State(state), ctx, Json(request)).await?;
+        timings.record(Stage::Ollama, started);
+        let response = with_verdict_headers(&verdict_state, response, verdict.as_ref());
+        return Ok(with_timing_header(&verdict_state, response, &timings));
     }
 
     // Handle non-streaming requests
     debug!("Handling non-streaming chat request");
-    let response = state.ollama_client.forward("/api/chat", &request).await?;
+    let started = Instant::now();
+    let response = state
+        .ollama_client
+        .for_model(&request.model)
+        .forward("/api/chat", &request)
+        .await?;
+    let backend = backend_origin(response.url());
     let body_bytes = response.bytes().await.map_err(|e| {
         error!("Failed to read response body: {}", e);
         ApiError::InternalError("Failed to read response body".to_string())
     })?;
 
+    timings.record(Stage::Ollama, started);
+
     let response_body: crate::types::ChatResponse =
         serde_json::from_slice(&body_bytes).map_err(|e| {
-            error!("Failed to parse response: {}", e);
+            error!("Failed to parse response: {}", privacy::redact_error(&e));
             ApiError::InternalError("Failed to parse response".to_string())
         })?;
+    debug!(
+        "Chat finished: done_reason={:?}, prompt_eval_count={:?}, eval_count={:?}",
+        response_body.done_reason, response_body.prompt_eval_count, response_body.eval_count
+    );
+    record_token_usage(
+        &state,
+        &ctx,
+        &request.model,
+        response_body.prompt_eval_count,
+        response_body.eval_count,
+    );
 
-    let assessment = state
-        .security_client
-        .assess_content(&response_body.message.content, &request.model, false)
+    let started = Instant::now();
+    let mut assessment = state
+        .scanner
+        .assess(&response_body.message.content, &request.model, false, &ctx)
         .await?;
+    timings.record(Stage::ResponseScan, started);
 
     if !assessment.is_safe {
         info!(
@@ -73,11 +226,27 @@ pub async fn handle_chat(
         )));
     }
 
-    Ok(build_json_response(body_bytes)?)
+    let body_bytes = match assessment.masked_content.take() {
+        Some(masked) => mask_response_body(&body_bytes, "message/content", masked)?,
+        None => body_bytes,
+    };
+    let body_bytes = apply_response_hooks(&state, "/api/chat", body_bytes, &ctx).await?;
+    store_response(
+        &state,
+        "/api/chat",
+        cache_key,
+        &body_bytes,
+        &backend,
+        unscanned || assessment.unscanned,
+    );
+    let response = with_backend_header(build_json_response(body_bytes)?, &backend);
+    let response = with_verdict_headers(&state, response, Some(&assessment));
+    Ok(with_timing_header(&state, response, &timings))
 }
 
 async fn handle_streaming_chat(
     State(state): State<AppState>,
+    ctx: ScanContext,
     Json(request): Json<ChatRequest>,
 ) -> Result<Response, ApiError> {
     debug!("Handling streaming chat request");
@@ -88,6 +257,7 @@ async fn handle_streaming_chat(
         request,
         "/api/chat",
         &model,
+        ctx,
     )
     .await
 }
@@ -0,0 +1,137 @@
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Request, State},
+    http::{request::Parts, Extensions},
+    middleware::Next,
+    response::Response,
+};
+use serde_json::Value;
+use std::convert::Infallible;
+use std::sync::Arc;
+use tracing::debug;
+
+use crate::access_log::AccessRecord;
+use crate::auth::{AuthenticatedUser, Tenant};
+use crate::handlers::ApiError;
+use crate::request_id::RequestId;
+use crate::security::ScanContext;
+
+// Header letting callers select another PANW AI security profile.
+pub const PROFILE_HEADER: &str = "X-PANW-Profile";
+
+// PANW profile selected by the caller, stored in the request extensions.
+#[derive(Debug, Clone)]
+pub struct ProfileOverride(pub String);
+
+// Builds the per-request PANW scan context from the incoming request.
+//
+// The authenticated subject (when JWT validation is enabled) is used as `app_user`
+// so assessments are attributed to the actual caller, and the request's correlation
+// ID becomes the PANW transaction ID.
+#[async_trait]
+impl<S> FromRequestParts<S> for ScanContext
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(scan_context(&parts.extensions, parts.uri.path()))
+    }
+}
+
+// Assembles the scan context from what the auth and request ID layers stored.
+//
+// Tenant settings apply when the caller used an API key. A JWT subject takes
+// precedence for `app_user`, and an `X-PANW-Profile` selection, which
+// `select_profile` only accepts when the tenant grants it, for the profile.
+pub fn scan_context(extensions: &Extensions, path: &str) -> ScanContext {
+    let tenant = extensions.get::<Arc<Tenant>>().cloned();
+    let app_user = extensions
+        .get::<AuthenticatedUser>()
+        .map(|user| user.subject.clone())
+        .or_else(|| tenant.as_ref().and_then(|t| t.app_user.clone()));
+    let profile_name = extensions
+        .get::<ProfileOverride>()
+        .map(|profile| profile.0.clone())
+        .or_else(|| tenant.as_ref().and_then(|t| t.profile_name.clone()));
+    let access = extensions.get::<AccessRecord>().cloned();
+    if let (Some(access), Some(user)) = (&access, &app_user) {
+        access.set_user(user);
+    }
+
+    ScanContext {
+        app_name: tenant.as_ref().and_then(|t| t.app_name.clone()),
+        app_user,
+        request_id: extensions.get::<RequestId>().map(|id| id.0.clone()),
+        endpoint: Some(path.to_string()),
+        profile_name,
+        tenant,
+        access,
+    }
+}
+
+// Who may select a PANW profile with the `X-PANW-Profile` header.
+//
+// # Fields
+//
+// * `allowed` - `security.allowed_profiles`, the profiles any selection must come from
+// * `jwt_claim` - JWT claim listing the profiles a token holder may select
+#[derive(Debug, Clone)]
+pub struct ProfileSelection {
+    pub allowed: Vec<String>,
+    pub jwt_claim: Option<String>,
+}
+
+impl ProfileSelection {
+    // Returns `true` if the authenticated caller may select `profile`.
+    //
+    // API key callers are limited to their tenant's `allowed_profiles`, and JWT
+    // callers to the profiles listed in `jwt_claim`. Without authentication every
+    // caller may pick any allowlisted profile.
+    fn permits(&self, extensions: &Extensions, profile: &str) -> bool {
+        if !self.allowed.iter().any(|allowed| allowed == profile) {
+            return false;
+        }
+        if let Some(tenant) = extensions.get::<Arc<Tenant>>() {
+            return tenant.allowed_profiles.iter().any(|p| p == profile);
+        }
+        if let Some(user) = extensions.get::<AuthenticatedUser>() {
+            let Some(claim) = &self.jwt_claim else {
+                return false;
+            };
+            return match user.claims.get(claim) {
+                Some(Value::String(profiles)) => profiles.split_whitespace().any(|p| p == profile),
+                Some(Value::Array(profiles)) => {
+                    profiles.iter().any(|p| p.as_str() == Some(profile))
+                }
+                _ => false,
+            };
+        }
+        true
+    }
+}
+
+// Middleware accepting the `X-PANW-Profile` header from callers allowed to use it.
+//
+// Requests naming a profile outside `security.allowed_profiles`, or one the
+// caller's tenant or token does not grant, are rejected with `400` rather than
+// silently scanned with the default profile.
+pub async fn select_profile(
+    State(selection): State<Arc<ProfileSelection>>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    if let Some(value) = request.headers().get(PROFILE_HEADER) {
+        let profile = value.to_str().unwrap_or_default().trim().to_string();
+        if !selection.permits(request.extensions(), &profile) {
+            return Err(ApiError::BadRequest(format!(
+                "PANW profile not allowed: {}",
+                profile
+            )));
+        }
+        debug!("Using PANW profile {} selected by the caller", profile);
+        request.extensions_mut().insert(ProfileOverride(profile));
+    }
+    Ok(next.run(request).await)
+}
@@ -1,42 +1,88 @@
 use axum::{extract::State, response::Response, Json};
-use tracing::debug;
+use sha2::{Digest, Sha256};
+use std::time::Instant;
+use tracing::{debug, info};
 
-use crate::handlers::utils::build_json_response;
+use crate::handlers::utils::{
+    apply_request_hooks, apply_response_hooks, build_json_response, enforce_options, enforce_quota,
+    ensure_model_allowed, ensure_not_banned, with_backend_header, with_timing_header,
+    with_verdict_headers,
+};
 use crate::handlers::ApiError;
+use crate::metrics::{Stage, StageTimings};
+use crate::ollama::backend_origin;
+use crate::security::ScanContext;
 use crate::types::EmbeddingsRequest;
 use crate::AppState;
 
 pub async fn handle_embeddings(
     State(state): State<AppState>,
-    Json(request): Json<EmbeddingsRequest>,
+    ctx: ScanContext,
+    Json(mut request): Json<EmbeddingsRequest>,
 ) -> Result<Response, ApiError> {
     debug!("Received embeddings request for model: {}", request.model);
+    ensure_not_banned(&state, &ctx)?;
+    ensure_model_allowed(&state, &ctx, &request.model)?;
+    enforce_quota(&state, &ctx).await?;
+    enforce_options(&state, &mut request.options)?;
+    let mut request = apply_request_hooks(&state, "/api/embeddings", request, &ctx).await?;
 
-    // Assess the prompt with the updated method signature
-    let assessment = state
-        .security_client
-        .assess_content(
-            &request.prompt,
-            &request.model,
-            true, // This is a prompt
-        )
-        .await?;
+    let mut timings = StageTimings::new(state.metrics(), ctx.access.clone());
+    let started = Instant::now();
+    let assessment = if state.scan_policy.should_scan_embedding() {
+        let mut assessment = state
+            .scanner
+            .assess(
+                &request.prompt,
+                &request.model,
+                true, // This is a prompt
+                &ctx,
+            )
+            .await?;
 
-    if !assessment.is_safe {
-        return Err(ApiError::SecurityIssue(format!(
-            "Embedding prompt violates security policy. Category: {}, Action: {}",
-            assessment.category, assessment.action
-        )));
-    }
+        if !assessment.is_safe {
+            return Err(ApiError::SecurityIssue(format!(
+                "Embedding prompt violates security policy. Category: {}, Action: {}",
+                assessment.category, assessment.action
+            )));
+        }
+        if let Some(masked) = assessment.masked_content.take() {
+            info!("Embedding prompt masked by the policy engine");
+            request.prompt = masked;
+        }
+        Some(assessment)
+    } else {
+        // Keep a verifiable trace of the unscanned input without storing its content
+        let digest = Sha256::digest(request.prompt.as_bytes());
+        let sha256: String = digest.iter().map(|byte| format!("{:02x}", byte)).collect();
+        info!(
+            target: "audit",
+            request_id = ctx.request_id.as_deref().unwrap_or_default(),
+            app_user = ctx.app_user.as_deref().unwrap_or_default(),
+            model = %request.model,
+            bytes = request.prompt.len(),
+            sha256 = %sha256,
+            "Embeddings input recorded without scan"
+        );
+        None
+    };
+    timings.record(Stage::PromptScan, started);
 
     // Forward to Ollama
+    let started = Instant::now();
     let response = state
         .ollama_client
+        .for_model(&request.model)
         .forward("/api/embeddings", &request)
         .await?;
+    let backend = backend_origin(response.url());
     let body_bytes = response
         .bytes()
         .await
         .map_err(|e| ApiError::InternalError(e.to_string()))?;
-    Ok(build_json_response(body_bytes)?)
+    timings.record(Stage::Ollama, started);
+    let body_bytes = apply_response_hooks(&state, "/api/embeddings", body_bytes, &ctx).await?;
+    let response = with_backend_header(build_json_response(body_bytes)?, &backend);
+    let response = with_verdict_headers(&state, response, assessment.as_ref());
+    Ok(with_timing_header(&state, response, &timings))
 }
@@ -1,8 +1,19 @@
-use axum::{extract::State, response::Response, Json};
+use axum::{extract::State, http::HeaderMap, response::Response, Json};
+use std::time::Instant;
 use tracing::{debug, error, info};
 
-use crate::handlers::utils::{build_json_response, handle_streaming_request};
+use crate::handlers::utils::{
+    apply_request_hooks, apply_response_hooks, build_json_response, cached_response,
+    enforce_options, enforce_quota, enforce_token_budget, ensure_model_allowed, ensure_not_banned,
+    handle_streaming_request, mask_response_body, record_token_usage, response_cache_key,
+    scan_session_window, store_response, with_backend_header, with_timing_header,
+    with_verdict_headers,
+};
 use crate::handlers::ApiError;
+use crate::metrics::{Stage, StageTimings};
+use crate::ollama::backend_origin;
+use crate::privacy;
+use crate::security::ScanContext;
 use crate::stream::SecurityAssessable;
 use crate::types::GenerateRequest;
 use crate::AppState;
@@ -11,58 +22,165 @@ impl SecurityAssessable for crate::types::GenerateResponse {
     fn get_content_for_assessment(&self) -> Option<(&str, &str)> {
         Some((&self.response, "generate_response"))
     }
+
+    fn replace_content(&mut self, content: String) {
+        self.response = content;
+    }
+
+    fn is_done(&self) -> bool {
+        self.done
+    }
+
+    fn keep_alive(model: &str) -> Self {
+        crate::types::GenerateResponse {
+            model: model.to_string(),
+            created_at: chrono::Utc::now().to_rfc3339(),
+            response: String::new(),
+            context: None,
+            done: false,
+            done_reason: None,
+            total_duration: None,
+            load_duration: None,
+            prompt_eval_count: None,
+            prompt_eval_duration: None,
+            eval_count: None,
+            eval_duration: None,
+            extra: Default::default(),
+        }
+    }
 }
 
 pub async fn handle_generate(
     State(state): State<AppState>,
-    Json(request): Json<GenerateRequest>,
+    ctx: ScanContext,
+    headers: HeaderMap,
+    Json(mut request): Json<GenerateRequest>,
 ) -> Result<Response, ApiError> {
     debug!("Received generate request for model: {}", request.model);
+    ensure_not_banned(&state, &ctx)?;
+    ensure_model_allowed(&state, &ctx, &request.model)?;
+    enforce_quota(&state, &ctx).await?;
+    enforce_options(&state, &mut request.options)?;
+    enforce_token_budget(&state, &ctx, &mut request.options)?;
+    let mut request = apply_request_hooks(&state, "/api/generate", request, &ctx).await?;
+    state
+        .policy
+        .enforce_prompt(&mut request.prompt)
+        .map_err(ApiError::BadRequest)?;
 
-    let assessment = state
-        .security_client
-        .assess_content(&request.prompt, &request.model, true)
-        .await?;
+    let cache_key = response_cache_key(
+        &state,
+        "/api/generate",
+        &request,
+        request.options.as_ref(),
+        request.stream.unwrap_or(false),
+        &ctx,
+    );
+    if let Some(response) = cached_response(&state, cache_key.as_ref())? {
+        return Ok(response);
+    }
 
-    if !assessment.is_safe {
-        info!(
-            "Security issue detected in prompt: category={}, action={}",
-            assessment.category, assessment.action
-        );
-        return Err(ApiError::SecurityIssue(format!(
-            "Content violates security policy. Category: {}, Action: {}",
-            assessment.category, assessment.action
-        )));
+    let mut timings = StageTimings::new(state.metrics(), ctx.access.clone());
+    let started = Instant::now();
+    let images = state.scan_policy.images_to_scan(request.images.as_deref());
+    let mut verdict = None;
+    let mut masked_prompt = None;
+    let mut unscanned = false;
+    for (index, prompt) in std::iter::once(&request.prompt).chain(images).enumerate() {
+        let mut assessment = state
+            .scanner
+            .assess(prompt, &request.model, true, &ctx)
+            .await?;
+
+        // Only the text prompt can be masked; images are blocked instead
+        if index == 0 {
+            masked_prompt = assessment.masked_content.take();
+        }
+        if !assessment.is_safe || assessment.masked_content.is_some() {
+            info!(
+                "Security issue detected in prompt: category={}, action={}",
+                assessment.category, assessment.action
+            );
+            return Err(ApiError::SecurityIssue(format!(
+                "Content violates security policy. Category: {}, Action: {}",
+                assessment.category, assessment.action
+            )));
+        }
+        unscanned |= assessment.unscanned;
+        verdict = Some(assessment);
+    }
+    if let Some(masked_prompt) = masked_prompt {
+        info!("Prompt masked by the policy engine");
+        request.prompt = masked_prompt;
+    }
+    // Generate requests carry no history, so only those with a session header are tracked
+    unscanned |= scan_session_window(
+        &state,
+        &ctx,
+        &headers,
+        &request.model,
+        None,
+        &request.prompt,
+    )
+    .await?;
+    timings.record(Stage::PromptScan, started);
+
+    // Enforce the guardrail after scanning so the trusted preamble is not sent to the scanner
+    if let Some(guardrail) = &state.guardrail {
+        guardrail.apply_to_generate(&mut request);
     }
 
     // Handle streaming requests
     if request.stream.unwrap_or(false) {
         debug!("Handling streaming generate request");
-        return handle_streaming_generate(State(state), Json(request)).await;
+        let verdict_state = state.clone();
+        let started = Instant::now();
+        let response = handle_streaming_generate(State(state), ctx, Json(request)).await?;
+        timings.record(Stage::Ollama, started);
+        let response = with_verdict_headers(&verdict_state, response, verdict.as_ref());
+        return Ok(with_timing_header(&verdict_state, response, &timings));
     }
 
     // Handle non-streaming requests
     debug!("Handling non-streaming generate request");
+    let started = Instant::now();
     let response = state
         .ollama_client
+        .for_model(&request.model)
         .forward("/api/generate", &request)
         .await?;
+    let backend = backend_origin(response.url());
 
     let body_bytes = response.bytes().await.map_err(|e| {
         error!("Failed to read response body: {}", e);
         ApiError::InternalError("Failed to read response body".to_string())
     })?;
 
+    timings.record(Stage::Ollama, started);
+
     let response_body: crate::types::GenerateResponse = serde_json::from_slice(&body_bytes)
         .map_err(|e| {
-            error!("Failed to parse response: {}", e);
+            error!("Failed to parse response: {}", privacy::redact_error(&e));
             ApiError::InternalError("Failed to parse response".to_string())
         })?;
+    debug!(
+        "Generation finished: done_reason={:?}, prompt_eval_count={:?}, eval_count={:?}",
+        response_body.done_reason, response_body.prompt_eval_count, response_body.eval_count
+    );
+    record_token_usage(
+        &state,
+        &ctx,
+        &request.model,
+        response_body.prompt_eval_count,
+        response_body.eval_count,
+    );
 
-    let assessment = state
-        .security_client
-        .assess_content(&response_body.response, &request.model, false)
+    let started = Instant::now();
+    let mut assessment = state
+        .scanner
+        .assess(&response_body.response, &request.model, false, &ctx)
         .await?;
+    timings.record(Stage::ResponseScan, started);
 
     if !assessment.is_safe {
         info!(
@@ -75,11 +193,27 @@ pub async fn handle_generate(
         )));
     }
 
-    Ok(build_json_response(body_bytes)?)
+    let body_bytes = match assessment.masked_content.take() {
+        Some(masked) => mask_response_body(&body_bytes, "response", masked)?,
+        None => body_bytes,
+    };
+    let body_bytes = apply_response_hooks(&state, "/api/generate", body_bytes, &ctx).await?;
+    store_response(
+        &state,
+        "/api/generate",
+        cache_key,
+        &body_bytes,
+        &backend,
+        unscanned || assessment.unscanned,
+    );
+    let response = with_backend_header(build_json_response(body_bytes)?, &backend);
+    let response = with_verdict_headers(&state, response, Some(&assessment));
+    Ok(with_timing_header(&state, response, &timings))
 }
 
 async fn handle_streaming_generate(
     State(state): State<AppState>,
+    ctx: ScanContext,
     Json(request): Json<GenerateRequest>,
 ) -> Result<Response, ApiError> {
     debug!("Handling streaming generate request");
@@ -90,6 +224,7 @@ async fn handle_streaming_generate(
         request,
         "/api/generate",
         &model,
+        ctx,
     )
     .await
 }
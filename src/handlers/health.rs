@@ -0,0 +1,42 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+
+use crate::circuit::CircuitState;
+use crate::AppState;
+
+// Readiness probe reporting whether the proxy can currently assess traffic.
+//
+// Returns 503 while the PANW circuit breaker is open, unless fail-open is enabled
+// in which case traffic is still served, until the startup credential check has
+// completed, and while the last check found the PANW API key or profile rejected.
+pub async fn handle_readyz(State(state): State<AppState>) -> Response {
+    let circuit = state.security_client.circuit_state();
+    let credentials = state.credentials.as_ref().map(|health| health.state());
+    let credentials_usable = state
+        .credentials
+        .as_ref()
+        .map(|health| health.is_usable())
+        .unwrap_or(true);
+    let ready =
+        (circuit != CircuitState::Open || state.security_client.fail_open()) && credentials_usable;
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (
+        status,
+        Json(json!({
+            "status": if ready { "ready" } else { "unavailable" },
+            "panw_circuit": circuit,
+            "panw_credentials": credentials,
+        })),
+    )
+        .into_response()
+}
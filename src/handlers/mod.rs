@@ -1,32 +1,104 @@
+pub mod admin;
 pub mod chat;
+pub mod context;
 pub mod embeddings;
 pub mod generate;
+pub mod health;
 pub mod models;
+pub mod openai;
 pub mod utils;
 pub mod version;
 
 use axum::{
-    http::StatusCode,
+    http::{header::RETRY_AFTER, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
 use serde_json::json;
-use tracing::{error, info};
+use tracing::{error, info, warn};
+
+use crate::privacy;
+
+// Headers describing the exhausted quota on `429` responses.
+pub const QUOTA_PERIOD_HEADER: &str = "X-Quota-Period";
+pub const QUOTA_RESOURCE_HEADER: &str = "X-Quota-Resource";
+pub const QUOTA_LIMIT_HEADER: &str = "X-Quota-Limit";
+pub const QUOTA_USED_HEADER: &str = "X-Quota-Used";
+pub const QUOTA_RESET_HEADER: &str = "X-Quota-Reset";
 
 pub enum ApiError {
     OllamaError(crate::ollama::OllamaError),
     SecurityError(crate::security::SecurityError),
     SecurityIssue(String),
+    ModelNotAllowed(String),
+    BadRequest(String),
+    PayloadTooLarge(String),
+    UnsupportedMediaType(String),
+    NotFound(String),
+    Unauthorized(String),
+    ServiceUnavailable(String),
+    TooManyRequests(std::time::Duration),
+    QuotaExceeded(crate::quota::QuotaExceeded),
     InternalError(String),
 }
 
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
+        let retry_after = match &self {
+            ApiError::TooManyRequests(retry_after) => Some(retry_after.as_secs().max(1)),
+            ApiError::QuotaExceeded(exceeded) => Some(exceeded.retry_after().as_secs().max(1)),
+            ApiError::SecurityError(crate::security::SecurityError::CircuitOpen(retry_after)) => {
+                Some(retry_after.as_secs().max(1))
+            }
+            ApiError::SecurityError(crate::security::SecurityError::QueueTimeout) => Some(1),
+            _ => None,
+        };
+        let quota_headers = match &self {
+            ApiError::QuotaExceeded(exceeded) => vec![
+                (QUOTA_PERIOD_HEADER, exceeded.period.as_str().to_string()),
+                (QUOTA_RESOURCE_HEADER, exceeded.resource.to_string()),
+                (QUOTA_LIMIT_HEADER, exceeded.limit.to_string()),
+                (QUOTA_USED_HEADER, exceeded.used.to_string()),
+                (QUOTA_RESET_HEADER, exceeded.reset_at.to_rfc3339()),
+            ],
+            _ => Vec::new(),
+        };
         let (status, error_message) = match self {
+            ApiError::OllamaError(crate::ollama::OllamaError::Timeout) => {
+                error!("Ollama request timed out");
+                (
+                    StatusCode::GATEWAY_TIMEOUT,
+                    "Ollama error: request timed out".to_string(),
+                )
+            }
             ApiError::OllamaError(err) => {
                 error!("Ollama error: {}", err);
                 (StatusCode::BAD_GATEWAY, format!("Ollama error: {}", err))
             }
+            ApiError::SecurityError(crate::security::SecurityError::Timeout) => {
+                error!("Security assessment timed out");
+                (
+                    StatusCode::GATEWAY_TIMEOUT,
+                    "Security error: assessment timed out".to_string(),
+                )
+            }
+            ApiError::SecurityError(crate::security::SecurityError::QueueTimeout) => {
+                warn!("No PANW scan slot freed up in time");
+                (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    "Security error: scan queue full, retry later".to_string(),
+                )
+            }
+            ApiError::SecurityError(crate::security::SecurityError::CircuitOpen(retry_after)) => {
+                warn!("Security assessment short-circuited, PANW circuit open");
+                (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    format!(
+                        "Security error: PANW API unavailable, retry in {}s",
+                        retry_after.as_secs().max(1)
+                    ),
+                )
+            }
             ApiError::SecurityError(err) => {
                 error!("Security error: {}", err);
                 (
@@ -38,7 +110,74 @@ impl IntoResponse for ApiError {
                 info!("Security issue detected: {}", msg);
                 (StatusCode::FORBIDDEN, format!("Security issue: {}", msg))
             }
+            ApiError::ModelNotAllowed(model) => {
+                info!("Rejected request for disallowed model: {}", model);
+                (
+                    StatusCode::FORBIDDEN,
+                    format!("Model not allowed: {}", model),
+                )
+            }
+            ApiError::BadRequest(msg) => {
+                info!("Bad request: {}", msg);
+                (StatusCode::BAD_REQUEST, format!("Bad request: {}", msg))
+            }
+            ApiError::PayloadTooLarge(msg) => {
+                info!("Payload too large: {}", msg);
+                (
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    format!("Payload too large: {}", msg),
+                )
+            }
+            ApiError::UnsupportedMediaType(msg) => {
+                info!("Unsupported media type: {}", msg);
+                (
+                    StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                    format!("Unsupported media type: {}", msg),
+                )
+            }
+            ApiError::NotFound(msg) => {
+                info!("Not found: {}", msg);
+                (StatusCode::NOT_FOUND, format!("Not found: {}", msg))
+            }
+            ApiError::Unauthorized(msg) => {
+                info!("Unauthorized request: {}", msg);
+                (StatusCode::UNAUTHORIZED, format!("Unauthorized: {}", msg))
+            }
+            ApiError::ServiceUnavailable(msg) => {
+                warn!("Service unavailable: {}", msg);
+                (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    format!("Service unavailable: {}", msg),
+                )
+            }
+            ApiError::TooManyRequests(retry_after) => (
+                StatusCode::TOO_MANY_REQUESTS,
+                format!(
+                    "Rate limit exceeded, retry in {}s",
+                    retry_after.as_secs().max(1)
+                ),
+            ),
+            ApiError::QuotaExceeded(exceeded) => {
+                info!(
+                    "Quota exceeded: {} {} limit of {}",
+                    exceeded.period.as_str(),
+                    exceeded.resource,
+                    exceeded.limit
+                );
+                (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    format!(
+                        "{} {} quota of {} exceeded, resets at {}",
+                        exceeded.period.as_str(),
+                        exceeded.resource,
+                        exceeded.limit,
+                        exceeded.reset_at.to_rfc3339()
+                    ),
+                )
+            }
             ApiError::InternalError(msg) => {
+                // May quote content, e.g. from a JSON error
+                let msg = privacy::redact(&msg).into_owned();
                 error!("Internal error: {}", msg);
                 (
                     StatusCode::INTERNAL_SERVER_ERROR,
@@ -51,7 +190,18 @@ impl IntoResponse for ApiError {
             "error": error_message,
         }));
 
-        (status, body).into_response()
+        let mut response = (status, body).into_response();
+        if let Some(secs) = retry_after {
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(secs));
+        }
+        for (name, value) in quota_headers {
+            if let Ok(value) = HeaderValue::from_str(&value) {
+                response.headers_mut().insert(name, value);
+            }
+        }
+        response
     }
 }
 
@@ -63,6 +213,34 @@ impl From<crate::ollama::OllamaError> for ApiError {
 
 impl From<crate::security::SecurityError> for ApiError {
     fn from(err: crate::security::SecurityError) -> Self {
-        ApiError::SecurityError(err)
+        match err {
+            // A block is a verdict on the content, not a failure of the scan
+            crate::security::SecurityError::BlockedContent(details) => {
+                ApiError::SecurityIssue(format!(
+                    "Content violates security policy. Category: {}, Action: {}",
+                    details.category, details.action
+                ))
+            }
+            err => ApiError::SecurityError(err),
+        }
+    }
+}
+
+impl From<crate::hooks::HookError> for ApiError {
+    fn from(err: crate::hooks::HookError) -> Self {
+        match err {
+            crate::hooks::HookError::Rejected { .. } => ApiError::SecurityIssue(err.to_string()),
+            crate::hooks::HookError::Failed { .. } => ApiError::InternalError(err.to_string()),
+        }
+    }
+}
+
+impl From<crate::auth::AuthError> for ApiError {
+    fn from(err: crate::auth::AuthError) -> Self {
+        match err {
+            // An unreachable IdP says nothing about the caller's token
+            crate::auth::AuthError::JwksError(_) => ApiError::ServiceUnavailable(err.to_string()),
+            _ => ApiError::Unauthorized(err.to_string()),
+        }
     }
 }
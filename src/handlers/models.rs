@@ -1,11 +1,17 @@
 use axum::http::Method;
 use axum::{extract::State, response::Response, Json};
+use bytes::Bytes;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tracing::debug;
 
-use crate::handlers::utils::build_json_response;
+use crate::handlers::utils::{build_json_response, ensure_model_allowed, with_backend_header};
 use crate::handlers::ApiError;
+use crate::ollama::backend_origin;
+use crate::security::ScanContext;
 use crate::AppState;
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -15,6 +21,79 @@ pub struct ModelRequest {
     pub path: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub stream: Option<bool>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// A cached model endpoint response.
+struct CachedResponse {
+    body: Bytes,
+    backend: Option<String>,
+    stored_at: Instant,
+}
+
+/// TTL cache for the read-only model endpoints (`/api/tags`, `/api/show`).
+///
+/// Clients such as OpenWebUI poll these constantly while the model set rarely
+/// changes. Any call that modifies models clears the whole cache.
+#[derive(Clone)]
+pub struct ModelCache {
+    ttl: Duration,
+    max_entries: usize,
+    entries: Arc<Mutex<HashMap<String, CachedResponse>>>,
+}
+
+impl ModelCache {
+    /// Creates a cache keeping up to `max_entries` responses for `ttl`; a zero TTL
+    /// disables caching.
+    pub fn new(ttl: Duration, max_entries: usize) -> Self {
+        Self {
+            ttl,
+            max_entries,
+            entries: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<(Bytes, Option<String>)> {
+        if self.ttl.is_zero() {
+            return None;
+        }
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(key)
+            .filter(|entry| entry.stored_at.elapsed() < self.ttl)
+            .map(|entry| (entry.body.clone(), entry.backend.clone()))
+    }
+
+    fn put(&self, key: String, body: Bytes, backend: Option<String>) {
+        if self.ttl.is_zero() {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, entry| entry.stored_at.elapsed() < self.ttl);
+        // Keys carry caller-chosen model names, so the entry count must be bounded
+        if entries.len() >= self.max_entries && !entries.contains_key(&key) {
+            return;
+        }
+        entries.insert(
+            key,
+            CachedResponse {
+                body,
+                backend,
+                stored_at: Instant::now(),
+            },
+        );
+    }
+
+    /// Returns `false` when caching is disabled.
+    pub fn is_enabled(&self) -> bool {
+        !self.ttl.is_zero()
+    }
+
+    /// Drops every cached response.
+    pub fn invalidate(&self) {
+        self.entries.lock().unwrap().clear();
+    }
 }
 
 /// Represents the available Ollama API endpoints.
@@ -63,6 +142,16 @@ impl OllamaEndpoint {
         }
     }
 
+    /// Returns `true` for endpoints whose responses may be cached.
+    fn is_cacheable(&self) -> bool {
+        matches!(self, Self::Tags | Self::Show)
+    }
+
+    /// Returns `true` for endpoints that change the set of models.
+    fn modifies_models(&self) -> bool {
+        matches!(self, Self::Create | Self::Copy | Self::Delete | Self::Pull)
+    }
+
     /// Determines if this endpoint should include model name in logs.
     fn includes_model_name_in_logs(&self) -> bool {
         matches!(self, Self::Show | Self::Delete | Self::Pull | Self::Push)
@@ -89,16 +178,39 @@ async fn forward_to_ollama<T: Serialize>(
 
     debug!("{}", log_message);
 
-    // Forward the request
+    // Serve read-only listings from the cache while they are fresh
+    let cache_key = match body {
+        Some(body) if endpoint.is_cacheable() => Some(format!(
+            "{}:{}",
+            endpoint.path(),
+            serde_json::to_string(body).unwrap_or_default()
+        )),
+        None if endpoint.is_cacheable() => Some(endpoint.path().to_string()),
+        _ => None,
+    };
+    if let Some((body_bytes, backend)) = cache_key
+        .as_deref()
+        .and_then(|key| lookup_cached(state, key))
+    {
+        debug!("Serving {} from cache", endpoint.path());
+        return cached_response(body_bytes, backend);
+    }
+
+    // Forward the request to the pool serving the model
+    let client = match model_name {
+        Some(name) => state.ollama_client.for_model(name),
+        None => state.ollama_client.clone(),
+    };
     let response = match endpoint.method() {
-        Method::GET => state.ollama_client.forward_get(endpoint.path()).await?,
+        Method::GET => client.forward_get(endpoint.path()).await?,
         Method::POST => {
             let body = body
                 .ok_or_else(|| ApiError::InternalError("Body required for POST request".into()))?;
-            state.ollama_client.forward(endpoint.path(), body).await?
+            client.forward(endpoint.path(), body).await?
         }
         _ => return Err(ApiError::InternalError("Unsupported HTTP method".into())),
     };
+    let backend = backend_origin(response.url());
 
     // Process the response
     let body_bytes = response
@@ -106,18 +218,147 @@ async fn forward_to_ollama<T: Serialize>(
         .await
         .map_err(|e| ApiError::InternalError(e.to_string()))?;
 
-    Ok(build_json_response(body_bytes)?)
+    if let Some(key) = cache_key {
+        state
+            .model_cache
+            .put(key, body_bytes.clone(), Some(backend.clone()));
+    }
+    if endpoint.modifies_models() {
+        debug!("Invalidating model cache");
+        state.model_cache.invalidate();
+    }
+
+    Ok(with_backend_header(
+        build_json_response(body_bytes)?,
+        &backend,
+    ))
 }
+
+/// Looks up a cached response, counting hits and misses.
+fn lookup_cached(state: &AppState, key: &str) -> Option<(Bytes, Option<String>)> {
+    if !state.model_cache.is_enabled() {
+        return None;
+    }
+    let cached = state.model_cache.get(key);
+    state.metrics.record_cache_lookup(cached.is_some());
+    cached
+}
+
+/// Builds a response from a cached body.
+fn cached_response(body_bytes: Bytes, backend: Option<String>) -> Result<Response, ApiError> {
+    let response = build_json_response(body_bytes)?;
+    Ok(match backend {
+        Some(backend) => with_backend_header(response, &backend),
+        None => response,
+    })
+}
+
+/// Returns the model a free-form create/copy request refers to.
+fn model_name_of(request: &Value) -> Option<&str> {
+    ["model", "name", "source"]
+        .iter()
+        .find_map(|key| request.get(*key).and_then(Value::as_str))
+}
+
 /// Handler for listing models (GET /api/tags)
-pub async fn handle_list_models(State(state): State<AppState>) -> Result<Response, ApiError> {
-    forward_to_ollama::<()>(&state, OllamaEndpoint::Tags, None, None).await
+///
+/// With model routes configured, the listings of every pool are merged
+/// so clients see the whole fleet. Models excluded by the policy or the
+/// caller's tenant are hidden.
+pub async fn handle_list_models(
+    State(state): State<AppState>,
+    ctx: ScanContext,
+) -> Result<Response, ApiError> {
+    let pools = state.ollama_client.pools();
+    let tenant_restricted = ctx
+        .tenant
+        .as_ref()
+        .map(|tenant| !tenant.models.is_unrestricted())
+        .unwrap_or(false);
+    if pools.len() == 1 && state.policy.is_unrestricted() && !tenant_restricted {
+        return forward_to_ollama::<()>(&state, OllamaEndpoint::Tags, None, None).await;
+    }
+
+    let cache_key = OllamaEndpoint::Tags.path();
+    if let Some((body_bytes, backend)) = lookup_cached(&state, cache_key) {
+        debug!("Serving {} from cache", cache_key);
+        if tenant_restricted {
+            return build_json_response(filter_for_tenant(&ctx, &body_bytes)?);
+        }
+        return cached_response(body_bytes, backend);
+    }
+
+    debug!(
+        "{} across {} pools",
+        OllamaEndpoint::Tags.log_prefix(),
+        pools.len()
+    );
+    let mut models: Vec<Value> = Vec::new();
+    for client in pools {
+        let response = client.forward_get(OllamaEndpoint::Tags.path()).await?;
+        let listing: Value = response
+            .json()
+            .await
+            .map_err(|e| ApiError::InternalError(e.to_string()))?;
+
+        for model in listing
+            .get("models")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            let name = model.get("name");
+            let allowed = name
+                .and_then(Value::as_str)
+                .map(|name| state.policy.is_allowed(name))
+                .unwrap_or(false);
+            if allowed && !models.iter().any(|known| known.get("name") == name) {
+                models.push(model.clone());
+            }
+        }
+    }
+
+    let body = Bytes::from(
+        serde_json::to_vec(&json!({ "models": models }))
+            .map_err(|e| ApiError::InternalError(e.to_string()))?,
+    );
+    state
+        .model_cache
+        .put(cache_key.to_string(), body.clone(), None);
+    if tenant_restricted {
+        return build_json_response(filter_for_tenant(&ctx, &body)?);
+    }
+    build_json_response(body)
+}
+
+/// Removes the models the caller's tenant may not use from a listing.
+fn filter_for_tenant(ctx: &ScanContext, body: &Bytes) -> Result<Bytes, ApiError> {
+    let mut listing: Value =
+        serde_json::from_slice(body).map_err(|e| ApiError::InternalError(e.to_string()))?;
+    if let (Some(tenant), Some(models)) = (
+        ctx.tenant.as_ref(),
+        listing.get_mut("models").and_then(Value::as_array_mut),
+    ) {
+        models.retain(|model| {
+            model
+                .get("name")
+                .and_then(Value::as_str)
+                .map(|name| tenant.models.is_allowed(name))
+                .unwrap_or(false)
+        });
+    }
+    serde_json::to_vec(&listing)
+        .map(Bytes::from)
+        .map_err(|e| ApiError::InternalError(e.to_string()))
 }
 
 /// Handler for showing model details (POST /api/show)
 pub async fn handle_show_model(
     State(state): State<AppState>,
+    ctx: ScanContext,
     Json(request): Json<ModelRequest>,
 ) -> Result<Response, ApiError> {
+    ensure_model_allowed(&state, &ctx, &request.name)?;
     forward_to_ollama(
         &state,
         OllamaEndpoint::Show,
@@ -132,7 +373,13 @@ pub async fn handle_create_model(
     State(state): State<AppState>,
     Json(request): Json<Value>,
 ) -> Result<Response, ApiError> {
-    forward_to_ollama(&state, OllamaEndpoint::Create, Some(&request), None).await
+    forward_to_ollama(
+        &state,
+        OllamaEndpoint::Create,
+        Some(&request),
+        model_name_of(&request),
+    )
+    .await
 }
 
 /// Handler for copying a model (POST /api/copy)
@@ -140,7 +387,13 @@ pub async fn handle_copy_model(
     State(state): State<AppState>,
     Json(request): Json<Value>,
 ) -> Result<Response, ApiError> {
-    forward_to_ollama(&state, OllamaEndpoint::Copy, Some(&request), None).await
+    forward_to_ollama(
+        &state,
+        OllamaEndpoint::Copy,
+        Some(&request),
+        model_name_of(&request),
+    )
+    .await
 }
 
 /// Handler for deleting a model (POST /api/delete)
@@ -0,0 +1,311 @@
+use axum::{
+    body::Body,
+    extract::State,
+    http::{header::CONTENT_TYPE, HeaderMap},
+    response::Response,
+    Json,
+};
+use bytes::{Bytes, BytesMut};
+use futures_util::stream::{self, StreamExt};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::time::Duration;
+use tracing::{debug, error};
+use uuid::Uuid;
+
+use crate::handlers::chat::handle_chat;
+use crate::handlers::ApiError;
+use crate::privacy;
+use crate::security::ScanContext;
+use crate::stream::with_keep_alive;
+use crate::types::{ChatRequest, ChatResponse, Message, ModelOptions};
+use crate::AppState;
+
+// Final frame of an OpenAI event stream.
+const DONE_FRAME: &str = "data: [DONE]\n\n";
+
+// SSE comment sent while the stream is idle; clients ignore it.
+const KEEP_ALIVE_FRAME: &str = ": keep-alive\n\n";
+
+// Chat completion request in the OpenAI format.
+//
+// Only the fields with an Ollama equivalent are used; others are ignored.
+#[derive(Debug, Deserialize)]
+pub struct ChatCompletionRequest {
+    pub model: String,
+    pub messages: Vec<ChatCompletionMessage>,
+    #[serde(default)]
+    pub stream: bool,
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub max_tokens: Option<i32>,
+    pub seed: Option<i64>,
+    pub stop: Option<StopSequences>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChatCompletionMessage {
+    pub role: String,
+    #[serde(default)]
+    pub content: Option<MessageContent>,
+}
+
+// Message content, either plain text or a list of typed parts.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum MessageContent {
+    Text(String),
+    Parts(Vec<Value>),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum StopSequences {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl ChatCompletionMessage {
+    // Converts to an Ollama message; `data:` image URLs become base64 images.
+    fn into_message(self) -> Message {
+        let (content, images) = match self.content {
+            None => (String::new(), Vec::new()),
+            Some(MessageContent::Text(text)) => (text, Vec::new()),
+            Some(MessageContent::Parts(parts)) => {
+                let mut texts = Vec::new();
+                let mut images = Vec::new();
+                for part in &parts {
+                    match part.get("type").and_then(Value::as_str) {
+                        Some("text") => {
+                            texts.extend(part.get("text").and_then(Value::as_str));
+                        }
+                        Some("image_url") => {
+                            let url = part
+                                .pointer("/image_url/url")
+                                .and_then(Value::as_str)
+                                .unwrap_or_default();
+                            if let Some((_, data)) = url.split_once(";base64,") {
+                                images.push(data.to_string());
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                (texts.join("\n"), images)
+            }
+        };
+        Message {
+            role: self.role,
+            content,
+            images: (!images.is_empty()).then_some(images),
+            tool_calls: None,
+            extra: Map::new(),
+        }
+    }
+}
+
+impl From<ChatCompletionRequest> for ChatRequest {
+    fn from(request: ChatCompletionRequest) -> Self {
+        let options = ModelOptions {
+            temperature: request.temperature,
+            top_p: request.top_p,
+            num_predict: request.max_tokens,
+            seed: request.seed,
+            stop: request.stop.map(|stop| match stop {
+                StopSequences::One(sequence) => vec![sequence],
+                StopSequences::Many(sequences) => sequences,
+            }),
+            ..ModelOptions::default()
+        };
+        ChatRequest {
+            model: request.model,
+            messages: request
+                .messages
+                .into_iter()
+                .map(ChatCompletionMessage::into_message)
+                .collect(),
+            stream: Some(request.stream),
+            format: None,
+            options: Some(options),
+            tools: None,
+            extra: Map::new(),
+        }
+    }
+}
+
+// OpenAI-compatible chat completions (POST /v1/chat/completions).
+//
+// The request is translated to an Ollama chat request and goes through the same
+// policy checks, hooks and security scanning as `/api/chat`. Streams are returned
+// as `text/event-stream` with one `data:` frame per chunk and a final
+// `data: [DONE]`, as OpenAI SDKs expect.
+pub async fn handle_chat_completions(
+    State(state): State<AppState>,
+    ctx: ScanContext,
+    headers: HeaderMap,
+    Json(request): Json<ChatCompletionRequest>,
+) -> Result<Response, ApiError> {
+    debug!(
+        "Received OpenAI chat completion request for model: {}",
+        request.model
+    );
+    let id = format!("chatcmpl-{}", Uuid::new_v4().simple());
+    let stream = request.stream;
+    let keep_alive = Duration::from_secs(state.streaming.keep_alive_secs);
+    let response = handle_chat(State(state), ctx, headers, Json(request.into())).await?;
+
+    let (mut parts, body) = response.into_parts();
+    if stream {
+        parts
+            .headers
+            .insert(CONTENT_TYPE, "text/event-stream".parse().unwrap());
+        parts
+            .headers
+            .insert("Cache-Control", "no-cache".parse().unwrap());
+        return Ok(Response::from_parts(parts, sse_body(body, id, keep_alive)));
+    }
+
+    let bytes = axum::body::to_bytes(body, usize::MAX)
+        .await
+        .map_err(|e| ApiError::InternalError(format!("Failed to read response body: {}", e)))?;
+    let chat: ChatResponse = serde_json::from_slice(&bytes).map_err(|e| {
+        error!("Failed to parse response: {}", privacy::redact_error(&e));
+        ApiError::InternalError("Failed to parse response".to_string())
+    })?;
+    let body = completion(&id, &chat).to_string();
+    parts.headers.remove(axum::http::header::CONTENT_LENGTH);
+    Ok(Response::from_parts(parts, Body::from(body)))
+}
+
+// Converts the Ollama chat stream into OpenAI server-sent events.
+//
+// Keep-alive chunks of the chat stream become empty deltas; the event stream
+// also sends its own keep-alive comments whenever it is idle.
+fn sse_body(body: Body, id: String, keep_alive: Duration) -> Body {
+    let mut buffer = BytesMut::new();
+    let mut finished = false;
+    let frames = body
+        .into_data_stream()
+        .map(move |chunk| {
+            let mut frames = Vec::new();
+            match chunk {
+                Ok(bytes) => {
+                    // Chunks are JSON objects, not always newline-terminated, and may be split
+                    buffer.extend_from_slice(&bytes);
+                    let mut values = serde_json::Deserializer::from_slice(&buffer).into_iter();
+                    let mut consumed = 0;
+                    loop {
+                        match values.next() {
+                            Some(Ok(value)) => {
+                                consumed = values.byte_offset();
+                                frames.extend(sse_frame(&id, value, &mut finished));
+                            }
+                            Some(Err(e)) if e.is_eof() => break,
+                            Some(Err(e)) => {
+                                error!(
+                                    "Failed to parse JSON in stream: {}",
+                                    privacy::redact_error(&e)
+                                );
+                                consumed = buffer.len();
+                                break;
+                            }
+                            None => {
+                                consumed = buffer.len();
+                                break;
+                            }
+                        }
+                    }
+                    let _ = buffer.split_to(consumed);
+                }
+                Err(e) => {
+                    error!("Error in stream: {}", e);
+                    frames.push(error_frame("Stream processing error"));
+                }
+            }
+            stream::iter(frames.into_iter().map(Ok::<_, std::convert::Infallible>))
+        })
+        .flatten()
+        .chain(stream::once(async {
+            Ok(Bytes::from_static(DONE_FRAME.as_bytes()))
+        }));
+    Body::from_stream(with_keep_alive(frames, keep_alive, || {
+        Bytes::from_static(KEEP_ALIVE_FRAME.as_bytes())
+    }))
+}
+
+// Builds the event for one chunk; an error object ends the stream with an error event.
+fn sse_frame(id: &str, value: Value, finished: &mut bool) -> Option<Bytes> {
+    if *finished {
+        return None;
+    }
+    if let Some(message) = value.get("error").and_then(Value::as_str) {
+        *finished = true;
+        return Some(error_frame(message));
+    }
+    let chat: ChatResponse = serde_json::from_value(value).ok()?;
+    Some(Bytes::from(format!(
+        "data: {}\n\n",
+        completion_chunk(id, &chat)
+    )))
+}
+
+fn error_frame(message: &str) -> Bytes {
+    Bytes::from(format!(
+        "data: {}\n\n",
+        json!({ "error": { "message": message, "type": "proxy_error" } })
+    ))
+}
+
+// Maps Ollama's `done_reason` to an OpenAI `finish_reason`.
+fn finish_reason(chat: &ChatResponse) -> Option<&str> {
+    if !chat.done {
+        return None;
+    }
+    match chat.done_reason.as_deref() {
+        Some("length") => Some("length"),
+        _ => Some("stop"),
+    }
+}
+
+fn usage(chat: &ChatResponse) -> Value {
+    let prompt = chat.prompt_eval_count.unwrap_or(0);
+    let completion = chat.eval_count.unwrap_or(0);
+    json!({
+        "prompt_tokens": prompt,
+        "completion_tokens": completion,
+        "total_tokens": prompt + completion,
+    })
+}
+
+fn completion(id: &str, chat: &ChatResponse) -> Value {
+    json!({
+        "id": id,
+        "object": "chat.completion",
+        "created": chrono::Utc::now().timestamp(),
+        "model": chat.model,
+        "choices": [{
+            "index": 0,
+            "message": { "role": chat.message.role, "content": chat.message.content },
+            "finish_reason": finish_reason(chat),
+        }],
+        "usage": usage(chat),
+    })
+}
+
+fn completion_chunk(id: &str, chat: &ChatResponse) -> Value {
+    let mut chunk = json!({
+        "id": id,
+        "object": "chat.completion.chunk",
+        "created": chrono::Utc::now().timestamp(),
+        "model": chat.model,
+        "choices": [{
+            "index": 0,
+            "delta": { "role": chat.message.role, "content": chat.message.content },
+            "finish_reason": finish_reason(chat),
+        }],
+    });
+    if chat.done {
+        chunk["usage"] = usage(chat);
+    }
+    chunk
+}
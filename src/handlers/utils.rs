@@ -1,17 +1,426 @@
-use axum::{body::Body, response::Response};
+use axum::{
+    body::Body,
+    http::{HeaderMap, HeaderValue},
+    response::Response,
+};
 use bytes::Bytes;
 use futures_util::stream::StreamExt;
 use http_body_util::StreamBody;
 use serde::{de::DeserializeOwned, Serialize};
-use serde_json::json;
-use tracing::error;
+use serde_json::{json, Value};
+use std::time::Duration;
+use tracing::{debug, error, info};
 
 use crate::{
+    budget::TokenBudget,
+    config::EnforcementMode,
+    events::{EventSink, GatewayEvent},
     handlers::ApiError,
-    stream::{SecurityAssessable, SecurityAssessedStream},
+    metrics::StageTimings,
+    privacy,
+    quota::QuotaStore,
+    response_cache::{CacheKey, CachedResponse},
+    security::{Assessment, ScanContext},
+    stream::{with_keep_alive, SecurityAssessable, SecurityAssessedStream, StreamError},
+    types::ModelOptions,
     AppState,
 };
 
+// Response header naming the Ollama backend that served the request.
+pub const BACKEND_HEADER: &str = "X-Ollama-Backend";
+
+// Response header reporting the time spent per stage.
+pub const TIMING_HEADER: &str = "X-Proxy-Timing";
+
+// Response header marking responses served from the response cache.
+pub const RESPONSE_CACHE_HEADER: &str = "X-Response-Cache";
+
+// Adds the `X-Ollama-Backend` header to a response.
+pub fn with_backend_header(mut response: Response, backend: &str) -> Response {
+    if let Ok(value) = HeaderValue::from_str(backend) {
+        response.headers_mut().insert(BACKEND_HEADER, value);
+    }
+    response
+}
+
+// Returns the response cache key of a request, `None` if it is not cacheable.
+//
+// Only non-streaming requests are cached, and by default only deterministic ones.
+// Nothing is cached while the runtime policy lets content through unenforced.
+pub fn response_cache_key<T: Serialize>(
+    state: &AppState,
+    endpoint: &str,
+    request: &T,
+    options: Option<&ModelOptions>,
+    stream: bool,
+    ctx: &ScanContext,
+) -> Option<CacheKey> {
+    if stream || !enforcing(state, endpoint) {
+        return None;
+    }
+    state
+        .response_cache
+        .as_ref()?
+        .key(endpoint, request, options, ctx)
+}
+
+// Builds the response for a request answered from the response cache.
+pub fn cached_response(
+    state: &AppState,
+    key: Option<&CacheKey>,
+) -> Result<Option<Response>, ApiError> {
+    let (Some(cache), Some(key)) = (&state.response_cache, key) else {
+        return Ok(None);
+    };
+    let Some(cached) = cache.get(key) else {
+        return Ok(None);
+    };
+    debug!("Response cache hit");
+    let mut response = with_backend_header(build_json_response(cached.body)?, &cached.backend);
+    response
+        .headers_mut()
+        .insert(RESPONSE_CACHE_HEADER, HeaderValue::from_static("hit"));
+    Ok(Some(response))
+}
+
+// Stores an allowed response for later identical requests.
+//
+// Responses let through without a verdict for the prompt or the response
+// (`unscanned`, see `Assessment`) are not stored, so they are scanned again.
+// Neither are responses allowed in monitor mode or with scanning switched off
+// for `endpoint`, which would otherwise be served unscanned once enforcing again.
+pub fn store_response(
+    state: &AppState,
+    endpoint: &str,
+    key: Option<CacheKey>,
+    body: &Bytes,
+    backend: &str,
+    unscanned: bool,
+) {
+    if unscanned {
+        debug!("Not caching a response allowed without a scan verdict");
+        return;
+    }
+    if !enforcing(state, endpoint) {
+        debug!("Not caching a response allowed without enforcement");
+        return;
+    }
+    if let (Some(cache), Some(key)) = (&state.response_cache, key) {
+        cache.put(
+            key,
+            CachedResponse {
+                body: body.clone(),
+                backend: backend.to_string(),
+            },
+        );
+    }
+}
+
+// Returns `true` if the runtime policy enforces scan verdicts on `endpoint`.
+fn enforcing(state: &AppState, endpoint: &str) -> bool {
+    let policy = state.runtime_policy.load();
+    policy.mode == EnforcementMode::Enforce && policy.scanning.is_enabled(Some(endpoint))
+}
+
+// Rejects requests for models excluded by the configured policy or the caller's tenant.
+pub fn ensure_model_allowed(
+    state: &AppState,
+    ctx: &ScanContext,
+    model: &str,
+) -> Result<(), ApiError> {
+    let tenant_allows = ctx
+        .tenant
+        .as_ref()
+        .map(|tenant| tenant.models.is_allowed(model))
+        .unwrap_or(true);
+    if let Some(access) = &ctx.access {
+        access.set_model(model);
+    }
+    if state.policy.is_allowed(model) && tenant_allows {
+        Ok(())
+    } else {
+        Err(ApiError::ModelNotAllowed(model.to_string()))
+    }
+}
+
+// Validates the request's model options and applies the configured limits.
+pub fn enforce_options(
+    state: &AppState,
+    options: &mut Option<ModelOptions>,
+) -> Result<(), ApiError> {
+    state
+        .policy
+        .enforce_options(options)
+        .map_err(ApiError::BadRequest)
+}
+
+// Rejects callers temporarily banned after repeated blocks with `429`.
+pub fn ensure_not_banned(state: &AppState, ctx: &ScanContext) -> Result<(), ApiError> {
+    match &state.bans {
+        Some(bans) => bans.check(ctx).map_err(ApiError::TooManyRequests),
+        None => Ok(()),
+    }
+}
+
+// Counts the request against the caller's quotas, failing with `429` once one is used up.
+pub async fn enforce_quota(state: &AppState, ctx: &ScanContext) -> Result<(), ApiError> {
+    let Some(quotas) = &state.quotas else {
+        return Ok(());
+    };
+    quotas
+        .admit(&TokenBudget::user_key(ctx))
+        .await
+        .map_err(ApiError::QuotaExceeded)
+}
+
+// Checks the caller's daily token budget and caps generation at the remaining tokens.
+//
+// Fails with `429` until midnight UTC once the budget is used up.
+pub fn enforce_token_budget(
+    state: &AppState,
+    ctx: &ScanContext,
+    options: &mut Option<ModelOptions>,
+) -> Result<(), ApiError> {
+    let Some(budget) = &state.budget else {
+        return Ok(());
+    };
+    let remaining = budget
+        .remaining(&TokenBudget::user_key(ctx))
+        .map_err(ApiError::TooManyRequests)?;
+    let remaining = i32::try_from(remaining).unwrap_or(i32::MAX);
+    let options = options.get_or_insert_with(ModelOptions::default);
+    match options.num_predict {
+        Some(num_predict) if num_predict >= 0 && num_predict <= remaining => {}
+        _ => options.num_predict = Some(remaining),
+    }
+    Ok(())
+}
+
+// Adds the token counts reported by Ollama to the caller's daily budget and quotas.
+//
+// The budget counts prompt and generated tokens, quotas only generated ones.
+// Both counts are also published as a usage event.
+pub fn record_token_usage(
+    state: &AppState,
+    ctx: &ScanContext,
+    model: &str,
+    prompt_eval_count: Option<u64>,
+    eval_count: Option<u64>,
+) {
+    let usage = UsageSinks {
+        budget: state.budget.as_ref(),
+        quotas: state.quotas.as_ref(),
+        events: state.events.as_ref(),
+    };
+    usage.record(
+        ctx,
+        model,
+        prompt_eval_count.unwrap_or(0),
+        eval_count.unwrap_or(0),
+    );
+}
+
+// Destinations of the token counts of a completed response.
+struct UsageSinks<'a> {
+    budget: Option<&'a TokenBudget>,
+    quotas: Option<&'a QuotaStore>,
+    events: Option<&'a EventSink>,
+}
+
+impl UsageSinks<'_> {
+    fn is_empty(&self) -> bool {
+        self.budget.is_none() && self.quotas.is_none() && self.events.is_none()
+    }
+
+    fn record(&self, ctx: &ScanContext, model: &str, prompt_tokens: u64, completion_tokens: u64) {
+        let user = TokenBudget::user_key(ctx);
+        if let Some(budget) = self.budget {
+            budget.record(&user, prompt_tokens + completion_tokens);
+        }
+        if let Some(quotas) = self.quotas {
+            quotas.record_tokens(&user, completion_tokens);
+        }
+        if let Some(events) = self.events {
+            events.publish(GatewayEvent::usage(
+                ctx,
+                model,
+                prompt_tokens,
+                completion_tokens,
+            ));
+        }
+    }
+
+    // Records the token counts of the final chunk of a streamed response.
+    fn record_stream_chunk(&self, ctx: &ScanContext, model: &str, bytes: &[u8]) {
+        let Ok(chunk) = serde_json::from_slice::<Value>(bytes) else {
+            return;
+        };
+        if chunk.get("done").and_then(Value::as_bool) != Some(true) {
+            return;
+        }
+        let count = |field: &str| chunk.get(field).and_then(Value::as_u64).unwrap_or(0);
+        self.record(ctx, model, count("prompt_eval_count"), count("eval_count"));
+    }
+}
+
+// Adds the newest user turn to its conversation and scans the recent turns together when due.
+//
+// Does nothing unless `policy.session_scan` is configured or when the request
+// cannot be tied to a conversation.
+//
+// # Arguments
+//
+// * `state` - Application state holding the session store and the scanner
+// * `ctx` - Caller identity and scan context
+// * `headers` - Request headers, checked for the session header
+// * `model` - The requested model
+// * `first_turn` - First user message of a chat, identifying the conversation without a header
+// * `turn` - The newest user turn
+//
+// # Returns
+//
+// * `Ok(true)` - The window was let through without a verdict (fail-open)
+// * `Ok(false)` - The window was allowed, or no scan was due
+// * `Err(ApiError)` - If the window is blocked or cannot be scanned
+pub async fn scan_session_window(
+    state: &AppState,
+    ctx: &ScanContext,
+    headers: &HeaderMap,
+    model: &str,
+    first_turn: Option<&str>,
+    turn: &str,
+) -> Result<bool, ApiError> {
+    let Some(sessions) = &state.sessions else {
+        return Ok(false);
+    };
+    let Some(key) = sessions.session_key(headers, ctx, model, first_turn) else {
+        return Ok(false);
+    };
+    let Some(window) = sessions.record_turn(&key, turn) else {
+        return Ok(false);
+    };
+
+    let assessment = state.scanner.assess(&window, model, true, ctx).await?;
+    // A window spans several turns, so a mask cannot be applied and blocks instead
+    if !assessment.is_safe || assessment.masked_content.is_some() {
+        info!(
+            "Security issue detected across conversation turns: category={}, action={}",
+            assessment.category, assessment.action
+        );
+        return Err(ApiError::SecurityIssue(format!(
+            "Conversation violates security policy. Category: {}, Action: {}",
+            assessment.category, assessment.action
+        )));
+    }
+    Ok(assessment.unscanned)
+}
+
+// Replaces the scanned text of a non-streaming Ollama response with its masked version.
+//
+// # Arguments
+//
+// * `body` - The JSON response body from Ollama
+// * `field` - Slash-separated path of the scanned text, e.g. `message/content`
+// * `masked` - The text to put in its place
+pub fn mask_response_body(body: &Bytes, field: &str, masked: String) -> Result<Bytes, ApiError> {
+    let mut value: Value = serde_json::from_slice(body).map_err(|e| {
+        error!("Failed to parse response: {}", privacy::redact_error(&e));
+        ApiError::InternalError("Failed to parse response".to_string())
+    })?;
+    if let Some(target) = value.pointer_mut(&format!("/{}", field)) {
+        *target = Value::String(masked);
+    }
+    info!("Response masked by the policy engine");
+    serde_json::to_vec(&value)
+        .map(Bytes::from)
+        .map_err(|e| ApiError::InternalError(format!("Failed to serialize response: {}", e)))
+}
+
+// Runs the registered request hooks on a request before it is scanned and forwarded.
+pub async fn apply_request_hooks<T>(
+    state: &AppState,
+    endpoint: &str,
+    request: T,
+    ctx: &ScanContext,
+) -> Result<T, ApiError>
+where
+    T: Serialize + DeserializeOwned,
+{
+    if state.hooks.is_empty() {
+        return Ok(request);
+    }
+    let mut body =
+        serde_json::to_value(&request).map_err(|e| ApiError::InternalError(e.to_string()))?;
+    state.hooks.run_request(endpoint, &mut body, ctx).await?;
+    serde_json::from_value(body)
+        .map_err(|e| ApiError::InternalError(format!("Hook produced an invalid request: {}", e)))
+}
+
+// Runs the registered response hooks on a non-streaming response body.
+pub async fn apply_response_hooks(
+    state: &AppState,
+    endpoint: &str,
+    body_bytes: Bytes,
+    ctx: &ScanContext,
+) -> Result<Bytes, ApiError> {
+    if state.hooks.is_empty() {
+        return Ok(body_bytes);
+    }
+    let mut body: Value =
+        serde_json::from_slice(&body_bytes).map_err(|e| ApiError::InternalError(e.to_string()))?;
+    state.hooks.run_response(endpoint, &mut body, ctx).await?;
+    serde_json::to_vec(&body)
+        .map(Bytes::from)
+        .map_err(|e| ApiError::InternalError(e.to_string()))
+}
+
+// Adds the PANW verdict of an allowed exchange to the response headers.
+//
+// Only applied when `security.verdict_headers` is enabled. Sets `X-PANW-Scan-Id`,
+// `X-PANW-Report-Id`, `X-PANW-Category` and `X-PANW-Action` so downstream apps can
+// correlate an answer with its scan.
+pub fn with_verdict_headers(
+    state: &AppState,
+    mut response: Response,
+    assessment: Option<&Assessment>,
+) -> Response {
+    let Some(assessment) = assessment.filter(|_| state.verdict_headers) else {
+        return response;
+    };
+
+    let scan_id = assessment.details.scan_id.to_string();
+    let headers = [
+        ("X-PANW-Scan-Id", scan_id.as_str()),
+        ("X-PANW-Report-Id", assessment.details.report_id.as_str()),
+        ("X-PANW-Category", assessment.category.as_str()),
+        ("X-PANW-Action", assessment.action.as_str()),
+    ];
+    for (name, value) in headers {
+        if value.is_empty() {
+            continue;
+        }
+        if let Ok(value) = HeaderValue::from_str(value) {
+            response.headers_mut().insert(name, value);
+        }
+    }
+    response
+}
+
+// Adds the `X-Proxy-Timing` header when `server.timing_header` is enabled.
+pub fn with_timing_header(
+    state: &AppState,
+    mut response: Response,
+    timings: &StageTimings,
+) -> Response {
+    if !state.timing_header {
+        return response;
+    }
+    if let Ok(value) = HeaderValue::from_str(&timings.header_value()) {
+        response.headers_mut().insert(TIMING_HEADER, value);
+    }
+    response
+}
+
 //Builds an HTTP response with JSON content type from the provided bytes.
 pub fn build_json_response(bytes: Bytes) -> Result<Response, ApiError> {
     Response::builder()
@@ -30,38 +439,122 @@ pub async fn handle_streaming_request<T, R>(
     request: T,
     endpoint: &str,
     model: &str,
+    context: ScanContext,
 ) -> Result<Response, ApiError>
 where
     T: Serialize + Send + 'static,
     R: SecurityAssessable + DeserializeOwned + Serialize + Send + Sync + Unpin + 'static,
 {
     // No need to clone, we already own the data
-    let stream = state.ollama_client.stream(endpoint, &request).await?;
+    let (backend, stream) = state
+        .ollama_client
+        .for_model(model)
+        .stream(endpoint, &request)
+        .await?;
 
-    let assessed_stream = SecurityAssessedStream::<_, R>::new(
+    let request_id = context.request_id.clone();
+    let hooks = state.hooks.clone();
+    let budget = state.budget.clone();
+    let quotas = state.quotas.clone();
+    let events = state.events.clone();
+    let usage_model = model.to_string();
+    let hook_endpoint = endpoint.to_string();
+    let hook_context = context.clone();
+    let assessed_stream = SecurityAssessedStream::<R>::new(
         stream,
-        state.security_client.clone(),
+        state.scanner.clone(),
         model.to_string(),
+        context,
+        &state.streaming,
     );
 
-    let mapped_stream = StreamExt::map(assessed_stream, |result| match result {
+    // Pass each chunk through the response hooks, counting tokens of the final one
+    let hooked_stream = StreamExt::then(assessed_stream, move |result| {
+        let hooks = hooks.clone();
+        let budget = budget.clone();
+        let quotas = quotas.clone();
+        let events = events.clone();
+        let usage_model = usage_model.clone();
+        let endpoint = hook_endpoint.clone();
+        let context = hook_context.clone();
+        async move {
+            let bytes = result?;
+            let usage = UsageSinks {
+                budget: budget.as_ref(),
+                quotas: quotas.as_ref(),
+                events: events.as_ref(),
+            };
+            if !usage.is_empty() {
+                usage.record_stream_chunk(&context, &usage_model, &bytes);
+            }
+            if hooks.is_empty() {
+                return Ok(bytes);
+            }
+            let mut chunk: Value = serde_json::from_slice(&bytes)?;
+            hooks.run_response(&endpoint, &mut chunk, &context).await?;
+            let mut json = serde_json::to_vec(&chunk)?;
+            json.push(b'\n');
+            Ok::<_, StreamError>(Bytes::from(json))
+        }
+    });
+
+    let error_request_id = request_id.clone();
+    let mapped_stream = StreamExt::map(hooked_stream, move |result| match result {
         Ok(bytes) => Ok::<_, std::convert::Infallible>(bytes),
         Err(e) => {
-            error!("Error in stream: {:?}", e);
+            let message = privacy::redact_error(&e);
+            error!("Error in stream: {}", message);
             Ok(Bytes::from(
                 json!({
-                    "error": format!("Stream processing error: {}", e)
+                    "error": format!("Stream processing error: {}", message),
+                    "request_id": error_request_id,
                 })
-                .to_string(),
+                .to_string()
+                    + "\n",
             ))
         }
     });
 
-    let stream_body = StreamBody::new(mapped_stream);
+    // End the stream with a final error object if the shutdown drain period expires
+    let shutdown = state.shutdown.clone();
+    let shutdown_frame = futures_util::stream::once(async move { shutdown.is_cancelled() })
+        .filter_map(move |cancelled| {
+            let request_id = request_id.clone();
+            async move {
+                cancelled.then(|| {
+                    Ok(Bytes::from(
+                        json!({
+                            "error": "Stream terminated: server is shutting down",
+                            "request_id": request_id,
+                        })
+                        .to_string()
+                            + "\n",
+                    ))
+                })
+            }
+        });
+    // Keep-alives are empty chunks, so NDJSON clients parse them like any other line
+    let keep_alive_model = model.to_string();
+    let mapped_stream = with_keep_alive(
+        mapped_stream,
+        Duration::from_secs(state.streaming.keep_alive_secs),
+        move || {
+            let mut json =
+                serde_json::to_vec(&R::keep_alive(&keep_alive_model)).unwrap_or_default();
+            json.push(b'\n');
+            Bytes::from(json)
+        },
+    );
+    let drained_stream = mapped_stream
+        .take_until(state.shutdown.clone().cancelled_owned())
+        .chain(shutdown_frame);
+
+    let stream_body = StreamBody::new(drained_stream);
     let body = Body::from_stream(stream_body);
 
     Response::builder()
         .header("Content-Type", "application/json")
+        .header(BACKEND_HEADER, backend)
         .body(body)
         .map_err(|e| ApiError::InternalError(format!("Failed to create response: {}", e)))
 }
@@ -1,17 +1,55 @@
-use axum::{extract::State, response::Response};
+use axum::{extract::State, response::Response, Json};
+use serde_json::{json, Value};
 use tracing::debug;
 
-use crate::handlers::utils::build_json_response;
+use crate::handlers::utils::{build_json_response, with_backend_header};
 use crate::handlers::ApiError;
+use crate::ollama::backend_origin;
 use crate::AppState;
 
+// Git commit the gateway was built from, set by build.rs.
+const GIT_COMMIT: &str = match option_env!("GIT_COMMIT") {
+    Some(commit) => commit,
+    None => "unknown",
+};
+
+// Returns the Ollama version with the gateway's own build info under `proxy`.
 pub async fn handle_version(State(state): State<AppState>) -> Result<Response, ApiError> {
     debug!("Forwarding version request");
     let response = state.ollama_client.forward_get("/api/version").await?;
+    let backend = backend_origin(response.url());
     let body_bytes = response
         .bytes()
         .await
         .map_err(|e| ApiError::InternalError(e.to_string()))?;
 
-    Ok(build_json_response(body_bytes)?)
+    // Bodies that are not a JSON object are passed through unchanged
+    let body_bytes = match serde_json::from_slice::<Value>(&body_bytes) {
+        Ok(Value::Object(mut body)) => {
+            body.insert("proxy".to_string(), proxy_info(&state));
+            serde_json::to_vec(&body)
+                .map_err(|e| ApiError::InternalError(e.to_string()))?
+                .into()
+        }
+        _ => body_bytes,
+    };
+
+    Ok(with_backend_header(
+        build_json_response(body_bytes)?,
+        &backend,
+    ))
+}
+
+// Returns the gateway's build info and security mode (GET /api/proxy/info).
+pub async fn handle_proxy_info(State(state): State<AppState>) -> Json<Value> {
+    Json(proxy_info(&state))
+}
+
+fn proxy_info(state: &AppState) -> Value {
+    json!({
+        "name": env!("CARGO_PKG_NAME"),
+        "version": env!("CARGO_PKG_VERSION"),
+        "git_commit": GIT_COMMIT,
+        "security_mode": state.runtime_policy.load().mode,
+    })
 }
@@ -0,0 +1,363 @@
+use chrono::{DateTime, Utc};
+use rusqlite::{params_from_iter, types::Value as SqlValue, Connection};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::mpsc;
+use tracing::{debug, error, warn};
+
+use crate::config::{ConfigError, HistoryConfig};
+
+// Interval between two removals of assessments past the retention period.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+
+// Largest number of verdicts written in one transaction.
+const WRITE_BATCH_SIZE: usize = 256;
+
+#[derive(Debug, Error)]
+pub enum HistoryError {
+    #[error("Assessment history database error: {0}")]
+    DatabaseError(#[from] rusqlite::Error),
+
+    #[error("Assessment history query failed: {0}")]
+    TaskError(#[from] tokio::task::JoinError),
+}
+
+// One scan verdict as stored in the history.
+//
+// The scanned content itself is never stored, only its SHA-256 and length.
+//
+// # Fields
+//
+// * `id` - Sequence number, also the pagination cursor
+// * `created_at` - When the verdict was returned
+// * `request_id` - Correlation ID of the client request
+// * `user` - Caller the request was attributed to, as for quotas
+// * `endpoint` - Gateway endpoint that served the request
+// * `model` - Model the content was sent to or produced by
+// * `profile_name` - PANW profile selected for the request, if overridden
+// * `is_prompt` - `true` for prompts, `false` for model responses
+// * `is_safe` - Whether the content was allowed
+// * `category` - Category assigned by the scanner
+// * `action` - Action recommended by the scanner
+// * `scanner` - Backend that returned the verdict
+// * `report_id` - PANW report ID, empty for other backends
+// * `content_sha256` - Hex-encoded hash of the scanned content
+// * `content_length` - Length of the scanned content in bytes
+#[derive(Debug, Clone, Serialize)]
+pub struct AssessmentRecord {
+    pub id: i64,
+    pub created_at: DateTime<Utc>,
+    pub request_id: Option<String>,
+    pub user: String,
+    pub endpoint: Option<String>,
+    pub model: String,
+    pub profile_name: Option<String>,
+    pub is_prompt: bool,
+    pub is_safe: bool,
+    pub category: String,
+    pub action: String,
+    pub scanner: String,
+    pub report_id: String,
+    pub content_sha256: String,
+    pub content_length: usize,
+}
+
+// Filters of `/admin/assessments`; unset filters match everything.
+//
+// # Fields
+//
+// * `user` - Only assessments of this caller
+// * `category` - Only assessments with this category, e.g. `malicious`
+// * `since` - Only assessments at or after this time
+// * `cursor` - Only assessments older than this ID, taken from `next_cursor`
+// * `limit` - Page size
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AssessmentQuery {
+    pub user: Option<String>,
+    pub category: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub cursor: Option<i64>,
+    pub limit: Option<usize>,
+}
+
+// One page of assessments, newest first.
+#[derive(Debug, Clone, Serialize)]
+pub struct AssessmentPage {
+    pub assessments: Vec<AssessmentRecord>,
+    // Cursor of the next page, `None` on the last one
+    pub next_cursor: Option<i64>,
+}
+
+// Persistent history of every scan verdict, stored in SQLite.
+//
+// Records are queued and written in batches by a single background writer, so
+// scanning is not delayed by the database. When the queue is full, new records
+// are dropped with a warning. Cheap to clone; all clones share the same queue
+// and database connection.
+#[derive(Clone)]
+pub struct AssessmentHistory {
+    retention: Option<chrono::Duration>,
+    connection: Arc<Mutex<Connection>>,
+    sender: mpsc::Sender<AssessmentRecord>,
+}
+
+impl AssessmentHistory {
+    // Opens or creates the history database.
+    //
+    // # Returns
+    //
+    // * `Ok(AssessmentHistory)` - The history, ready for writes
+    // * `Err(ConfigError)` - If the database cannot be opened or initialized, or the
+    //   retention period is out of range
+    pub fn open(config: &HistoryConfig) -> Result<Self, ConfigError> {
+        let retention = match config.retention_days {
+            0 => None,
+            days => Some(
+                i64::try_from(days)
+                    .ok()
+                    .and_then(chrono::Duration::try_days)
+                    .ok_or_else(|| {
+                        ConfigError::ValidationError(format!(
+                            "history.retention_days is out of range: {}",
+                            days
+                        ))
+                    })?,
+            ),
+        };
+        let connection = Connection::open(&config.database)
+            .and_then(|connection| {
+                connection.execute_batch(
+                    "CREATE TABLE IF NOT EXISTS assessments (
+                        id INTEGER PRIMARY KEY AUTOINCREMENT,
+                        created_at INTEGER NOT NULL,
+                        request_id TEXT,
+                        user TEXT NOT NULL,
+                        endpoint TEXT,
+                        model TEXT NOT NULL,
+                        profile_name TEXT,
+                        is_prompt INTEGER NOT NULL,
+                        is_safe INTEGER NOT NULL,
+                        category TEXT NOT NULL,
+                        action TEXT NOT NULL,
+                        scanner TEXT NOT NULL,
+                        report_id TEXT NOT NULL,
+                        content_sha256 TEXT NOT NULL,
+                        content_length INTEGER NOT NULL
+                    );
+                    CREATE INDEX IF NOT EXISTS assessments_user ON assessments (user, id);
+                    CREATE INDEX IF NOT EXISTS assessments_category ON assessments (category, id);
+                    CREATE INDEX IF NOT EXISTS assessments_created_at ON assessments (created_at);",
+                )?;
+                Ok(connection)
+            })
+            .map_err(|e| {
+                ConfigError::ValidationError(format!(
+                    "Cannot open assessment history database {}: {}",
+                    config.database, e
+                ))
+            })?;
+        let connection = Arc::new(Mutex::new(connection));
+        let (sender, receiver) = mpsc::channel(config.buffer_size.max(1));
+        tokio::spawn(run_writer(receiver, connection.clone()));
+        Ok(Self {
+            retention,
+            connection,
+            sender,
+        })
+    }
+
+    // Queues a verdict for the background writer.
+    //
+    // The `id` of the record is ignored; the database assigns it.
+    pub fn record(&self, record: AssessmentRecord) {
+        if self.sender.try_send(record).is_err() {
+            warn!("Assessment history queue is full, dropping a verdict");
+        }
+    }
+
+    // Returns one page of assessments matching the filters, newest first.
+    //
+    // # Arguments
+    //
+    // * `query` - Filters and pagination cursor
+    // * `limit` - Page size, already bounded by the caller
+    //
+    // # Returns
+    //
+    // * `Ok(AssessmentPage)` - The matching assessments and the cursor of the next page
+    // * `Err(HistoryError)` - If the database cannot be read
+    pub async fn query(
+        &self,
+        query: AssessmentQuery,
+        limit: usize,
+    ) -> Result<AssessmentPage, HistoryError> {
+        let history = self.clone();
+        Ok(tokio::task::spawn_blocking(move || history.query_blocking(&query, limit)).await??)
+    }
+
+    fn query_blocking(
+        &self,
+        query: &AssessmentQuery,
+        limit: usize,
+    ) -> Result<AssessmentPage, rusqlite::Error> {
+        let mut conditions = Vec::new();
+        let mut values: Vec<SqlValue> = Vec::new();
+        if let Some(user) = &query.user {
+            values.push(SqlValue::Text(user.clone()));
+            conditions.push(format!("user = ?{}", values.len()));
+        }
+        if let Some(category) = &query.category {
+            values.push(SqlValue::Text(category.clone()));
+            conditions.push(format!("category = ?{}", values.len()));
+        }
+        if let Some(since) = query.since {
+            values.push(SqlValue::Integer(since.timestamp_millis()));
+            conditions.push(format!("created_at >= ?{}", values.len()));
+        }
+        if let Some(cursor) = query.cursor {
+            values.push(SqlValue::Integer(cursor));
+            conditions.push(format!("id < ?{}", values.len()));
+        }
+        // One extra row tells whether another page follows
+        values.push(SqlValue::Integer(limit as i64 + 1));
+        let sql = format!(
+            "SELECT id, created_at, request_id, user, endpoint, model, profile_name, is_prompt,
+                is_safe, category, action, scanner, report_id, content_sha256, content_length
+             FROM assessments {} ORDER BY id DESC LIMIT ?{}",
+            if conditions.is_empty() {
+                String::new()
+            } else {
+                format!("WHERE {}", conditions.join(" AND "))
+            },
+            values.len()
+        );
+
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(&sql)?;
+        let mut assessments = statement
+            .query_map(params_from_iter(values), |row| {
+                Ok(AssessmentRecord {
+                    id: row.get(0)?,
+                    created_at: DateTime::from_timestamp_millis(row.get(1)?).unwrap_or_default(),
+                    request_id: row.get(2)?,
+                    user: row.get(3)?,
+                    endpoint: row.get(4)?,
+                    model: row.get(5)?,
+                    profile_name: row.get(6)?,
+                    is_prompt: row.get(7)?,
+                    is_safe: row.get(8)?,
+                    category: row.get(9)?,
+                    action: row.get(10)?,
+                    scanner: row.get(11)?,
+                    report_id: row.get(12)?,
+                    content_sha256: row.get(13)?,
+                    content_length: row.get::<_, i64>(14)? as usize,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let next_cursor = if assessments.len() > limit {
+            assessments.truncate(limit);
+            assessments.last().map(|record| record.id)
+        } else {
+            None
+        };
+        Ok(AssessmentPage {
+            assessments,
+            next_cursor,
+        })
+    }
+
+    // Removes assessments older than the retention period every hour.
+    //
+    // Does nothing if assessments are kept forever.
+    pub fn spawn_pruner(&self) {
+        let Some(retention) = self.retention else {
+            return;
+        };
+        let history = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+            loop {
+                interval.tick().await;
+                let history = history.clone();
+                // Nothing can be older than a cutoff before the earliest date
+                let Some(cutoff) = Utc::now().checked_sub_signed(retention) else {
+                    continue;
+                };
+                let cutoff = cutoff.timestamp_millis();
+                let result = tokio::task::spawn_blocking(move || {
+                    history
+                        .connection
+                        .lock()
+                        .unwrap()
+                        .execute("DELETE FROM assessments WHERE created_at < ?1", [cutoff])
+                })
+                .await;
+                match result {
+                    Ok(Ok(removed)) => debug!("Pruned {} expired assessments", removed),
+                    Ok(Err(e)) => error!("Failed to prune assessment history: {}", e),
+                    Err(e) => error!("Assessment history pruning failed: {}", e),
+                }
+            }
+        });
+    }
+}
+
+// Writes queued verdicts in batches, one transaction each, until every handle is dropped.
+async fn run_writer(
+    mut receiver: mpsc::Receiver<AssessmentRecord>,
+    connection: Arc<Mutex<Connection>>,
+) {
+    let mut batch = Vec::with_capacity(WRITE_BATCH_SIZE);
+    while receiver.recv_many(&mut batch, WRITE_BATCH_SIZE).await > 0 {
+        let records = std::mem::take(&mut batch);
+        let count = records.len();
+        let connection = connection.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            insert_records(&mut connection.lock().unwrap(), &records)
+        })
+        .await;
+        match result {
+            Ok(Ok(())) => debug!("Recorded {} assessments", count),
+            Ok(Err(e)) => error!("Failed to record {} assessments: {}", count, e),
+            Err(e) => error!("Assessment history writer failed: {}", e),
+        }
+    }
+}
+
+fn insert_records(
+    connection: &mut Connection,
+    records: &[AssessmentRecord],
+) -> Result<(), rusqlite::Error> {
+    let transaction = connection.transaction()?;
+    {
+        let mut statement = transaction.prepare_cached(
+            "INSERT INTO assessments (created_at, request_id, user, endpoint, model,
+                profile_name, is_prompt, is_safe, category, action, scanner, report_id,
+                content_sha256, content_length)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+        )?;
+        for record in records {
+            statement.execute(rusqlite::params![
+                record.created_at.timestamp_millis(),
+                record.request_id,
+                record.user,
+                record.endpoint,
+                record.model,
+                record.profile_name,
+                record.is_prompt,
+                record.is_safe,
+                record.category,
+                record.action,
+                record.scanner,
+                record.report_id,
+                record.content_sha256,
+                record.content_length as i64,
+            ])?;
+        }
+    }
+    transaction.commit()
+}
@@ -0,0 +1,147 @@
+// Hooks implemented as WebAssembly modules.
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+use crate::config::{ConfigError, HookConfig};
+use crate::security::ScanContext;
+use async_trait::async_trait;
+use serde_json::Value;
+use std::sync::Arc;
+use thiserror::Error;
+use tracing::debug;
+
+// Represents errors returned by request and response hooks.
+#[derive(Debug, Error)]
+pub enum HookError {
+    #[error("Rejected by hook {hook}: {reason}")]
+    Rejected { hook: String, reason: String },
+
+    #[error("Hook {hook} failed: {message}")]
+    Failed { hook: String, message: String },
+}
+
+// A transformation applied to requests before they are forwarded to Ollama
+// and to responses before they are returned to the client.
+//
+// Hooks see the JSON body and may change it in place, e.g. to strip PII or
+// inject metadata, or reject the exchange to enforce custom policies. Both
+// methods default to leaving the body untouched.
+#[async_trait]
+pub trait Hook: Send + Sync {
+    // Short identifier of the hook, used in logs and errors.
+    fn name(&self) -> &str;
+
+    // Inspects or rewrites a request body.
+    //
+    // # Arguments
+    //
+    // * `endpoint` - The Ollama endpoint, e.g. `/api/chat`
+    // * `body` - The request body, changed in place
+    // * `ctx` - Per-request attribution
+    //
+    // # Returns
+    //
+    // * `Ok(())` - To forward the (possibly changed) request
+    // * `Err(HookError)` - To reject the request
+    async fn on_request(
+        &self,
+        _endpoint: &str,
+        _body: &mut Value,
+        _ctx: &ScanContext,
+    ) -> Result<(), HookError> {
+        Ok(())
+    }
+
+    // Inspects or rewrites a response body, or each chunk of a streamed response.
+    //
+    // # Arguments
+    //
+    // * `endpoint` - The Ollama endpoint, e.g. `/api/chat`
+    // * `body` - The response body, changed in place
+    // * `ctx` - Per-request attribution
+    //
+    // # Returns
+    //
+    // * `Ok(())` - To return the (possibly changed) response
+    // * `Err(HookError)` - To reject the response
+    async fn on_response(
+        &self,
+        _endpoint: &str,
+        _body: &mut Value,
+        _ctx: &ScanContext,
+    ) -> Result<(), HookError> {
+        Ok(())
+    }
+}
+
+// Ordered list of hooks registered at startup.
+#[derive(Clone, Default)]
+pub struct HookPipeline {
+    hooks: Arc<Vec<Arc<dyn Hook>>>,
+}
+
+impl HookPipeline {
+    pub fn new(hooks: Vec<Arc<dyn Hook>>) -> Self {
+        Self {
+            hooks: Arc::new(hooks),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    // Runs every hook on a request body, stopping at the first rejection.
+    pub async fn run_request(
+        &self,
+        endpoint: &str,
+        body: &mut Value,
+        ctx: &ScanContext,
+    ) -> Result<(), HookError> {
+        for hook in self.hooks.iter() {
+            debug!("Running request hook {} for {}", hook.name(), endpoint);
+            hook.on_request(endpoint, body, ctx).await?;
+        }
+        Ok(())
+    }
+
+    // Runs every hook on a response body, stopping at the first rejection.
+    pub async fn run_response(
+        &self,
+        endpoint: &str,
+        body: &mut Value,
+        ctx: &ScanContext,
+    ) -> Result<(), HookError> {
+        for hook in self.hooks.iter() {
+            debug!("Running response hook {} for {}", hook.name(), endpoint);
+            hook.on_response(endpoint, body, ctx).await?;
+        }
+        Ok(())
+    }
+}
+
+// Loads the hooks listed in config, in order.
+//
+// # Arguments
+//
+// * `configs` - The `hooks` section of the configuration
+//
+// # Returns
+//
+// * `Ok(Vec<Arc<dyn Hook>>)` - The loaded hooks
+// * `Err(ConfigError)` - If a module cannot be loaded or WASM support is not built in
+pub fn load_hooks(configs: &[HookConfig]) -> Result<Vec<Arc<dyn Hook>>, ConfigError> {
+    configs.iter().map(load_wasm_hook).collect()
+}
+
+#[cfg(feature = "wasm")]
+fn load_wasm_hook(config: &HookConfig) -> Result<Arc<dyn Hook>, ConfigError> {
+    Ok(Arc::new(wasm::WasmHook::load(config)?))
+}
+
+#[cfg(not(feature = "wasm"))]
+fn load_wasm_hook(_config: &HookConfig) -> Result<Arc<dyn Hook>, ConfigError> {
+    Err(ConfigError::ValidationError(
+        "WASM hooks require building with the `wasm` feature".into(),
+    ))
+}
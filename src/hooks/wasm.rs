@@ -0,0 +1,179 @@
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use tracing::debug;
+use wasmtime::{
+    Config as EngineConfig, Engine, Instance, Module, Store, StoreLimits, StoreLimitsBuilder,
+};
+
+use crate::config::{ConfigError, HookConfig};
+use crate::hooks::{Hook, HookError};
+use crate::security::ScanContext;
+
+// A hook implemented by a WebAssembly module.
+//
+// The module exports its `memory`, an `alloc(len: i32) -> i32` function and
+// `on_request` and/or `on_response` taking `(ptr: i32, len: i32)` of a JSON
+// input `{"endpoint": ..., "body": ...}`. They return `0` to leave the body
+// unchanged, or a pointer and length packed as `(ptr << 32) | len` of a JSON
+// output: `{"body": ...}` to replace the body or `{"reject": "reason"}` to
+// reject the exchange.
+//
+// Every call runs in a fresh instance limited to `max_fuel` units of work and
+// `max_memory_bytes` of memory, so a faulty module cannot keep state between
+// requests, hang the gateway or exhaust its memory.
+#[derive(Clone)]
+pub struct WasmHook {
+    name: String,
+    engine: Engine,
+    module: Module,
+    max_fuel: u64,
+    max_memory_bytes: usize,
+}
+
+impl WasmHook {
+    pub fn load(config: &HookConfig) -> Result<Self, ConfigError> {
+        let mut engine_config = EngineConfig::new();
+        engine_config.consume_fuel(true);
+        let engine = Engine::new(&engine_config)
+            .map_err(|e| ConfigError::ValidationError(format!("WASM engine: {}", e)))?;
+        let module = Module::from_file(&engine, &config.path).map_err(|e| {
+            ConfigError::ValidationError(format!("Failed to load hook {}: {}", config.path, e))
+        })?;
+
+        Ok(Self {
+            name: config.name.clone(),
+            engine,
+            module,
+            max_fuel: config.max_fuel,
+            max_memory_bytes: config.max_memory_bytes,
+        })
+    }
+
+    // Calls an export of a fresh instance with the JSON input.
+    //
+    // Returns `Ok(None)` when the module does not export the function or leaves
+    // the body unchanged.
+    fn call(&self, export: &str, input: &[u8]) -> Result<Option<Value>, HookError> {
+        let failed = |message: String| HookError::Failed {
+            hook: self.name.clone(),
+            message,
+        };
+
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(self.max_memory_bytes)
+            .trap_on_grow_failure(true)
+            .build();
+        let mut store = Store::new(&self.engine, limits);
+        store.limiter(|limits: &mut StoreLimits| limits);
+        store
+            .set_fuel(self.max_fuel)
+            .map_err(|e| failed(e.to_string()))?;
+        let instance =
+            Instance::new(&mut store, &self.module, &[]).map_err(|e| failed(e.to_string()))?;
+        let Ok(function) = instance.get_typed_func::<(i32, i32), i64>(&mut store, export) else {
+            return Ok(None);
+        };
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| failed("module does not export its memory".into()))?;
+        let alloc = instance
+            .get_typed_func::<i32, i32>(&mut store, "alloc")
+            .map_err(|e| failed(e.to_string()))?;
+
+        let len = i32::try_from(input.len()).map_err(|_| failed("input too large".into()))?;
+        let ptr = alloc
+            .call(&mut store, len)
+            .map_err(|e| failed(e.to_string()))?;
+        memory
+            .write(&mut store, ptr as u32 as usize, input)
+            .map_err(|e| failed(e.to_string()))?;
+
+        let packed = function
+            .call(&mut store, (ptr, len))
+            .map_err(|e| failed(e.to_string()))?;
+        if packed == 0 {
+            return Ok(None);
+        }
+
+        let out_ptr = (packed >> 32) as u32 as usize;
+        let out_len = (packed & 0xffff_ffff) as u32 as usize;
+        // Check the range before allocating, the length comes from the module
+        let in_bounds = out_ptr
+            .checked_add(out_len)
+            .is_some_and(|end| end <= memory.data_size(&store));
+        if !in_bounds {
+            return Err(failed("output is out of the module memory".into()));
+        }
+        let mut output = vec![0; out_len];
+        memory
+            .read(&store, out_ptr, &mut output)
+            .map_err(|e| failed(e.to_string()))?;
+        serde_json::from_slice(&output)
+            .map(Some)
+            .map_err(|e| failed(format!("invalid output: {}", e)))
+    }
+
+    async fn run(
+        &self,
+        export: &'static str,
+        endpoint: &str,
+        body: &mut Value,
+    ) -> Result<(), HookError> {
+        let input =
+            serde_json::to_vec(&json!({ "endpoint": endpoint, "body": body })).map_err(|e| {
+                HookError::Failed {
+                    hook: self.name.clone(),
+                    message: e.to_string(),
+                }
+            })?;
+
+        // Module execution is CPU-bound, keep it off the async workers
+        let hook = self.clone();
+        let output = tokio::task::spawn_blocking(move || hook.call(export, &input))
+            .await
+            .map_err(|e| HookError::Failed {
+                hook: self.name.clone(),
+                message: e.to_string(),
+            })??;
+
+        let Some(mut output) = output else {
+            return Ok(());
+        };
+        if let Some(reason) = output.get("reject") {
+            return Err(HookError::Rejected {
+                hook: self.name.clone(),
+                reason: reason.as_str().unwrap_or("rejected").to_string(),
+            });
+        }
+        if let Some(new_body) = output.get_mut("body") {
+            debug!("Hook {} rewrote the {} body", self.name, export);
+            *body = new_body.take();
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Hook for WasmHook {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn on_request(
+        &self,
+        endpoint: &str,
+        body: &mut Value,
+        _ctx: &ScanContext,
+    ) -> Result<(), HookError> {
+        self.run("on_request", endpoint, body).await
+    }
+
+    async fn on_response(
+        &self,
+        endpoint: &str,
+        body: &mut Value,
+        _ctx: &ScanContext,
+    ) -> Result<(), HookError> {
+        self.run("on_response", endpoint, body).await
+    }
+}
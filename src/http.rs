@@ -0,0 +1,122 @@
+use crate::config::{ConfigError, ConnectionConfig, HttpVersion, UpstreamTlsConfig};
+use reqwest::{Certificate, Client, ClientBuilder, Identity};
+use std::fs;
+use std::time::Duration;
+use tracing::warn;
+
+// Builds an HTTP client for an upstream service (Ollama or PANW).
+//
+// Applies the connect timeout, an optional overall request timeout, the
+// connection pool settings and the upstream TLS settings: extra trusted CA
+// certificates, a client identity for mTLS, and the `insecure_skip_verify`
+// escape hatch.
+//
+// # Arguments
+//
+// * `connect_timeout` - Maximum time to establish a connection
+// * `timeout` - Optional maximum time for a complete request
+// * `tls` - TLS settings for this upstream
+// * `connection` - Pooling, keepalive and HTTP version settings for this upstream
+//
+// # Returns
+//
+// * `Ok(Client)` - The configured client
+// * `Err(ConfigError)` - If a certificate or key cannot be read or parsed
+pub fn build_client(
+    connect_timeout: Duration,
+    timeout: Option<Duration>,
+    tls: &UpstreamTlsConfig,
+    connection: &ConnectionConfig,
+) -> Result<Client, ConfigError> {
+    let mut builder = client_builder(connect_timeout, connection);
+
+    if let Some(timeout) = timeout {
+        builder = builder.timeout(timeout);
+    }
+
+    if let Some(ca_path) = &tls.ca_bundle_path {
+        for cert in load_ca_bundle(ca_path)? {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+
+    match (&tls.client_cert_path, &tls.client_key_path) {
+        (Some(cert_path), Some(key_path)) => {
+            let cert = fs::read(cert_path)?;
+            let key = fs::read(key_path)?;
+            let identity = Identity::from_pkcs8_pem(&cert, &key).map_err(|e| {
+                ConfigError::TlsError(format!("Invalid client certificate or key: {}", e))
+            })?;
+            builder = builder.identity(identity);
+        }
+        (None, None) => {}
+        _ => {
+            return Err(ConfigError::TlsError(
+                "client_cert_path and client_key_path must be set together".into(),
+            ))
+        }
+    }
+
+    if tls.insecure_skip_verify {
+        warn!("TLS certificate verification is disabled for an upstream connection");
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+
+    builder
+        .build()
+        .map_err(|e| ConfigError::TlsError(format!("Failed to build HTTP client: {}", e)))
+}
+
+// Returns a client builder with the timeouts and connection settings shared by all upstreams.
+pub fn client_builder(connect_timeout: Duration, connection: &ConnectionConfig) -> ClientBuilder {
+    let mut builder = Client::builder()
+        .connect_timeout(connect_timeout)
+        .pool_max_idle_per_host(connection.pool_max_idle_per_host)
+        .pool_idle_timeout(Duration::from_secs(connection.pool_idle_timeout_secs));
+
+    if connection.tcp_keepalive_secs > 0 {
+        builder = builder.tcp_keepalive(Duration::from_secs(connection.tcp_keepalive_secs));
+    }
+
+    // PINGs only apply to HTTP/2 connections, negotiated or forced
+    if connection.http_version != HttpVersion::Http1
+        && connection.http2_keep_alive_interval_secs > 0
+    {
+        builder = builder
+            .http2_keep_alive_interval(Duration::from_secs(
+                connection.http2_keep_alive_interval_secs,
+            ))
+            .http2_keep_alive_while_idle(true);
+    }
+
+    match connection.http_version {
+        HttpVersion::Auto => builder,
+        HttpVersion::Http1 => builder.http1_only(),
+        HttpVersion::Http2 => builder.http2_prior_knowledge().http2_adaptive_window(true),
+    }
+}
+
+// Reads every certificate from a PEM bundle file.
+fn load_ca_bundle(path: &str) -> Result<Vec<Certificate>, ConfigError> {
+    let pem = fs::read_to_string(path)?;
+    let end_marker = "-----END CERTIFICATE-----";
+
+    let certs = pem
+        .split_inclusive(end_marker)
+        .filter(|block| block.contains("-----BEGIN CERTIFICATE-----"))
+        .map(|block| {
+            Certificate::from_pem(block.trim().as_bytes()).map_err(|e| {
+                ConfigError::TlsError(format!("Invalid CA certificate in {}: {}", path, e))
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if certs.is_empty() {
+        return Err(ConfigError::TlsError(format!(
+            "No certificates found in CA bundle {}",
+            path
+        )));
+    }
+
+    Ok(certs)
+}
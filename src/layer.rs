@@ -0,0 +1,472 @@
+use crate::handlers::context::scan_context;
+use crate::handlers::ApiError;
+use crate::scanner::Scanner;
+use crate::security::ScanContext;
+use axum::{
+    body::{to_bytes, Body, Bytes, HttpBody},
+    extract::Request,
+    http::{header::CONTENT_TYPE, HeaderMap},
+    response::{IntoResponse, Response},
+};
+use http_body_util::LengthLimitError;
+use serde_json::Value;
+use std::convert::Infallible;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tower::{Layer, Service};
+use tracing::{debug, error, info};
+
+// Largest body buffered for inspection by default.
+const DEFAULT_MAX_BODY_BYTES: usize = 10 * 1024 * 1024;
+
+// Tower layer scanning JSON request and response bodies with a `Scanner`.
+//
+// Wrap any Axum route with it to apply the same prompt/response assessment
+// as the bundled Ollama handlers:
+//
+// ```
+// let app = Router::new()
+//     .route("/my/llm", post(my_handler))
+//     .layer(SecurityScanLayer::new(Arc::new(security_client)));
+// ```
+//
+// Text is taken from the configured JSON fields. A field path is a dotted list
+// of keys; arrays along the path are walked element by element, so
+// `messages.content` selects the content of every chat message.
+// `application/x-ndjson` bodies are buffered and the text of each field is
+// joined across lines, so a streamed answer is scanned as a whole.
+//
+// Flagged or blocked content is rejected with `403`. Bodies that cannot be
+// scanned fail closed: a request that is not JSON is
+// rejected with `415`, one that does not parse with `400`, and an unscannable
+// response is replaced by a `500`. Bodies above the size limit get `413`.
+#[derive(Clone)]
+pub struct SecurityScanLayer {
+    scanner: Arc<dyn Scanner>,
+    request_fields: Arc<Vec<String>>,
+    response_fields: Arc<Vec<String>>,
+    max_body_bytes: usize,
+    allow_unscannable: bool,
+}
+
+impl SecurityScanLayer {
+    // Creates a layer with the Ollama field conventions.
+    //
+    // # Arguments
+    //
+    // * `scanner` - The backend assessing the extracted text
+    //
+    // # Returns
+    //
+    // A layer scanning `prompt`, `messages.content` and `input` in requests and
+    // `response` and `message.content` in responses
+    pub fn new(scanner: Arc<dyn Scanner>) -> Self {
+        Self {
+            scanner,
+            request_fields: Arc::new(
+                ["prompt", "messages.content", "input"]
+                    .iter()
+                    .map(|f| f.to_string())
+                    .collect(),
+            ),
+            response_fields: Arc::new(
+                ["response", "message.content"]
+                    .iter()
+                    .map(|f| f.to_string())
+                    .collect(),
+            ),
+            max_body_bytes: DEFAULT_MAX_BODY_BYTES,
+            allow_unscannable: false,
+        }
+    }
+
+    // Sets the JSON fields assessed as prompts in request bodies.
+    pub fn with_request_fields<I, F>(mut self, fields: I) -> Self
+    where
+        I: IntoIterator<Item = F>,
+        F: Into<String>,
+    {
+        self.request_fields = Arc::new(fields.into_iter().map(Into::into).collect());
+        self
+    }
+
+    // Sets the JSON fields assessed as model output in response bodies.
+    pub fn with_response_fields<I, F>(mut self, fields: I) -> Self
+    where
+        I: IntoIterator<Item = F>,
+        F: Into<String>,
+    {
+        self.response_fields = Arc::new(fields.into_iter().map(Into::into).collect());
+        self
+    }
+
+    // Sets the largest body buffered for inspection; larger bodies are rejected with `413`.
+    pub fn with_max_body_bytes(mut self, max_body_bytes: usize) -> Self {
+        self.max_body_bytes = max_body_bytes;
+        self
+    }
+
+    // Passes bodies that are not JSON or do not parse through unscanned instead of
+    // rejecting them. Only for routes that also carry binary or free-form payloads.
+    pub fn with_allow_unscannable(mut self, allow: bool) -> Self {
+        self.allow_unscannable = allow;
+        self
+    }
+}
+
+impl<S> Layer<S> for SecurityScanLayer {
+    type Service = SecurityScanService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        SecurityScanService {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+// Service produced by `SecurityScanLayer`.
+#[derive(Clone)]
+pub struct SecurityScanService<S> {
+    inner: S,
+    layer: SecurityScanLayer,
+}
+
+impl<S> Service<Request> for SecurityScanService<S>
+where
+    S: Service<Request, Response = Response, Error = Infallible> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Response, Infallible>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        // Take the service that was polled ready and leave a fresh clone behind
+        let clone = self.inner.clone();
+        let inner = std::mem::replace(&mut self.inner, clone);
+        let layer = self.layer.clone();
+
+        Box::pin(async move {
+            match scan_exchange(inner, layer, request).await {
+                Ok(response) => Ok(response),
+                Err(e) => Ok(e.into_response()),
+            }
+        })
+    }
+}
+
+// Scans the request, runs the inner service and scans its response.
+async fn scan_exchange<S>(
+    mut inner: S,
+    layer: SecurityScanLayer,
+    request: Request,
+) -> Result<Response, ApiError>
+where
+    S: Service<Request, Response = Response, Error = Infallible>,
+{
+    let ctx = scan_context(request.extensions(), request.uri().path());
+
+    let (parts, body) = request.into_parts();
+    let mut model = String::from("unknown");
+    let request = if skips_scan(&layer, &parts.headers, &body) {
+        Request::from_parts(parts, body)
+    } else if body_format(&parts.headers) == BodyFormat::Other {
+        return Err(ApiError::UnsupportedMediaType(
+            "Request body must be JSON to be scanned".to_string(),
+        ));
+    } else {
+        let bytes = read_body(body, layer.max_body_bytes, "request").await?;
+        match parse_body(&bytes) {
+            Some(values) => {
+                if let Some(name) = values
+                    .first()
+                    .and_then(|value| value.get("model"))
+                    .and_then(Value::as_str)
+                {
+                    model = name.to_string();
+                }
+                assess_fields(&layer, &values, &layer.request_fields, &model, true, &ctx).await?;
+            }
+            None if layer.allow_unscannable => debug!("Passing unparseable request body through"),
+            None => {
+                return Err(ApiError::BadRequest(
+                    "Request body is not valid JSON".to_string(),
+                ))
+            }
+        }
+        Request::from_parts(parts, Body::from(bytes))
+    };
+
+    let response = match inner.call(request).await {
+        Ok(response) => response,
+        Err(never) => match never {},
+    };
+
+    let (parts, body) = response.into_parts();
+    if skips_scan(&layer, &parts.headers, &body) {
+        return Ok(Response::from_parts(parts, body));
+    }
+    let bytes = match body_format(&parts.headers) {
+        BodyFormat::Other => None,
+        _ => Some(read_body(body, layer.max_body_bytes, "response").await?),
+    };
+    match bytes.as_ref().and_then(parse_body) {
+        Some(values) => {
+            assess_fields(&layer, &values, &layer.response_fields, &model, false, &ctx).await?
+        }
+        None if layer.allow_unscannable => debug!("Passing unparseable response body through"),
+        None => {
+            error!("Response body cannot be scanned, withholding it");
+            return Err(ApiError::InternalError(
+                "Response body could not be scanned".to_string(),
+            ));
+        }
+    }
+
+    Ok(Response::from_parts(
+        parts,
+        Body::from(bytes.unwrap_or_default()),
+    ))
+}
+
+// Format of a body, from its `Content-Type`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BodyFormat {
+    Json,
+    NdJson,
+    Other,
+}
+
+fn body_format(headers: &HeaderMap) -> BodyFormat {
+    let content_type = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    if content_type.starts_with("application/json") {
+        BodyFormat::Json
+    } else if content_type.starts_with("application/x-ndjson") {
+        BodyFormat::NdJson
+    } else {
+        BodyFormat::Other
+    }
+}
+
+// Returns `true` for bodies left unbuffered: empty ones, and non-JSON ones when
+// unscannable bodies are allowed.
+fn skips_scan(layer: &SecurityScanLayer, headers: &HeaderMap, body: &Body) -> bool {
+    body.size_hint().exact() == Some(0)
+        || (layer.allow_unscannable && body_format(headers) == BodyFormat::Other)
+}
+
+// Buffers a body up to the size limit.
+async fn read_body(body: Body, max_body_bytes: usize, kind: &str) -> Result<Bytes, ApiError> {
+    to_bytes(body, max_body_bytes).await.map_err(|e| {
+        if e.into_inner().is::<LengthLimitError>() {
+            return ApiError::PayloadTooLarge(format!(
+                "The {} body exceeds {} bytes and cannot be scanned",
+                kind, max_body_bytes
+            ));
+        }
+        error!("Failed to buffer {} body", kind);
+        ApiError::InternalError(format!("Failed to read {} body", kind))
+    })
+}
+
+// Parses a JSON document, or an NDJSON stream of them, whatever the declared type:
+// streaming endpoints often label NDJSON as `application/json`.
+//
+// # Returns
+//
+// The parsed values, or `None` if the body is neither
+fn parse_body(bytes: &Bytes) -> Option<Vec<Value>> {
+    if bytes.iter().all(u8::is_ascii_whitespace) {
+        return Some(Vec::new());
+    }
+    if let Ok(value) = serde_json::from_slice(bytes) {
+        return Some(vec![value]);
+    }
+    bytes
+        .split(|byte| *byte == b'\n')
+        .filter(|line| !line.iter().all(u8::is_ascii_whitespace))
+        .map(|line| serde_json::from_slice(line).ok())
+        .collect()
+}
+
+// Assesses every string found under `fields`, failing on the first unsafe one.
+//
+// The strings of an NDJSON stream are joined per field before being assessed.
+async fn assess_fields(
+    layer: &SecurityScanLayer,
+    values: &[Value],
+    fields: &[String],
+    model: &str,
+    is_prompt: bool,
+    ctx: &ScanContext,
+) -> Result<(), ApiError> {
+    for field in fields {
+        let path = field.split('.').collect::<Vec<_>>();
+        let mut texts = Vec::new();
+        for value in values {
+            collect_strings(value, &path, &mut texts);
+        }
+        let texts = if values.len() > 1 {
+            vec![texts.concat()]
+        } else {
+            texts.into_iter().map(str::to_string).collect()
+        };
+
+        for text in texts.iter().filter(|text| !text.is_empty()) {
+            debug!("Scanning field {} ({} bytes)", field, text.len());
+            let assessment = layer.scanner.assess(text, model, is_prompt, ctx).await?;
+
+            // Masks cannot be written back into the raw body, so they block instead
+            if !assessment.is_safe || assessment.masked_content.is_some() {
+                let kind = if is_prompt { "Request" } else { "Response" };
+                info!(
+                    "Security issue detected in {} field {}: category={}, action={}",
+                    kind.to_lowercase(),
+                    field,
+                    assessment.category,
+                    assessment.action
+                );
+                return Err(ApiError::SecurityIssue(format!(
+                    "{} content violates security policy. Category: {}, Action: {}",
+                    kind, assessment.category, assessment.action
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Collects the non-empty strings at `path`, walking into arrays along the way.
+fn collect_strings<'a>(value: &'a Value, path: &[&str], out: &mut Vec<&'a str>) {
+    match value {
+        Value::Array(items) => {
+            for item in items {
+                collect_strings(item, path, out);
+            }
+        }
+        _ => match path.split_first() {
+            Some((key, rest)) => {
+                if let Some(child) = value.get(*key) {
+                    collect_strings(child, rest, out);
+                }
+            }
+            None => {
+                if let Some(text) = value.as_str().filter(|text| !text.is_empty()) {
+                    out.push(text);
+                }
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::security::{Assessment, SecurityError};
+    use crate::types::ScanResponse;
+    use async_trait::async_trait;
+    use axum::http::StatusCode;
+    use axum::routing::post;
+    use axum::Router;
+    use tower::Service;
+
+    // Flags content containing "flagged" and blocks content containing "blocked".
+    struct KeywordScanner;
+
+    #[async_trait]
+    impl Scanner for KeywordScanner {
+        fn name(&self) -> &str {
+            "keyword"
+        }
+
+        async fn assess(
+            &self,
+            content: &str,
+            _model_name: &str,
+            _is_prompt: bool,
+            _ctx: &ScanContext,
+        ) -> Result<Assessment, SecurityError> {
+            let mut details = ScanResponse::default_safe_response();
+            if content.contains("blocked") {
+                details.category = "malicious".to_string();
+                details.action = "block".to_string();
+                return Err(SecurityError::BlockedContent(Box::new(details)));
+            }
+            let is_safe = !content.contains("flagged");
+            Ok(Assessment {
+                is_safe,
+                category: if is_safe { "benign" } else { "malicious" }.to_string(),
+                action: "allow".to_string(),
+                details,
+                masked_content: None,
+                unscanned: false,
+            })
+        }
+    }
+
+    // Echoes the request body back as the response.
+    fn app() -> Router {
+        Router::new()
+            .route(
+                "/echo",
+                post(|headers: HeaderMap, body: Bytes| async move { (headers, body) }),
+            )
+            .layer(SecurityScanLayer::new(Arc::new(KeywordScanner)).with_max_body_bytes(64))
+    }
+
+    async fn send(content_type: &str, body: &str) -> StatusCode {
+        let request = Request::post("/echo")
+            .header(CONTENT_TYPE, content_type)
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        app().call(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn allows_safe_content() {
+        let status = send("application/json", r#"{"prompt": "hello"}"#).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn rejects_flagged_content() {
+        let status = send("application/json", r#"{"prompt": "flagged"}"#).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn rejects_blocked_content() {
+        let status = send("application/json", r#"{"prompt": "blocked"}"#).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn rejects_non_json_requests() {
+        let status = send("text/plain", "hello").await;
+        assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    #[tokio::test]
+    async fn rejects_unparseable_requests() {
+        let status = send("application/json", r#"{"prompt": "#).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn rejects_oversized_requests() {
+        let body = format!(r#"{{"prompt": "{}"}}"#, "a".repeat(100));
+        let status = send("application/json", &body).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    }
+}
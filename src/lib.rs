@@ -0,0 +1,1136 @@
+// Structured per-request access logging.
+pub mod access_log;
+
+// JWT validation of incoming requests.
+pub mod auth;
+
+// Temporary bans after repeated blocks.
+pub mod ban;
+
+// Load generation for the `bench` command.
+pub mod bench;
+
+// Per-user daily token budgets.
+pub mod budget;
+
+// Circuit breaker for the PANW AI Runtime API.
+pub mod circuit;
+
+// Configuration loading and management.
+pub mod config;
+
+// Periodic verification of the PANW credentials.
+pub mod credentials;
+
+// Publishing of gateway events to Kafka or NATS.
+pub mod events;
+
+// HTTP request handlers for API endpoints.
+pub mod handlers;
+
+// Request and response transformation hooks.
+pub mod hooks;
+
+// Persistent history of scan verdicts.
+pub mod history;
+
+// Shared construction of upstream HTTP clients.
+pub mod http;
+
+// Reusable Tower layer applying security scanning to arbitrary routes.
+pub mod layer;
+
+// Token bucket rate limiting.
+pub mod rate_limit;
+
+// Client for interacting with Ollama API services.
+pub mod ollama;
+
+// Loading of the PANW API token from files and secret managers.
+pub mod secrets;
+
+// Request correlation IDs.
+pub mod request_id;
+
+// Pluggable content security backends.
+pub mod scanner;
+
+// Wildcard matching of model names.
+pub mod pattern;
+
+// Scriptable stub Ollama and PANW servers for benchmarks and the testkit.
+#[cfg_attr(not(feature = "testkit"), allow(dead_code))]
+mod mock;
+
+// Model access policy.
+pub mod policy;
+
+// OPA and Cedar policy engines deciding on scan verdicts.
+pub mod policy_engine;
+
+// Redaction of user content in logs and error bodies.
+pub mod privacy;
+
+// Upstream connectivity checks run by the `validate` command.
+pub mod preflight;
+
+// In-process runtime statistics.
+pub mod metrics;
+
+// Encrypted store of blocked exchanges.
+pub mod quarantine;
+
+// Per-user request and token quotas persisted in SQLite.
+pub mod quota;
+
+// Cache of allowed responses to identical deterministic requests.
+pub mod response_cache;
+
+// Conversation tracking for cumulative multi-turn scans.
+pub mod session;
+
+// Security assessment and content filtering using PANW AI Runtime API.
+pub mod security;
+
+// Graceful shutdown and drain handling.
+pub mod shutdown;
+
+// Utilities for handling streaming responses.
+pub mod stream;
+
+// In-process gateway against stub upstreams for integration tests, available
+// with the `testkit` feature.
+#[cfg(feature = "testkit")]
+pub mod testkit;
+
+// TLS termination for the listener.
+pub mod tls;
+
+// Common type definitions used throughout the application.
+pub mod types;
+
+pub use crate::config::Config;
+pub use crate::layer::SecurityScanLayer;
+pub use crate::ollama::OllamaClient;
+pub use crate::security::SecurityClient;
+
+use crate::access_log::AccessLogger;
+use crate::auth::{AdminToken, JwtValidator, TenantRegistry};
+use crate::ban::BanList;
+use crate::budget::TokenBudget;
+use crate::config::StreamingConfig;
+use crate::credentials::CredentialHealth;
+use crate::events::EventSink;
+use crate::handlers::models::ModelCache;
+use crate::handlers::*;
+use crate::history::AssessmentHistory;
+use crate::hooks::{Hook, HookPipeline};
+use crate::metrics::Metrics;
+use crate::policy::{Guardrail, ModelPolicy, RuntimePolicy, ScanPolicy};
+use crate::policy_engine::PolicyEngine;
+use crate::quarantine::QuarantineStore;
+use crate::quota::QuotaStore;
+use crate::response_cache::ResponseCache;
+use crate::scanner::ban::BanningScanner;
+use crate::scanner::cache::{CachingScanner, ScanCache};
+use crate::scanner::enforcement::EnforcingScanner;
+use crate::scanner::events::PublishingScanner;
+use crate::scanner::history::RecordingScanner;
+use crate::scanner::metered::MeteredScanner;
+use crate::scanner::policy::PolicyScanner;
+use crate::scanner::quarantine::QuarantineScanner;
+use crate::scanner::Scanner;
+use crate::session::SessionStore;
+use arc_swap::ArcSwap;
+use axum::{
+    body::Body,
+    extract::DefaultBodyLimit,
+    middleware,
+    routing::{delete, get, post},
+    Router,
+};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use tower_http::compression::predicate::{DefaultPredicate, Predicate};
+use tower_http::compression::CompressionLayer;
+use tower_http::decompression::RequestDecompressionLayer;
+use tower_http::trace::TraceLayer;
+use tracing::info;
+
+// Error returned while assembling the gateway from its configuration.
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+// Shared application state containing clients for external services.
+//
+// This state is shared across all request handlers and contains
+// initialized clients for communicating with Ollama and security services.
+// Prompts and responses are assessed by the configured scanner backend,
+// the PANW AI Runtime API by default.
+#[derive(Clone)]
+pub struct AppState {
+    ollama_client: OllamaClient,
+    security_client: SecurityClient,
+    scanner: Arc<dyn Scanner>,
+    shutdown: CancellationToken,
+    model_cache: ModelCache,
+    policy: Arc<ModelPolicy>,
+    scan_policy: Arc<ScanPolicy>,
+    verdict_headers: bool,
+    timing_header: bool,
+    streaming: StreamingConfig,
+    metrics: Metrics,
+    runtime_policy: Arc<ArcSwap<RuntimePolicy>>,
+    quarantine: Option<QuarantineStore>,
+    history: Option<AssessmentHistory>,
+    events: Option<EventSink>,
+    hooks: HookPipeline,
+    guardrail: Option<Arc<Guardrail>>,
+    budget: Option<TokenBudget>,
+    sessions: Option<SessionStore>,
+    bans: Option<BanList>,
+    quotas: Option<QuotaStore>,
+    scan_cache: Option<ScanCache>,
+    response_cache: Option<ResponseCache>,
+    credentials: Option<CredentialHealth>,
+    access_log: Option<AccessLogger>,
+    jwt_validator: Option<JwtValidator>,
+}
+
+impl AppState {
+    // Creates a new builder for constructing AppState with a fluent API.
+    //
+    // # Returns
+    //
+    // A new AppStateBuilder instance for configuring and building the application state.
+    //
+    // # Examples
+    //
+    // ```
+    // let state = AppState::builder()
+    //     .with_ollama_client(ollama_client)
+    //     .with_security_client(security_client)
+    //     .build()?;
+    // ```
+    pub fn builder() -> AppStateBuilder {
+        AppStateBuilder::default()
+    }
+
+    // Returns the token that terminates active streams when cancelled.
+    //
+    // Cancel it once the shutdown drain period expires so open streams end
+    // with a final error object instead of being cut mid-frame.
+    pub fn shutdown_token(&self) -> CancellationToken {
+        self.shutdown.clone()
+    }
+
+    // Returns the registry collecting runtime statistics.
+    pub fn metrics(&self) -> Metrics {
+        self.metrics.clone()
+    }
+    // Returns the sink publishing gateway events, if events are configured.
+    //
+    // Flush it once the server has stopped so queued events are not lost.
+    pub fn events(&self) -> Option<EventSink> {
+        self.events.clone()
+    }
+}
+
+// Builder for creating AppState instances with a fluent API.
+//
+// This builder ensures that all required components are provided
+// before constructing the final AppState.
+#[derive(Default)]
+pub struct AppStateBuilder {
+    ollama_client: Option<OllamaClient>,
+    security_client: Option<SecurityClient>,
+    scanner: Option<Arc<dyn Scanner>>,
+    policy_engine: Option<(Arc<dyn PolicyEngine>, String, bool)>,
+    shutdown: Option<CancellationToken>,
+    model_cache: Option<ModelCache>,
+    policy: Option<ModelPolicy>,
+    scan_policy: Option<ScanPolicy>,
+    verdict_headers: bool,
+    timing_header: bool,
+    streaming: StreamingConfig,
+    metrics: Option<Metrics>,
+    runtime_policy: Option<RuntimePolicy>,
+    quarantine: Option<QuarantineStore>,
+    history: Option<AssessmentHistory>,
+    events: Option<EventSink>,
+    hooks: Vec<Arc<dyn Hook>>,
+    guardrail: Option<Guardrail>,
+    budget: Option<TokenBudget>,
+    sessions: Option<SessionStore>,
+    bans: Option<BanList>,
+    quotas: Option<QuotaStore>,
+    scan_cache: Option<ScanCache>,
+    response_cache: Option<ResponseCache>,
+    credentials: Option<CredentialHealth>,
+    access_log: Option<AccessLogger>,
+    jwt_validator: Option<JwtValidator>,
+}
+
+impl AppStateBuilder {
+    // Sets the Ollama client for the application state.
+    //
+    // # Arguments
+    //
+    // * `client` - An initialized OllamaClient instance
+    //
+    // # Returns
+    //
+    // The builder instance for method chaining
+    pub fn with_ollama_client(mut self, client: OllamaClient) -> Self {
+        self.ollama_client = Some(client);
+        self
+    }
+
+    // Sets the security client for the application state.
+    //
+    // # Arguments
+    //
+    // * `client` - An initialized SecurityClient instance for PANW AI Runtime API
+    //
+    // # Returns
+    //
+    // The builder instance for method chaining
+    pub fn with_security_client(mut self, client: SecurityClient) -> Self {
+        self.security_client = Some(client);
+        self
+    }
+
+    // Sets the scanner used to assess prompts and responses.
+    //
+    // # Arguments
+    //
+    // * `scanner` - The scanner backend; defaults to the PANW security client
+    //
+    // # Returns
+    //
+    // The builder instance for method chaining
+    pub fn with_scanner(mut self, scanner: Arc<dyn Scanner>) -> Self {
+        self.scanner = Some(scanner);
+        self
+    }
+
+    // Sets the token used to terminate active streams during shutdown.
+    //
+    // # Arguments
+    //
+    // * `token` - Cancelled when the drain timeout elapses after a shutdown signal
+    //
+    // # Returns
+    //
+    // The builder instance for method chaining
+    pub fn with_shutdown_token(mut self, token: CancellationToken) -> Self {
+        self.shutdown = Some(token);
+        self
+    }
+
+    // Sets the cache for `/api/tags` and `/api/show` responses.
+    //
+    // # Arguments
+    //
+    // * `cache` - The cache shared by the model handlers; caching is disabled if not set
+    //
+    // # Returns
+    //
+    // The builder instance for method chaining
+    pub fn with_model_cache(mut self, cache: ModelCache) -> Self {
+        self.model_cache = Some(cache);
+        self
+    }
+
+    // Sets the policy deciding which models clients may use.
+    //
+    // # Arguments
+    //
+    // * `policy` - The model allow/deny lists; all models are allowed if not set
+    //
+    // # Returns
+    //
+    // The builder instance for method chaining
+    pub fn with_policy(mut self, policy: ModelPolicy) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    // Sets the policy deciding which chat messages are scanned.
+    //
+    // # Arguments
+    //
+    // * `policy` - The conversation scope settings; every message is scanned if not set
+    //
+    // # Returns
+    //
+    // The builder instance for method chaining
+    pub fn with_scan_policy(mut self, policy: ScanPolicy) -> Self {
+        self.scan_policy = Some(policy);
+        self
+    }
+
+    // Enables the `X-PANW-*` verdict headers on allowed responses.
+    //
+    // # Arguments
+    //
+    // * `enabled` - Whether scan metadata is exposed to clients
+    //
+    // # Returns
+    //
+    // The builder instance for method chaining
+    pub fn with_verdict_headers(mut self, enabled: bool) -> Self {
+        self.verdict_headers = enabled;
+        self
+    }
+
+    // Enables the `X-Proxy-Timing` header reporting the time spent per stage.
+    //
+    // # Arguments
+    //
+    // * `enabled` - Whether stage durations are exposed to clients
+    //
+    // # Returns
+    //
+    // The builder instance for method chaining
+    pub fn with_timing_header(mut self, enabled: bool) -> Self {
+        self.timing_header = enabled;
+        self
+    }
+
+    // Sets the read-ahead buffer and scan concurrency of streamed responses.
+    //
+    // # Arguments
+    //
+    // * `streaming` - Buffer size and scan concurrency per stream
+    //
+    // # Returns
+    //
+    // The builder instance for method chaining
+    pub fn with_streaming(mut self, streaming: StreamingConfig) -> Self {
+        self.streaming = streaming;
+        self
+    }
+
+    // Sets the registry collecting runtime statistics.
+    //
+    // # Arguments
+    //
+    // * `metrics` - The registry shared with the clients; a fresh one is created if not set
+    //
+    // # Returns
+    //
+    // The builder instance for method chaining
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    // Sets the initial enforcement mode and per-endpoint scanning switches.
+    //
+    // # Arguments
+    //
+    // * `policy` - Settings changeable at runtime through `/admin/policy`; enforcing everywhere if not set
+    //
+    // # Returns
+    //
+    // The builder instance for method chaining
+    pub fn with_runtime_policy(mut self, policy: RuntimePolicy) -> Self {
+        self.runtime_policy = Some(policy);
+        self
+    }
+
+    // Lets a policy engine override the verdicts of the scanner.
+    //
+    // # Arguments
+    //
+    // * `engine` - The OPA or Cedar engine deciding on each verdict
+    // * `mask_text` - Replacement used when the engine masks content without providing one
+    // * `flagged_only` - If `true`, only flagged content is sent to the engine
+    //
+    // # Returns
+    //
+    // The builder instance for method chaining
+    pub fn with_policy_engine(
+        mut self,
+        engine: Arc<dyn PolicyEngine>,
+        mask_text: &str,
+        flagged_only: bool,
+    ) -> Self {
+        self.policy_engine = Some((engine, mask_text.to_string(), flagged_only));
+        self
+    }
+
+    // Sets the store receiving blocked prompts and responses.
+    //
+    // # Arguments
+    //
+    // * `store` - The encrypted quarantine; blocked content is not kept if not set
+    //
+    // # Returns
+    //
+    // The builder instance for method chaining
+    pub fn with_quarantine(mut self, store: QuarantineStore) -> Self {
+        self.quarantine = Some(store);
+        self
+    }
+
+    // Sets the database recording every scan verdict.
+    //
+    // # Arguments
+    //
+    // * `history` - The assessment history, queried through `/admin/assessments`; verdicts are not kept if not set
+    //
+    // # Returns
+    //
+    // The builder instance for method chaining
+    pub fn with_history(mut self, history: AssessmentHistory) -> Self {
+        self.history = Some(history);
+        self
+    }
+
+    // Sets the sink publishing verdict and usage events.
+    //
+    // # Arguments
+    //
+    // * `events` - The buffered event publisher; no events are published if not set
+    //
+    // # Returns
+    //
+    // The builder instance for method chaining
+    pub fn with_events(mut self, events: EventSink) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    // Registers a hook transforming requests and responses.
+    //
+    // # Arguments
+    //
+    // * `hook` - The hook; hooks run in the order they are registered
+    //
+    // # Returns
+    //
+    // The builder instance for method chaining
+    pub fn with_hook(mut self, hook: Arc<dyn Hook>) -> Self {
+        self.hooks.push(hook);
+        self
+    }
+
+    // Sets the system prompt enforced on chat and generate requests.
+    //
+    // # Arguments
+    //
+    // * `guardrail` - The safety preamble; requests are forwarded unchanged if not set
+    //
+    // # Returns
+    //
+    // The builder instance for method chaining
+    pub fn with_guardrail(mut self, guardrail: Guardrail) -> Self {
+        self.guardrail = Some(guardrail);
+        self
+    }
+
+    // Sets the daily token budget applied to each user.
+    //
+    // # Arguments
+    //
+    // * `budget` - The shared usage tracker; token usage is unlimited if not set
+    //
+    // # Returns
+    //
+    // The builder instance for method chaining
+    pub fn with_token_budget(mut self, budget: TokenBudget) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    // Enables cumulative scans of the recent turns of each conversation.
+    //
+    // # Arguments
+    //
+    // * `sessions` - The shared store of recent user turns
+    //
+    // # Returns
+    //
+    // The builder instance for method chaining
+    pub fn with_session_store(mut self, sessions: SessionStore) -> Self {
+        self.sessions = Some(sessions);
+        self
+    }
+
+    // Enables temporary bans of callers whose requests are blocked repeatedly.
+    //
+    // # Arguments
+    //
+    // * `bans` - The shared ban list, also managed through `/admin/bans`
+    //
+    // # Returns
+    //
+    // The builder instance for method chaining
+    pub fn with_ban_list(mut self, bans: BanList) -> Self {
+        self.bans = Some(bans);
+        self
+    }
+
+    // Enables per-user daily and monthly quotas.
+    //
+    // # Arguments
+    //
+    // * `quotas` - The store counting requests and generated tokens
+    //
+    // # Returns
+    //
+    // The builder instance for method chaining
+    pub fn with_quotas(mut self, quotas: QuotaStore) -> Self {
+        self.quotas = Some(quotas);
+        self
+    }
+
+    // Sets the cache reusing allowed verdicts for content scanned before.
+    //
+    // # Arguments
+    //
+    // * `cache` - The shared verdict cache; every piece of content is scanned if not set
+    //
+    // # Returns
+    //
+    // The builder instance for method chaining
+    pub fn with_scan_cache(mut self, cache: ScanCache) -> Self {
+        self.scan_cache = Some(cache);
+        self
+    }
+
+    // Enables the cache of non-streaming generate and chat responses.
+    //
+    // # Arguments
+    //
+    // * `cache` - The shared response cache, also flushed through `/admin/policy/flush-caches`
+    //
+    // # Returns
+    //
+    // The builder instance for method chaining
+    pub fn with_response_cache(mut self, cache: ResponseCache) -> Self {
+        self.response_cache = Some(cache);
+        self
+    }
+
+    // Sets the result holder of the PANW credential check reported by `/readyz`.
+    //
+    // # Arguments
+    //
+    // * `health` - Updated by the credential checker; credentials are not reported if not set
+    //
+    // # Returns
+    //
+    // The builder instance for method chaining
+    pub fn with_credential_health(mut self, health: CredentialHealth) -> Self {
+        self.credentials = Some(health);
+        self
+    }
+
+    // Sets the writer of the per-request access log.
+    //
+    // # Arguments
+    //
+    // * `logger` - The access log output; no access log is written if not set
+    //
+    // # Returns
+    //
+    // The builder instance for method chaining
+    pub fn with_access_log(mut self, logger: AccessLogger) -> Self {
+        self.access_log = Some(logger);
+        self
+    }
+
+    // Sets the validator used when `auth.jwt` is configured.
+    //
+    // # Arguments
+    //
+    // * `validator` - Validator with its JWKS client; `build_router` creates one if not set
+    //
+    // # Returns
+    //
+    // The builder instance for method chaining
+    pub fn with_jwt_validator(mut self, validator: JwtValidator) -> Self {
+        self.jwt_validator = Some(validator);
+        self
+    }
+
+    // Builds the AppState from the configured components.
+    //
+    // # Returns
+    //
+    // * `Ok(AppState)` - The fully constructed application state
+    // * `Err(&'static str)` - Error message if any required component is missing
+    //
+    // # Errors
+    //
+    // Returns an error if either the Ollama client or security client is not provided
+    pub fn build(self) -> Result<AppState, &'static str> {
+        let ollama_client = self.ollama_client.ok_or("OllamaClient is required")?;
+        let security_client = self.security_client.ok_or("SecurityClient is required")?;
+        let metrics = self.metrics.unwrap_or_default();
+        let scanner = self
+            .scanner
+            .unwrap_or_else(|| Arc::new(security_client.clone()));
+        let runtime_policy = Arc::new(ArcSwap::from_pointee(
+            self.runtime_policy.unwrap_or_default(),
+        ));
+        let scanner: Arc<dyn Scanner> = match &self.scan_cache {
+            Some(cache) => Arc::new(CachingScanner::new(scanner, cache.clone())),
+            None => scanner,
+        };
+        let scanner: Arc<dyn Scanner> = match &self.policy_engine {
+            Some((engine, mask_text, flagged_only)) => Arc::new(PolicyScanner::new(
+                scanner,
+                engine.clone(),
+                mask_text,
+                *flagged_only,
+            )),
+            None => scanner,
+        };
+        let mut scanner: Arc<dyn Scanner> = Arc::new(MeteredScanner::new(scanner, metrics.clone()));
+        if let Some(store) = &self.quarantine {
+            scanner = Arc::new(QuarantineScanner::new(scanner, store.clone()));
+        }
+        if let Some(history) = &self.history {
+            scanner = Arc::new(RecordingScanner::new(scanner, history.clone()));
+        }
+        if let Some(events) = &self.events {
+            scanner = Arc::new(PublishingScanner::new(scanner, events.clone()));
+        }
+        let mut scanner: Arc<dyn Scanner> =
+            Arc::new(EnforcingScanner::new(scanner, runtime_policy.clone()));
+        if let Some(bans) = &self.bans {
+            scanner = Arc::new(BanningScanner::new(scanner, bans.clone()));
+        }
+        Ok(AppState {
+            ollama_client,
+            security_client,
+            scanner,
+            shutdown: self.shutdown.unwrap_or_default(),
+            model_cache: self
+                .model_cache
+                .unwrap_or_else(|| ModelCache::new(Duration::ZERO, 0)),
+            policy: Arc::new(self.policy.unwrap_or_default()),
+            scan_policy: Arc::new(self.scan_policy.unwrap_or_default()),
+            verdict_headers: self.verdict_headers,
+            timing_header: self.timing_header,
+            streaming: self.streaming,
+            metrics,
+            runtime_policy,
+            quarantine: self.quarantine,
+            history: self.history,
+            events: self.events,
+            hooks: HookPipeline::new(self.hooks),
+            guardrail: self.guardrail.map(Arc::new),
+            budget: self.budget,
+            sessions: self.sessions,
+            bans: self.bans,
+            quotas: self.quotas,
+            scan_cache: self.scan_cache,
+            response_cache: self.response_cache,
+            credentials: self.credentials,
+            access_log: self.access_log,
+            jwt_validator: self.jwt_validator,
+        })
+    }
+}
+
+// Creates the PANW AI Runtime API client described by the configuration.
+//
+// Resolves the API token from the file, secret manager or inline value.
+//
+// # Arguments
+//
+// * `config` - The loaded gateway configuration
+//
+// # Returns
+//
+// * `Ok(SecurityClient)` - The client, without metrics attached
+// * `Err(BoxError)` - If the token cannot be resolved or the HTTP client cannot be built
+pub async fn build_security_client(config: &Config) -> Result<SecurityClient, BoxError> {
+    let api_key = secrets::resolve_api_key(&config.security).await?;
+    Ok(SecurityClient::new(
+        &config.security.resolved_base_url(),
+        &api_key,
+        &config.security.profile_name,
+        &config.security.app_name,
+        &config.security.app_user,
+    )
+    .with_scan_path(&config.security.scan_path())
+    .with_http_client(http::build_client(
+        Duration::from_secs(config.security.connect_timeout_secs),
+        Some(Duration::from_secs(config.security.timeout_secs)),
+        &config.security.tls,
+        &config.security.connection,
+    )?)
+    .with_concurrency_limit(
+        config.security.max_concurrent_scans,
+        Duration::from_secs(config.security.scan_queue_timeout_secs),
+    )
+    .with_retry_policy(config.security.retry.clone())
+    .with_circuit_breaker(&config.security.circuit_breaker, config.security.fail_open)
+    .with_content_limit(config.security.content_limit.clone()))
+}
+
+// Creates the application state from the configuration.
+//
+// This resolves the PANW API token, builds the Ollama and PANW clients with their
+// timeouts, TLS and resilience settings, and selects the configured scanner backend.
+//
+// # Arguments
+//
+// * `config` - The loaded and validated configuration
+//
+// # Returns
+//
+// * `Ok(AppState)` - State ready to be passed to `build_router`
+// * `Err(BoxError)` - If a secret, certificate or scanner setting cannot be loaded
+pub async fn build_state(config: &Config) -> Result<AppState, BoxError> {
+    privacy::set_enabled(config.logging.privacy_mode);
+    let metrics = Metrics::new();
+
+    // Create clients for Ollama and the PANW AI Runtime API
+    let mut ollama_client = OllamaClient::new_pool(&config.ollama.base_url)
+        .with_load_balancing(config.ollama.load_balancing)
+        .with_model_routes(&config.ollama.routes)
+        .with_http_client(http::build_client(
+            Duration::from_secs(config.ollama.connect_timeout_secs),
+            None,
+            &config.ollama.tls,
+            &config.ollama.connection,
+        )?)
+        .with_timeout(Duration::from_secs(config.ollama.timeout_secs))
+        .with_metrics(metrics.clone());
+    if let Some(fallback_url) = &config.ollama.fallback_url {
+        ollama_client = ollama_client.with_fallback(fallback_url);
+    }
+    let has_pools = config.ollama.base_url.len() > 1 || !config.ollama.routes.is_empty();
+    if has_pools && config.ollama.health_check.enabled {
+        ollama_client.spawn_health_checker(
+            Duration::from_secs(config.ollama.health_check.interval_secs),
+            &config.ollama.health_check.path,
+        );
+    }
+    let security_client = build_security_client(config)
+        .await?
+        .with_metrics(metrics.clone());
+    let scanner = scanner::build_scanner(
+        &config.scanner,
+        &config.ollama,
+        &security_client,
+        &ollama_client,
+    )?;
+    info!("Using {} scanner backend", scanner.name());
+
+    // Create application state
+    let mut builder = AppState::builder()
+        .with_ollama_client(ollama_client)
+        .with_security_client(security_client)
+        .with_scanner(scanner)
+        .with_model_cache(ModelCache::new(
+            Duration::from_secs(config.ollama.model_cache_ttl_secs),
+            config.ollama.model_cache_max_entries,
+        ))
+        .with_policy(ModelPolicy::new(&config.policy))
+        .with_scan_policy(ScanPolicy::new(&config.policy))
+        .with_verdict_headers(config.security.verdict_headers)
+        .with_timing_header(config.server.timing_header)
+        .with_streaming(config.server.streaming.clone())
+        .with_metrics(metrics)
+        .with_runtime_policy(RuntimePolicy::new(config.security.mode));
+    if let Some(engine) = &config.policy.engine {
+        let policy_engine = policy_engine::build_policy_engine(engine)?;
+        info!(
+            "Evaluating scan verdicts with the {} policy engine",
+            policy_engine.name()
+        );
+        builder = builder.with_policy_engine(policy_engine, &engine.mask_text, engine.flagged_only);
+    }
+    if let Some(quarantine) = &config.quarantine {
+        info!("Quarantining blocked content in {}", quarantine.directory);
+        let store = QuarantineStore::new(quarantine)?;
+        store.spawn_pruner();
+        builder = builder.with_quarantine(store);
+    }
+    if let Some(jwt) = &config.auth.jwt {
+        builder = builder.with_jwt_validator(JwtValidator::new(jwt)?);
+    }
+    if let Some(history) = &config.history {
+        info!("Recording assessments in {}", history.database);
+        let history = AssessmentHistory::open(history)?;
+        history.spawn_pruner();
+        builder = builder.with_history(history);
+    }
+    if let Some(events) = &config.events {
+        info!(
+            "Publishing verdicts to {} and usage to {}",
+            events.verdict_topic, events.usage_topic
+        );
+        builder = builder.with_events(EventSink::connect(events).await?);
+    }
+    if let Some(guardrail) = &config.guardrail {
+        info!("Enforcing guardrail system prompt ({:?})", guardrail.mode);
+        builder = builder.with_guardrail(Guardrail::new(guardrail));
+    }
+    if config.logging.access_log.enabled {
+        builder = builder.with_access_log(AccessLogger::new(&config.logging.access_log)?);
+    }
+    if config.security.scan_cache.ttl_secs > 0 {
+        info!(
+            "Caching allowed scan verdicts for {}s",
+            config.security.scan_cache.ttl_secs
+        );
+        builder = builder.with_scan_cache(ScanCache::new(&config.security.scan_cache));
+    }
+    if config.ollama.response_cache.ttl_secs > 0 {
+        info!(
+            "Caching responses to identical requests for {}s",
+            config.ollama.response_cache.ttl_secs
+        );
+        builder = builder.with_response_cache(ResponseCache::new(&config.ollama.response_cache));
+    }
+    if let Some(limit) = config.policy.daily_token_budget {
+        info!("Limiting each user to {} tokens per day", limit);
+        builder = builder.with_token_budget(TokenBudget::new(limit));
+    }
+    if let Some(session_scan) = &config.policy.session_scan {
+        info!(
+            "Scanning the last {} turns of each conversation every {} turns",
+            session_scan.window_turns, session_scan.scan_every_turns
+        );
+        builder = builder.with_session_store(SessionStore::new(session_scan));
+    }
+    if let Some(auto_ban) = &config.policy.auto_ban {
+        info!(
+            "Banning callers for {}s after {} blocked requests within {}s",
+            auto_ban.cooldown_secs, auto_ban.threshold, auto_ban.window_secs
+        );
+        builder = builder.with_ban_list(BanList::new(auto_ban));
+    }
+    if let Some(quotas) = &config.policy.quotas {
+        info!("Enforcing user quotas stored in {}", quotas.database);
+        builder = builder.with_quotas(QuotaStore::open(quotas)?);
+    }
+    for hook in hooks::load_hooks(&config.hooks)? {
+        info!("Loaded hook {}", hook.name());
+        builder = builder.with_hook(hook);
+    }
+    let check = &config.security.credential_check;
+    let credential_health =
+        (check.enabled && config.scanner.uses_panw()).then(CredentialHealth::new);
+    if let Some(health) = &credential_health {
+        builder = builder.with_credential_health(health.clone());
+    }
+    let state = builder.build()?;
+
+    // Verify the PANW credentials now and keep re-checking them
+    if let Some(health) = credential_health {
+        credentials::spawn_credential_checker(
+            state.security_client.clone(),
+            health,
+            state.metrics(),
+            Duration::from_secs(check.interval_secs),
+        );
+    }
+
+    // Pick up rotated API keys from the mounted secret file
+    if let (None, Some(path)) = (
+        &config.security.secret_source,
+        &config.security.api_key_file,
+    ) {
+        secrets::spawn_api_key_file_watcher(path.clone(), state.security_client.clone());
+    }
+
+    Ok(state)
+}
+
+// Builds the gateway router for an existing application state.
+//
+// Use this to mount the gateway into an existing Axum application with a
+// custom-built `AppState` (e.g. a different scanner).
+//
+// # Arguments
+//
+// * `state` - The application state shared by all handlers
+// * `config` - Configuration providing the auth settings
+//
+// # Returns
+//
+// A router serving the Ollama API endpoints plus `/readyz`, and `/admin` when configured.
+//
+// # Panics
+//
+// If `auth.jwt` is configured, the state carries no `JwtValidator` and the JWKS
+// client cannot be built from the `auth.jwt` TLS settings. `build_state` reports
+// that case as an error instead.
+pub fn build_router(state: AppState, config: &Config) -> Router {
+    // Bound request bodies before they are buffered and deserialized
+    let limits = &config.server.body_limits;
+    let mut app: Router<AppState> = Router::new()
+        .route(
+            "/api/generate",
+            post(generate::handle_generate).layer(DefaultBodyLimit::max(limits.generate_bytes)),
+        )
+        .route(
+            "/api/chat",
+            post(chat::handle_chat).layer(DefaultBodyLimit::max(limits.chat_bytes)),
+        )
+        .route("/api/tags", get(models::handle_list_models))
+        .route("/api/show", post(models::handle_show_model))
+        .route("/api/create", post(models::handle_create_model))
+        .route("/api/copy", post(models::handle_copy_model))
+        .route("/api/delete", post(models::handle_delete_model))
+        .route("/api/pull", post(models::handle_pull_model))
+        .route("/api/push", post(models::handle_push_model))
+        .route(
+            "/api/embeddings",
+            post(embeddings::handle_embeddings)
+                .layer(DefaultBodyLimit::max(limits.embeddings_bytes)),
+        )
+        .route("/api/version", get(version::handle_version))
+        .route("/api/proxy/info", get(version::handle_proxy_info))
+        .route(
+            "/v1/chat/completions",
+            post(openai::handle_chat_completions).layer(DefaultBodyLimit::max(limits.chat_bytes)),
+        )
+        .layer(DefaultBodyLimit::max(limits.default_bytes));
+
+    // Let callers pick an allowlisted PANW profile; runs after authentication
+    if !config.security.allowed_profiles.is_empty() {
+        app = app.route_layer(middleware::from_fn_with_state(
+            Arc::new(context::ProfileSelection {
+                allowed: config.security.allowed_profiles.clone(),
+                jwt_claim: config
+                    .auth
+                    .jwt
+                    .as_ref()
+                    .and_then(|jwt| jwt.profiles_claim.clone()),
+            }),
+            context::select_profile,
+        ));
+    }
+
+    // Require a valid bearer token when JWT validation is configured
+    if let Some(jwt) = &config.auth.jwt {
+        info!("JWT validation enabled for issuer {}", jwt.issuer);
+        app = app.route_layer(middleware::from_fn_with_state(
+            state
+                .jwt_validator
+                .clone()
+                .unwrap_or_else(|| JwtValidator::new(jwt).expect("invalid auth.jwt TLS settings")),
+            auth::require_jwt,
+        ));
+    }
+
+    // Or map client API keys to their tenants
+    if !config.auth.api_keys.is_empty() {
+        info!(
+            "API key authentication enabled for {} tenants",
+            config.auth.api_keys.len()
+        );
+        app = app.route_layer(middleware::from_fn_with_state(
+            TenantRegistry::new(&config.auth.api_keys),
+            auth::require_api_key,
+        ));
+    }
+
+    // The admin API uses its own token instead of the client JWT
+    if let Some(admin) = &config.auth.admin {
+        info!("Admin API enabled");
+        let admin_routes = Router::new()
+            .route("/admin/stats", get(admin::handle_stats))
+            .route(
+                "/admin/policy",
+                get(admin::handle_get_policy).patch(admin::handle_update_policy),
+            )
+            .route(
+                "/admin/policy/flush-caches",
+                post(admin::handle_flush_caches),
+            )
+            .route("/admin/quarantine", get(admin::handle_list_quarantine))
+            .route("/admin/quarantine/:id", get(admin::handle_get_quarantine))
+            .route("/admin/assessments", get(admin::handle_list_assessments))
+            .route("/admin/usage", get(admin::handle_usage))
+            .route("/admin/bans", get(admin::handle_list_bans))
+            .route("/admin/bans/:principal", delete(admin::handle_lift_ban))
+            .route_layer(middleware::from_fn_with_state(
+                AdminToken::new(admin),
+                auth::require_admin_token,
+            ));
+        app = app.merge(admin_routes);
+    }
+
+    // Probes are added after the auth layer so orchestrators can reach them unauthenticated
+    app = app
+        .route("/readyz", get(health::handle_readyz))
+        .layer(middleware::from_fn_with_state(
+            state.metrics(),
+            metrics::count_requests,
+        ));
+
+    // Strip quoted request content from extractor rejections
+    if config.logging.privacy_mode {
+        app = app.layer(middleware::from_fn(privacy::redact_rejections));
+    }
+
+    // Inside the request ID layer so every line carries the ID
+    if let Some(logger) = &state.access_log {
+        app = app.layer(middleware::from_fn_with_state(
+            logger.clone(),
+            access_log::log_access,
+        ));
+    }
+
+    // Outside the access log so it records uncompressed sizes
+    if config.server.compression {
+        app = app
+            .layer(CompressionLayer::new().compress_when(DefaultPredicate::new().and(BufferedBody)))
+            .layer(RequestDecompressionLayer::new());
+    }
+
+    // The ID is assigned before the trace span is created so the span can carry it
+    app.layer(TraceLayer::new_for_http().make_span_with(request_id::make_span::<Body>))
+        .layer(middleware::from_fn(request_id::propagate_request_id))
+        .with_state(state)
+}
+
+// Compression predicate skipping streamed responses.
+//
+// Compressed streams would be buffered by the encoder, delaying tokens, so only
+// bodies whose size is known upfront are compressed.
+#[derive(Clone, Copy)]
+struct BufferedBody;
+
+impl Predicate for BufferedBody {
+    fn should_compress<B>(&self, response: &axum::http::Response<B>) -> bool
+    where
+        B: axum::body::HttpBody,
+    {
+        response.body().size_hint().exact().is_some()
+    }
+}
+
+// Builds the complete gateway router from the configuration.
+//
+// This is the entry point for embedding the gateway into another Rust service:
+//
+// ```
+// let config = panw_api_ollama::config::load_config("config.yaml", None)?;
+// let app = axum::Router::new().nest("/ollama", panw_api_ollama::router(&config).await?);
+// ```
+//
+// # Arguments
+//
+// * `config` - The loaded and validated configuration
+//
+// # Returns
+//
+// * `Ok(Router)` - The gateway router with its state applied
+// * `Err(BoxError)` - If the application state cannot be created
+pub async fn router(config: &Config) -> Result<Router, BoxError> {
+    let state = build_state(config).await?;
+    Ok(build_router(state, config))
+}
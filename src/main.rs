@@ -1,121 +1,112 @@
-// Configuration loading and management.
-mod config;
+use axum::Router;
+use clap::{Parser, Subcommand};
+use panw_api_ollama::bench::{
+    self, BenchEndpoint, BenchOptions, BenchReport, MockOptions, RunReport,
+};
+use panw_api_ollama::security::ScanContext;
+use panw_api_ollama::{
+    build_router, build_security_client, build_state, config, preflight, shutdown, tls, BoxError,
+    Config,
+};
+use serde_json::json;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+use tracing_subscriber::EnvFilter;
 
-// HTTP request handlers for API endpoints.
-mod handlers;
+// Command line arguments.
+#[derive(Parser)]
+#[command(version, about = "Ollama proxy secured by PANW AI Runtime Security")]
+struct Cli {
+    /// Path to the base configuration file
+    #[arg(long, global = true, default_value = "config.yaml")]
+    config: String,
 
-// Client for interacting with Ollama API services.
-mod ollama;
+    /// Profile overlay merged on top of the base config (e.g. `prod` loads `config.prod.yaml`)
+    #[arg(long, global = true, env = "PANW_OLLAMA_PROFILE")]
+    profile: Option<String>,
 
-// Security assessment and content filtering using PANW AI Runtime API.
-mod security;
+    #[command(subcommand)]
+    command: Option<Command>,
+}
 
-// Utilities for handling streaming responses.
-mod stream;
+// Commands besides running the server, which is the default.
+#[derive(Subcommand)]
+enum Command {
+    /// Scan a prompt and/or response with the configured PANW profile and print the verdict as JSON
+    Check {
+        /// Prompt text to scan
+        #[arg(long, required_unless_present = "response")]
+        prompt: Option<String>,
 
-// Common type definitions used throughout the application.
-mod types;
+        /// Response text to scan
+        #[arg(long)]
+        response: Option<String>,
 
-use crate::handlers::*;
-use crate::ollama::OllamaClient;
-use crate::security::SecurityClient;
-use axum::{
-    routing::{get, post},
-    Router,
-};
-use std::net::{IpAddr, SocketAddr};
-use std::str::FromStr;
-use tower_http::trace::TraceLayer;
-use tracing::info;
+        /// Model name reported in the scan metadata
+        #[arg(long, default_value = "panw-api-ollama-check")]
+        model: String,
+    },
 
-// Shared application state containing clients for external services.
-//
-// This state is shared across all request handlers and contains
-// initialized clients for communicating with Ollama and security services.
-// The PANW AI Runtime API is used for security assessments of prompts and responses.
-#[derive(Clone)]
-pub struct AppState {
-    ollama_client: OllamaClient,
-    security_client: SecurityClient,
-}
+    /// Check the configuration and upstream connectivity, exiting non-zero on failure
+    Validate,
 
-impl AppState {
-    // Creates a new builder for constructing AppState with a fluent API.
-    //
-    // # Returns
-    //
-    // A new AppStateBuilder instance for configuring and building the application state.
-    //
-    // # Examples
-    //
-    // ```
-    // let state = AppState::builder()
-    //     .with_ollama_client(ollama_client)
-    //     .with_security_client(security_client)
-    //     .build()?;
-    // ```
-    pub fn builder() -> AppStateBuilder {
-        AppStateBuilder::default()
-    }
-}
+    /// Send concurrent chat or generate traffic through the gateway and report throughput and latency
+    Bench {
+        /// Gateway URL; defaults to the configured listener
+        #[arg(long, conflicts_with = "mock")]
+        target: Option<String>,
 
-// Builder for creating AppState instances with a fluent API.
-//
-// This builder ensures that all required components are provided
-// before constructing the final AppState.
-#[derive(Default)]
-pub struct AppStateBuilder {
-    ollama_client: Option<OllamaClient>,
-    security_client: Option<SecurityClient>,
-}
+        /// Ollama URL called directly with the same traffic to measure the gateway overhead
+        #[arg(long, conflicts_with = "mock")]
+        baseline: Option<String>,
 
-impl AppStateBuilder {
-    // Sets the Ollama client for the application state.
-    //
-    // # Arguments
-    //
-    // * `client` - An initialized OllamaClient instance
-    //
-    // # Returns
-    //
-    // The builder instance for method chaining
-    pub fn with_ollama_client(mut self, client: OllamaClient) -> Self {
-        self.ollama_client = Some(client);
-        self
-    }
+        /// Run an in-process gateway against stub Ollama and PANW servers
+        #[arg(long)]
+        mock: bool,
 
-    // Sets the security client for the application state.
-    //
-    // # Arguments
-    //
-    // * `client` - An initialized SecurityClient instance for PANW AI Runtime API
-    //
-    // # Returns
-    //
-    // The builder instance for method chaining
-    pub fn with_security_client(mut self, client: SecurityClient) -> Self {
-        self.security_client = Some(client);
-        self
-    }
+        /// Delay of the stub Ollama before each answer, in milliseconds
+        #[arg(long, default_value_t = 50, requires = "mock")]
+        mock_ollama_latency_ms: u64,
 
-    // Builds the AppState from the configured components.
-    //
-    // # Returns
-    //
-    // * `Ok(AppState)` - The fully constructed application state
-    // * `Err(&'static str)` - Error message if any required component is missing
-    //
-    // # Errors
-    //
-    // Returns an error if either the Ollama client or security client is not provided
-    pub fn build(self) -> Result<AppState, &'static str> {
-        let ollama_client = self.ollama_client.ok_or("OllamaClient is required")?;
-        let security_client = self.security_client.ok_or("SecurityClient is required")?;
-        Ok(AppState {
-            ollama_client,
-            security_client,
-        })
-    }
+        /// Delay of the stub PANW API before each verdict, in milliseconds
+        #[arg(long, default_value_t = 20, requires = "mock")]
+        mock_panw_latency_ms: u64,
+
+        /// Endpoint to load: chat or generate
+        #[arg(long, default_value = "chat")]
+        endpoint: BenchEndpoint,
+
+        /// Model named in every request
+        #[arg(long, default_value = "llama3")]
+        model: String,
+
+        /// Prompt sent in every request
+        #[arg(long, default_value = "Write a haiku about network security.")]
+        prompt: String,
+
+        /// Request streamed responses
+        #[arg(long)]
+        stream: bool,
+
+        /// Requests in flight at once
+        #[arg(long, default_value_t = 8)]
+        concurrency: usize,
+
+        /// Requests sent to the gateway, and to the baseline if any
+        #[arg(long, default_value_t = 200)]
+        requests: usize,
+
+        /// Bearer token sent to the gateway
+        #[arg(long, env = "PANW_OLLAMA_BENCH_API_KEY")]
+        api_key: Option<String>,
+
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
 }
 
 // Application entry point that initializes and runs the server.
@@ -130,7 +121,7 @@ impl AppStateBuilder {
 // # Returns
 //
 // * `Ok(())` - Server started successfully
-// * `Err(Box<dyn std::error::Error>)` - Error during initialization or execution
+// * `Err(BoxError)` - Error during initialization or execution
 //
 // # Errors
 //
@@ -139,52 +130,230 @@ impl AppStateBuilder {
 // - Server address binding fails
 // - Other I/O errors occur during server startup
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+async fn main() -> Result<(), BoxError> {
     // Initialize logging
+    // Warnings by default plus the audit trail; override with RUST_LOG
     tracing_subscriber::fmt()
-        .with_max_level(tracing::Level::WARN)
+        .with_env_filter(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn,audit=info")),
+        )
         .init();
-    info!("Starting panw-api-ollama server");
 
     // Load configuration
-    let config = config::load_config("config.yaml").map_err(|e| {
+    let cli = Cli::parse();
+    let config = config::load_config(&cli.config, cli.profile.as_deref()).map_err(|e| {
         eprintln!("Failed to load configuration: {}", e);
         e
     })?;
 
-    // Create application state
-    let state = AppState {
-        ollama_client: OllamaClient::new(&config.ollama.base_url),
-        security_client: SecurityClient::new(
-            &config.security.base_url,
-            &config.security.api_key,
-            &config.security.profile_name,
-            &config.security.app_name,
-            &config.security.app_user,
-        ),
+    match cli.command {
+        Some(Command::Check {
+            prompt,
+            response,
+            model,
+        }) => check(&config, prompt.as_deref(), response.as_deref(), &model).await,
+        Some(Command::Validate) => validate(&config).await,
+        Some(Command::Bench {
+            target,
+            baseline,
+            mock,
+            mock_ollama_latency_ms,
+            mock_panw_latency_ms,
+            endpoint,
+            model,
+            prompt,
+            stream,
+            concurrency,
+            requests,
+            api_key,
+            json,
+        }) => {
+            let options = BenchOptions {
+                target,
+                baseline,
+                endpoint,
+                model,
+                prompt,
+                stream,
+                concurrency,
+                requests,
+                api_key,
+                mock: mock.then(|| MockOptions {
+                    ollama_latency: Duration::from_millis(mock_ollama_latency_ms),
+                    panw_latency: Duration::from_millis(mock_panw_latency_ms),
+                }),
+            };
+            run_bench(&config, &options, json).await
+        }
+        None => serve(config).await,
+    }
+}
+
+// Scans content with the configured PANW profile and prints the verdict.
+//
+// # Arguments
+//
+// * `config` - The loaded configuration
+// * `prompt` - Prompt text to scan, if any
+// * `response` - Response text to scan, if any
+// * `model` - Model name reported in the scan metadata
+async fn check(
+    config: &Config,
+    prompt: Option<&str>,
+    response: Option<&str>,
+    model: &str,
+) -> Result<(), BoxError> {
+    let client = build_security_client(config).await?;
+    let result = client
+        .scan_exchange(prompt, response, model, &ScanContext::default())
+        .await?;
+
+    let output = json!({
+        "profile_name": client.profile_name(),
+        "category": result.category,
+        "action": result.action,
+        "detected": result.detected_categories(),
+        "scan": result,
+    });
+    println!("{}", serde_json::to_string_pretty(&output)?);
+    Ok(())
+}
+
+// Verifies the PANW API key and the Ollama upstreams of a loaded configuration.
+//
+// Prints one line per check and exits with status 1 if any of them failed, so
+// deployment pipelines can stop before rolling out a broken configuration.
+async fn validate(config: &Config) -> Result<(), BoxError> {
+    println!("ok    configuration");
+    let outcomes = preflight::run_checks(config).await;
+    for outcome in &outcomes {
+        match &outcome.result {
+            Ok(detail) => println!("ok    {}: {}", outcome.name, detail),
+            Err(error) => println!("FAIL  {}: {}", outcome.name, error),
+        }
+    }
+
+    if outcomes.iter().all(|outcome| outcome.is_ok()) {
+        Ok(())
+    } else {
+        std::process::exit(1);
+    }
+}
+
+// Runs the benchmark and prints its report.
+//
+// # Arguments
+//
+// * `config` - The loaded configuration
+// * `options` - Traffic shape and targets
+// * `as_json` - Print the report as JSON instead of a table
+async fn run_bench(config: &Config, options: &BenchOptions, as_json: bool) -> Result<(), BoxError> {
+    let report = bench::run(config, options).await?;
+    if as_json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print_bench_report(&report);
+    }
+    Ok(())
+}
+
+fn print_bench_report(report: &BenchReport) {
+    println!(
+        "{:?} requests, stream={}, concurrency={}",
+        report.endpoint, report.stream, report.concurrency
+    );
+    println!(
+        "{:<10} {:>8} {:>8} {:>10} {:>9} {:>9} {:>9} {:>9}",
+        "run", "requests", "failed", "req/s", "p50 ms", "p95 ms", "p99 ms", "max ms"
+    );
+    let print_run = |name: &str, run: &RunReport| {
+        let (p50, p95, p99, max) = run
+            .latency
+            .as_ref()
+            .map_or((0.0, 0.0, 0.0, 0.0), |l| (l.p50, l.p95, l.p99, l.max));
+        println!(
+            "{:<10} {:>8} {:>8} {:>10.1} {:>9.1} {:>9.1} {:>9.1} {:>9.1}",
+            name, run.requests, run.failures, run.throughput, p50, p95, p99, max
+        );
     };
+    print_run("gateway", &report.gateway);
+    if let Some(baseline) = &report.baseline {
+        print_run("baseline", baseline);
+    }
+    if let Some(overhead) = &report.overhead {
+        println!(
+            "{:<10} {:>8} {:>8} {:>10} {:>9.1} {:>9.1} {:>9.1} {:>9.1}",
+            "overhead", "", "", "", overhead.p50, overhead.p95, overhead.p99, overhead.max
+        );
+    }
+}
+
+// Runs the gateway until a shutdown signal is received.
+async fn serve(config: Config) -> Result<(), BoxError> {
+    info!("Starting panw-api-ollama server");
 
-    // Build router with all the Ollama API endpoints
-    let app = Router::new()
-        .route("/api/generate", post(generate::handle_generate))
-        .route("/api/chat", post(chat::handle_chat))
-        .route("/api/tags", get(models::handle_list_models))
-        .route("/api/show", post(models::handle_show_model))
-        .route("/api/create", post(models::handle_create_model))
-        .route("/api/copy", post(models::handle_copy_model))
-        .route("/api/delete", post(models::handle_delete_model))
-        .route("/api/pull", post(models::handle_pull_model))
-        .route("/api/push", post(models::handle_push_model))
-        .route("/api/embeddings", post(embeddings::handle_embeddings))
-        .route("/api/version", get(version::handle_version))
-        .layer(TraceLayer::new_for_http())
-        .with_state(state);
+    // Create application state and router
+    let state = build_state(&config).await?;
+    let stream_abort = state.shutdown_token();
+    let events = state.events();
+    let app: Router = build_router(state, &config);
 
     // Start the server using the new Axum 0.7 API
     let addr = SocketAddr::new(IpAddr::from_str(&config.server.host)?, config.server.port);
-    info!("Listening on {}", addr);
-    let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    let drain_timeout = Duration::from_secs(config.server.shutdown_timeout_secs);
+    let give_up = CancellationToken::new();
+    let shutdown = shutdown::shutdown_signal(stream_abort, drain_timeout, give_up.clone());
 
+    let server = async {
+        run_server(&config, app, addr, shutdown).await?;
+        info!("Server stopped");
+        if let Some(events) = &events {
+            if !events.flush(drain_timeout).await {
+                warn!("Gateway events still queued after the shutdown drain timeout were lost");
+            }
+        }
+        Ok(())
+    };
+    tokio::select! {
+        result = server => result,
+        _ = give_up.cancelled() => {
+            Err("Requests still in flight after the shutdown drain timeout".into())
+        }
+    }
+}
+
+// Serves the router over HTTP, or HTTPS when `server.tls` is set, until `shutdown` resolves.
+async fn run_server(
+    config: &Config,
+    app: Router,
+    addr: SocketAddr,
+    shutdown: impl std::future::Future<Output = ()> + Send + 'static,
+) -> Result<(), BoxError> {
+    if let Some(tls_config) = &config.server.tls {
+        let rustls_config = tls::load_rustls_config(tls_config).await?;
+        tls::spawn_cert_reloader(rustls_config.clone(), tls_config.clone());
+
+        let handle = axum_server::Handle::new();
+        let shutdown_handle = handle.clone();
+        tokio::spawn(async move {
+            shutdown.await;
+            shutdown_handle.graceful_shutdown(None);
+        });
+
+        info!("Listening on https://{}", addr);
+        axum_server::bind_rustls(addr, rustls_config)
+            .handle(handle)
+            .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+            .await?;
+    } else {
+        info!("Listening on {}", addr);
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .with_graceful_shutdown(shutdown)
+        .await?;
+    }
     Ok(())
 }
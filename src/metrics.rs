@@ -0,0 +1,300 @@
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use serde_json::{json, Map, Value};
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::access_log::AccessRecord;
+
+// Number of recent PANW call latencies kept for percentile computation.
+const LATENCY_WINDOW: usize = 1024;
+
+#[derive(Default)]
+struct Counters {
+    requests: AtomicU64,
+    responses_2xx: AtomicU64,
+    responses_4xx: AtomicU64,
+    responses_5xx: AtomicU64,
+    scans: AtomicU64,
+    blocks: AtomicU64,
+    panw_calls: AtomicU64,
+    panw_errors: AtomicU64,
+    credential_checks: AtomicU64,
+    credential_failures: AtomicU64,
+    ollama_errors: AtomicU64,
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+}
+
+struct MetricsInner {
+    started_at: Instant,
+    counters: Counters,
+    blocks_by_category: Mutex<HashMap<String, u64>>,
+    panw_latencies: Mutex<VecDeque<Duration>>,
+    stage_latencies: Mutex<HashMap<Stage, VecDeque<Duration>>>,
+}
+
+// Phases of a proxied request whose duration is tracked separately.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Stage {
+    PromptScan,
+    Ollama,
+    ResponseScan,
+}
+
+impl Stage {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Stage::PromptScan => "prompt_scan",
+            Stage::Ollama => "ollama",
+            Stage::ResponseScan => "response_scan",
+        }
+    }
+}
+
+// Stage durations of one request, recorded into the registry as they are added.
+//
+// Rendered as the `X-Proxy-Timing` header, e.g. `prompt_scan=12ms, ollama=840ms`.
+pub struct StageTimings {
+    metrics: Metrics,
+    access: Option<AccessRecord>,
+    stages: Vec<(Stage, Duration)>,
+}
+
+impl StageTimings {
+    pub fn new(metrics: Metrics, access: Option<AccessRecord>) -> Self {
+        Self {
+            metrics,
+            access,
+            stages: Vec::new(),
+        }
+    }
+
+    // Adds the time spent in a stage since `started`.
+    pub fn record(&mut self, stage: Stage, started: Instant) {
+        let elapsed = started.elapsed();
+        self.metrics.record_stage(stage, elapsed);
+        if let Some(access) = &self.access {
+            access.add_stage(stage, elapsed);
+        }
+        self.stages.push((stage, elapsed));
+    }
+
+    pub fn header_value(&self) -> String {
+        self.stages
+            .iter()
+            .map(|(stage, duration)| format!("{}={}ms", stage.as_str(), duration.as_millis()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+// In-process registry of runtime statistics since start.
+//
+// Cheap to clone; all clones share the same counters. Exposed as JSON by the
+// `/admin/stats` endpoint.
+#[derive(Clone)]
+pub struct Metrics {
+    inner: Arc<MetricsInner>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(MetricsInner {
+                started_at: Instant::now(),
+                counters: Counters::default(),
+                blocks_by_category: Mutex::new(HashMap::new()),
+                panw_latencies: Mutex::new(VecDeque::with_capacity(LATENCY_WINDOW)),
+                stage_latencies: Mutex::new(HashMap::new()),
+            }),
+        }
+    }
+
+    // Records a completed HTTP request by its response status.
+    pub fn record_request(&self, status: u16) {
+        let counters = &self.inner.counters;
+        counters.requests.fetch_add(1, Ordering::Relaxed);
+        match status {
+            200..=299 => counters.responses_2xx.fetch_add(1, Ordering::Relaxed),
+            400..=499 => counters.responses_4xx.fetch_add(1, Ordering::Relaxed),
+            500..=599 => counters.responses_5xx.fetch_add(1, Ordering::Relaxed),
+            _ => 0,
+        };
+    }
+
+    // Records a scanner verdict; `blocked_category` is set for unsafe content.
+    pub fn record_scan(&self, blocked_category: Option<&str>) {
+        let counters = &self.inner.counters;
+        counters.scans.fetch_add(1, Ordering::Relaxed);
+        if let Some(category) = blocked_category {
+            counters.blocks.fetch_add(1, Ordering::Relaxed);
+            *self
+                .inner
+                .blocks_by_category
+                .lock()
+                .unwrap()
+                .entry(category.to_string())
+                .or_insert(0) += 1;
+        }
+    }
+
+    // Records one call to the PANW AI Runtime API.
+    pub fn record_panw_call(&self, latency: Duration, success: bool) {
+        let counters = &self.inner.counters;
+        counters.panw_calls.fetch_add(1, Ordering::Relaxed);
+        if !success {
+            counters.panw_errors.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let mut latencies = self.inner.panw_latencies.lock().unwrap();
+        if latencies.len() == LATENCY_WINDOW {
+            latencies.pop_front();
+        }
+        latencies.push_back(latency);
+    }
+
+    // Records a periodic PANW credential check; `valid` is `false` if the key or profile was rejected.
+    pub fn record_credential_check(&self, valid: bool) {
+        let counters = &self.inner.counters;
+        counters.credential_checks.fetch_add(1, Ordering::Relaxed);
+        if !valid {
+            counters.credential_failures.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    // Records the time a request spent in one stage.
+    pub fn record_stage(&self, stage: Stage, latency: Duration) {
+        let mut stages = self.inner.stage_latencies.lock().unwrap();
+        let latencies = stages
+            .entry(stage)
+            .or_insert_with(|| VecDeque::with_capacity(LATENCY_WINDOW));
+        if latencies.len() == LATENCY_WINDOW {
+            latencies.pop_front();
+        }
+        latencies.push_back(latency);
+    }
+
+    // Records a failed call to an Ollama upstream.
+    pub fn record_ollama_error(&self) {
+        self.inner
+            .counters
+            .ollama_errors
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Records a lookup in the model listing cache.
+    pub fn record_cache_lookup(&self, hit: bool) {
+        let counters = &self.inner.counters;
+        if hit {
+            counters.cache_hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            counters.cache_misses.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    // Returns all statistics as a JSON object.
+    pub fn snapshot(&self) -> Value {
+        let counters = &self.inner.counters;
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+
+        let blocks_by_category: Map<String, Value> = self
+            .inner
+            .blocks_by_category
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(category, count)| (category.clone(), json!(count)))
+            .collect();
+
+        let panw_latencies = latency_summary(&self.inner.panw_latencies.lock().unwrap());
+        let stages: Map<String, Value> = self
+            .inner
+            .stage_latencies
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(stage, latencies)| (stage.as_str().to_string(), latency_summary(latencies)))
+            .collect();
+
+        let hits = load(&counters.cache_hits);
+        let misses = load(&counters.cache_misses);
+        let hit_rate = if hits + misses == 0 {
+            None
+        } else {
+            Some(hits as f64 / (hits + misses) as f64)
+        };
+
+        json!({
+            "uptime_secs": self.inner.started_at.elapsed().as_secs(),
+            "requests": {
+                "total": load(&counters.requests),
+                "2xx": load(&counters.responses_2xx),
+                "4xx": load(&counters.responses_4xx),
+                "5xx": load(&counters.responses_5xx),
+            },
+            "scans": {
+                "total": load(&counters.scans),
+                "blocked": load(&counters.blocks),
+                "blocked_by_category": blocks_by_category,
+            },
+            "panw": {
+                "calls": load(&counters.panw_calls),
+                "latency_ms": panw_latencies,
+                "credential_checks": load(&counters.credential_checks),
+                "credential_failures": load(&counters.credential_failures),
+            },
+            "stage_latency_ms": stages,
+            "model_cache": {
+                "hits": hits,
+                "misses": misses,
+                "hit_rate": hit_rate,
+            },
+            "upstream_errors": {
+                "ollama": load(&counters.ollama_errors),
+                "panw": load(&counters.panw_errors),
+            },
+        })
+    }
+}
+
+// Summarizes a latency window as p50/p90/p99/max in milliseconds.
+fn latency_summary(window: &VecDeque<Duration>) -> Value {
+    let mut latencies: Vec<Duration> = window.iter().copied().collect();
+    latencies.sort();
+    let percentile = |p: f64| -> Option<u128> {
+        if latencies.is_empty() {
+            return None;
+        }
+        let index = ((latencies.len() - 1) as f64 * p).round() as usize;
+        Some(latencies[index].as_millis())
+    };
+    json!({
+        "p50": percentile(0.50),
+        "p90": percentile(0.90),
+        "p99": percentile(0.99),
+        "max": latencies.last().map(Duration::as_millis),
+    })
+}
+
+// Middleware counting every request by its response status.
+pub async fn count_requests(
+    State(metrics): State<Metrics>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let response = next.run(request).await;
+    metrics.record_request(response.status().as_u16());
+    response
+}
@@ -0,0 +1,366 @@
+use axum::{
+    body::Body,
+    extract::State,
+    http::header::CONTENT_TYPE,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use chrono::Utc;
+use futures_util::stream;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::convert::Infallible;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+// Text returned by the stub Ollama server until another reply is set.
+const DEFAULT_REPLY: &str = "This is a reply from the stub Ollama server, \
+                             long enough to be streamed in a few chunks.";
+
+// A server listening on a local port until dropped.
+pub struct MockServer {
+    pub url: String,
+    handle: JoinHandle<()>,
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+// Starts serving a router on an ephemeral local port.
+pub async fn serve(router: Router) -> io::Result<MockServer> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let handle = tokio::spawn(async move {
+        let _ = axum::serve(
+            listener,
+            router.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .await;
+    });
+    Ok(MockServer {
+        url: format!("http://{}", addr),
+        handle,
+    })
+}
+
+// Verdict the stub PANW API returns for a scan.
+//
+// * `Allow` - Benign content, allowed
+// * `Flag` - Malicious content PANW only alerts on
+// * `Block` - Malicious content PANW blocks
+// * `Malformed` - A `200` response that is not a scan result
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MockVerdict {
+    Allow,
+    Flag,
+    Block,
+    Malformed,
+}
+
+// Content of a scan received by the stub PANW API.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ScannedContent {
+    #[serde(default)]
+    pub prompt: Option<String>,
+    #[serde(default)]
+    pub response: Option<String>,
+}
+
+struct PanwScript {
+    verdict: MockVerdict,
+    rules: Vec<(String, MockVerdict)>,
+    latency: Duration,
+    scans: Vec<ScannedContent>,
+}
+
+// Stub PANW AI Runtime scan API with scriptable verdicts.
+//
+// Every scan is allowed unless scripted otherwise. The script can be changed
+// while requests are in flight; it applies from the next scan on.
+pub struct MockPanw {
+    server: MockServer,
+    script: Arc<Mutex<PanwScript>>,
+}
+
+impl MockPanw {
+    // Starts the stub on a local port.
+    pub async fn start() -> io::Result<Self> {
+        let script = Arc::new(Mutex::new(PanwScript {
+            verdict: MockVerdict::Allow,
+            rules: Vec::new(),
+            latency: Duration::ZERO,
+            scans: Vec::new(),
+        }));
+        // The scan path is configurable, so every POST is answered as a scan
+        let router = Router::new()
+            .fallback(post(handle_scan))
+            .with_state(script.clone());
+        Ok(Self {
+            server: serve(router).await?,
+            script,
+        })
+    }
+
+    // Base URL to configure as `security.base_url`.
+    pub fn url(&self) -> &str {
+        &self.server.url
+    }
+
+    // Sets the verdict for scans no rule matches.
+    pub fn set_verdict(&self, verdict: MockVerdict) {
+        self.script().verdict = verdict;
+    }
+
+    // Returns `verdict` for prompts or responses containing `needle`.
+    //
+    // Rules are checked in the order they were added; the first match wins.
+    pub fn verdict_when_contains(&self, needle: &str, verdict: MockVerdict) {
+        self.script().rules.push((needle.to_string(), verdict));
+    }
+
+    // Delays every verdict, e.g. beyond `security.timeout_secs`.
+    pub fn set_latency(&self, latency: Duration) {
+        self.script().latency = latency;
+    }
+
+    // Returns the content of every scan received so far, in order.
+    pub fn scans(&self) -> Vec<ScannedContent> {
+        self.script().scans.clone()
+    }
+
+    fn script(&self) -> MutexGuard<'_, PanwScript> {
+        self.script.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+async fn handle_scan(
+    State(script): State<Arc<Mutex<PanwScript>>>,
+    Json(request): Json<Value>,
+) -> Response {
+    let content: ScannedContent = request
+        .pointer("/contents/0")
+        .cloned()
+        .and_then(|content| serde_json::from_value(content).ok())
+        .unwrap_or_default();
+    let text = content
+        .prompt
+        .as_deref()
+        .or(content.response.as_deref())
+        .unwrap_or_default()
+        .to_string();
+    let is_prompt = content.prompt.is_some();
+
+    let (verdict, latency) = {
+        let mut script = script.lock().unwrap_or_else(|e| e.into_inner());
+        script.scans.push(content);
+        let verdict = script
+            .rules
+            .iter()
+            .find(|(needle, _)| text.contains(needle.as_str()))
+            .map_or(script.verdict, |(_, verdict)| *verdict);
+        (verdict, script.latency)
+    };
+    tokio::time::sleep(latency).await;
+
+    let (category, action) = match verdict {
+        MockVerdict::Allow => ("benign", "allow"),
+        MockVerdict::Flag => ("malicious", "alert"),
+        MockVerdict::Block => ("malicious", "block"),
+        MockVerdict::Malformed => {
+            return Response::builder()
+                .header(CONTENT_TYPE, "application/json")
+                .body(Body::from("{\"report_id\": "))
+                .unwrap_or_default();
+        }
+    };
+    let flagged = verdict != MockVerdict::Allow;
+    Json(json!({
+        "report_id": format!("R{}", Uuid::new_v4().simple()),
+        "scan_id": Uuid::new_v4(),
+        "tr_id": request.get("tr_id"),
+        "profile_name": request.pointer("/ai_profile/profile_name"),
+        "category": category,
+        "action": action,
+        "prompt_detected": { "injection": flagged && is_prompt },
+        "response_detected": { "toxic_content": flagged && !is_prompt },
+    }))
+    .into_response()
+}
+
+struct OllamaScript {
+    reply: String,
+    latency: Duration,
+    malformed: bool,
+    requests: Vec<Value>,
+}
+
+// Stub Ollama server answering chat, generate and embeddings requests.
+//
+// Answers stream one chunk per word unless the request sets `"stream": false`,
+// like Ollama does.
+pub struct MockOllama {
+    server: MockServer,
+    script: Arc<Mutex<OllamaScript>>,
+}
+
+impl MockOllama {
+    // Starts the stub on a local port.
+    pub async fn start() -> io::Result<Self> {
+        let script = Arc::new(Mutex::new(OllamaScript {
+            reply: DEFAULT_REPLY.to_string(),
+            latency: Duration::ZERO,
+            malformed: false,
+            requests: Vec::new(),
+        }));
+        let router = Router::new()
+            .route("/", get(|| async { "Ollama is running" }))
+            .route(
+                "/api/version",
+                get(|| async { Json(json!({ "version": "0.0.0-mock" })) }),
+            )
+            .route("/api/tags", get(|| async { Json(json!({ "models": [] })) }))
+            .route("/api/chat", post(handle_chat))
+            .route("/api/generate", post(handle_generate))
+            .route("/api/embeddings", post(handle_embeddings))
+            .with_state(script.clone());
+        Ok(Self {
+            server: serve(router).await?,
+            script,
+        })
+    }
+
+    // Base URL to configure as `ollama.base_url`.
+    pub fn url(&self) -> &str {
+        &self.server.url
+    }
+
+    // Sets the text of chat and generate answers.
+    pub fn set_reply(&self, reply: &str) {
+        self.script().reply = reply.to_string();
+    }
+
+    // Delays every answer, standing in for model inference.
+    pub fn set_latency(&self, latency: Duration) {
+        self.script().latency = latency;
+    }
+
+    // Makes answers invalid JSON.
+    pub fn set_malformed(&self, malformed: bool) {
+        self.script().malformed = malformed;
+    }
+
+    // Returns the body of every chat, generate and embeddings request received so far.
+    pub fn requests(&self) -> Vec<Value> {
+        self.script().requests.clone()
+    }
+
+    fn script(&self) -> MutexGuard<'_, OllamaScript> {
+        self.script.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+// Records a request and returns the reply, latency and malformed flag to answer it with.
+fn record(script: &Mutex<OllamaScript>, request: &Value) -> (String, Duration, bool) {
+    let mut script = script.lock().unwrap_or_else(|e| e.into_inner());
+    script.requests.push(request.clone());
+    (script.reply.clone(), script.latency, script.malformed)
+}
+
+async fn handle_chat(
+    State(script): State<Arc<Mutex<OllamaScript>>>,
+    Json(request): Json<Value>,
+) -> Response {
+    let (reply, latency, malformed) = record(&script, &request);
+    tokio::time::sleep(latency).await;
+    answer(&request, &reply, malformed, |text, done| {
+        json!({
+            "message": { "role": "assistant", "content": text },
+            "done": done,
+        })
+    })
+}
+
+async fn handle_generate(
+    State(script): State<Arc<Mutex<OllamaScript>>>,
+    Json(request): Json<Value>,
+) -> Response {
+    let (reply, latency, malformed) = record(&script, &request);
+    tokio::time::sleep(latency).await;
+    answer(
+        &request,
+        &reply,
+        malformed,
+        |text, done| json!({ "response": text, "done": done }),
+    )
+}
+
+async fn handle_embeddings(
+    State(script): State<Arc<Mutex<OllamaScript>>>,
+    Json(request): Json<Value>,
+) -> Response {
+    let (_, latency, malformed) = record(&script, &request);
+    tokio::time::sleep(latency).await;
+    if malformed {
+        return malformed_body();
+    }
+    Json(json!({ "embedding": [0.1, 0.2, 0.3] })).into_response()
+}
+
+// Builds a non-streaming answer or an NDJSON stream of one chunk per word.
+fn answer(
+    request: &Value,
+    reply: &str,
+    malformed: bool,
+    chunk: impl Fn(&str, bool) -> Value,
+) -> Response {
+    if malformed {
+        return malformed_body();
+    }
+    let model = request.get("model").cloned().unwrap_or(Value::Null);
+    let with_metadata = |mut value: Value, done: bool| {
+        value["model"] = model.clone();
+        value["created_at"] = json!(Utc::now().to_rfc3339());
+        if done {
+            value["done_reason"] = json!("stop");
+            value["prompt_eval_count"] = json!(16);
+            value["eval_count"] = json!(reply.split_whitespace().count());
+        }
+        value
+    };
+
+    let streamed = request
+        .get("stream")
+        .and_then(Value::as_bool)
+        .unwrap_or(true);
+    if !streamed {
+        return Json(with_metadata(chunk(reply, true), true)).into_response();
+    }
+
+    let mut lines: Vec<String> = reply
+        .split_inclusive(' ')
+        .map(|word| with_metadata(chunk(word, false), false).to_string() + "\n")
+        .collect();
+    lines.push(with_metadata(chunk("", true), true).to_string() + "\n");
+    Response::builder()
+        .header(CONTENT_TYPE, "application/x-ndjson")
+        .body(Body::from_stream(stream::iter(
+            lines.into_iter().map(Ok::<_, Infallible>),
+        )))
+        .unwrap_or_default()
+}
+
+fn malformed_body() -> Response {
+    Response::builder()
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from("{\"model\": "))
+        .unwrap_or_default()
+}
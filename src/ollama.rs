@@ -1,48 +1,337 @@
+use crate::config::{LoadBalancing, ModelRoute};
+use crate::metrics::Metrics;
+use crate::pattern;
+use crate::privacy;
+use crate::request_id;
 use bytes::Bytes;
 use futures_util::Stream;
-use reqwest::{Client, Response, StatusCode};
+use futures_util::StreamExt;
+use reqwest::{Client, Method, Response, StatusCode};
 use serde::Serialize;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 use thiserror::Error;
-use tracing::{debug, error};
+use tracing::{debug, error, info, warn};
 
 #[derive(Debug, Error)]
 pub enum OllamaError {
     #[error("HTTP request failed: {0}")]
-    RequestError(#[from] reqwest::Error),
+    RequestError(reqwest::Error),
+
+    #[error("Ollama request timed out")]
+    Timeout,
 
     #[error("Ollama API error: {status} - {message}")]
     ApiError { status: StatusCode, message: String },
 }
 
+impl From<reqwest::Error> for OllamaError {
+    fn from(err: reqwest::Error) -> Self {
+        if err.is_timeout() {
+            OllamaError::Timeout
+        } else {
+            OllamaError::RequestError(err)
+        }
+    }
+}
+
+// A single Ollama instance of the pool.
+struct Upstream {
+    base_url: String,
+    healthy: AtomicBool,
+    in_flight: AtomicUsize,
+}
+
+fn build_pool(base_urls: &[String]) -> Arc<Vec<Upstream>> {
+    Arc::new(
+        base_urls
+            .iter()
+            .map(|base_url| Upstream {
+                base_url: base_url.trim_end_matches('/').to_string(),
+                healthy: AtomicBool::new(true),
+                in_flight: AtomicUsize::new(0),
+            })
+            .collect(),
+    )
+}
+
+// Pool dedicated to the models matching `pattern`.
+struct RoutePool {
+    pattern: String,
+    upstreams: Arc<Vec<Upstream>>,
+    next: Arc<AtomicUsize>,
+}
+
+// Marks a request as in flight on an upstream until dropped.
+struct UpstreamGuard {
+    pool: Arc<Vec<Upstream>>,
+    index: usize,
+}
+
+impl UpstreamGuard {
+    fn base_url(&self) -> &str {
+        &self.pool[self.index].base_url
+    }
+}
+
+impl Drop for UpstreamGuard {
+    fn drop(&mut self) {
+        self.pool[self.index]
+            .in_flight
+            .fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
 #[derive(Clone)]
 pub struct OllamaClient {
     client: Client,
-    base_url: String,
+    upstreams: Arc<Vec<Upstream>>,
+    next: Arc<AtomicUsize>,
+    load_balancing: LoadBalancing,
+    timeout: Duration,
+    routes: Arc<Vec<RoutePool>>,
+    fallback: Option<Arc<str>>,
+    metrics: Option<Metrics>,
 }
 
 impl OllamaClient {
     pub fn new(base_url: &str) -> Self {
+        Self::new_pool(&[base_url.to_string()])
+    }
+
+    // Creates a client balancing requests over several Ollama instances.
+    //
+    // # Arguments
+    //
+    // * `base_urls` - Base URLs of the pool members, at least one
+    pub fn new_pool(base_urls: &[String]) -> Self {
         Self {
             client: Client::new(),
-            base_url: base_url.to_string(),
+            upstreams: build_pool(base_urls),
+            next: Arc::new(AtomicUsize::new(0)),
+            load_balancing: LoadBalancing::default(),
+            timeout: Duration::MAX,
+            routes: Arc::new(Vec::new()),
+            fallback: None,
+            metrics: None,
         }
     }
 
-    pub async fn forward<T: Serialize>(
+    // Sets a backend that takes over when the selected one fails.
+    //
+    // Only idempotent endpoints (generation, chat, embeddings and read-only
+    // model calls) are retried there, and only on connection errors or 5xx
+    // responses. Streams fail over only before their first byte.
+    pub fn with_fallback(mut self, base_url: &str) -> Self {
+        self.fallback = Some(Arc::from(base_url.trim_end_matches('/')));
+        self
+    }
+
+    // Counts failed upstream calls in the metrics registry.
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    // Routes models matching a pattern to dedicated pools.
+    //
+    // Models without a matching route are served by the default pool.
+    //
+    // # Arguments
+    //
+    // * `routes` - Routing table, the first matching pattern wins
+    pub fn with_model_routes(mut self, routes: &[ModelRoute]) -> Self {
+        self.routes = Arc::new(
+            routes
+                .iter()
+                .map(|route| RoutePool {
+                    pattern: route.model.clone(),
+                    upstreams: build_pool(&route.base_url),
+                    next: Arc::new(AtomicUsize::new(0)),
+                })
+                .collect(),
+        );
+        self
+    }
+
+    // Returns a client bound to the pool serving `model`.
+    //
+    // # Arguments
+    //
+    // * `model` - Name of the model the request is for
+    //
+    // # Returns
+    //
+    // A client sending requests to the first matching route, or to the default pool
+    pub fn for_model(&self, model: &str) -> OllamaClient {
+        match self
+            .routes
+            .iter()
+            .find(|route| pattern::matches(&route.pattern, model))
+        {
+            Some(route) => {
+                debug!("Routing model {} via pattern {}", model, route.pattern);
+                self.with_pool(route.upstreams.clone(), route.next.clone())
+            }
+            None => self.clone(),
+        }
+    }
+
+    // Returns one client per pool, the default pool first.
+    //
+    // Used by endpoints that aggregate over the whole fleet, like the model list.
+    pub fn pools(&self) -> Vec<OllamaClient> {
+        std::iter::once(self.with_pool(self.upstreams.clone(), self.next.clone()))
+            .chain(
+                self.routes
+                    .iter()
+                    .map(|route| self.with_pool(route.upstreams.clone(), route.next.clone())),
+            )
+            .collect()
+    }
+
+    fn with_pool(&self, upstreams: Arc<Vec<Upstream>>, next: Arc<AtomicUsize>) -> OllamaClient {
+        Self {
+            client: self.client.clone(),
+            upstreams,
+            next,
+            load_balancing: self.load_balancing,
+            timeout: self.timeout,
+            routes: Arc::new(Vec::new()),
+            fallback: self.fallback.clone(),
+            metrics: self.metrics.clone(),
+        }
+    }
+
+    // Sets the strategy used to pick a pool member for each request.
+    pub fn with_load_balancing(mut self, load_balancing: LoadBalancing) -> Self {
+        self.load_balancing = load_balancing;
+        self
+    }
+
+    // Replaces the default HTTP client, e.g. with one configured for timeouts and TLS.
+    pub fn with_http_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    // Sets the request timeout for calls to Ollama.
+    //
+    // For streaming calls the timeout bounds the wait for the response
+    // headers only, so long generations are not cut off mid-stream.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    // Picks the upstream for the next request.
+    //
+    // Unhealthy members are skipped; if the whole pool is marked unhealthy every
+    // member is considered again so requests still get a chance to succeed.
+    fn select_upstream(&self) -> UpstreamGuard {
+        let healthy: Vec<usize> = (0..self.upstreams.len())
+            .filter(|&i| self.upstreams[i].healthy.load(Ordering::Relaxed))
+            .collect();
+        let candidates = if healthy.is_empty() {
+            (0..self.upstreams.len()).collect()
+        } else {
+            healthy
+        };
+
+        let index = match self.load_balancing {
+            LoadBalancing::RoundRobin => {
+                let n = self.next.fetch_add(1, Ordering::Relaxed);
+                candidates[n % candidates.len()]
+            }
+            LoadBalancing::LeastConnections => candidates
+                .iter()
+                .copied()
+                .min_by_key(|&i| self.upstreams[i].in_flight.load(Ordering::Relaxed))
+                .unwrap_or(0),
+        };
+
+        self.upstreams[index]
+            .in_flight
+            .fetch_add(1, Ordering::Relaxed);
+        UpstreamGuard {
+            pool: self.upstreams.clone(),
+            index,
+        }
+    }
+
+    // Starts a background task probing every upstream of every pool at a fixed interval.
+    //
+    // # Arguments
+    //
+    // * `interval` - Time between two probes of the pool
+    // * `path` - Endpoint requested on each upstream, any 2xx marks it healthy
+    pub fn spawn_health_checker(&self, interval: Duration, path: &str) {
+        let client = self.client.clone();
+        let pools: Vec<Arc<Vec<Upstream>>> = std::iter::once(self.upstreams.clone())
+            .chain(self.routes.iter().map(|route| route.upstreams.clone()))
+            .collect();
+        let path = path.to_string();
+
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                for upstream in pools.iter().flat_map(|pool| pool.iter()) {
+                    let healthy = client
+                        .get(format!("{}{}", upstream.base_url, path))
+                        .timeout(interval)
+                        .send()
+                        .await
+                        .map(|response| response.status().is_success())
+                        .unwrap_or(false);
+
+                    let was_healthy = upstream.healthy.swap(healthy, Ordering::Relaxed);
+                    if was_healthy && !healthy {
+                        warn!("Ollama upstream {} is unhealthy", upstream.base_url);
+                    } else if !was_healthy && healthy {
+                        info!("Ollama upstream {} recovered", upstream.base_url);
+                    }
+                }
+            }
+        });
+    }
+
+    // Sends a request to a single base URL and checks the response status.
+    async fn send_to<T: Serialize>(
         &self,
+        base_url: &str,
+        method: Method,
         endpoint: &str,
-        body: &T,
+        body: Option<&T>,
+        streaming: bool,
     ) -> Result<Response, OllamaError> {
-        let url = format!("{}{}", self.base_url, endpoint);
-        debug!("Forwarding request to {}", url);
+        let url = format!("{}{}", base_url, endpoint);
+        debug!("Forwarding {} request to {}", method, url);
 
-        let response = self.client.post(&url).json(body).send().await?;
+        let mut request = self.client.request(method, &url);
+        if let Some(id) = request_id::current() {
+            request = request.header(request_id::REQUEST_ID_HEADER, id);
+        }
+        if let Some(body) = body {
+            request = request.json(body);
+        }
+
+        let response = if streaming {
+            // Only bound the wait for the headers, not the whole stream
+            tokio::time::timeout(self.timeout, request.send())
+                .await
+                .map_err(|_| OllamaError::Timeout)??
+        } else {
+            request.timeout(self.timeout).send().await?
+        };
 
         if !response.status().is_success() {
             let status = response.status();
             let message = response
                 .text()
                 .await
+                .map(|message| privacy::redact(&message).into_owned())
                 .unwrap_or_else(|_| "Unknown error".to_string());
             error!("Ollama API error: {} - {}", status, message);
             return Err(OllamaError::ApiError { status, message });
@@ -51,50 +340,121 @@ impl OllamaClient {
         Ok(response)
     }
 
-    pub async fn forward_get(&self, endpoint: &str) -> Result<Response, OllamaError> {
-        debug!("Forwarding GET request to {}{}", self.base_url, endpoint);
-        let response = self
-            .client
-            .get(&format!("{}{}", self.base_url, endpoint))
-            .send()
-            .await?;
+    // Sends a request to the pool, failing over to the fallback backend if allowed.
+    //
+    // # Returns
+    //
+    // The response, plus the guard of the pool member while it is still in use
+    async fn send<T: Serialize>(
+        &self,
+        method: Method,
+        endpoint: &str,
+        body: Option<&T>,
+        streaming: bool,
+    ) -> Result<(Response, Option<UpstreamGuard>), OllamaError> {
+        let upstream = self.select_upstream();
+        let result = self
+            .send_to(
+                upstream.base_url(),
+                method.clone(),
+                endpoint,
+                body,
+                streaming,
+            )
+            .await;
 
-        if !response.status().is_success() {
-            let status = response.status();
-            let message = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            error!("Ollama API error: {} - {}", status, message);
-            return Err(OllamaError::ApiError { status, message });
+        match (result, self.fallback.as_deref()) {
+            (Err(e), Some(fallback)) if is_idempotent(endpoint) && is_failover_error(&e) => {
+                warn!(
+                    "Ollama backend {} failed ({}), retrying on fallback {}",
+                    upstream.base_url(),
+                    e,
+                    fallback
+                );
+                drop(upstream);
+                if let Some(metrics) = &self.metrics {
+                    metrics.record_ollama_error();
+                }
+                let result = self
+                    .send_to(fallback, method, endpoint, body, streaming)
+                    .await;
+                self.record_outcome(&result);
+                Ok((result?, None))
+            }
+            (result, _) => {
+                self.record_outcome(&result);
+                result.map(|response| (response, Some(upstream)))
+            }
+        }
+    }
+
+    fn record_outcome(&self, result: &Result<Response, OllamaError>) {
+        if let (Err(_), Some(metrics)) = (result, &self.metrics) {
+            metrics.record_ollama_error();
         }
+    }
 
+    pub async fn forward<T: Serialize>(
+        &self,
+        endpoint: &str,
+        body: &T,
+    ) -> Result<Response, OllamaError> {
+        let (response, _upstream) = self.send(Method::POST, endpoint, Some(body), false).await?;
+        Ok(response)
+    }
+
+    pub async fn forward_get(&self, endpoint: &str) -> Result<Response, OllamaError> {
+        let (response, _upstream) = self.send::<()>(Method::GET, endpoint, None, false).await?;
         Ok(response)
     }
 
+    // Starts a streaming request.
+    //
+    // # Returns
+    //
+    // The backend serving the stream (see `backend_origin`) and the body stream
     pub async fn stream<T: Serialize>(
         &self,
         endpoint: &str,
         body: &T,
-    ) -> Result<impl Stream<Item = Result<Bytes, reqwest::Error>>, OllamaError> {
-        debug!("Streaming from {}{}", self.base_url, endpoint);
-        let response = self
-            .client
-            .post(&format!("{}{}", self.base_url, endpoint))
-            .json(body)
-            .send()
-            .await?;
+    ) -> Result<(String, impl Stream<Item = Result<Bytes, reqwest::Error>>), OllamaError> {
+        let (response, upstream) = self.send(Method::POST, endpoint, Some(body), true).await?;
+        let backend = backend_origin(response.url());
 
-        if !response.status().is_success() {
-            let status = response.status();
-            let message = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            error!("Ollama API error: {} - {}", status, message);
-            return Err(OllamaError::ApiError { status, message });
-        }
+        // Keep the upstream counted as busy until the stream is dropped
+        Ok((
+            backend,
+            response.bytes_stream().map(move |chunk| {
+                let _ = &upstream;
+                chunk
+            }),
+        ))
+    }
+}
+
+// Returns `scheme://host:port` of the backend that served a response.
+pub fn backend_origin(url: &reqwest::Url) -> String {
+    url.origin().ascii_serialization()
+}
+
+// Endpoints that can be replayed on another backend without side effects.
+fn is_idempotent(endpoint: &str) -> bool {
+    matches!(
+        endpoint,
+        "/api/generate"
+            | "/api/chat"
+            | "/api/embeddings"
+            | "/api/tags"
+            | "/api/show"
+            | "/api/version"
+    )
+}
 
-        Ok(response.bytes_stream())
+// Connection failures and server errors are worth retrying elsewhere.
+fn is_failover_error(err: &OllamaError) -> bool {
+    match err {
+        OllamaError::RequestError(e) => e.is_connect(),
+        OllamaError::ApiError { status, .. } => status.is_server_error(),
+        OllamaError::Timeout => false,
     }
 }
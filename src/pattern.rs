@@ -0,0 +1,40 @@
+// Matches a model name against a wildcard pattern.
+//
+// `*` matches any sequence of characters (including none), every other
+// character must match exactly. Matching is case-insensitive since Ollama
+// model names are.
+//
+// # Arguments
+//
+// * `pattern` - The pattern, e.g. `llama3*` or `*-embed`
+// * `name` - The model name to test
+//
+// # Returns
+//
+// `true` if the whole name matches the pattern
+pub fn matches(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.to_lowercase();
+    let name = name.to_lowercase();
+
+    let mut parts = pattern.split('*');
+    // `split` always yields at least one item
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // No wildcard, the prefix must be the whole name
+        return rest.is_empty();
+    };
+
+    for part in middle {
+        match rest.find(part) {
+            Some(pos) => rest = &rest[pos + part.len()..],
+            None => return false,
+        }
+    }
+
+    rest.len() >= last.len() && rest.ends_with(last)
+}
@@ -0,0 +1,507 @@
+use crate::config::{
+    ConversationScope, EmbeddingsScanConfig, EmbeddingsScanMode, EnforcementMode, GuardrailConfig,
+    GuardrailMode, LimitMode, OptionLimitsConfig, PolicyConfig, PromptLimitMode,
+    PromptLimitsConfig,
+};
+use crate::pattern;
+use crate::types::{ChatRequest, GenerateRequest, Message, ModelOptions};
+use serde::{Deserialize, Serialize};
+use serde_json::Map;
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::collections::HashSet;
+use tracing::debug;
+
+// Decides which models clients may use.
+//
+// A model is permitted when it matches no `blocked_models` pattern and, if
+// `allowed_models` is non-empty, at least one of those patterns. Names are
+// compared both as given and without the implicit `:latest` tag, so `llama3`
+// also covers `llama3:latest`.
+#[derive(Debug, Clone, Default)]
+pub struct ModelPolicy {
+    allowed: Vec<String>,
+    blocked: Vec<String>,
+    limits: OptionLimitsConfig,
+    prompt_limits: PromptLimitsConfig,
+}
+
+// Characters per token used to approximate token counts.
+const CHARS_PER_TOKEN: usize = 4;
+
+impl ModelPolicy {
+    pub fn new(config: &PolicyConfig) -> Self {
+        Self {
+            allowed: config.allowed_models.clone(),
+            blocked: config.blocked_models.clone(),
+            limits: config.option_limits.clone(),
+            prompt_limits: config.prompt_limits.clone(),
+        }
+    }
+
+    // Creates a policy permitting only models matching one of `patterns`.
+    pub fn allowing(patterns: &[String]) -> Self {
+        Self {
+            allowed: patterns.to_vec(),
+            ..Self::default()
+        }
+    }
+
+    // Validates the client's model options and applies the configured limits.
+    //
+    // # Arguments
+    //
+    // * `options` - The `options` field of the request, filled in if a limit applies
+    //
+    // # Returns
+    //
+    // * `Ok(())` - If the options are valid, possibly after clamping
+    // * `Err(String)` - If an option is invalid or exceeds a limit in reject mode
+    pub fn enforce_options(&self, options: &mut Option<ModelOptions>) -> Result<(), String> {
+        if let Some(options) = options.as_ref() {
+            options.validate()?;
+        }
+
+        if let Some(max) = self.limits.max_num_ctx {
+            if let Some(num_ctx) = options.as_mut().and_then(|o| o.num_ctx.as_mut()) {
+                if *num_ctx > max {
+                    if self.limits.mode == LimitMode::Reject {
+                        return Err(format!("num_ctx {} exceeds the limit of {}", num_ctx, max));
+                    }
+                    debug!("Clamping num_ctx from {} to {}", num_ctx, max);
+                    *num_ctx = max;
+                }
+            }
+        }
+
+        if let Some(max) = self.limits.max_num_predict {
+            let max = i32::try_from(max).unwrap_or(i32::MAX);
+            let options = options.get_or_insert_with(ModelOptions::default);
+            match options.num_predict {
+                // Unlimited or unset generation is always capped
+                None | Some(-2) | Some(-1) => options.num_predict = Some(max),
+                Some(num_predict) if num_predict > max => {
+                    if self.limits.mode == LimitMode::Reject {
+                        return Err(format!(
+                            "num_predict {} exceeds the limit of {}",
+                            num_predict, max
+                        ));
+                    }
+                    debug!("Clamping num_predict from {} to {}", num_predict, max);
+                    options.num_predict = Some(max);
+                }
+                Some(_) => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    // Returns the maximum prompt size in characters, if any limit is configured.
+    fn max_prompt_chars(&self) -> Option<usize> {
+        let from_tokens = self
+            .prompt_limits
+            .max_tokens
+            .map(|tokens| tokens.saturating_mul(CHARS_PER_TOKEN));
+        match (self.prompt_limits.max_chars, from_tokens) {
+            (Some(chars), Some(tokens)) => Some(chars.min(tokens)),
+            (chars, tokens) => chars.or(tokens),
+        }
+    }
+
+    // Applies the prompt size limits to a generate prompt.
+    //
+    // # Arguments
+    //
+    // * `prompt` - The prompt of the request, cut to the limit in truncate mode
+    //
+    // # Returns
+    //
+    // * `Ok(())` - If the prompt fits, possibly after truncation
+    // * `Err(String)` - If the prompt is too long in reject mode
+    pub fn enforce_prompt(&self, prompt: &mut String) -> Result<(), String> {
+        let Some(max) = self.max_prompt_chars() else {
+            return Ok(());
+        };
+        let length = prompt.chars().count();
+        if length <= max {
+            return Ok(());
+        }
+        if self.prompt_limits.mode == PromptLimitMode::Reject {
+            return Err(prompt_too_long(length, max));
+        }
+        debug!("Truncating prompt from {} to {} characters", length, max);
+        truncate_chars(prompt, max);
+        Ok(())
+    }
+
+    // Applies the prompt size limits to a chat conversation.
+    //
+    // In truncate mode the oldest non-system messages are dropped until the
+    // conversation fits; if the newest message alone is still too long, its
+    // content is cut.
+    //
+    // # Arguments
+    //
+    // * `messages` - The conversation sent by the client
+    //
+    // # Returns
+    //
+    // * `Ok(())` - If the conversation fits, possibly after truncation
+    // * `Err(String)` - If it is too long in reject mode, or system messages alone exceed the limit
+    pub fn enforce_messages(&self, messages: &mut Vec<Message>) -> Result<(), String> {
+        let Some(max) = self.max_prompt_chars() else {
+            return Ok(());
+        };
+        let size = |messages: &[Message]| -> usize {
+            messages.iter().map(|m| m.content.chars().count()).sum()
+        };
+        let length = size(messages);
+        if length <= max {
+            return Ok(());
+        }
+        if self.prompt_limits.mode == PromptLimitMode::Reject {
+            return Err(prompt_too_long(length, max));
+        }
+
+        let mut dropped = 0;
+        while size(messages) > max {
+            let non_system = messages.iter().filter(|m| m.role != "system").count();
+            if non_system <= 1 {
+                break;
+            }
+            if let Some(index) = messages.iter().position(|m| m.role != "system") {
+                messages.remove(index);
+                dropped += 1;
+            }
+        }
+
+        let overflow = size(messages).saturating_sub(max);
+        if overflow > 0 {
+            let Some(last) = messages.iter_mut().rev().find(|m| m.role != "system") else {
+                return Err(prompt_too_long(length, max));
+            };
+            let keep = last.content.chars().count().saturating_sub(overflow);
+            if keep == 0 {
+                return Err(prompt_too_long(length, max));
+            }
+            truncate_chars(&mut last.content, keep);
+        }
+        debug!(
+            "Truncated conversation from {} characters, dropped {} messages",
+            length, dropped
+        );
+        Ok(())
+    }
+
+    // Returns `true` if no restriction is configured.
+    pub fn is_unrestricted(&self) -> bool {
+        self.allowed.is_empty() && self.blocked.is_empty()
+    }
+
+    // Checks whether a model may be used.
+    //
+    // # Arguments
+    //
+    // * `model` - The model name from the request or listing
+    //
+    // # Returns
+    //
+    // `true` if the model is permitted
+    pub fn is_allowed(&self, model: &str) -> bool {
+        let short = model.strip_suffix(":latest").unwrap_or(model);
+        let matches_any = |patterns: &[String]| {
+            patterns
+                .iter()
+                .any(|p| pattern::matches(p, model) || pattern::matches(p, short))
+        };
+
+        if matches_any(&self.blocked) {
+            return false;
+        }
+        self.allowed.is_empty() || matches_any(&self.allowed)
+    }
+}
+
+fn prompt_too_long(length: usize, max: usize) -> String {
+    format!(
+        "Prompt of {} characters exceeds the limit of {} characters",
+        length, max
+    )
+}
+
+// Cuts a string to at most `max` characters, respecting character boundaries.
+fn truncate_chars(text: &mut String, max: usize) {
+    if let Some((index, _)) = text.char_indices().nth(max) {
+        text.truncate(index);
+    }
+}
+
+// Decides which parts of a chat conversation are sent to the scanner.
+#[derive(Debug, Clone, Default)]
+pub struct ScanPolicy {
+    scope: ConversationScope,
+    window: usize,
+    allow_scope_header: bool,
+    skip_roles: Vec<String>,
+    scan_images: bool,
+    embeddings: EmbeddingsScanConfig,
+}
+
+impl ScanPolicy {
+    pub fn new(config: &PolicyConfig) -> Self {
+        Self {
+            scope: config.conversation_scope,
+            window: config.scan_window,
+            allow_scope_header: config.allow_scope_header,
+            skip_roles: config.skip_roles.clone(),
+            scan_images: config.scan_images,
+            embeddings: config.embeddings.clone(),
+        }
+    }
+
+    // Decides whether an embeddings input is sent to the scanner.
+    //
+    // Always `true` in scan mode; in hash mode only a `sample_rate` fraction is scanned.
+    pub fn should_scan_embedding(&self) -> bool {
+        match self.embeddings.mode {
+            EmbeddingsScanMode::Scan => true,
+            EmbeddingsScanMode::Hash => rand::random::<f64>() < self.embeddings.sample_rate,
+        }
+    }
+
+    // Returns the attached images to scan, none unless `scan_images` is enabled.
+    //
+    // # Arguments
+    //
+    // * `images` - The base64-encoded images of a request or message
+    pub fn images_to_scan<'a>(&self, images: Option<&'a [String]>) -> &'a [String] {
+        match images {
+            Some(images) if self.scan_images => images,
+            _ => &[],
+        }
+    }
+
+    // Resolves the scope for a request.
+    //
+    // # Arguments
+    //
+    // * `header` - Value of the `X-Scan-Scope` header, if present
+    //
+    // # Returns
+    //
+    // * `Ok(ConversationScope)` - The requested scope, or the configured one
+    // * `Err(String)` - If the header holds an unknown scope or overrides are disabled
+    pub fn scope_for(&self, header: Option<&str>) -> Result<ConversationScope, String> {
+        let Some(value) = header else {
+            return Ok(self.scope);
+        };
+        if !self.allow_scope_header {
+            return Err("X-Scan-Scope header is not enabled".to_string());
+        }
+        match value.trim().to_ascii_lowercase().as_str() {
+            "all" => Ok(ConversationScope::All),
+            "latest" => Ok(ConversationScope::Latest),
+            "window" => Ok(ConversationScope::Window),
+            other => Err(format!("Unknown scan scope: {}", other)),
+        }
+    }
+
+    // Selects the texts to scan as prompts for a chat request.
+    //
+    // Messages of skipped roles are ignored before the scope is applied. Tool
+    // results are always treated as prompts since they are a common injection vector. Images
+    // attached to the selected messages are included if image scanning is enabled. Texts
+    // repeated verbatim within the request, such as a resent system prompt, are returned once.
+    //
+    // # Arguments
+    //
+    // * `messages` - The conversation sent by the client
+    // * `scope` - The scope resolved with `scope_for`
+    //
+    // # Returns
+    //
+    // The texts to assess, one scan each
+    pub fn prompts_to_scan<'a>(
+        &self,
+        messages: &'a [Message],
+        scope: ConversationScope,
+    ) -> Vec<Cow<'a, str>> {
+        let messages: Vec<&Message> = messages
+            .iter()
+            .filter(|message| !self.skip_roles.contains(&message.role))
+            .collect();
+
+        let selected: Vec<&'a Message> = match scope {
+            ConversationScope::All => messages,
+            ConversationScope::Latest => {
+                // The newest user message plus any tool results that followed it
+                match messages.iter().rposition(|message| message.role == "user") {
+                    Some(index) => messages[index..]
+                        .iter()
+                        .copied()
+                        .filter(|message| message.role == "user" || message.role == "tool")
+                        .collect(),
+                    None => messages.last().copied().into_iter().collect(),
+                }
+            }
+            ConversationScope::Window => {
+                let start = messages.len().saturating_sub(self.window);
+                messages[start..].to_vec()
+            }
+        };
+
+        let mut texts: Vec<Cow<'a, str>> = if scope == ConversationScope::Window {
+            let window: Vec<&str> = selected
+                .iter()
+                .map(|message| message.content.as_str())
+                .collect();
+            if window.is_empty() {
+                Vec::new()
+            } else {
+                vec![Cow::Owned(window.join("\n\n"))]
+            }
+        } else {
+            selected
+                .iter()
+                .map(|message| Cow::Borrowed(message.content.as_str()))
+                .collect()
+        };
+
+        for message in selected {
+            texts.extend(
+                self.images_to_scan(message.images.as_deref())
+                    .iter()
+                    .map(|image| Cow::Borrowed(image.as_str())),
+            );
+        }
+
+        let mut seen = HashSet::new();
+        texts.retain(|text| seen.insert(Sha256::digest(text.as_bytes())));
+        texts
+    }
+}
+
+// Scanning switches of the endpoints served by the gateway.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EndpointScanning {
+    pub chat: bool,
+    pub generate: bool,
+    pub embeddings: bool,
+}
+
+impl Default for EndpointScanning {
+    fn default() -> Self {
+        Self {
+            chat: true,
+            generate: true,
+            embeddings: true,
+        }
+    }
+}
+
+impl EndpointScanning {
+    // Returns `false` when scanning was switched off for the endpoint.
+    //
+    // Paths other than the chat, generate and embeddings endpoints are always scanned.
+    pub fn is_enabled(&self, endpoint: Option<&str>) -> bool {
+        match endpoint {
+            Some("/api/chat" | "/v1/chat/completions") => self.chat,
+            Some("/api/generate") => self.generate,
+            Some("/api/embeddings") => self.embeddings,
+            _ => true,
+        }
+    }
+}
+
+// Settings that operators may change at runtime through `/admin/policy`.
+//
+// Held in an `ArcSwap` so the request path reads them without locking.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RuntimePolicy {
+    pub mode: EnforcementMode,
+    pub scanning: EndpointScanning,
+}
+
+impl RuntimePolicy {
+    pub fn new(mode: EnforcementMode) -> Self {
+        Self {
+            mode,
+            scanning: EndpointScanning::default(),
+        }
+    }
+}
+
+// Placeholder replaced by the model name in guardrail prompts.
+const MODEL_PLACEHOLDER: &str = "{{model}}";
+
+// Enforces a safety preamble as system prompt on chat and generate requests.
+#[derive(Debug, Clone)]
+pub struct Guardrail {
+    mode: GuardrailMode,
+    system_prompt: String,
+    models: Vec<(String, String)>,
+}
+
+impl Guardrail {
+    pub fn new(config: &GuardrailConfig) -> Self {
+        Self {
+            mode: config.mode,
+            system_prompt: config.system_prompt.clone(),
+            models: config
+                .models
+                .iter()
+                .map(|entry| (entry.model.clone(), entry.system_prompt.clone()))
+                .collect(),
+        }
+    }
+
+    // Returns the rendered guardrail for a model, or `None` if it is exempt.
+    fn prompt_for(&self, model: &str) -> Option<String> {
+        let template = self
+            .models
+            .iter()
+            .find(|(pattern, _)| pattern::matches(pattern, model))
+            .map(|(_, prompt)| prompt)
+            .unwrap_or(&self.system_prompt);
+        (!template.trim().is_empty()).then(|| template.replace(MODEL_PLACEHOLDER, model))
+    }
+
+    // Adds the guardrail as the first system message of a chat request.
+    pub fn apply_to_chat(&self, request: &mut ChatRequest) {
+        let Some(prompt) = self.prompt_for(&request.model) else {
+            return;
+        };
+        if self.mode == GuardrailMode::Replace {
+            request.messages.retain(|message| message.role != "system");
+        }
+        debug!(
+            "Adding guardrail system message for model {}",
+            request.model
+        );
+        request.messages.insert(
+            0,
+            Message {
+                role: "system".to_string(),
+                content: prompt,
+                images: None,
+                tool_calls: None,
+                extra: Map::new(),
+            },
+        );
+    }
+
+    // Sets the guardrail as, or in front of, the `system` field of a generate request.
+    pub fn apply_to_generate(&self, request: &mut GenerateRequest) {
+        let Some(prompt) = self.prompt_for(&request.model) else {
+            return;
+        };
+        debug!("Adding guardrail system prompt for model {}", request.model);
+        request.system = match (self.mode, request.system.take()) {
+            (GuardrailMode::Prepend, Some(system)) if !system.is_empty() => {
+                Some(format!("{}\n\n{}", prompt, system))
+            }
+            _ => Some(prompt),
+        };
+    }
+}
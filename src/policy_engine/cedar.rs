@@ -0,0 +1,92 @@
+use async_trait::async_trait;
+use cedar_policy::{
+    Authorizer, Context, Decision, Entities, EntityId, EntityTypeName, EntityUid, PolicySet,
+    Request,
+};
+use std::fs;
+use std::str::FromStr;
+
+use crate::config::{ConfigError, PolicyEngineBackendConfig};
+use crate::policy_engine::{PolicyDecision, PolicyEngine, PolicyEngineError, PolicyInput};
+
+// Decisions asked in order; the first one the policies permit wins.
+const DECISIONS: [(&str, PolicyDecision); 3] = [
+    ("block", PolicyDecision::Block),
+    ("mask", PolicyDecision::Mask(None)),
+    ("allow", PolicyDecision::Allow),
+];
+
+// Policy engine evaluating Cedar policies in-process.
+//
+// Each scan is evaluated as a request of principal `User::"<user>"` on
+// resource `Model::"<model>"`, with the rest of the input as context, for the
+// actions `Action::"block"`, `Action::"mask"` and `Action::"allow"` in turn.
+// The first permitted action is the decision; if none is permitted, the
+// scanner verdict stands.
+pub struct CedarEngine {
+    policies: PolicySet,
+    authorizer: Authorizer,
+}
+
+impl CedarEngine {
+    pub fn load(backend: &PolicyEngineBackendConfig) -> Result<Self, ConfigError> {
+        let PolicyEngineBackendConfig::Cedar { policy_file } = backend else {
+            unreachable!("CedarEngine::load called with a non-Cedar backend");
+        };
+        let policies = PolicySet::from_str(&fs::read_to_string(policy_file)?).map_err(|e| {
+            ConfigError::ValidationError(format!(
+                "Invalid Cedar policies in {}: {}",
+                policy_file, e
+            ))
+        })?;
+        Ok(Self {
+            policies,
+            authorizer: Authorizer::new(),
+        })
+    }
+}
+
+fn entity(type_name: &str, id: &str) -> Result<EntityUid, PolicyEngineError> {
+    let type_name = EntityTypeName::from_str(type_name)
+        .map_err(|e| PolicyEngineError::InvalidResponse(e.to_string()))?;
+    Ok(EntityUid::from_type_name_and_id(
+        type_name,
+        EntityId::new(id),
+    ))
+}
+
+#[async_trait]
+impl PolicyEngine for CedarEngine {
+    fn name(&self) -> &str {
+        "cedar"
+    }
+
+    async fn decide(
+        &self,
+        input: &PolicyInput,
+    ) -> Result<Option<PolicyDecision>, PolicyEngineError> {
+        let principal = entity("User", &input.user)?;
+        let resource = entity("Model", &input.model)?;
+        let context_value = serde_json::to_value(input)
+            .map_err(|e| PolicyEngineError::InvalidResponse(e.to_string()))?;
+        for (action, decision) in DECISIONS {
+            let context = Context::from_json_value(context_value.clone(), None)
+                .map_err(|e| PolicyEngineError::InvalidResponse(e.to_string()))?;
+            let request = Request::new(
+                principal.clone(),
+                entity("Action", action)?,
+                resource.clone(),
+                context,
+                None,
+            )
+            .map_err(|e| PolicyEngineError::InvalidResponse(e.to_string()))?;
+            let response =
+                self.authorizer
+                    .is_authorized(&request, &self.policies, &Entities::empty());
+            if response.decision() == Decision::Allow {
+                return Ok(Some(decision));
+            }
+        }
+        Ok(None)
+    }
+}
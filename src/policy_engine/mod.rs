@@ -0,0 +1,145 @@
+// Cedar policies evaluated in-process, available with the `cedar` feature.
+#[cfg(feature = "cedar")]
+pub mod cedar;
+
+// Decisions delegated to an Open Policy Agent server.
+pub mod opa;
+
+use async_trait::async_trait;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+
+use crate::budget::TokenBudget;
+use crate::config::{ConfigError, PolicyEngineBackendConfig, PolicyEngineConfig};
+use crate::security::ScanContext;
+use crate::types::{PromptDetected, ResponseDetected};
+
+#[derive(Debug, Error)]
+pub enum PolicyEngineError {
+    #[error("Policy engine request failed: {0}")]
+    RequestError(String),
+
+    #[error("Invalid policy engine response: {0}")]
+    InvalidResponse(String),
+}
+
+// Outcome of a policy evaluation, overriding the scanner verdict.
+//
+// * `Allow` - Forward the content as is
+// * `Block` - Reject the content
+// * `Mask` - Forward the given replacement instead of the content, or the
+//   configured mask text if the engine provides none
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PolicyDecision {
+    Allow,
+    Block,
+    Mask(Option<String>),
+}
+
+// What the policy engine is told about a scan.
+//
+// # Fields
+//
+// * `user` - Caller the request is attributed to, as for quotas
+// * `app_user` - PANW `app_user` of the caller, if known
+// * `app_name` - PANW `app_name` of the caller's tenant, if known
+// * `model` - Model the content was sent to or produced by
+// * `endpoint` - Gateway endpoint that served the request
+// * `is_prompt` - `true` for prompts, `false` for model responses
+// * `assessment` - The scanner verdict
+#[derive(Debug, Clone, Serialize)]
+pub struct PolicyInput {
+    pub user: String,
+    pub app_user: String,
+    pub app_name: String,
+    pub model: String,
+    pub endpoint: String,
+    pub is_prompt: bool,
+    pub assessment: AssessmentInput,
+}
+
+// The scanner verdict as passed to the policy engine.
+#[derive(Debug, Clone, Serialize)]
+pub struct AssessmentInput {
+    pub is_safe: bool,
+    pub category: String,
+    pub action: String,
+    pub scanner: String,
+    pub profile_name: String,
+    pub prompt_detected: PromptDetected,
+    pub response_detected: ResponseDetected,
+}
+
+impl PolicyInput {
+    pub fn new(
+        ctx: &ScanContext,
+        model: &str,
+        is_prompt: bool,
+        assessment: AssessmentInput,
+    ) -> Self {
+        Self {
+            user: TokenBudget::user_key(ctx),
+            app_user: ctx.app_user.clone().unwrap_or_default(),
+            app_name: ctx.app_name.clone().unwrap_or_default(),
+            model: model.to_string(),
+            endpoint: ctx.endpoint.clone().unwrap_or_default(),
+            is_prompt,
+            assessment,
+        }
+    }
+}
+
+// An engine deciding what happens to scanned content.
+#[async_trait]
+pub trait PolicyEngine: Send + Sync {
+    // Short identifier of the engine, used in logs.
+    fn name(&self) -> &str;
+
+    // Evaluates the policy for one scan.
+    //
+    // # Returns
+    //
+    // * `Ok(Some(decision))` - The decision replacing the scanner verdict
+    // * `Ok(None)` - The policy makes no decision; the scanner verdict stands
+    // * `Err(PolicyEngineError)` - If the policy cannot be evaluated
+    async fn decide(
+        &self,
+        input: &PolicyInput,
+    ) -> Result<Option<PolicyDecision>, PolicyEngineError>;
+}
+
+// Builds the policy engine selected in config.
+//
+// # Returns
+//
+// * `Ok(Arc<dyn PolicyEngine>)` - The configured engine
+// * `Err(ConfigError)` - If the policy cannot be loaded or its support is not built in
+pub fn build_policy_engine(
+    config: &PolicyEngineConfig,
+) -> Result<Arc<dyn PolicyEngine>, ConfigError> {
+    let timeout = Duration::from_millis(config.timeout_ms);
+    match &config.backend {
+        PolicyEngineBackendConfig::Opa {
+            url,
+            tls,
+            connection,
+        } => Ok(Arc::new(opa::OpaEngine::new(
+            url, timeout, tls, connection,
+        )?)),
+        PolicyEngineBackendConfig::Cedar { .. } => build_cedar(&config.backend),
+    }
+}
+
+#[cfg(feature = "cedar")]
+fn build_cedar(backend: &PolicyEngineBackendConfig) -> Result<Arc<dyn PolicyEngine>, ConfigError> {
+    Ok(Arc::new(cedar::CedarEngine::load(backend)?))
+}
+
+#[cfg(not(feature = "cedar"))]
+fn build_cedar(_backend: &PolicyEngineBackendConfig) -> Result<Arc<dyn PolicyEngine>, ConfigError> {
+    Err(ConfigError::ValidationError(
+        "Cedar policies require building with the `cedar` feature".into(),
+    ))
+}
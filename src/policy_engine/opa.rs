@@ -0,0 +1,81 @@
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use crate::config::{ConfigError, ConnectionConfig, UpstreamTlsConfig};
+use crate::http;
+use crate::policy_engine::{PolicyDecision, PolicyEngine, PolicyEngineError, PolicyInput};
+
+// Policy engine querying an Open Policy Agent decision endpoint.
+//
+// The scan is posted as `{"input": ...}` to the configured data API URL, e.g.
+// `http://opa:8181/v1/data/gateway/decision`. The `result` is either a decision
+// string (`allow`, `block` or `mask`) or an object with a `decision` and an
+// optional replacement `content`. An undefined result leaves the verdict as is.
+pub struct OpaEngine {
+    client: Client,
+    url: String,
+}
+
+impl OpaEngine {
+    // Creates an engine querying `url`, each decision bounded by `timeout`.
+    pub fn new(
+        url: &str,
+        timeout: Duration,
+        tls: &UpstreamTlsConfig,
+        connection: &ConnectionConfig,
+    ) -> Result<Self, ConfigError> {
+        let client = http::build_client(timeout, Some(timeout), tls, connection)?;
+        Ok(Self {
+            client,
+            url: url.to_string(),
+        })
+    }
+}
+
+#[async_trait]
+impl PolicyEngine for OpaEngine {
+    fn name(&self) -> &str {
+        "opa"
+    }
+
+    async fn decide(
+        &self,
+        input: &PolicyInput,
+    ) -> Result<Option<PolicyDecision>, PolicyEngineError> {
+        let body: Value = self
+            .client
+            .post(&self.url)
+            .json(&json!({ "input": input }))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| PolicyEngineError::RequestError(e.to_string()))?
+            .json()
+            .await
+            .map_err(|e| PolicyEngineError::InvalidResponse(e.to_string()))?;
+
+        let (decision, content) = match body.get("result") {
+            None | Some(Value::Null) => return Ok(None),
+            Some(Value::String(decision)) => (decision.as_str(), None),
+            Some(result) => (
+                result
+                    .get("decision")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default(),
+                result.get("content").and_then(Value::as_str),
+            ),
+        };
+        match decision {
+            "allow" => Ok(Some(PolicyDecision::Allow)),
+            "block" => Ok(Some(PolicyDecision::Block)),
+            "mask" => Ok(Some(PolicyDecision::Mask(content.map(str::to_string)))),
+            "" => Ok(None),
+            other => Err(PolicyEngineError::InvalidResponse(format!(
+                "unknown decision {}",
+                other
+            ))),
+        }
+    }
+}
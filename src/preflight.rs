@@ -0,0 +1,155 @@
+use reqwest::StatusCode;
+use serde::Deserialize;
+use std::time::Duration;
+
+use crate::credentials;
+use crate::security::SecurityError;
+use crate::{build_security_client, http, Config};
+
+// Result of one startup check.
+#[derive(Debug)]
+pub struct CheckOutcome {
+    pub name: String,
+    pub result: Result<String, String>,
+}
+
+impl CheckOutcome {
+    fn new(name: impl Into<String>, result: Result<String, String>) -> Self {
+        Self {
+            name: name.into(),
+            result,
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.result.is_ok()
+    }
+}
+
+#[derive(Deserialize)]
+struct VersionResponse {
+    version: String,
+}
+
+// Verifies that the upstream services of a configuration are usable.
+//
+// Sends a benign test scan to the PANW AI Runtime API when a PANW backed scanner
+// is configured, and requests `/api/version` from every configured Ollama URL.
+// The configuration itself is expected to be loaded and validated already.
+//
+// # Arguments
+//
+// * `config` - The loaded configuration
+//
+// # Returns
+//
+// One outcome per check, with an actionable message on failure
+pub async fn run_checks(config: &Config) -> Vec<CheckOutcome> {
+    let mut outcomes = Vec::new();
+
+    if config.scanner.uses_panw() {
+        outcomes.push(CheckOutcome::new(
+            "PANW AI Runtime API",
+            check_panw(config).await,
+        ));
+    }
+
+    let mut urls: Vec<&String> = Vec::new();
+    let configured = config
+        .ollama
+        .base_url
+        .iter()
+        .chain(
+            config
+                .ollama
+                .routes
+                .iter()
+                .flat_map(|route| &route.base_url),
+        )
+        .chain(config.ollama.fallback_url.as_ref());
+    for url in configured {
+        if !urls.contains(&url) {
+            urls.push(url);
+        }
+    }
+    for url in urls {
+        outcomes.push(CheckOutcome::new(
+            format!("Ollama {}", url),
+            check_ollama(config, url).await,
+        ));
+    }
+
+    outcomes
+}
+
+async fn check_panw(config: &Config) -> Result<String, String> {
+    let client = build_security_client(config)
+        .await
+        .map_err(|e| format!("cannot create the PANW client: {}", e))?;
+    let result = credentials::probe(&client)
+        .await
+        .map_err(|e| describe_panw_error(&e, config))?;
+    Ok(format!(
+        "test scan with profile '{}' returned {}/{}",
+        client.profile_name(),
+        result.category,
+        result.action
+    ))
+}
+
+fn describe_panw_error(error: &SecurityError, config: &Config) -> String {
+    match error {
+        SecurityError::AssessmentError(message)
+            if message.starts_with("401") || message.starts_with("403") =>
+        {
+            format!(
+                "the API key was rejected ({}); check security.api_key or its secret source",
+                message
+            )
+        }
+        SecurityError::AssessmentError(message) if message.starts_with("400") => format!(
+            "the test scan was refused ({}); check that profile '{}' exists for this API key",
+            message, config.security.profile_name
+        ),
+        SecurityError::RequestError(_) | SecurityError::Timeout => format!(
+            "{}; check security.base_url or security.region ({}{}) and network access",
+            error,
+            config.security.resolved_base_url(),
+            config.security.scan_path()
+        ),
+        other => other.to_string(),
+    }
+}
+
+async fn check_ollama(config: &Config, base_url: &str) -> Result<String, String> {
+    let client = http::build_client(
+        Duration::from_secs(config.ollama.connect_timeout_secs),
+        Some(Duration::from_secs(config.ollama.timeout_secs)),
+        &config.ollama.tls,
+        &config.ollama.connection,
+    )
+    .map_err(|e| format!("cannot create the Ollama client: {}", e))?;
+
+    let url = format!("{}/api/version", base_url.trim_end_matches('/'));
+    let response = client.get(&url).send().await.map_err(|e| {
+        format!(
+            "{} is unreachable ({}); check ollama.base_url and that Ollama is running",
+            url, e
+        )
+    })?;
+
+    match response.status() {
+        status if status.is_success() => {
+            let version: VersionResponse = response
+                .json()
+                .await
+                .map_err(|e| format!("{} did not return an Ollama version: {}", url, e))?;
+            Ok(format!("version {}", version.version))
+        }
+        StatusCode::NOT_FOUND => Err(format!(
+            "{} returned 404; the URL does not point to an Ollama server",
+            url
+        )),
+        status => Err(format!("{} returned {}", url, status)),
+    }
+}
@@ -0,0 +1,74 @@
+use axum::{
+    extract::Request,
+    http::{header::CONTENT_TYPE, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::fmt::Display;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Set once at startup from `logging.privacy_mode`.
+//
+// Kept global like the request ID so the many log statements handling
+// user content need no extra parameter.
+static PRIVACY_MODE: AtomicBool = AtomicBool::new(false);
+
+// Enables or disables redaction for the whole process.
+pub fn set_enabled(enabled: bool) {
+    PRIVACY_MODE.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    PRIVACY_MODE.load(Ordering::Relaxed)
+}
+
+// Returns text that may contain user content in a form safe to log.
+//
+// In privacy mode only a short SHA-256 prefix and the length are kept, e.g.
+// `[redacted sha256=3f2a9c0d1e4b5a6f len=512]`, so identical content can still
+// be correlated across log lines.
+pub fn redact(text: &str) -> Cow<'_, str> {
+    if !is_enabled() {
+        return Cow::Borrowed(text);
+    }
+    let digest = Sha256::digest(text.as_bytes());
+    let prefix: String = digest[..8]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    Cow::Owned(format!("[redacted sha256={} len={}]", prefix, text.len()))
+}
+
+// Like `redact`, for errors whose message may quote content, e.g. JSON parse errors.
+pub fn redact_error(error: &impl Display) -> String {
+    redact(&error.to_string()).into_owned()
+}
+
+// Middleware replacing plain-text client error bodies with a generic message.
+//
+// Request body rejections produced by axum extractors quote offending values
+// of the client's JSON; in privacy mode they are replaced by a JSON error
+// without details.
+pub async fn redact_rejections(request: Request, next: Next) -> Response {
+    let response = next.run(request).await;
+    let is_plain_text = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/plain"));
+    if !response.status().is_client_error() || !is_plain_text {
+        return response;
+    }
+
+    let status = response.status();
+    let message = match status {
+        StatusCode::PAYLOAD_TOO_LARGE => "Request body too large",
+        StatusCode::UNSUPPORTED_MEDIA_TYPE => "Unsupported content type",
+        _ => "Invalid request body",
+    };
+    (status, Json(json!({ "error": message }))).into_response()
+}
@@ -0,0 +1,347 @@
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tracing::{debug, error, warn};
+use uuid::Uuid;
+
+use crate::config::{ConfigError, QuarantineConfig};
+use crate::types::ScanResponse;
+
+// Extension of the encrypted record files.
+const RECORD_EXTENSION: &str = "qrn";
+
+// Length of the AES-GCM nonce stored in front of each record.
+const NONCE_LEN: usize = 12;
+
+// Interval between two removals of records past the retention period.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+
+#[derive(Debug, Error)]
+pub enum QuarantineError {
+    #[error("Quarantine I/O error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("Quarantine record could not be encrypted")]
+    EncryptionError,
+
+    #[error("Quarantine record could not be decrypted")]
+    DecryptionError,
+
+    #[error("Quarantine record is malformed: {0}")]
+    JsonError(#[from] serde_json::Error),
+}
+
+// A blocked prompt or response kept for review by security analysts.
+//
+// # Fields
+//
+// * `id` - Identifier of the record, used by `/admin/quarantine/{id}`
+// * `created_at` - When the content was blocked
+// * `request_id` - Correlation ID of the client request
+// * `app_user` - Caller the content was attributed to
+// * `endpoint` - Gateway endpoint that served the request
+// * `model` - Model the content was sent to or produced by
+// * `is_prompt` - `true` for prompts, `false` for model responses
+// * `category` - Category assigned by the scanner
+// * `action` - Action recommended by the scanner
+// * `content` - The full offending content
+// * `findings` - The complete scan result
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuarantineRecord {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub request_id: Option<String>,
+    pub app_user: Option<String>,
+    pub endpoint: Option<String>,
+    pub model: String,
+    pub is_prompt: bool,
+    pub category: String,
+    pub action: String,
+    pub content: String,
+    pub findings: ScanResponse,
+}
+
+// Summary of a record returned by the listing endpoint, without the content.
+#[derive(Debug, Clone, Serialize)]
+pub struct QuarantineSummary {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub request_id: Option<String>,
+    pub app_user: Option<String>,
+    pub endpoint: Option<String>,
+    pub model: String,
+    pub is_prompt: bool,
+    pub category: String,
+}
+
+// One page of the quarantine listing, newest first.
+#[derive(Debug, Clone, Serialize)]
+pub struct QuarantinePage {
+    pub records: Vec<QuarantineSummary>,
+    // Cursor of the next page, `None` on the last one
+    pub next_cursor: Option<String>,
+}
+
+impl From<&QuarantineRecord> for QuarantineSummary {
+    fn from(record: &QuarantineRecord) -> Self {
+        Self {
+            id: record.id,
+            created_at: record.created_at,
+            request_id: record.request_id.clone(),
+            app_user: record.app_user.clone(),
+            endpoint: record.endpoint.clone(),
+            model: record.model.clone(),
+            is_prompt: record.is_prompt,
+            category: record.category.clone(),
+        }
+    }
+}
+
+// Directory of AES-256-GCM encrypted JSON records, one file per blocked exchange.
+//
+// Records hold the offending content in full, so they are never written in
+// clear text; each file is a random nonce followed by the ciphertext. Files are
+// named `<created_at millis>-<id>.qrn`, so records are listed and pruned by
+// name, decrypting only the ones returned.
+#[derive(Clone)]
+pub struct QuarantineStore {
+    directory: PathBuf,
+    cipher: Arc<Aes256Gcm>,
+    retention: Option<chrono::Duration>,
+}
+
+impl QuarantineStore {
+    // Opens the quarantine directory, creating it if needed.
+    //
+    // # Arguments
+    //
+    // * `config` - Quarantine section of the configuration
+    //
+    // # Returns
+    //
+    // * `Ok(QuarantineStore)` - The store, ready for writes
+    // * `Err(ConfigError)` - If the key or the retention period is invalid, or the
+    //   directory cannot be created
+    pub fn new(config: &QuarantineConfig) -> Result<Self, ConfigError> {
+        let encoded = match (&config.key, &config.key_file) {
+            (Some(key), _) => key.trim().to_string(),
+            (None, Some(path)) => fs::read_to_string(path)?.trim().to_string(),
+            (None, None) => {
+                return Err(ConfigError::ValidationError(
+                    "quarantine key missing".into(),
+                ))
+            }
+        };
+        let key = STANDARD
+            .decode(encoded)
+            .ok()
+            .filter(|key| key.len() == 32)
+            .ok_or_else(|| {
+                ConfigError::ValidationError(
+                    "quarantine key must be 32 bytes, base64-encoded".into(),
+                )
+            })?;
+
+        let retention = match config.retention_days {
+            0 => None,
+            days => Some(
+                i64::try_from(days)
+                    .ok()
+                    .and_then(chrono::Duration::try_days)
+                    .ok_or_else(|| {
+                        ConfigError::ValidationError(format!(
+                            "quarantine.retention_days is out of range: {}",
+                            days
+                        ))
+                    })?,
+            ),
+        };
+
+        fs::create_dir_all(&config.directory)?;
+        Ok(Self {
+            directory: PathBuf::from(&config.directory),
+            cipher: Arc::new(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key))),
+            retention,
+        })
+    }
+
+    // Encrypts and stores a record.
+    pub async fn store(&self, record: &QuarantineRecord) -> Result<(), QuarantineError> {
+        let plaintext = serde_json::to_vec(record)?;
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, plaintext.as_slice())
+            .map_err(|_| QuarantineError::EncryptionError)?;
+
+        let mut data = nonce.to_vec();
+        data.extend_from_slice(&ciphertext);
+        let name = record_name(record.created_at.timestamp_millis(), record.id);
+        tokio::fs::write(self.path_of(&name), data).await?;
+        debug!("Quarantined blocked content as {}", record.id);
+        Ok(())
+    }
+
+    // Stores a record in the background, logging failures.
+    pub fn spawn_store(&self, record: QuarantineRecord) {
+        let store = self.clone();
+        tokio::spawn(async move {
+            if let Err(e) = store.store(&record).await {
+                error!("Failed to quarantine record {}: {}", record.id, e);
+            }
+        });
+    }
+
+    // Removes records older than the retention period, every hour.
+    //
+    // Does nothing when records are kept forever (`retention_days: 0`).
+    pub fn spawn_pruner(&self) {
+        let Some(retention) = self.retention else {
+            return;
+        };
+        let store = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+            loop {
+                interval.tick().await;
+                // Nothing can be older than a cutoff before the earliest date
+                let Some(cutoff) = Utc::now().checked_sub_signed(retention) else {
+                    continue;
+                };
+                match store.prune(cutoff.timestamp_millis()).await {
+                    Ok(removed) => debug!("Pruned {} expired quarantine records", removed),
+                    Err(e) => error!("Failed to prune quarantine records: {}", e),
+                }
+            }
+        });
+    }
+
+    // Deletes the records created before `cutoff`, in milliseconds since the epoch.
+    async fn prune(&self, cutoff: i64) -> Result<usize, QuarantineError> {
+        let mut removed = 0;
+        for name in self.record_names().await? {
+            if created_millis(&name).is_some_and(|millis| millis < cutoff) {
+                tokio::fs::remove_file(self.path_of(&name)).await?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    // Reads and decrypts one record.
+    //
+    // # Returns
+    //
+    // * `Ok(Some(record))` - The record
+    // * `Ok(None)` - If no record has this ID
+    // * `Err(QuarantineError)` - If the file cannot be read or decrypted
+    pub async fn get(&self, id: Uuid) -> Result<Option<QuarantineRecord>, QuarantineError> {
+        let suffix = format!("-{}", id);
+        let Some(name) = self
+            .record_names()
+            .await?
+            .into_iter()
+            .find(|name| name.ends_with(&suffix))
+        else {
+            return Ok(None);
+        };
+        let data = match tokio::fs::read(self.path_of(&name)).await {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        self.decrypt(&data).map(Some)
+    }
+
+    // Lists stored records, newest first.
+    //
+    // Only the records of the requested page are read and decrypted.
+    //
+    // # Arguments
+    //
+    // * `limit` - Maximum number of records returned
+    // * `cursor` - Only records older than this one, taken from `next_cursor`
+    pub async fn list(
+        &self,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> Result<QuarantinePage, QuarantineError> {
+        let mut names: Vec<String> = self
+            .record_names()
+            .await?
+            .into_iter()
+            .filter(|name| cursor.is_none_or(|cursor| name.as_str() < cursor))
+            .collect();
+        names.sort_unstable_by(|a, b| b.cmp(a));
+
+        let next_cursor = (names.len() > limit).then(|| names[limit - 1].clone());
+        let mut records = Vec::with_capacity(limit.min(names.len()));
+        for name in names.iter().take(limit) {
+            let path = self.path_of(name);
+            let data = match tokio::fs::read(&path).await {
+                Ok(data) => data,
+                // Pruned since the directory was read
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+            match self.decrypt(&data) {
+                Ok(record) => records.push(QuarantineSummary::from(&record)),
+                Err(e) => warn!("Skipping quarantine file {}: {}", path.display(), e),
+            }
+        }
+        Ok(QuarantinePage {
+            records,
+            next_cursor,
+        })
+    }
+
+    // Names of the record files, without their extension.
+    async fn record_names(&self) -> Result<Vec<String>, QuarantineError> {
+        let mut entries = tokio::fs::read_dir(&self.directory).await?;
+        let mut names = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some(RECORD_EXTENSION) {
+                continue;
+            }
+            if let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) {
+                names.push(name.to_string());
+            }
+        }
+        Ok(names)
+    }
+
+    fn decrypt(&self, data: &[u8]) -> Result<QuarantineRecord, QuarantineError> {
+        if data.len() < NONCE_LEN {
+            return Err(QuarantineError::DecryptionError);
+        }
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        let plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| QuarantineError::DecryptionError)?;
+        Ok(serde_json::from_slice(&plaintext)?)
+    }
+
+    fn path_of(&self, name: &str) -> PathBuf {
+        self.directory
+            .join(format!("{}.{}", name, RECORD_EXTENSION))
+    }
+}
+
+// File name of a record, zero-padded so names sort in creation order.
+fn record_name(created_millis: i64, id: Uuid) -> String {
+    format!("{:013}-{}", created_millis.max(0), id)
+}
+
+// Creation time encoded in a record file name.
+fn created_millis(name: &str) -> Option<i64> {
+    name.split_once('-')?.0.parse().ok()
+}
@@ -0,0 +1,238 @@
+use chrono::{DateTime, Datelike, Duration as ChronoDuration, NaiveDate, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::error;
+
+use crate::config::{ConfigError, QuotaConfig, QuotaLimitsConfig};
+
+// Period a quota applies to; usage resets at the start of the next one (UTC).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuotaPeriod {
+    Daily,
+    Monthly,
+}
+
+impl QuotaPeriod {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            QuotaPeriod::Daily => "daily",
+            QuotaPeriod::Monthly => "monthly",
+        }
+    }
+
+    // Row key of the period containing `now`, e.g. `d:2025-03-14` or `m:2025-03`.
+    fn key(self, now: DateTime<Utc>) -> String {
+        match self {
+            QuotaPeriod::Daily => now.format("d:%Y-%m-%d").to_string(),
+            QuotaPeriod::Monthly => now.format("m:%Y-%m").to_string(),
+        }
+    }
+
+    // Start of the next period.
+    fn reset_at(self, now: DateTime<Utc>) -> DateTime<Utc> {
+        let today = now.date_naive();
+        let next = match self {
+            QuotaPeriod::Daily => today + ChronoDuration::days(1),
+            QuotaPeriod::Monthly => {
+                let (year, month) = match today.month() {
+                    12 => (today.year() + 1, 1),
+                    month => (today.year(), month + 1),
+                };
+                NaiveDate::from_ymd_opt(year, month, 1).unwrap_or(today)
+            }
+        };
+        next.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc()
+    }
+}
+
+// A quota the caller has used up.
+#[derive(Debug, Clone)]
+pub struct QuotaExceeded {
+    pub period: QuotaPeriod,
+    pub resource: &'static str,
+    pub limit: u64,
+    pub used: u64,
+    pub reset_at: DateTime<Utc>,
+}
+
+impl QuotaExceeded {
+    // Time until the quota resets.
+    pub fn retry_after(&self) -> Duration {
+        (self.reset_at - Utc::now()).to_std().unwrap_or_default()
+    }
+}
+
+// Per-user daily and monthly request and token quotas persisted in SQLite.
+//
+// Users are identified like for the token budget. Generated tokens are taken
+// from the `eval_count` reported by Ollama once a response completes. Cheap to
+// clone; all clones share the same database connection.
+#[derive(Clone)]
+pub struct QuotaStore {
+    daily: QuotaLimitsConfig,
+    monthly: QuotaLimitsConfig,
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl QuotaStore {
+    // Opens or creates the usage database.
+    //
+    // # Returns
+    //
+    // * `Ok(QuotaStore)` - The store, ready to count usage
+    // * `Err(ConfigError)` - If the database cannot be opened or initialized
+    pub fn open(config: &QuotaConfig) -> Result<Self, ConfigError> {
+        let connection = Connection::open(&config.database)
+            .and_then(|connection| {
+                connection.execute_batch(
+                    "CREATE TABLE IF NOT EXISTS quota_usage (
+                        principal TEXT NOT NULL,
+                        period TEXT NOT NULL,
+                        requests INTEGER NOT NULL DEFAULT 0,
+                        tokens INTEGER NOT NULL DEFAULT 0,
+                        PRIMARY KEY (principal, period)
+                    )",
+                )?;
+                Ok(connection)
+            })
+            .map_err(|e| {
+                ConfigError::ValidationError(format!(
+                    "Cannot open quota database {}: {}",
+                    config.database, e
+                ))
+            })?;
+        Ok(Self {
+            daily: config.daily.clone(),
+            monthly: config.monthly.clone(),
+            connection: Arc::new(Mutex::new(connection)),
+        })
+    }
+
+    // Counts a request unless one of the caller's quotas is used up.
+    //
+    // Database errors are logged and the request is let through.
+    //
+    // # Returns
+    //
+    // * `Ok(())` - The request was counted and may proceed
+    // * `Err(QuotaExceeded)` - The first quota found exhausted
+    pub async fn admit(&self, principal: &str) -> Result<(), QuotaExceeded> {
+        let store = self.clone();
+        let principal = principal.to_string();
+        let result = tokio::task::spawn_blocking(move || store.admit_blocking(&principal)).await;
+        match result {
+            Ok(Ok(outcome)) => outcome,
+            Ok(Err(e)) => {
+                error!("Quota database error, allowing the request: {}", e);
+                Ok(())
+            }
+            Err(e) => {
+                error!("Quota check failed, allowing the request: {}", e);
+                Ok(())
+            }
+        }
+    }
+
+    fn admit_blocking(&self, principal: &str) -> rusqlite::Result<Result<(), QuotaExceeded>> {
+        let now = Utc::now();
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+        for (period, limits) in [
+            (QuotaPeriod::Daily, &self.daily),
+            (QuotaPeriod::Monthly, &self.monthly),
+        ] {
+            let (requests, tokens): (u64, u64) = transaction
+                .query_row(
+                    "SELECT requests, tokens FROM quota_usage WHERE principal = ?1 AND period = ?2",
+                    params![principal, period.key(now)],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .optional()?
+                .unwrap_or_default();
+            for (resource, limit, used) in [
+                ("requests", limits.requests, requests),
+                ("tokens", limits.tokens, tokens),
+            ] {
+                if let Some(limit) = limit.filter(|limit| used >= *limit) {
+                    return Ok(Err(QuotaExceeded {
+                        period,
+                        resource,
+                        limit,
+                        used,
+                        reset_at: period.reset_at(now),
+                    }));
+                }
+            }
+        }
+        for period in [QuotaPeriod::Daily, QuotaPeriod::Monthly] {
+            add_usage(&transaction, principal, &period.key(now), 1, 0)?;
+        }
+        transaction.commit()?;
+        Ok(Ok(()))
+    }
+
+    // Adds generated tokens to the caller's usage in the background.
+    pub fn record_tokens(&self, principal: &str, tokens: u64) {
+        if tokens == 0 {
+            return;
+        }
+        let store = self.clone();
+        let principal = principal.to_string();
+        tokio::task::spawn_blocking(move || {
+            let now = Utc::now();
+            let connection = store.connection.lock().unwrap();
+            for period in [QuotaPeriod::Daily, QuotaPeriod::Monthly] {
+                if let Err(e) = add_usage(&connection, &principal, &period.key(now), 0, tokens) {
+                    error!("Failed to record token usage: {}", e);
+                }
+            }
+        });
+    }
+}
+
+fn add_usage(
+    connection: &Connection,
+    principal: &str,
+    period: &str,
+    requests: u64,
+    tokens: u64,
+) -> rusqlite::Result<()> {
+    connection.execute(
+        "INSERT INTO quota_usage (principal, period, requests, tokens) VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT (principal, period) DO UPDATE SET
+            requests = requests + excluded.requests,
+            tokens = tokens + excluded.tokens",
+        params![principal, period, requests, tokens],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn daily_period_resets_at_next_midnight() {
+        let now = Utc.with_ymd_and_hms(2025, 3, 14, 15, 30, 0).unwrap();
+        let reset = QuotaPeriod::Daily.reset_at(now);
+        assert_eq!(reset, Utc.with_ymd_and_hms(2025, 3, 15, 0, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn monthly_period_resets_on_the_first() {
+        let now = Utc.with_ymd_and_hms(2025, 3, 31, 23, 59, 59).unwrap();
+        let reset = QuotaPeriod::Monthly.reset_at(now);
+        assert_eq!(reset, Utc.with_ymd_and_hms(2025, 4, 1, 0, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn monthly_period_rolls_over_the_year() {
+        let now = Utc.with_ymd_and_hms(2025, 12, 1, 0, 0, 0).unwrap();
+        let reset = QuotaPeriod::Monthly.reset_at(now);
+        assert_eq!(reset, Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap());
+    }
+}
@@ -0,0 +1,46 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Token bucket allowing bursts up to its per-minute budget.
+#[derive(Debug)]
+pub struct RateLimiter {
+    capacity: f64,
+    refill_per_sec: f64,
+    bucket: Mutex<(f64, Instant)>,
+}
+
+impl RateLimiter {
+    // Creates a limiter admitting `requests` per minute on average.
+    pub fn per_minute(requests: u32) -> Self {
+        let capacity = f64::from(requests.max(1));
+        Self {
+            capacity,
+            refill_per_sec: capacity / 60.0,
+            bucket: Mutex::new((capacity, Instant::now())),
+        }
+    }
+
+    // Takes one token if available.
+    //
+    // # Returns
+    //
+    // * `Ok(())` - If the request is admitted
+    // * `Err(Duration)` - Time until the next token is available
+    pub fn try_acquire(&self) -> Result<(), Duration> {
+        let mut bucket = self.bucket.lock().unwrap();
+        let (tokens, updated_at) = &mut *bucket;
+        let now = Instant::now();
+        *tokens = (*tokens + now.duration_since(*updated_at).as_secs_f64() * self.refill_per_sec)
+            .min(self.capacity);
+        *updated_at = now;
+
+        if *tokens >= 1.0 {
+            *tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - *tokens) / self.refill_per_sec,
+            ))
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use axum::{
+    body::{to_bytes, Body},
+    extract::Request,
+    http::{header::CONTENT_TYPE, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use serde_json::Value;
+use tracing::{debug, info_span, warn, Span};
+use uuid::Uuid;
+
+// Header carrying the correlation ID between clients, the proxy, Ollama and PANW.
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+// Longest client-supplied ID accepted; longer values are replaced.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+// Largest error body rewritten to include the request ID.
+const MAX_ERROR_BODY_BYTES: usize = 64 * 1024;
+
+tokio::task_local! {
+    static CURRENT_REQUEST_ID: String;
+}
+
+// Correlation ID of the request, stored in the request extensions.
+#[derive(Debug, Clone)]
+pub struct RequestId(pub String);
+
+// Returns the ID of the request being handled by the current task, if any.
+//
+// Used by the Ollama client to forward the ID without threading it through
+// every call.
+pub fn current() -> Option<String> {
+    CURRENT_REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+// Middleware assigning a correlation ID to every request.
+//
+// The client's `X-Request-Id` is kept when it is a reasonable token, otherwise a
+// UUID is generated. The ID is made available to handlers (and used as the PANW
+// `tr_id`), echoed in the `X-Request-Id` response header and added as `request_id`
+// to JSON error bodies.
+pub async fn propagate_request_id(mut request: Request, next: Next) -> Response {
+    let id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| is_valid_request_id(value))
+        .map(str::to_owned)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+    debug!("Handling request {}", id);
+
+    request.extensions_mut().insert(RequestId(id.clone()));
+    let response = CURRENT_REQUEST_ID
+        .scope(id.clone(), next.run(request))
+        .await;
+
+    let mut response = if is_json_error(&response) {
+        add_id_to_error_body(response, &id).await
+    } else {
+        response
+    };
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+fn is_valid_request_id(value: &str) -> bool {
+    !value.is_empty()
+        && value.len() <= MAX_REQUEST_ID_LEN
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'))
+}
+
+fn is_json_error(response: &Response) -> bool {
+    (response.status().is_client_error() || response.status().is_server_error())
+        && response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.starts_with("application/json"))
+            .unwrap_or(false)
+}
+
+// Creates the tracing span of a request, carrying its correlation ID.
+//
+// Used with `TraceLayer::make_span_with` inside `propagate_request_id`, so every
+// log line emitted while handling the request can be tied to its ID.
+pub fn make_span<B>(request: &axum::http::Request<B>) -> Span {
+    let id = request
+        .extensions()
+        .get::<RequestId>()
+        .map(|id| id.0.as_str())
+        .unwrap_or_default();
+    info_span!(
+        "request",
+        method = %request.method(),
+        uri = %request.uri(),
+        version = ?request.version(),
+        request_id = %id,
+    )
+}
+
+// Inserts `request_id` into a JSON error object, leaving other bodies untouched.
+async fn add_id_to_error_body(response: Response, id: &str) -> Response {
+    let (mut parts, body) = response.into_parts();
+    let bytes = match to_bytes(body, MAX_ERROR_BODY_BYTES).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            warn!("Failed to read error body for request {}: {}", id, e);
+            return Response::from_parts(parts, Body::empty());
+        }
+    };
+
+    let body = match serde_json::from_slice::<Value>(&bytes) {
+        Ok(Value::Object(mut object)) => {
+            object.insert("request_id".to_string(), Value::String(id.to_string()));
+            parts.headers.remove(axum::http::header::CONTENT_LENGTH);
+            Body::from(Value::Object(object).to_string())
+        }
+        _ => Body::from(bytes),
+    };
+    Response::from_parts(parts, body)
+}
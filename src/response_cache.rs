@@ -0,0 +1,107 @@
+use bytes::Bytes;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::config::ResponseCacheConfig;
+use crate::security::ScanContext;
+use crate::types::ModelOptions;
+
+pub type CacheKey = [u8; 32];
+
+// Response returned for a cached request.
+#[derive(Debug, Clone)]
+pub struct CachedResponse {
+    pub body: Bytes,
+    pub backend: String,
+}
+
+// Content-addressed cache of allowed non-streaming generate and chat responses.
+//
+// Entries are keyed by a hash of the endpoint, the effective PANW profile and
+// the request as forwarded to Ollama, serialized with sorted keys. A hit skips
+// both the scans and the model. Cheap to clone; all clones share the same
+// entries.
+#[derive(Clone)]
+pub struct ResponseCache {
+    ttl: Duration,
+    max_entries: usize,
+    max_entry_bytes: usize,
+    deterministic_only: bool,
+    entries: Arc<Mutex<HashMap<CacheKey, (CachedResponse, Instant)>>>,
+}
+
+impl ResponseCache {
+    pub fn new(config: &ResponseCacheConfig) -> Self {
+        Self {
+            ttl: Duration::from_secs(config.ttl_secs),
+            max_entries: config.max_entries,
+            max_entry_bytes: config.max_entry_bytes,
+            deterministic_only: config.deterministic_only,
+            entries: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    // Returns the cache key of a request, or `None` if it must not be cached.
+    //
+    // # Arguments
+    //
+    // * `endpoint` - The Ollama endpoint the request is sent to
+    // * `request` - The request as forwarded
+    // * `options` - Its model options, checked for deterministic sampling
+    // * `ctx` - Scan context providing the effective PANW profile
+    pub fn key<T: Serialize>(
+        &self,
+        endpoint: &str,
+        request: &T,
+        options: Option<&ModelOptions>,
+        ctx: &ScanContext,
+    ) -> Option<CacheKey> {
+        if self.deterministic_only && !is_deterministic(options) {
+            return None;
+        }
+        // `Value` objects are sorted maps, so field order does not matter
+        let normalized = serde_json::to_vec(&serde_json::to_value(request).ok()?).ok()?;
+        let mut hasher = Sha256::new();
+        hasher.update(endpoint);
+        hasher.update([0]);
+        hasher.update(ctx.profile_name.as_deref().unwrap_or_default());
+        hasher.update([0]);
+        hasher.update(normalized);
+        Some(hasher.finalize().into())
+    }
+
+    pub fn get(&self, key: &CacheKey) -> Option<CachedResponse> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(key)
+            .filter(|(_, stored_at)| stored_at.elapsed() < self.ttl)
+            .map(|(response, _)| response.clone())
+    }
+
+    pub fn put(&self, key: CacheKey, response: CachedResponse) {
+        if response.body.len() > self.max_entry_bytes {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.max_entries {
+            entries.retain(|_, (_, stored_at)| stored_at.elapsed() < self.ttl);
+            if entries.len() >= self.max_entries {
+                return;
+            }
+        }
+        entries.insert(key, (response, Instant::now()));
+    }
+
+    // Drops all cached responses.
+    pub fn invalidate(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
+
+// Returns `true` if sampling is pinned by a zero temperature or a fixed seed.
+fn is_deterministic(options: Option<&ModelOptions>) -> bool {
+    options.is_some_and(|options| options.temperature == Some(0.0) || options.seed.is_some())
+}
@@ -0,0 +1,44 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+
+use crate::ban::BanList;
+use crate::scanner::Scanner;
+use crate::security::{Assessment, ScanContext, SecurityError};
+
+// Decorator counting blocked requests towards the caller's temporary ban.
+//
+// Wraps the enforcing scanner, so content let through in monitor mode is not
+// counted.
+pub struct BanningScanner {
+    inner: Arc<dyn Scanner>,
+    bans: BanList,
+}
+
+impl BanningScanner {
+    pub fn new(inner: Arc<dyn Scanner>, bans: BanList) -> Self {
+        Self { inner, bans }
+    }
+}
+
+#[async_trait]
+impl Scanner for BanningScanner {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    async fn assess(
+        &self,
+        content: &str,
+        model_name: &str,
+        is_prompt: bool,
+        ctx: &ScanContext,
+    ) -> Result<Assessment, SecurityError> {
+        let result = self.inner.assess(content, model_name, is_prompt, ctx).await;
+        match &result {
+            Ok(assessment) if !assessment.is_safe => self.bans.record_block(ctx),
+            Err(SecurityError::BlockedContent(_)) => self.bans.record_block(ctx),
+            _ => {}
+        }
+        result
+    }
+}
@@ -0,0 +1,112 @@
+use async_trait::async_trait;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+use crate::config::ScanCacheConfig;
+use crate::scanner::Scanner;
+use crate::security::{Assessment, ScanContext, SecurityError};
+
+type CacheKey = [u8; 32];
+
+// Shared store of allowed verdicts keyed by a hash of the scanned content.
+//
+// Only the SHA-256 of the content is kept. Cheap to clone; all clones share
+// the same entries.
+#[derive(Clone)]
+pub struct ScanCache {
+    ttl: Duration,
+    max_entries: usize,
+    entries: Arc<Mutex<HashMap<CacheKey, (Assessment, Instant)>>>,
+}
+
+impl ScanCache {
+    pub fn new(config: &ScanCacheConfig) -> Self {
+        Self {
+            ttl: Duration::from_secs(config.ttl_secs),
+            max_entries: config.max_entries,
+            entries: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    // Hashes what determines a verdict: the profile override, the direction and the content.
+    fn key(content: &str, is_prompt: bool, ctx: &ScanContext) -> CacheKey {
+        let mut hasher = Sha256::new();
+        hasher.update(ctx.profile_name.as_deref().unwrap_or_default());
+        hasher.update([0, u8::from(is_prompt)]);
+        hasher.update(content);
+        hasher.finalize().into()
+    }
+
+    fn get(&self, key: &CacheKey) -> Option<Assessment> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(key)
+            .filter(|(_, stored_at)| stored_at.elapsed() < self.ttl)
+            .map(|(assessment, _)| assessment.clone())
+    }
+
+    fn put(&self, key: CacheKey, assessment: Assessment) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.max_entries {
+            entries.retain(|_, (_, stored_at)| stored_at.elapsed() < self.ttl);
+            if entries.len() >= self.max_entries {
+                return;
+            }
+        }
+        entries.insert(key, (assessment, Instant::now()));
+    }
+
+    // Drops all cached verdicts.
+    pub fn invalidate(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
+
+// Decorator answering repeated content from the scan cache.
+//
+// Only allowed verdicts are cached, so blocked content is always rescanned and
+// reaches the quarantine and audit trail every time.
+pub struct CachingScanner {
+    inner: Arc<dyn Scanner>,
+    cache: ScanCache,
+}
+
+impl CachingScanner {
+    pub fn new(inner: Arc<dyn Scanner>, cache: ScanCache) -> Self {
+        Self { inner, cache }
+    }
+}
+
+#[async_trait]
+impl Scanner for CachingScanner {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    async fn assess(
+        &self,
+        content: &str,
+        model_name: &str,
+        is_prompt: bool,
+        ctx: &ScanContext,
+    ) -> Result<Assessment, SecurityError> {
+        let key = ScanCache::key(content, is_prompt, ctx);
+        if let Some(assessment) = self.cache.get(&key) {
+            debug!("Scan cache hit for {} bytes of content", content.len());
+            return Ok(assessment);
+        }
+
+        let assessment = self
+            .inner
+            .assess(content, model_name, is_prompt, ctx)
+            .await?;
+        // Fail-open placeholders are not verdicts and must be rescanned once PANW is back
+        if assessment.is_safe && !assessment.unscanned {
+            self.cache.put(key, assessment.clone());
+        }
+        Ok(assessment)
+    }
+}
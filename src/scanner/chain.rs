@@ -0,0 +1,110 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use crate::config::{OnBlock, OnError};
+use crate::scanner::Scanner;
+use crate::security::{Assessment, ScanContext, SecurityError};
+use crate::types::ScanResponse;
+
+// A single stage of a scanner chain with its policies.
+pub struct ChainStage {
+    pub scanner: Arc<dyn Scanner>,
+    pub on_block: OnBlock,
+    pub on_error: OnError,
+}
+
+// Runs an ordered list of scanners, e.g. a fast local filter before the PANW API.
+//
+// Content is allowed only if no stage blocks it. A blocking stage either ends the
+// chain immediately (`short_circuit`) or lets later stages run for visibility
+// (`continue`). Each stage verdict is written to the `audit` log target.
+pub struct ChainScanner {
+    stages: Vec<ChainStage>,
+}
+
+impl ChainScanner {
+    pub fn new(stages: Vec<ChainStage>) -> Self {
+        Self { stages }
+    }
+}
+
+#[async_trait]
+impl Scanner for ChainScanner {
+    fn name(&self) -> &str {
+        "chain"
+    }
+
+    async fn assess(
+        &self,
+        content: &str,
+        model_name: &str,
+        is_prompt: bool,
+        ctx: &ScanContext,
+    ) -> Result<Assessment, SecurityError> {
+        let mut allowed: Option<Assessment> = None;
+        let mut unscanned = false;
+        let mut blocked: Option<Result<Assessment, SecurityError>> = None;
+
+        for (index, stage) in self.stages.iter().enumerate() {
+            let result = stage
+                .scanner
+                .assess(content, model_name, is_prompt, ctx)
+                .await;
+
+            let verdict = match &result {
+                Ok(assessment) if assessment.is_safe => "allow",
+                Ok(_) | Err(SecurityError::BlockedContent(_)) => "block",
+                Err(_) => "error",
+            };
+            info!(
+                target: "audit",
+                stage = index,
+                scanner = stage.scanner.name(),
+                verdict,
+                model = model_name,
+                is_prompt,
+                "Scanner chain stage verdict"
+            );
+
+            match result {
+                Ok(assessment) if assessment.is_safe => {
+                    unscanned |= assessment.unscanned;
+                    allowed = Some(assessment);
+                }
+                Err(e) if !matches!(e, SecurityError::BlockedContent(_)) => match stage.on_error {
+                    OnError::Fail => return Err(e),
+                    OnError::Skip => {
+                        warn!(
+                            "Scanner {} failed, skipping stage: {}",
+                            stage.scanner.name(),
+                            e
+                        );
+                        unscanned = true;
+                    }
+                },
+                blocking => {
+                    if stage.on_block == OnBlock::ShortCircuit {
+                        return blocking;
+                    }
+                    blocked.get_or_insert(blocking);
+                }
+            }
+        }
+
+        if let Some(blocking) = blocked {
+            return blocking;
+        }
+
+        let mut assessment = allowed.unwrap_or_else(|| Assessment {
+            is_safe: true,
+            category: "benign".to_string(),
+            action: "allow".to_string(),
+            details: ScanResponse::default_safe_response(),
+            masked_content: None,
+            unscanned: false,
+        });
+        assessment.unscanned = unscanned;
+        Ok(assessment)
+    }
+}
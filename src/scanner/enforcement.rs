@@ -0,0 +1,91 @@
+use arc_swap::ArcSwap;
+use async_trait::async_trait;
+use std::sync::Arc;
+use tracing::{debug, info};
+
+use crate::config::EnforcementMode;
+use crate::policy::RuntimePolicy;
+use crate::scanner::Scanner;
+use crate::security::{Assessment, ScanContext, SecurityError};
+use crate::types::ScanResponse;
+
+// Decorator applying the runtime policy to the wrapped scanner.
+//
+// Endpoints with scanning switched off are passed without a scan. In monitor
+// mode, flagged content is let through and the verdict only goes to the
+// `audit` log target.
+pub struct EnforcingScanner {
+    inner: Arc<dyn Scanner>,
+    policy: Arc<ArcSwap<RuntimePolicy>>,
+}
+
+impl EnforcingScanner {
+    pub fn new(inner: Arc<dyn Scanner>, policy: Arc<ArcSwap<RuntimePolicy>>) -> Self {
+        Self { inner, policy }
+    }
+}
+
+#[async_trait]
+impl Scanner for EnforcingScanner {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    async fn assess(
+        &self,
+        content: &str,
+        model_name: &str,
+        is_prompt: bool,
+        ctx: &ScanContext,
+    ) -> Result<Assessment, SecurityError> {
+        let policy = self.policy.load();
+        if !policy.scanning.is_enabled(ctx.endpoint.as_deref()) {
+            debug!("Scanning disabled for {:?}, skipping", ctx.endpoint);
+            return Ok(allowed(ScanResponse::default_safe_response()));
+        }
+
+        let result = self.inner.assess(content, model_name, is_prompt, ctx).await;
+        if policy.mode == EnforcementMode::Enforce {
+            return result;
+        }
+
+        match result {
+            Ok(assessment) if !assessment.is_safe => {
+                info!(
+                    target: "audit",
+                    request_id = ctx.request_id.as_deref().unwrap_or_default(),
+                    model = model_name,
+                    is_prompt,
+                    category = %assessment.category,
+                    action = %assessment.action,
+                    "Monitor mode, letting flagged content through"
+                );
+                Ok(allowed(assessment.details))
+            }
+            Err(SecurityError::BlockedContent(details)) => {
+                info!(
+                    target: "audit",
+                    request_id = ctx.request_id.as_deref().unwrap_or_default(),
+                    model = model_name,
+                    is_prompt,
+                    category = %details.category,
+                    action = %details.action,
+                    "Monitor mode, letting blocked content through"
+                );
+                Ok(allowed(*details))
+            }
+            result => result,
+        }
+    }
+}
+
+fn allowed(details: ScanResponse) -> Assessment {
+    Assessment {
+        is_safe: true,
+        category: details.category.clone(),
+        action: "allow".to_string(),
+        details,
+        masked_content: None,
+        unscanned: false,
+    }
+}
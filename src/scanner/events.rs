@@ -0,0 +1,73 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::budget::TokenBudget;
+use crate::events::{EventSink, GatewayEvent};
+use crate::scanner::Scanner;
+use crate::security::{Assessment, ScanContext, SecurityError};
+
+// Decorator publishing every verdict of the wrapped scanner as a gateway event.
+//
+// Failed scans are not published; they carry no verdict.
+pub struct PublishingScanner {
+    inner: Arc<dyn Scanner>,
+    events: EventSink,
+}
+
+impl PublishingScanner {
+    pub fn new(inner: Arc<dyn Scanner>, events: EventSink) -> Self {
+        Self { inner, events }
+    }
+}
+
+#[async_trait]
+impl Scanner for PublishingScanner {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    async fn assess(
+        &self,
+        content: &str,
+        model_name: &str,
+        is_prompt: bool,
+        ctx: &ScanContext,
+    ) -> Result<Assessment, SecurityError> {
+        let result = self.inner.assess(content, model_name, is_prompt, ctx).await;
+
+        let verdict = match &result {
+            Ok(assessment) => Some((
+                assessment.is_safe,
+                assessment.category.clone(),
+                assessment.action.clone(),
+                assessment.details.report_id.clone(),
+            )),
+            Err(SecurityError::BlockedContent(details)) => Some((
+                false,
+                details.category.clone(),
+                details.action.clone(),
+                details.report_id.clone(),
+            )),
+            Err(_) => None,
+        };
+        if let Some((is_safe, category, action, report_id)) = verdict {
+            self.events.publish(GatewayEvent::Verdict {
+                id: Uuid::new_v4(),
+                timestamp: Utc::now(),
+                request_id: ctx.request_id.clone(),
+                user: TokenBudget::user_key(ctx),
+                endpoint: ctx.endpoint.clone(),
+                model: model_name.to_string(),
+                is_prompt,
+                is_safe,
+                category,
+                action,
+                scanner: self.inner.name().to_string(),
+                report_id,
+            });
+        }
+        result
+    }
+}
@@ -0,0 +1,76 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+
+use crate::budget::TokenBudget;
+use crate::history::{AssessmentHistory, AssessmentRecord};
+use crate::scanner::Scanner;
+use crate::security::{Assessment, ScanContext, SecurityError};
+
+// Decorator recording every verdict of the wrapped scanner in the assessment history.
+//
+// Failed scans are not recorded; they carry no verdict.
+pub struct RecordingScanner {
+    inner: Arc<dyn Scanner>,
+    history: AssessmentHistory,
+}
+
+impl RecordingScanner {
+    pub fn new(inner: Arc<dyn Scanner>, history: AssessmentHistory) -> Self {
+        Self { inner, history }
+    }
+}
+
+#[async_trait]
+impl Scanner for RecordingScanner {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    async fn assess(
+        &self,
+        content: &str,
+        model_name: &str,
+        is_prompt: bool,
+        ctx: &ScanContext,
+    ) -> Result<Assessment, SecurityError> {
+        let result = self.inner.assess(content, model_name, is_prompt, ctx).await;
+
+        let verdict = match &result {
+            Ok(assessment) => Some((
+                assessment.is_safe,
+                assessment.category.clone(),
+                assessment.action.clone(),
+                assessment.details.report_id.clone(),
+            )),
+            Err(SecurityError::BlockedContent(details)) => Some((
+                false,
+                details.category.clone(),
+                details.action.clone(),
+                details.report_id.clone(),
+            )),
+            Err(_) => None,
+        };
+        if let Some((is_safe, category, action, report_id)) = verdict {
+            self.history.record(AssessmentRecord {
+                id: 0,
+                created_at: Utc::now(),
+                request_id: ctx.request_id.clone(),
+                user: TokenBudget::user_key(ctx),
+                endpoint: ctx.endpoint.clone(),
+                model: model_name.to_string(),
+                profile_name: ctx.profile_name.clone(),
+                is_prompt,
+                is_safe,
+                category,
+                action,
+                scanner: self.inner.name().to_string(),
+                report_id,
+                content_sha256: format!("{:x}", Sha256::digest(content.as_bytes())),
+                content_length: content.len(),
+            });
+        }
+        result
+    }
+}
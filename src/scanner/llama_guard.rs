@@ -0,0 +1,115 @@
+use async_trait::async_trait;
+use serde_json::Map;
+use tracing::{debug, warn};
+
+use crate::ollama::OllamaClient;
+use crate::scanner::Scanner;
+use crate::security::{Assessment, ScanContext, SecurityError};
+use crate::types::{ChatRequest, ChatResponse, Message, ScanResponse};
+
+// Scanner using a Llama Guard model served by Ollama.
+//
+// The guard model answers `safe`, or `unsafe` followed by the violated hazard
+// categories (e.g. `S1,S10`) on the next line.
+#[derive(Clone)]
+pub struct LlamaGuardScanner {
+    client: OllamaClient,
+    model: String,
+}
+
+impl LlamaGuardScanner {
+    pub fn new(client: OllamaClient, model: &str) -> Self {
+        Self {
+            client,
+            model: model.to_string(),
+        }
+    }
+
+    // Builds the conversation to classify. Responses are classified as the
+    // assistant turn, which Llama Guard evaluates against the response policy.
+    fn build_messages(content: &str, is_prompt: bool) -> Vec<Message> {
+        let message = |role: &str, content: &str| Message {
+            images: None,
+            tool_calls: None,
+            extra: Map::new(),
+            role: role.to_string(),
+            content: content.to_string(),
+        };
+
+        if is_prompt {
+            vec![message("user", content)]
+        } else {
+            vec![message("user", ""), message("assistant", content)]
+        }
+    }
+}
+
+#[async_trait]
+impl Scanner for LlamaGuardScanner {
+    fn name(&self) -> &str {
+        "llama_guard"
+    }
+
+    async fn assess(
+        &self,
+        content: &str,
+        _model_name: &str,
+        is_prompt: bool,
+        _ctx: &ScanContext,
+    ) -> Result<Assessment, SecurityError> {
+        if content.trim().is_empty() {
+            return Ok(Assessment {
+                is_safe: true,
+                category: "benign".to_string(),
+                action: "allow".to_string(),
+                details: ScanResponse::default_safe_response(),
+                masked_content: None,
+                unscanned: false,
+            });
+        }
+
+        let request = ChatRequest {
+            model: self.model.clone(),
+            messages: Self::build_messages(content, is_prompt),
+            stream: Some(false),
+            format: None,
+            options: None,
+            tools: None,
+            extra: Map::new(),
+        };
+
+        let response: ChatResponse = self
+            .client
+            .forward("/api/chat", &request)
+            .await
+            .map_err(|e| SecurityError::AssessmentError(format!("Llama Guard error: {}", e)))?
+            .json()
+            .await?;
+
+        let verdict = response.message.content.trim();
+        debug!("Llama Guard verdict: {}", verdict);
+
+        if verdict.starts_with("safe") {
+            return Ok(Assessment {
+                is_safe: true,
+                category: "benign".to_string(),
+                action: "allow".to_string(),
+                details: ScanResponse::default_safe_response(),
+                masked_content: None,
+                unscanned: false,
+            });
+        }
+
+        let hazards = verdict.lines().nth(1).unwrap_or("").trim();
+        warn!(
+            "Llama Guard flagged content, hazard categories: {}",
+            hazards
+        );
+
+        // Blocks are reported as errors, like the PANW backend does
+        let mut details = ScanResponse::default_safe_response();
+        details.category = "malicious".to_string();
+        details.action = "block".to_string();
+        Err(SecurityError::BlockedContent(Box::new(details)))
+    }
+}
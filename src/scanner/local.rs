@@ -0,0 +1,238 @@
+use async_trait::async_trait;
+use regex::Regex;
+use tracing::warn;
+
+use crate::config::{ConfigError, LocalScannerConfig};
+use crate::scanner::Scanner;
+use crate::security::{Assessment, ScanContext, SecurityError};
+use crate::types::ScanResponse;
+
+// Candidate card numbers: 13 to 19 digits, optionally separated by spaces or dashes.
+const CARD_CANDIDATE_PATTERN: &str = r"\b(?:\d[ -]?){12,18}\d\b";
+
+// Candidate secrets: runs of characters found in keys, tokens and base64 strings.
+const SECRET_CANDIDATE_PATTERN: &str = r"[A-Za-z0-9_\-+/=.]+";
+
+// Zero-latency scanner matching content against local rules.
+//
+// Checks configurable regular expressions, a case-insensitive keyword blocklist,
+// (optionally) Luhn-valid credit card numbers and high-entropy tokens that look
+// like secrets. Intended as the first stage of a scanner chain so obvious
+// violations never reach the PANW API, or as the only backend in offline mode.
+pub struct LocalScanner {
+    patterns: Vec<(String, Regex)>,
+    keywords: Vec<String>,
+    card_candidates: Option<Regex>,
+    secret_candidates: Option<Regex>,
+    secret_min_length: usize,
+    secret_min_entropy: f64,
+}
+
+impl LocalScanner {
+    // Compiles the configured rules.
+    //
+    // # Returns
+    //
+    // * `Ok(LocalScanner)` - The scanner ready to use
+    // * `Err(ConfigError)` - If a configured regular expression is invalid
+    pub fn new(config: &LocalScannerConfig) -> Result<Self, ConfigError> {
+        let patterns = config
+            .regex
+            .iter()
+            .map(|rule| {
+                Regex::new(&rule.pattern)
+                    .map(|regex| (rule.name.clone(), regex))
+                    .map_err(|e| {
+                        ConfigError::ValidationError(format!(
+                            "Invalid regex for local scanner rule {}: {}",
+                            rule.name, e
+                        ))
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let card_candidates = if config.detect_credit_cards {
+            Some(Regex::new(CARD_CANDIDATE_PATTERN).expect("valid card pattern"))
+        } else {
+            None
+        };
+
+        let secret_candidates = if config.detect_secrets {
+            Some(Regex::new(SECRET_CANDIDATE_PATTERN).expect("valid secret pattern"))
+        } else {
+            None
+        };
+
+        Ok(Self {
+            patterns,
+            keywords: config.keywords.iter().map(|k| k.to_lowercase()).collect(),
+            card_candidates,
+            secret_candidates,
+            secret_min_length: config.secret_min_length,
+            secret_min_entropy: config.secret_min_entropy,
+        })
+    }
+
+    // Returns the name of the first rule matching the content, if any.
+    fn find_violation(&self, content: &str) -> Option<String> {
+        let lowercase = content.to_lowercase();
+        if let Some(keyword) = self
+            .keywords
+            .iter()
+            .find(|k| lowercase.contains(k.as_str()))
+        {
+            return Some(format!("keyword:{}", keyword));
+        }
+
+        if let Some((name, _)) = self.patterns.iter().find(|(_, re)| re.is_match(content)) {
+            return Some(format!("regex:{}", name));
+        }
+
+        if let Some(cards) = &self.card_candidates {
+            if cards.find_iter(content).any(|m| is_luhn_valid(m.as_str())) {
+                return Some("credit_card".to_string());
+            }
+        }
+
+        if let Some(secrets) = &self.secret_candidates {
+            if secrets
+                .find_iter(content)
+                .any(|m| self.is_secret(m.as_str()))
+            {
+                return Some("secret".to_string());
+            }
+        }
+
+        None
+    }
+
+    // A candidate is a secret if it is long, mixes letters and digits, and is random enough.
+    fn is_secret(&self, candidate: &str) -> bool {
+        candidate.len() >= self.secret_min_length
+            && candidate.chars().any(|c| c.is_ascii_alphabetic())
+            && candidate.chars().any(|c| c.is_ascii_digit())
+            && shannon_entropy(candidate) >= self.secret_min_entropy
+    }
+}
+
+// Shannon entropy of a string in bits per character.
+fn shannon_entropy(text: &str) -> f64 {
+    let mut counts = [0usize; 256];
+    for byte in text.bytes() {
+        counts[byte as usize] += 1;
+    }
+    let len = text.len() as f64;
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+// Validates a card number candidate with the Luhn checksum, ignoring separators.
+fn is_luhn_valid(candidate: &str) -> bool {
+    let digits: Vec<u32> = candidate.chars().filter_map(|c| c.to_digit(10)).collect();
+    if !(13..=19).contains(&digits.len()) {
+        return false;
+    }
+
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &d)| {
+            if i % 2 == 1 {
+                let doubled = d * 2;
+                if doubled > 9 {
+                    doubled - 9
+                } else {
+                    doubled
+                }
+            } else {
+                d
+            }
+        })
+        .sum();
+
+    sum.is_multiple_of(10)
+}
+
+#[async_trait]
+impl Scanner for LocalScanner {
+    fn name(&self) -> &str {
+        "local"
+    }
+
+    async fn assess(
+        &self,
+        content: &str,
+        _model_name: &str,
+        is_prompt: bool,
+        _ctx: &ScanContext,
+    ) -> Result<Assessment, SecurityError> {
+        let mut details = ScanResponse::default_safe_response();
+
+        let Some(rule) = self.find_violation(content) else {
+            return Ok(Assessment {
+                is_safe: true,
+                category: "benign".to_string(),
+                action: "allow".to_string(),
+                details,
+                masked_content: None,
+                unscanned: false,
+            });
+        };
+
+        warn!("Local scanner blocked content, matched rule: {}", rule);
+        if rule == "credit_card" || rule == "secret" {
+            if is_prompt {
+                details.prompt_detected.dlp = true;
+            } else {
+                details.response_detected.dlp = true;
+            }
+        }
+        details.category = "malicious".to_string();
+        details.action = "block".to_string();
+
+        // Blocks are reported as errors, like the PANW and Llama Guard backends
+        Err(SecurityError::BlockedContent(Box::new(details)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn luhn_accepts_valid_card_numbers() {
+        assert!(is_luhn_valid("4111 1111 1111 1111"));
+        assert!(is_luhn_valid("5500-0000-0000-0004"));
+        assert!(is_luhn_valid("378282246310005"));
+    }
+
+    #[test]
+    fn luhn_rejects_invalid_checksums_and_lengths() {
+        assert!(!is_luhn_valid("4111 1111 1111 1112"));
+        assert!(!is_luhn_valid("4111 1111 1111"));
+        assert!(!is_luhn_valid("41111111111111111111"));
+    }
+
+    #[tokio::test]
+    async fn blocks_are_reported_as_errors() {
+        let scanner = LocalScanner::new(&LocalScannerConfig::default()).unwrap();
+        let ctx = ScanContext::default();
+
+        let allowed = scanner.assess("hello", "model", true, &ctx).await.unwrap();
+        assert!(allowed.is_safe);
+        let blocked = scanner
+            .assess("card 4111 1111 1111 1111", "model", true, &ctx)
+            .await;
+        assert!(matches!(
+            blocked,
+            Err(SecurityError::BlockedContent(details)) if details.prompt_detected.dlp
+        ));
+    }
+}
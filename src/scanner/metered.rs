@@ -0,0 +1,57 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+
+use crate::metrics::Metrics;
+use crate::scanner::Scanner;
+use crate::security::{Assessment, ScanContext, SecurityError};
+
+// Decorator counting the verdicts of the wrapped scanner.
+//
+// Blocks are counted per category, including content rejected through
+// `SecurityError::BlockedContent`. Verdicts are also added to the request's
+// access log record.
+pub struct MeteredScanner {
+    inner: Arc<dyn Scanner>,
+    metrics: Metrics,
+}
+
+impl MeteredScanner {
+    pub fn new(inner: Arc<dyn Scanner>, metrics: Metrics) -> Self {
+        Self { inner, metrics }
+    }
+}
+
+#[async_trait]
+impl Scanner for MeteredScanner {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    async fn assess(
+        &self,
+        content: &str,
+        model_name: &str,
+        is_prompt: bool,
+        ctx: &ScanContext,
+    ) -> Result<Assessment, SecurityError> {
+        let result = self.inner.assess(content, model_name, is_prompt, ctx).await;
+        if let Some(access) = &ctx.access {
+            match &result {
+                Ok(assessment) => access.add_verdict(&assessment.category, &assessment.action),
+                Err(SecurityError::BlockedContent(details)) => {
+                    access.add_verdict(&details.category, &details.action)
+                }
+                Err(_) => {}
+            }
+        }
+        match &result {
+            Ok(assessment) if assessment.is_safe => self.metrics.record_scan(None),
+            Ok(assessment) => self.metrics.record_scan(Some(&assessment.category)),
+            Err(SecurityError::BlockedContent(details)) => {
+                self.metrics.record_scan(Some(&details.category))
+            }
+            Err(_) => {}
+        }
+        result
+    }
+}
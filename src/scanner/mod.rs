@@ -0,0 +1,168 @@
+// Decorator counting blocked requests towards temporary bans.
+pub mod ban;
+
+// Decorator answering repeated content from a verdict cache.
+pub mod cache;
+
+// Ordered pipeline of several scanners.
+pub mod chain;
+
+// Decorator publishing verdicts as gateway events.
+pub mod events;
+
+// Decorator recording verdicts in the assessment history.
+pub mod history;
+
+// Llama Guard scanner backed by a guard model served by Ollama.
+pub mod llama_guard;
+
+// Runtime enforcement mode and per-endpoint scanning switches.
+pub mod enforcement;
+
+// Built-in regex, keyword and credit card pre-filter.
+pub mod local;
+
+// Decorator recording scan verdicts in the metrics registry.
+pub mod metered;
+
+// Decorator persisting blocked content to the quarantine store.
+pub mod quarantine;
+
+// Decorator letting a policy engine override verdicts.
+pub mod policy;
+
+// PANW AI Runtime API scanner, the default backend.
+pub mod panw;
+
+use crate::config::{ConfigError, OllamaConfig, ScannerBackend, ScannerConfig};
+use crate::http;
+use crate::ollama::OllamaClient;
+use crate::scanner::chain::{ChainScanner, ChainStage};
+use crate::security::{Assessment, ScanContext, SecurityClient, SecurityError};
+use async_trait::async_trait;
+use std::sync::Arc;
+use std::time::Duration;
+
+// A content security backend able to assess prompts and responses.
+//
+// Handlers and the streaming pipeline only talk to this trait, so alternative
+// backends can be selected in config without touching them.
+//
+// Every backend reports its verdict the same way:
+//
+// * `Ok(Assessment)` with `is_safe: true` - The content is allowed
+// * `Ok(Assessment)` with `is_safe: false` - The content is flagged without a block
+//   action, e.g. a PANW threat category with action `allow`; handlers reject it too
+// * `Err(SecurityError::BlockedContent)` - The backend's policy blocks the content
+// * Any other `Err(SecurityError)` - The content could not be assessed
+#[async_trait]
+pub trait Scanner: Send + Sync {
+    // Short identifier of the backend, used in logs.
+    fn name(&self) -> &str;
+
+    // Assesses a piece of content.
+    //
+    // # Arguments
+    //
+    // * `content` - The text content to assess
+    // * `model_name` - Name of the AI model associated with this content
+    // * `is_prompt` - If `true`, content is a prompt to an AI; if `false`, an AI response
+    // * `ctx` - Per-request attribution
+    //
+    // # Returns
+    //
+    // * `Ok(Assessment)` - The content is allowed, or flagged without a block action
+    // * `Err(SecurityError)` - `BlockedContent` if the content is blocked, otherwise the failure
+    async fn assess(
+        &self,
+        content: &str,
+        model_name: &str,
+        is_prompt: bool,
+        ctx: &ScanContext,
+    ) -> Result<Assessment, SecurityError>;
+}
+
+// Builds the scanner selected in config.
+//
+// # Arguments
+//
+// * `config` - Scanner section of the configuration
+// * `ollama_config` - Timeouts, TLS and pool settings for a separate Llama Guard server
+// * `security_client` - PANW client used by the `panw` backend
+// * `ollama_client` - Ollama client used by the `llama_guard` backend unless it sets its own URL
+//
+// # Returns
+//
+// * `Ok(Arc<dyn Scanner>)` - The configured scanner
+// * `Err(ConfigError)` - If the selected backend is missing its settings
+pub fn build_scanner(
+    config: &ScannerConfig,
+    ollama_config: &OllamaConfig,
+    security_client: &SecurityClient,
+    ollama_client: &OllamaClient,
+) -> Result<Arc<dyn Scanner>, ConfigError> {
+    match config.backend {
+        ScannerBackend::Chain => {
+            let stages = config
+                .chain
+                .iter()
+                .map(|stage| {
+                    Ok(ChainStage {
+                        scanner: build_backend(
+                            stage.backend,
+                            config,
+                            ollama_config,
+                            security_client,
+                            ollama_client,
+                        )?,
+                        on_block: stage.on_block,
+                        on_error: stage.on_error,
+                    })
+                })
+                .collect::<Result<Vec<_>, ConfigError>>()?;
+            Ok(Arc::new(ChainScanner::new(stages)))
+        }
+        backend => build_backend(
+            backend,
+            config,
+            ollama_config,
+            security_client,
+            ollama_client,
+        ),
+    }
+}
+
+// Builds a single, non-chain scanner backend.
+fn build_backend(
+    backend: ScannerBackend,
+    config: &ScannerConfig,
+    ollama_config: &OllamaConfig,
+    security_client: &SecurityClient,
+    ollama_client: &OllamaClient,
+) -> Result<Arc<dyn Scanner>, ConfigError> {
+    match backend {
+        ScannerBackend::Panw => Ok(Arc::new(security_client.clone())),
+        ScannerBackend::LlamaGuard => {
+            let guard_config = config.llama_guard.clone().unwrap_or_default();
+            let client = match &guard_config.base_url {
+                Some(base_url) => OllamaClient::new(base_url)
+                    .with_http_client(http::build_client(
+                        Duration::from_secs(ollama_config.connect_timeout_secs),
+                        None,
+                        &ollama_config.tls,
+                        &ollama_config.connection,
+                    )?)
+                    .with_timeout(Duration::from_secs(ollama_config.timeout_secs)),
+                None => ollama_client.clone(),
+            };
+            Ok(Arc::new(llama_guard::LlamaGuardScanner::new(
+                client,
+                &guard_config.model,
+            )))
+        }
+        ScannerBackend::Local => Ok(Arc::new(local::LocalScanner::new(&config.local)?)),
+        ScannerBackend::Chain => Err(ConfigError::ValidationError(
+            "Nested scanner chains are not supported".into(),
+        )),
+    }
+}
@@ -0,0 +1,22 @@
+use async_trait::async_trait;
+
+use crate::scanner::Scanner;
+use crate::security::{Assessment, ScanContext, SecurityClient, SecurityError};
+
+#[async_trait]
+impl Scanner for SecurityClient {
+    fn name(&self) -> &str {
+        "panw"
+    }
+
+    async fn assess(
+        &self,
+        content: &str,
+        model_name: &str,
+        is_prompt: bool,
+        ctx: &ScanContext,
+    ) -> Result<Assessment, SecurityError> {
+        self.assess_content(content, model_name, is_prompt, ctx)
+            .await
+    }
+}
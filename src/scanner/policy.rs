@@ -0,0 +1,136 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use crate::policy_engine::{AssessmentInput, PolicyDecision, PolicyEngine, PolicyInput};
+use crate::scanner::Scanner;
+use crate::security::{Assessment, ScanContext, SecurityError};
+
+// Decorator letting a policy engine override the verdict of the wrapped scanner.
+//
+// The engine receives the verdict with the caller, model and endpoint, and may
+// allow, block or mask the content. Every override is written to the `audit`
+// log target. If the engine makes no decision or fails, the scanner verdict
+// stands.
+pub struct PolicyScanner {
+    inner: Arc<dyn Scanner>,
+    engine: Arc<dyn PolicyEngine>,
+    mask_text: String,
+    flagged_only: bool,
+}
+
+impl PolicyScanner {
+    // Wraps a scanner with a policy engine.
+    //
+    // # Arguments
+    //
+    // * `inner` - The scanner whose verdicts are evaluated
+    // * `engine` - The policy engine deciding on them
+    // * `mask_text` - Replacement used when the engine masks content without providing one
+    // * `flagged_only` - If `true`, only flagged content is evaluated
+    pub fn new(
+        inner: Arc<dyn Scanner>,
+        engine: Arc<dyn PolicyEngine>,
+        mask_text: &str,
+        flagged_only: bool,
+    ) -> Self {
+        Self {
+            inner,
+            engine,
+            mask_text: mask_text.to_string(),
+            flagged_only,
+        }
+    }
+}
+
+#[async_trait]
+impl Scanner for PolicyScanner {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    async fn assess(
+        &self,
+        content: &str,
+        model_name: &str,
+        is_prompt: bool,
+        ctx: &ScanContext,
+    ) -> Result<Assessment, SecurityError> {
+        let result = self.inner.assess(content, model_name, is_prompt, ctx).await;
+        let unscanned = matches!(&result, Ok(assessment) if assessment.unscanned);
+        let (is_safe, category, action, details) = match &result {
+            Ok(assessment) => (
+                assessment.is_safe,
+                &assessment.category,
+                &assessment.action,
+                &assessment.details,
+            ),
+            Err(SecurityError::BlockedContent(details)) => {
+                (false, &details.category, &details.action, details.as_ref())
+            }
+            Err(_) => return result,
+        };
+        if is_safe && self.flagged_only {
+            return result;
+        }
+
+        let input = PolicyInput::new(
+            ctx,
+            model_name,
+            is_prompt,
+            AssessmentInput {
+                is_safe,
+                category: category.clone(),
+                action: action.clone(),
+                scanner: self.inner.name().to_string(),
+                profile_name: ctx.profile_name.clone().unwrap_or_default(),
+                prompt_detected: details.prompt_detected.clone(),
+                response_detected: details.response_detected.clone(),
+            },
+        );
+        let decision = match self.engine.decide(&input).await {
+            Ok(Some(decision)) => decision,
+            Ok(None) => return result,
+            Err(e) => {
+                warn!(
+                    "Policy engine {} failed, keeping the scanner verdict: {}",
+                    self.engine.name(),
+                    e
+                );
+                return result;
+            }
+        };
+
+        let (is_safe, decided_action, masked_content) = match decision {
+            PolicyDecision::Allow if is_safe => return result,
+            PolicyDecision::Block if !is_safe => return result,
+            PolicyDecision::Allow => (true, "allow", None),
+            PolicyDecision::Block => (false, "block", None),
+            PolicyDecision::Mask(replacement) => (
+                true,
+                "mask",
+                Some(replacement.unwrap_or_else(|| self.mask_text.clone())),
+            ),
+        };
+        info!(
+            target: "audit",
+            request_id = ctx.request_id.as_deref().unwrap_or_default(),
+            user = %input.user,
+            model = model_name,
+            is_prompt,
+            category = %category,
+            scanner_action = %action,
+            decision = decided_action,
+            engine = self.engine.name(),
+            "Policy engine overrode the scanner verdict"
+        );
+        Ok(Assessment {
+            is_safe,
+            category: category.clone(),
+            action: decided_action.to_string(),
+            details: details.clone(),
+            masked_content,
+            unscanned,
+        })
+    }
+}
@@ -0,0 +1,71 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::quarantine::{QuarantineRecord, QuarantineStore};
+use crate::scanner::Scanner;
+use crate::security::{Assessment, ScanContext, SecurityError};
+use crate::types::ScanResponse;
+
+// Decorator persisting content flagged by the wrapped scanner to the quarantine.
+//
+// Records are written in the background so blocking a request is not delayed
+// by the store.
+pub struct QuarantineScanner {
+    inner: Arc<dyn Scanner>,
+    store: QuarantineStore,
+}
+
+impl QuarantineScanner {
+    pub fn new(inner: Arc<dyn Scanner>, store: QuarantineStore) -> Self {
+        Self { inner, store }
+    }
+}
+
+#[async_trait]
+impl Scanner for QuarantineScanner {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    async fn assess(
+        &self,
+        content: &str,
+        model_name: &str,
+        is_prompt: bool,
+        ctx: &ScanContext,
+    ) -> Result<Assessment, SecurityError> {
+        let result = self.inner.assess(content, model_name, is_prompt, ctx).await;
+
+        let flagged = match &result {
+            Ok(assessment) if !assessment.is_safe => Some((
+                assessment.category.clone(),
+                assessment.action.clone(),
+                assessment.details.clone(),
+            )),
+            Err(SecurityError::BlockedContent(details)) => Some((
+                details.category.clone(),
+                details.action.clone(),
+                ScanResponse::clone(details),
+            )),
+            _ => None,
+        };
+        if let Some((category, action, findings)) = flagged {
+            self.store.spawn_store(QuarantineRecord {
+                id: Uuid::new_v4(),
+                created_at: Utc::now(),
+                request_id: ctx.request_id.clone(),
+                app_user: ctx.app_user.clone(),
+                endpoint: ctx.endpoint.clone(),
+                model: model_name.to_string(),
+                is_prompt,
+                category,
+                action,
+                content: content.to_string(),
+                findings,
+            });
+        }
+        result
+    }
+}
@@ -0,0 +1,192 @@
+use crate::config::{ConfigError, SecretSource, SecurityConfig};
+use crate::security::SecurityClient;
+use std::fs;
+use std::time::{Duration, SystemTime};
+use tracing::{error, info};
+
+// How often the API key file is checked for changes.
+const API_KEY_FILE_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+// Resolves the PANW API token from the configured source.
+//
+// Sources are tried in order of precedence: an external secret manager
+// (`secret_source`), then `api_key_file`, then the inline `api_key` value.
+//
+// # Arguments
+//
+// * `security` - The security section of the configuration
+//
+// # Returns
+//
+// * `Ok(String)` - The API token
+// * `Err(ConfigError)` - If the secret cannot be read or the source is unavailable
+pub async fn resolve_api_key(security: &SecurityConfig) -> Result<String, ConfigError> {
+    if let Some(source) = &security.secret_source {
+        return fetch_from_source(source, security).await;
+    }
+
+    if let Some(path) = &security.api_key_file {
+        return read_api_key_file(path);
+    }
+
+    Ok(security.api_key.clone())
+}
+
+// Reads the API token from a file, ignoring surrounding whitespace.
+fn read_api_key_file(path: &str) -> Result<String, ConfigError> {
+    let api_key = fs::read_to_string(path)?.trim().to_string();
+    if api_key.is_empty() {
+        return Err(ConfigError::ValidationError(format!(
+            "API key file {} is empty",
+            path
+        )));
+    }
+    Ok(api_key)
+}
+
+// Re-reads the API key file whenever it changes and updates the client.
+//
+// This lets mounted secrets (e.g. Kubernetes secrets) be rotated without a restart.
+pub fn spawn_api_key_file_watcher(path: String, security_client: SecurityClient) {
+    tokio::spawn(async move {
+        let modified = |path: &str| fs::metadata(path).and_then(|m| m.modified()).ok();
+        let mut loaded_at: Option<SystemTime> = modified(&path);
+        let mut interval = tokio::time::interval(API_KEY_FILE_POLL_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            let current = modified(&path);
+            if current.is_none() || current == loaded_at {
+                continue;
+            }
+
+            match read_api_key_file(&path) {
+                Ok(api_key) => {
+                    security_client.update_api_key(&api_key);
+                    info!("Reloaded PANW API key from {}", path);
+                    loaded_at = current;
+                }
+                Err(e) => error!("Failed to reload PANW API key from {}: {}", path, e),
+            }
+        }
+    });
+}
+
+async fn fetch_from_source(
+    source: &SecretSource,
+    security: &SecurityConfig,
+) -> Result<String, ConfigError> {
+    match source {
+        SecretSource::Vault { .. } => fetch_from_vault(source, security).await,
+        SecretSource::AwsSecretsManager { .. } => fetch_from_aws(source).await,
+    }
+}
+
+// Reads the API token from a HashiCorp Vault KV v2 secret.
+//
+// The request is bounded by the `security` connect and request timeouts, so an
+// unreachable Vault fails startup instead of blocking it.
+#[cfg(feature = "vault")]
+async fn fetch_from_vault(
+    source: &SecretSource,
+    security: &SecurityConfig,
+) -> Result<String, ConfigError> {
+    let SecretSource::Vault {
+        address,
+        token,
+        mount,
+        path,
+        field,
+        tls,
+        connection,
+    } = source
+    else {
+        unreachable!("fetch_from_vault called with a non-Vault source");
+    };
+
+    let url = format!(
+        "{}/v1/{}/data/{}",
+        address.trim_end_matches('/'),
+        mount,
+        path
+    );
+    let client = crate::http::build_client(
+        Duration::from_secs(security.connect_timeout_secs),
+        Some(Duration::from_secs(security.timeout_secs)),
+        tls,
+        connection,
+    )?;
+    let body: serde_json::Value = client
+        .get(&url)
+        .header("X-Vault-Token", token)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| ConfigError::SecretError(format!("Vault request failed: {}", e)))?
+        .json()
+        .await
+        .map_err(|e| ConfigError::SecretError(format!("Invalid Vault response: {}", e)))?;
+
+    body["data"]["data"][field.as_str()]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| {
+            ConfigError::SecretError(format!(
+                "Field {} not found in Vault secret {}",
+                field, path
+            ))
+        })
+}
+
+#[cfg(not(feature = "vault"))]
+async fn fetch_from_vault(
+    _source: &SecretSource,
+    _security: &SecurityConfig,
+) -> Result<String, ConfigError> {
+    Err(ConfigError::SecretError(
+        "Vault support requires building with the `vault` feature".into(),
+    ))
+}
+
+// Reads the API token from AWS Secrets Manager using the default credential chain.
+#[cfg(feature = "aws-secrets")]
+async fn fetch_from_aws(source: &SecretSource) -> Result<String, ConfigError> {
+    let SecretSource::AwsSecretsManager { secret_id, field } = source else {
+        unreachable!("fetch_from_aws called with a non-AWS source");
+    };
+
+    let aws_config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
+    let client = aws_sdk_secretsmanager::Client::new(&aws_config);
+    let output = client
+        .get_secret_value()
+        .secret_id(secret_id)
+        .send()
+        .await
+        .map_err(|e| ConfigError::SecretError(format!("AWS Secrets Manager error: {}", e)))?;
+
+    let secret = output.secret_string().ok_or_else(|| {
+        ConfigError::SecretError(format!("Secret {} has no string value", secret_id))
+    })?;
+
+    // JSON secrets hold several key/value pairs; pick the configured field
+    match field {
+        Some(field) => serde_json::from_str::<serde_json::Value>(secret)
+            .ok()
+            .and_then(|json| json[field.as_str()].as_str().map(str::to_string))
+            .ok_or_else(|| {
+                ConfigError::SecretError(format!(
+                    "Field {} not found in secret {}",
+                    field, secret_id
+                ))
+            }),
+        None => Ok(secret.to_string()),
+    }
+}
+
+#[cfg(not(feature = "aws-secrets"))]
+async fn fetch_from_aws(_source: &SecretSource) -> Result<String, ConfigError> {
+    Err(ConfigError::SecretError(
+        "AWS Secrets Manager support requires building with the `aws-secrets` feature".into(),
+    ))
+}
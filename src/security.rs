@@ -1,9 +1,23 @@
+use crate::access_log::AccessRecord;
+use crate::auth::Tenant;
+use crate::circuit::{BreakerPermit, CircuitBreaker, CircuitState};
+use crate::config::{CircuitBreakerConfig, ContentLimitConfig, OversizeStrategy, RetryConfig};
+use crate::metrics::Metrics;
+use crate::privacy;
 use crate::types::{AiProfile, Content, Metadata, ScanRequest, ScanResponse};
+use arc_swap::ArcSwap;
+use rand::Rng;
 use reqwest::Client;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 use thiserror::Error;
+use tokio::sync::{Semaphore, SemaphorePermit};
 use tracing::{debug, error, warn};
 use uuid::Uuid;
 
+// Marks the text left out between the head and the tail of oversized content.
+const HEAD_TAIL_SEPARATOR: &str = "\n...\n";
+
 // Represents errors that can occur during security assessments with the PANW AI Runtime API.
 //
 // This enum covers various failure modes when assessing content security using Palo Alto Networks'
@@ -11,7 +25,10 @@ use uuid::Uuid;
 #[derive(Debug, Error)]
 pub enum SecurityError {
     #[error("HTTP request failed: {0}")]
-    RequestError(#[from] reqwest::Error),
+    RequestError(reqwest::Error),
+
+    #[error("PANW security assessment request timed out")]
+    Timeout,
 
     #[error("PANW security assessment error: {0}")]
     AssessmentError(String),
@@ -20,7 +37,23 @@ pub enum SecurityError {
     JsonError(#[from] serde_json::Error),
 
     #[error("Content blocked by PANW AI security policy")]
-    BlockedContent,
+    BlockedContent(Box<ScanResponse>),
+
+    #[error("Timed out waiting for a free PANW scan slot")]
+    QueueTimeout,
+
+    #[error("PANW AI Runtime API unavailable (circuit open)")]
+    CircuitOpen(Duration),
+}
+
+impl From<reqwest::Error> for SecurityError {
+    fn from(err: reqwest::Error) -> Self {
+        if err.is_timeout() {
+            SecurityError::Timeout
+        } else {
+            SecurityError::RequestError(err)
+        }
+    }
 }
 
 // Represents the result of a security assessment from PANW AI Runtime API.
@@ -34,12 +67,42 @@ pub enum SecurityError {
 // * `category` - Security category assigned to the content (e.g., "benign", "malicious")
 // * `action` - Recommended action to take ("allow", "block", etc.)
 // * `details` - Complete findings from the PANW AI security scan
+// * `masked_content` - Content to forward in place of the assessed one, set when a
+//   policy engine masks it
+// * `unscanned` - Set when the content was allowed without a verdict, e.g. fail-open
+//   while the PANW circuit is open; such assessments are never cached or stored
 #[derive(Debug, Clone)]
 pub struct Assessment {
     pub is_safe: bool,
     pub category: String,
     pub action: String,
     pub details: ScanResponse,
+    pub masked_content: Option<String>,
+    pub unscanned: bool,
+}
+
+// Per-request attribution passed along with each PANW assessment.
+//
+// Values set here take precedence over the global defaults from `SecurityConfig`.
+//
+// # Fields
+//
+// * `app_name` - Application name of the caller's tenant
+// * `app_user` - Identifier of the caller, typically the validated JWT subject
+// * `request_id` - Correlation ID of the client request, used as the PANW `tr_id`
+// * `endpoint` - Path of the gateway endpoint being served, e.g. `/api/chat`
+// * `profile_name` - AI security profile selected by the caller, overriding the active one
+// * `tenant` - The API key tenant of the caller, when API keys are configured
+// * `access` - Collects details for the access log, when it is enabled
+#[derive(Debug, Clone, Default)]
+pub struct ScanContext {
+    pub app_name: Option<String>,
+    pub app_user: Option<String>,
+    pub request_id: Option<String>,
+    pub endpoint: Option<String>,
+    pub profile_name: Option<String>,
+    pub tenant: Option<Arc<Tenant>>,
+    pub access: Option<AccessRecord>,
 }
 
 // Client for performing security assessments using the PANW AI Runtime API.
@@ -63,10 +126,18 @@ pub struct Assessment {
 pub struct SecurityClient {
     client: Client,
     base_url: String,
-    api_key: String,
-    profile_name: String,
+    scan_path: String,
+    api_key: Arc<RwLock<String>>,
+    profile_name: Arc<ArcSwap<String>>,
     app_name: String,
     app_user: String,
+    scan_permits: Arc<Semaphore>,
+    queue_timeout: Duration,
+    retry: RetryConfig,
+    breaker: CircuitBreaker,
+    fail_open: bool,
+    content_limit: ContentLimitConfig,
+    metrics: Option<Metrics>,
 }
 
 impl Content {
@@ -126,14 +197,168 @@ impl SecurityClient {
     ) -> Self {
         Self {
             client: Client::new(),
-            base_url: base_url.to_string(),
-            api_key: api_key.to_string(),
-            profile_name: profile_name.to_string(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            scan_path: "/v1/scan/sync/request".to_string(),
+            api_key: Arc::new(RwLock::new(api_key.to_string())),
+            profile_name: Arc::new(ArcSwap::from_pointee(profile_name.to_string())),
             app_name: app_name.to_string(),
             app_user: app_user.to_string(),
+            scan_permits: Arc::new(Semaphore::new(Semaphore::MAX_PERMITS)),
+            queue_timeout: Duration::MAX,
+            retry: RetryConfig::default(),
+            breaker: CircuitBreaker::new(5, Duration::from_secs(30)),
+            fail_open: false,
+            content_limit: ContentLimitConfig::default(),
+            metrics: None,
         }
     }
 
+    // Replaces the default HTTP client used to reach the PANW AI Runtime API.
+    //
+    // # Arguments
+    //
+    // * `client` - A client configured with the desired timeouts and TLS settings
+    //
+    // # Returns
+    //
+    // The client instance for method chaining
+    pub fn with_http_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    // Overrides the path of the scan endpoint, e.g. for another API version or a gateway prefix.
+    //
+    // # Arguments
+    //
+    // * `scan_path` - Path appended to the base URL, starting with `/`
+    //
+    // # Returns
+    //
+    // The client instance for method chaining
+    pub fn with_scan_path(mut self, scan_path: &str) -> Self {
+        self.scan_path = scan_path.to_string();
+        self
+    }
+
+    // Bounds the number of scan requests in flight against the PANW AI Runtime API.
+    //
+    // Callers beyond the limit queue until a slot frees up, so a burst of traffic
+    // doesn't trip PANW rate limits. The limit is shared by all clones of this client.
+    //
+    // # Arguments
+    //
+    // * `max_concurrent` - Maximum number of simultaneous scan requests
+    // * `queue_timeout` - How long a caller may wait for a free slot before failing
+    //
+    // # Returns
+    //
+    // The client instance for method chaining
+    pub fn with_concurrency_limit(
+        mut self,
+        max_concurrent: usize,
+        queue_timeout: Duration,
+    ) -> Self {
+        self.scan_permits = Arc::new(Semaphore::new(max_concurrent));
+        self.queue_timeout = queue_timeout;
+        self
+    }
+
+    // Sets the policy used to retry transient PANW failures.
+    //
+    // # Arguments
+    //
+    // * `retry` - Retry attempts, backoff timing and retryable status codes
+    //
+    // # Returns
+    //
+    // The client instance for method chaining
+    pub fn with_retry_policy(mut self, retry: RetryConfig) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    // Configures the circuit breaker guarding the PANW AI Runtime API.
+    //
+    // # Arguments
+    //
+    // * `config` - Failure threshold and cooldown period of the breaker
+    // * `fail_open` - If `true`, content is allowed through while the circuit is open;
+    //   otherwise assessments fail with `SecurityError::CircuitOpen`
+    //
+    // # Returns
+    //
+    // The client instance for method chaining
+    pub fn with_circuit_breaker(mut self, config: &CircuitBreakerConfig, fail_open: bool) -> Self {
+        self.breaker = CircuitBreaker::new(
+            config.failure_threshold,
+            Duration::from_secs(config.cooldown_secs),
+        );
+        self.fail_open = fail_open;
+        self
+    }
+
+    // Sets how content above the PANW size limit is scanned.
+    //
+    // # Arguments
+    //
+    // * `content_limit` - Maximum size of one content entry and the oversize strategy
+    //
+    // # Returns
+    //
+    // The client instance for method chaining
+    pub fn with_content_limit(mut self, content_limit: ContentLimitConfig) -> Self {
+        self.content_limit = content_limit;
+        self
+    }
+
+    // Records the latency and outcome of every PANW call.
+    //
+    // # Arguments
+    //
+    // * `metrics` - The registry exposed by `/admin/stats`
+    //
+    // # Returns
+    //
+    // The client instance for method chaining
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    // Replaces the PANW API token used by this client and all of its clones.
+    //
+    // # Arguments
+    //
+    // * `api_key` - The new Palo Alto Networks API token
+    pub fn update_api_key(&self, api_key: &str) {
+        *self.api_key.write().unwrap() = api_key.to_string();
+    }
+
+    // Returns the AI security profile currently used for assessments.
+    pub fn profile_name(&self) -> String {
+        self.profile_name.load().to_string()
+    }
+
+    // Switches the AI security profile for this client and all of its clones.
+    //
+    // # Arguments
+    //
+    // * `profile_name` - Name of the PANW AI security profile to use from now on
+    pub fn set_profile_name(&self, profile_name: &str) {
+        self.profile_name.store(Arc::new(profile_name.to_string()));
+    }
+
+    // Returns the current state of the PANW circuit breaker.
+    pub fn circuit_state(&self) -> CircuitState {
+        self.breaker.state()
+    }
+
+    // Returns whether content is allowed through when PANW is unavailable.
+    pub fn fail_open(&self) -> bool {
+        self.fail_open
+    }
+
     // Creates a default safe assessment for empty content.
     //
     // When empty content is provided for assessment, this function returns
@@ -148,10 +373,15 @@ impl SecurityClient {
             category: "benign".to_string(),
             action: "allow".to_string(),
             details: ScanResponse::default_safe_response(),
+            masked_content: None,
+            unscanned: false,
         }
     }
 
-    // Prepares a Content object for PANW assessment based on the provided text.
+    // Prepares the Content objects for PANW assessment based on the provided text.
+    //
+    // Content above the configured size limit is chunked, or reduced to its head
+    // and tail or its beginning, depending on the oversize strategy.
     //
     // # Arguments
     //
@@ -160,15 +390,55 @@ impl SecurityClient {
     //
     // # Returns
     //
-    // * `Ok(Content)` - A properly configured Content object
+    // * `Ok(Vec<Content>)` - One or more properly configured Content objects
     // * `Err(SecurityError)` - If content object creation fails
-    fn prepare_content(&self, content: &str, is_prompt: bool) -> Result<Content, SecurityError> {
-        if is_prompt {
-            Content::new(Some(content.to_string()), None)
+    fn prepare_contents(
+        &self,
+        content: &str,
+        is_prompt: bool,
+    ) -> Result<Vec<Content>, SecurityError> {
+        let max_bytes = self.content_limit.max_bytes;
+        let pieces = if content.len() <= max_bytes {
+            vec![content.to_string()]
         } else {
-            Content::new(None, Some(content.to_string()))
-        }
-        .map_err(|e| SecurityError::AssessmentError(e.to_string()))
+            match self.content_limit.strategy {
+                OversizeStrategy::Chunk => {
+                    debug!(
+                        "Splitting {} bytes of content into chunks of {} bytes",
+                        content.len(),
+                        max_bytes
+                    );
+                    split_chunks(content, max_bytes)
+                }
+                OversizeStrategy::HeadTail => {
+                    warn!(
+                        "Content of {} bytes exceeds the PANW limit, scanning head and tail only",
+                        content.len()
+                    );
+                    vec![head_and_tail(content, max_bytes)]
+                }
+                OversizeStrategy::Truncate => {
+                    warn!(
+                        "Content of {} bytes exceeds the PANW limit, scanning the first {} bytes only",
+                        content.len(),
+                        max_bytes
+                    );
+                    vec![content[..floor_char_boundary(content, max_bytes)].to_string()]
+                }
+            }
+        };
+
+        pieces
+            .into_iter()
+            .map(|piece| {
+                if is_prompt {
+                    Content::new(Some(piece), None)
+                } else {
+                    Content::new(None, Some(piece))
+                }
+                .map_err(|e| SecurityError::AssessmentError(e.to_string()))
+            })
+            .collect()
     }
 
     // Processes scan results from the PANW AI Runtime API into an Assessment.
@@ -187,6 +457,8 @@ impl SecurityClient {
             category: scan_result.category.clone(),
             action: scan_result.action.clone(),
             details: scan_result,
+            masked_content: None,
+            unscanned: false,
         };
 
         if assessment.action == "block" {
@@ -194,7 +466,7 @@ impl SecurityClient {
                 "PANW Security threat detected! Category: {}, Findings: {:#?}",
                 assessment.category, assessment.details.prompt_detected
             );
-            return Err(SecurityError::BlockedContent);
+            return Err(SecurityError::BlockedContent(Box::new(assessment.details)));
         }
 
         Ok(assessment)
@@ -211,6 +483,7 @@ impl SecurityClient {
     // * `content` - The text content to assess with PANW AI Runtime API
     // * `model_name` - Name of the AI model associated with this content
     // * `is_prompt` - If `true`, content is treated as a prompt to an AI; if `false`, as an AI response
+    // * `ctx` - Per-request attribution overriding the configured defaults
     //
     // # Returns
     //
@@ -230,6 +503,7 @@ impl SecurityClient {
         content: &str,
         model_name: &str,
         is_prompt: bool,
+        ctx: &ScanContext,
     ) -> Result<Assessment, SecurityError> {
         // Skip assessment for empty content early
         if content.trim().is_empty() {
@@ -237,17 +511,56 @@ impl SecurityClient {
             return Ok(self.create_safe_assessment());
         }
 
-        // Create the content object
-        let content_obj = self.prepare_content(content, is_prompt)?;
+        // Create the content objects
+        let contents = self.prepare_contents(content, is_prompt)?;
 
         // Create and send the request payload
-        let payload = self.create_scan_request(content_obj, model_name);
-        let scan_result = self.send_security_request(&payload).await?;
+        let payload = self.create_scan_request(contents, model_name, ctx);
+        let scan_result = match self.send_security_request(&payload).await {
+            Err(SecurityError::CircuitOpen(_)) if self.fail_open => {
+                warn!("PANW circuit open, allowing content without assessment (fail-open)");
+                return Ok(Assessment {
+                    unscanned: true,
+                    ..self.create_safe_assessment()
+                });
+            }
+            result => result?,
+        };
 
         // Process results into an assessment
         self.process_scan_result(scan_result)
     }
 
+    // Scans a prompt and/or response as one exchange and returns the raw verdict.
+    //
+    // Unlike `assess_content`, a `block` action is returned as a result rather than
+    // an error, and content is sent as is without applying the size limit. Used by
+    // the `check` command to tune profiles.
+    //
+    // # Arguments
+    //
+    // * `prompt` - The prompt text to scan, if any
+    // * `response` - The response text to scan, if any
+    // * `model_name` - Name of the AI model reported in the scan metadata
+    // * `ctx` - Per-request attribution overriding the configured defaults
+    //
+    // # Returns
+    //
+    // * `Ok(ScanResponse)` - The verdict and findings of the PANW AI Runtime API
+    // * `Err(SecurityError)` - If neither text is given or the API call fails
+    pub async fn scan_exchange(
+        &self,
+        prompt: Option<&str>,
+        response: Option<&str>,
+        model_name: &str,
+        ctx: &ScanContext,
+    ) -> Result<ScanResponse, SecurityError> {
+        let content = Content::new(prompt.map(String::from), response.map(String::from))
+            .map_err(|e| SecurityError::AssessmentError(e.to_string()))?;
+        let payload = self.create_scan_request(vec![content], model_name, ctx);
+        self.send_security_request(&payload).await
+    }
+
     // Creates a scan request payload for the PANW AI Runtime API.
     //
     // This internal helper function constructs a properly formatted request object
@@ -255,24 +568,42 @@ impl SecurityClient {
     //
     // # Arguments
     //
-    // * `content_obj` - Content object containing prompt or response text to assess
+    // * `contents` - Content objects containing prompt or response text to assess
     // * `model_name` - Name of the AI model associated with this content
+    // * `ctx` - Per-request attribution overriding the configured defaults
     //
     // # Returns
     //
     // A `ScanRequest` object ready to be serialized and sent to the PANW AI Runtime API.
-    fn create_scan_request(&self, content_obj: Content, model_name: &str) -> ScanRequest {
+    fn create_scan_request(
+        &self,
+        contents: Vec<Content>,
+        model_name: &str,
+        ctx: &ScanContext,
+    ) -> ScanRequest {
         ScanRequest {
-            tr_id: Uuid::new_v4().to_string(),
+            tr_id: ctx
+                .request_id
+                .clone()
+                .unwrap_or_else(|| Uuid::new_v4().to_string()),
             ai_profile: AiProfile {
-                profile_name: self.profile_name.clone(),
+                profile_name: ctx
+                    .profile_name
+                    .clone()
+                    .unwrap_or_else(|| self.profile_name()),
             },
             metadata: Metadata {
-                app_name: self.app_name.to_string(),
-                app_user: self.app_user.to_string(),
+                app_name: ctx
+                    .app_name
+                    .clone()
+                    .unwrap_or_else(|| self.app_name.to_string()),
+                app_user: ctx
+                    .app_user
+                    .clone()
+                    .unwrap_or_else(|| self.app_user.to_string()),
                 ai_model: model_name.to_string(),
             },
-            contents: vec![content_obj],
+            contents,
         }
     }
 
@@ -293,23 +624,24 @@ impl SecurityClient {
         &self,
         payload: &ScanRequest,
     ) -> Result<(reqwest::StatusCode, String), SecurityError> {
+        let api_key = self.api_key.read().unwrap().clone();
         let response = self
             .client
-            .post(&format!("{}/v1/scan/sync/request", self.base_url))
+            .post(format!("{}{}", self.base_url, self.scan_path))
             .header("Content-Type", "application/json")
-            .header("x-pan-token", &self.api_key) // PANW specific authentication header
+            .header("x-pan-token", &api_key) // PANW specific authentication header
             .json(payload)
             .send()
             .await
             .map_err(|e| {
                 error!("PANW security assessment request failed: {}", e);
-                SecurityError::RequestError(e)
+                SecurityError::from(e)
             })?;
 
         let status = response.status();
         let body_text = response.text().await.map_err(|e| {
             error!("Failed to read PANW response body: {}", e);
-            SecurityError::RequestError(e)
+            SecurityError::from(e)
         })?;
 
         Ok((status, body_text))
@@ -335,9 +667,10 @@ impl SecurityClient {
         body_text: String,
     ) -> Result<ScanResponse, SecurityError> {
         // Debug the raw response only when debug is enabled
-        debug!("Raw PANW response body:\n{}", body_text);
+        debug!("Raw PANW response body:\n{}", privacy::redact(&body_text));
 
         if !status.is_success() {
+            let body_text = privacy::redact(&body_text);
             error!("PANW security assessment error: {} - {}", status, body_text);
             return Err(SecurityError::AssessmentError(format!(
                 "{}: {}",
@@ -351,10 +684,53 @@ impl SecurityClient {
         })
     }
 
+    // Waits for a free scan slot within the configured queue timeout.
+    async fn acquire_scan_permit(&self) -> Result<SemaphorePermit<'_>, SecurityError> {
+        tokio::time::timeout(self.queue_timeout, self.scan_permits.acquire())
+            .await
+            .map_err(|_| {
+                warn!("Timed out waiting for a PANW scan slot");
+                SecurityError::QueueTimeout
+            })?
+            .map_err(|_| SecurityError::AssessmentError("Scan limiter closed".to_string()))
+    }
+
+    // Determines whether a failed attempt should be retried under the retry policy.
+    fn is_retryable(&self, outcome: &Result<(reqwest::StatusCode, String), SecurityError>) -> bool {
+        match outcome {
+            Ok((status, _)) => self.retry.retry_on.contains(&status.as_u16()),
+            Err(SecurityError::RequestError(e)) => {
+                self.retry.retry_on_network_errors && (e.is_connect() || e.is_request())
+            }
+            Err(SecurityError::Timeout) => self.retry.retry_on_network_errors,
+            Err(_) => false,
+        }
+    }
+
+    // Computes the exponential backoff delay before the next attempt.
+    //
+    // With jitter enabled the delay is picked uniformly between half and the full
+    // backoff value, so clients retrying together don't hit PANW in lockstep.
+    fn backoff_delay(&self, attempt: u32) -> Duration {
+        let exponential = self
+            .retry
+            .base_delay_ms
+            .saturating_mul(2u64.saturating_pow(attempt.saturating_sub(1)));
+        let delay_ms = exponential.min(self.retry.max_delay_ms);
+
+        if self.retry.jitter && delay_ms > 1 {
+            Duration::from_millis(rand::thread_rng().gen_range(delay_ms / 2..=delay_ms))
+        } else {
+            Duration::from_millis(delay_ms)
+        }
+    }
+
     // Sends a security assessment request to the PANW AI Runtime API endpoint and processes the response.
     //
     // This internal helper function coordinates the HTTP communication with the Palo Alto Networks
     // security service by calling the appropriate sub-functions for request and response handling.
+    // Transient failures (network errors and the configured status codes) are retried with
+    // exponential backoff up to the configured number of attempts.
     //
     // # Arguments
     //
@@ -369,11 +745,152 @@ impl SecurityClient {
     //
     // May return errors for network failures, non-200 status codes,
     // or issues parsing the JSON response from the PANW service.
+    // Returns `SecurityError::QueueTimeout` if no scan slot frees up in time, and
+    // `SecurityError::CircuitOpen` with the remaining cooldown while the circuit
+    // breaker is open.
     async fn send_security_request(
         &self,
         payload: &ScanRequest,
     ) -> Result<ScanResponse, SecurityError> {
-        let (status, body_text) = self.make_api_request(payload).await?;
-        self.parse_api_response(status, body_text)
+        let Some(breaker_permit) = self.breaker.try_acquire() else {
+            debug!("PANW circuit open, short-circuiting scan");
+            return Err(SecurityError::CircuitOpen(self.breaker.retry_after()));
+        };
+
+        let started = Instant::now();
+        let result = self.send_with_retry(payload, breaker_permit).await;
+        if let Some(metrics) = &self.metrics {
+            metrics.record_panw_call(started.elapsed(), result.is_ok());
+        }
+        result
+    }
+
+    // Runs the scan request, retrying transient failures per the retry policy.
+    //
+    // Only outages of the API count as circuit breaker failures: transport errors,
+    // timeouts and 5xx statuses. A queue timeout leaves the breaker untouched, and
+    // any other answer, including a 4xx status, shows the API is reachable.
+    async fn send_with_retry(
+        &self,
+        payload: &ScanRequest,
+        breaker_permit: BreakerPermit,
+    ) -> Result<ScanResponse, SecurityError> {
+        let mut attempt = 1;
+        loop {
+            let outcome = {
+                let _permit = match self.acquire_scan_permit().await {
+                    Ok(permit) => permit,
+                    Err(e) => {
+                        breaker_permit.release();
+                        return Err(e);
+                    }
+                };
+                self.make_api_request(payload).await
+            };
+
+            if attempt >= self.retry.max_attempts || !self.is_retryable(&outcome) {
+                let outage = match &outcome {
+                    Ok((status, _)) => status.is_server_error(),
+                    Err(SecurityError::RequestError(_) | SecurityError::Timeout) => true,
+                    Err(_) => false,
+                };
+                if outage {
+                    breaker_permit.failure();
+                } else {
+                    breaker_permit.success();
+                }
+                let (status, body_text) = outcome?;
+                return self.parse_api_response(status, body_text);
+            }
+
+            let delay = self.backoff_delay(attempt);
+            warn!(
+                "Transient PANW failure on attempt {}/{}, retrying in {:?}",
+                attempt, self.retry.max_attempts, delay
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+// Joins the beginning and the end of text with a separator, in at most `max_bytes`.
+fn head_and_tail(text: &str, max_bytes: usize) -> String {
+    let budget = max_bytes.saturating_sub(HEAD_TAIL_SEPARATOR.len());
+    let head = &text[..floor_char_boundary(text, budget / 2)];
+    let tail_start = ceil_char_boundary(text, text.len() - (budget - head.len()));
+    format!("{}{}{}", head, HEAD_TAIL_SEPARATOR, &text[tail_start..])
+}
+
+// Splits text into pieces of at most `max_bytes`, respecting UTF-8 boundaries.
+fn split_chunks(text: &str, max_bytes: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        let mut end = floor_char_boundary(rest, max_bytes);
+        if end == 0 {
+            // A single character wider than the limit
+            end = ceil_char_boundary(rest, 1);
+        }
+        chunks.push(rest[..end].to_string());
+        rest = &rest[end..];
+    }
+    chunks
+}
+
+// Largest char boundary of `text` not above `index`.
+fn floor_char_boundary(text: &str, index: usize) -> usize {
+    let mut index = index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+// Smallest char boundary of `text` not below `index`.
+fn ceil_char_boundary(text: &str, index: usize) -> usize {
+    let mut index = index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index += 1;
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn head_and_tail_fits_the_budget() {
+        let text = "é".repeat(500);
+        for max_bytes in [10, 11, 64, 257] {
+            let joined = head_and_tail(&text, max_bytes);
+            assert!(
+                joined.len() <= max_bytes,
+                "{} > {}",
+                joined.len(),
+                max_bytes
+            );
+            assert!(joined.contains(HEAD_TAIL_SEPARATOR));
+        }
+    }
+
+    #[test]
+    fn head_and_tail_keeps_both_ends() {
+        let text = format!("start{}end", "x".repeat(1000));
+        let joined = head_and_tail(&text, 100);
+        assert!(joined.starts_with("start"));
+        assert!(joined.ends_with("end"));
+    }
+
+    #[test]
+    fn prepare_contents_head_tail_yields_one_piece() {
+        let client = SecurityClient::new("http://localhost", "key", "profile", "app", "user")
+            .with_content_limit(ContentLimitConfig {
+                max_bytes: 100,
+                strategy: OversizeStrategy::HeadTail,
+            });
+        let contents = client.prepare_contents(&"a".repeat(1000), true).unwrap();
+        assert_eq!(contents.len(), 1);
     }
 }
@@ -0,0 +1,149 @@
+use axum::http::HeaderMap;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+use crate::budget::TokenBudget;
+use crate::config::SessionScanConfig;
+use crate::security::ScanContext;
+
+// Separator placed between turns of the combined window sent to the scanner.
+const TURN_SEPARATOR: &str = "\n\n";
+
+#[derive(Debug)]
+struct Session {
+    turns: VecDeque<String>,
+    unscanned: usize,
+    last_seen: Instant,
+}
+
+// Recent user turns per conversation, scanned together at intervals.
+//
+// Multi-turn jailbreaks spread an attack over messages that each look benign;
+// scanning the combined window of the last turns catches them. Conversations
+// are identified by the session header or, for chat, by a hash of the caller,
+// the model and the first user message. Cheap to clone; all clones share the
+// same sessions.
+#[derive(Debug, Clone)]
+pub struct SessionStore {
+    header: String,
+    window_turns: usize,
+    scan_every_turns: usize,
+    idle_timeout: Duration,
+    max_sessions: usize,
+    sessions: Arc<Mutex<HashMap<String, Session>>>,
+}
+
+impl SessionStore {
+    pub fn new(config: &SessionScanConfig) -> Self {
+        Self {
+            header: config.header.to_ascii_lowercase(),
+            window_turns: config.window_turns,
+            scan_every_turns: config.scan_every_turns,
+            idle_timeout: Duration::from_secs(config.idle_timeout_secs),
+            max_sessions: config.max_sessions,
+            sessions: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    // Identifies the conversation a request belongs to.
+    //
+    // # Arguments
+    //
+    // * `headers` - Request headers, checked for the session header
+    // * `ctx` - Caller identity, so sessions of different users never mix
+    // * `model` - The requested model
+    // * `first_turn` - First user message of a chat, used when no header is sent
+    //
+    // # Returns
+    //
+    // The session key, or `None` if the request cannot be tied to a conversation
+    pub fn session_key(
+        &self,
+        headers: &HeaderMap,
+        ctx: &ScanContext,
+        model: &str,
+        first_turn: Option<&str>,
+    ) -> Option<String> {
+        let user = TokenBudget::user_key(ctx);
+        let session = headers
+            .get(&self.header)
+            .and_then(|value| value.to_str().ok())
+            .filter(|value| !value.is_empty());
+        let mut hasher = Sha256::new();
+        hasher.update(&user);
+        hasher.update([0]);
+        match (session, first_turn) {
+            (Some(session), _) => hasher.update(session),
+            (None, Some(first_turn)) => {
+                hasher.update(model);
+                hasher.update([0]);
+                hasher.update(first_turn);
+            }
+            (None, None) => return None,
+        }
+        Some(
+            hasher
+                .finalize()
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect(),
+        )
+    }
+
+    // Adds a user turn to a session.
+    //
+    // A turn identical to the previous one, e.g. a retried request, is not added again.
+    //
+    // # Returns
+    //
+    // The combined window of recent turns when a scan is due, `None` otherwise
+    pub fn record_turn(&self, key: &str, turn: &str) -> Option<String> {
+        if turn.is_empty() {
+            return None;
+        }
+        let mut sessions = self.sessions.lock().unwrap();
+        if !sessions.contains_key(key) && sessions.len() >= self.max_sessions {
+            sessions.retain(|_, session| session.last_seen.elapsed() < self.idle_timeout);
+            if sessions.len() >= self.max_sessions {
+                debug!("Session store is full, not tracking a new conversation");
+                return None;
+            }
+        }
+
+        let session = sessions.entry(key.to_string()).or_insert_with(|| Session {
+            turns: VecDeque::new(),
+            unscanned: 0,
+            last_seen: Instant::now(),
+        });
+        if session.last_seen.elapsed() >= self.idle_timeout {
+            session.turns.clear();
+            session.unscanned = 0;
+        }
+        session.last_seen = Instant::now();
+        if session.turns.back().is_some_and(|last| last == turn) {
+            return None;
+        }
+        session.turns.push_back(turn.to_string());
+        while session.turns.len() > self.window_turns {
+            session.turns.pop_front();
+        }
+        session.unscanned += 1;
+
+        // A single turn was already scanned on its own
+        if session.unscanned < self.scan_every_turns || session.turns.len() < 2 {
+            return None;
+        }
+        session.unscanned = 0;
+        Some(
+            session
+                .turns
+                .iter()
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+                .join(TURN_SEPARATOR),
+        )
+    }
+}
@@ -0,0 +1,66 @@
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+// Extra time given to connections after active streams were told to terminate.
+const FORCED_EXIT_GRACE: Duration = Duration::from_secs(5);
+
+// Waits for Ctrl+C or SIGTERM.
+async fn wait_for_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+// Resolves once a shutdown signal is received, starting the drain period.
+//
+// The server stops accepting connections as soon as this future completes, while
+// in-flight requests keep running. If they are still active after `drain_timeout`,
+// `stream_abort` is cancelled so open streams end with a final NDJSON error object.
+// If connections still linger shortly after, `give_up` is cancelled and the caller
+// decides how to stop the server.
+//
+// # Arguments
+//
+// * `stream_abort` - Token observed by streaming responses
+// * `drain_timeout` - How long in-flight requests may run after the signal
+// * `give_up` - Token cancelled once the drain and the grace period are both over
+pub async fn shutdown_signal(
+    stream_abort: CancellationToken,
+    drain_timeout: Duration,
+    give_up: CancellationToken,
+) {
+    wait_for_signal().await;
+    info!(
+        "Shutdown signal received, draining in-flight requests for up to {:?}",
+        drain_timeout
+    );
+
+    tokio::spawn(async move {
+        tokio::time::sleep(drain_timeout).await;
+        warn!("Drain timeout elapsed, terminating active streams");
+        stream_abort.cancel();
+
+        tokio::time::sleep(FORCED_EXIT_GRACE).await;
+        warn!("Requests still in flight after drain timeout, giving up on them");
+        give_up.cancel();
+    });
+}
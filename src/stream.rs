@@ -1,12 +1,20 @@
-use crate::security::{Assessment, SecurityClient};
+use crate::config::StreamingConfig;
+use crate::privacy;
+use crate::scanner::Scanner;
+use crate::security::{Assessment, ScanContext};
 use crate::types::{PromptDetected, ResponseDetected, ScanResponse};
 use bytes::Bytes;
+use futures_util::stream::{self, BoxStream, StreamExt};
 use futures_util::Stream;
 use serde::{de::DeserializeOwned, Serialize};
+use std::marker::PhantomData;
 use std::pin::Pin;
+use std::sync::Arc;
 use std::task::{Context, Poll};
+use std::time::Duration;
 use thiserror::Error;
-use tracing::{debug, error};
+use tokio::sync::mpsc;
+use tracing::{debug, error, info};
 
 #[derive(Debug, Error)]
 pub enum StreamError {
@@ -19,56 +27,271 @@ pub enum StreamError {
     #[error("Security issue detected")]
     SecurityIssue,
 
+    #[error("Response violates security policy. {0}")]
+    ResponseBlocked(String),
+
+    #[error("Hook error: {0}")]
+    HookError(#[from] crate::hooks::HookError),
+
     #[error("Unknown error")]
     Unknown,
 }
 
-pub struct SecurityAssessedStream<S, T>
-where
-    S: Stream<Item = Result<Bytes, reqwest::Error>>,
-    T: DeserializeOwned + SecurityAssessable + Serialize + Send + Sync + 'static,
-{
-    inner: Pin<Box<S>>,
-    security_client: SecurityClient,
-    model_name: String,
-    buffer: Option<T>,
-    error: Option<StreamError>,
+// Chat or generate stream whose chunks are security-assessed before being forwarded.
+//
+// A reader task pulls chunks from Ollama into a bounded channel; the stream
+// scans them with at most `max_scans_in_flight` concurrent assessments and
+// yields them in order. When the client reads slowly or scans lag behind, the
+// channel fills up and the reader stops polling Ollama, so memory stays bounded
+// and backpressure reaches the upstream connection. The stream ends after the
+// first chunk that is blocked or cannot be assessed.
+pub struct SecurityAssessedStream<T> {
+    inner: BoxStream<'static, Result<Bytes, StreamError>>,
     finished: bool,
+    _chunk: PhantomData<fn() -> T>,
 }
 
 pub trait SecurityAssessable {
     fn get_content_for_assessment(&self) -> Option<(&str, &str)>;
+
+    // Replaces the assessed content, when a policy engine masks it.
+    fn replace_content(&mut self, content: String);
+
+    // Returns `true` for the final chunk of a stream.
+    fn is_done(&self) -> bool;
+
+    // Builds an empty, non-final chunk of `model`, sent as an NDJSON keep-alive.
+    //
+    // Unlike a blank line it parses as a regular chunk, so line-by-line JSON
+    // clients such as ollama-python and ollama-js accept it.
+    fn keep_alive(model: &str) -> Self
+    where
+        Self: Sized;
+}
+
+// Sends a frame built by `frame` whenever `stream` yields nothing for `interval`.
+//
+// Keeps clients and idle-timeout proxies from dropping a stream while chunks
+// are held back by slow scans or a slow model. A zero interval disables it.
+//
+// # Arguments
+//
+// * `stream` - The response body stream
+// * `interval` - Idle time after which a keep-alive frame is sent
+// * `frame` - Builds the keep-alive frame, ignored by clients of the stream's format
+pub fn with_keep_alive<S, E, F>(
+    stream: S,
+    interval: Duration,
+    frame: F,
+) -> BoxStream<'static, Result<Bytes, E>>
+where
+    S: Stream<Item = Result<Bytes, E>> + Send + 'static,
+    E: Send + 'static,
+    F: Fn() -> Bytes + Send + Sync + 'static,
+{
+    let frame = Arc::new(frame);
+    if interval.is_zero() {
+        return stream.boxed();
+    }
+    stream::unfold(Box::pin(stream), move |mut stream| {
+        let frame = frame.clone();
+        async move {
+            match tokio::time::timeout(interval, stream.next()).await {
+                Ok(Some(item)) => Some((item, stream)),
+                Ok(None) => None,
+                Err(_) => {
+                    debug!("Stream idle for {:?}, sending keep-alive", interval);
+                    Some((Ok(frame()), stream))
+                }
+            }
+        }
+    })
+    .boxed()
+}
+
+// Chunk that passed its own scan, with the text it adds to the response.
+struct AssessedChunk {
+    bytes: Bytes,
+    content: String,
+    done: bool,
+}
+
+// Scans the complete text of a streamed response once its final chunk arrives.
+//
+// A blocked verdict ends the stream with an error object in place of the final
+// chunk; the incident is counted, quarantined and audited like any other block.
+async fn verify_full_response(
+    scanner: &dyn Scanner,
+    model_name: &str,
+    context: &ScanContext,
+    full_response: &str,
+) -> Result<(), StreamError> {
+    if full_response.is_empty() {
+        return Ok(());
+    }
+    debug!(
+        "Verifying the full streamed response ({} bytes)",
+        full_response.len()
+    );
+    let assessment = scanner
+        .assess(full_response, model_name, false, context)
+        .await?;
+    // A mask cannot be applied to chunks already sent, so it blocks the response
+    if !assessment.is_safe || assessment.masked_content.is_some() {
+        info!(
+            target: "audit",
+            request_id = context.request_id.as_deref().unwrap_or_default(),
+            model = model_name,
+            category = %assessment.category,
+            action = %assessment.action,
+            "Full streamed response blocked at end of stream"
+        );
+        return Err(StreamError::ResponseBlocked(format!(
+            "Category: {}, Action: {}",
+            assessment.category, assessment.action
+        )));
+    }
+    Ok(())
 }
 
-impl<S, T> SecurityAssessedStream<S, T>
+impl<T> SecurityAssessedStream<T>
 where
-    S: Stream<Item = Result<Bytes, reqwest::Error>>,
     T: DeserializeOwned + SecurityAssessable + Serialize + Send + Sync + 'static,
 {
-    pub fn new(stream: S, security_client: SecurityClient, model_name: String) -> Self {
+    // Starts reading the upstream stream into the bounded buffer.
+    //
+    // # Arguments
+    //
+    // * `stream` - Chunks received from Ollama
+    // * `scanner` - Scanner assessing the content of each chunk
+    // * `model_name` - Model reported in the scan metadata
+    // * `context` - Caller and request details for the scans
+    // * `config` - Buffer size and scan concurrency
+    pub fn new<S>(
+        stream: S,
+        scanner: Arc<dyn Scanner>,
+        model_name: String,
+        context: ScanContext,
+        config: &StreamingConfig,
+    ) -> Self
+    where
+        S: Stream<Item = Result<Bytes, reqwest::Error>> + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel(config.buffer_chunks.max(1));
+        tokio::spawn(async move {
+            let mut stream = Box::pin(stream);
+            while let Some(item) = stream.next().await {
+                // The receiver is gone once the client disconnects; drop the upstream
+                if sender.send(item).await.is_err() {
+                    debug!("Stream consumer dropped, closing upstream");
+                    break;
+                }
+            }
+        });
+
+        let received = stream::unfold(receiver, |mut receiver| async move {
+            receiver.recv().await.map(|item| (item, receiver))
+        });
+        let final_scanner = scanner.clone();
+        let final_model_name = model_name.clone();
+        let final_context = context.clone();
+        let assessed = received
+            .map(move |item| {
+                let scanner = scanner.clone();
+                let model_name = model_name.clone();
+                let context = context.clone();
+                async move {
+                    let mut bytes = item.map_err(|e| {
+                        error!("Error in stream: {}", e);
+                        StreamError::Unknown
+                    })?;
+                    let mut chunk = serde_json::from_slice::<T>(&bytes).map_err(|e| {
+                        error!(
+                            "Failed to parse JSON in stream: {}",
+                            privacy::redact_error(&e)
+                        );
+                        StreamError::JsonError(e)
+                    })?;
+                    let done = chunk.is_done();
+                    let mut content = chunk
+                        .get_content_for_assessment()
+                        .map(|(content, _)| content.to_string())
+                        .unwrap_or_default();
+                    let mut assessment =
+                        Self::assess_content(scanner.as_ref(), &model_name, &context, &chunk)
+                            .await?;
+                    if let Some(masked) = assessment.masked_content.take() {
+                        debug!("Streaming chunk masked by the policy engine");
+                        let newline = bytes.ends_with(b"\n");
+                        chunk.replace_content(masked.clone());
+                        let mut masked_bytes = serde_json::to_vec(&chunk)?;
+                        if newline {
+                            masked_bytes.push(b'\n');
+                        }
+                        bytes = Bytes::from(masked_bytes);
+                        content = masked;
+                    }
+                    Ok::<AssessedChunk, StreamError>(AssessedChunk {
+                        bytes,
+                        content,
+                        done,
+                    })
+                }
+            })
+            .buffered(config.max_scans_in_flight.max(1));
+
+        // Chunks passing one by one can still add up to a blocked response, so the
+        // full text is scanned again before the final chunk is released
+        let mut response_text = String::new();
+        let inner = assessed
+            .then(move |result| {
+                let full_response = match &result {
+                    Ok(chunk) => {
+                        response_text.push_str(&chunk.content);
+                        chunk.done.then(|| std::mem::take(&mut response_text))
+                    }
+                    Err(_) => None,
+                };
+                let scanner = final_scanner.clone();
+                let model_name = final_model_name.clone();
+                let context = final_context.clone();
+                async move {
+                    let chunk = result?;
+                    if let Some(full_response) = full_response {
+                        verify_full_response(
+                            scanner.as_ref(),
+                            &model_name,
+                            &context,
+                            &full_response,
+                        )
+                        .await?;
+                    }
+                    Ok(chunk.bytes)
+                }
+            })
+            .boxed();
+
         Self {
-            inner: Box::pin(stream),
-            security_client,
-            model_name,
-            buffer: None,
-            error: None,
+            inner,
             finished: false,
+            _chunk: PhantomData,
         }
     }
 
     // Static method to assess content
     async fn assess_content(
-        security_client: &SecurityClient,
+        scanner: &dyn Scanner,
         model_name: &str,
-        chunk: T,
+        context: &ScanContext,
+        chunk: &T,
     ) -> Result<Assessment, StreamError> {
         if let Some((content, content_type)) = chunk.get_content_for_assessment() {
             if !content.is_empty() {
                 debug!("Assessing streaming content of type: {}", content_type);
                 // Determine if this is a prompt or response based on content_type
                 let is_prompt = content_type.contains("prompt");
-                let assessment = security_client
-                    .assess_content(content, model_name, is_prompt)
+                let assessment = scanner
+                    .assess(content, model_name, is_prompt, context)
                     .await?;
                 if !assessment.is_safe {
                     error!(
@@ -111,84 +334,38 @@ where
                 created_at: None,
                 completed_at: None,
             },
+            masked_content: None,
+            unscanned: false,
         })
     }
 }
 
-impl<S, T> Stream for SecurityAssessedStream<S, T>
-where
-    S: Stream<Item = Result<Bytes, reqwest::Error>> + Unpin,
-    T: DeserializeOwned + SecurityAssessable + Serialize + Unpin + Send + Sync + 'static,
-{
+impl<T> Stream for SecurityAssessedStream<T> {
     type Item = Result<Bytes, StreamError>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        // Early return for finished state
         if self.finished {
             return Poll::Ready(None);
         }
 
-        // Handle pending errors first
-        if let Some(err) = self.error.take() {
-            self.finished = true;
-            return Poll::Ready(Some(Err(err)));
-        }
-
-        // Process buffered items before polling the inner stream
-        if let Some(item) = self.buffer.take() {
-            let json = match serde_json::to_vec(&item) {
-                Ok(json) => json,
-                Err(e) => return Poll::Ready(Some(Err(StreamError::JsonError(e)))),
-            };
-            return Poll::Ready(Some(Ok(Bytes::from(json))));
-        }
-
         match self.inner.as_mut().poll_next(cx) {
-            Poll::Ready(Some(Ok(bytes))) => {
-                match serde_json::from_slice::<T>(&bytes) {
-                    Ok(chunk) => {
-                        // Clone bytes before moving to async task
-                        let bytes_clone = bytes.clone();
-
-                        // We need to return to the executor to do the async assessment
-                        let this = self.get_mut();
-                        let security_client = this.security_client.clone();
-                        let model_name = this.model_name.clone();
-
-                        tokio::spawn(async move {
-                            // Use the static method to avoid type mismatch issues
-                            // Pass chunk by value instead of reference
-                            let result = match SecurityAssessedStream::<S, T>::assess_content(
-                                &security_client,
-                                &model_name,
-                                chunk,
-                            )
-                            .await
-                            {
-                                Ok(_) => Ok(bytes_clone),
-                                Err(e) => Err(e),
-                            };
-                            result
-                        });
-
-                        // Return the original bytes without waiting for assessment
-                        Poll::Ready(Some(Ok(bytes)))
-                    }
-                    Err(e) => {
-                        error!("Failed to parse JSON in stream: {}", e);
-                        Poll::Ready(Some(Err(StreamError::JsonError(e))))
-                    }
-                }
-            }
-            Poll::Ready(Some(Err(e))) => {
-                error!("Error in stream: {}", e);
-                Poll::Ready(Some(Err(StreamError::Unknown)))
+            Poll::Ready(Some(Err(e)))
+                if matches!(
+                    e,
+                    StreamError::SecurityIssue
+                        | StreamError::SecurityError(_)
+                        | StreamError::ResponseBlocked(_)
+                ) =>
+            {
+                self.finished = true;
+                Poll::Ready(Some(Err(e)))
             }
             Poll::Ready(None) => {
                 debug!("Stream ended");
+                self.finished = true;
                 Poll::Ready(None)
             }
-            Poll::Pending => Poll::Pending,
+            other => other,
         }
     }
 }
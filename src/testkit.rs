@@ -0,0 +1,114 @@
+use reqwest::{Client, Response};
+use serde_json::Value;
+
+use crate::config::{self, ConfigError};
+use crate::mock::{self, MockServer};
+use crate::{build_router, build_state, BoxError, Config};
+
+pub use crate::mock::{MockOllama, MockPanw, MockVerdict, ScannedContent};
+
+// Configuration of a test gateway before overrides; `{ollama}` and `{panw}` are
+// replaced by the stub URLs. Retries are off so failures surface at once, and
+// the credential check is off so only requests reach the PANW stub.
+const BASE_CONFIG: &str = r#"
+server:
+  host: "127.0.0.1"
+  port: 0
+ollama:
+  base_url: "{ollama}"
+  model_cache_ttl_secs: 0
+  health_check:
+    enabled: false
+security:
+  base_url: "{panw}"
+  api_key: "testkit-api-key"
+  profile_name: "testkit"
+  app_name: "testkit"
+  app_user: "testkit"
+  timeout_secs: 5
+  retry:
+    max_attempts: 1
+  credential_check:
+    enabled: false
+"#;
+
+// A gateway served on a local port, with stub Ollama and PANW upstreams.
+//
+// Script the stubs through `ollama` and `panw`, send requests with `post` or
+// `get`, then check what reached each stub with `ollama.requests()` and
+// `panw.scans()`. Everything stops when the gateway is dropped.
+pub struct TestGateway {
+    pub ollama: MockOllama,
+    pub panw: MockPanw,
+    server: MockServer,
+    client: Client,
+}
+
+impl TestGateway {
+    // Starts a gateway with the default test configuration.
+    pub async fn start() -> Result<Self, BoxError> {
+        Self::with_config("").await
+    }
+
+    // Starts a gateway with YAML overrides merged into the test configuration.
+    //
+    // # Arguments
+    //
+    // * `overrides` - YAML merged key by key, e.g. `"policy:\n  scan_images: true"`
+    //
+    // # Returns
+    //
+    // * `Ok(TestGateway)` - The running gateway and its stubs
+    // * `Err(BoxError)` - If the configuration is invalid or a server cannot start
+    pub async fn with_config(overrides: &str) -> Result<Self, BoxError> {
+        let ollama = MockOllama::start().await?;
+        let panw = MockPanw::start().await?;
+        let config = test_config(ollama.url(), panw.url(), overrides)?;
+        let state = build_state(&config).await?;
+        let server = mock::serve(build_router(state, &config)).await?;
+        Ok(Self {
+            ollama,
+            panw,
+            server,
+            client: Client::new(),
+        })
+    }
+
+    // Absolute URL of a gateway path.
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.server.url, path)
+    }
+
+    // Sends a JSON body to a gateway path.
+    pub async fn post(&self, path: &str, body: &Value) -> Result<Response, reqwest::Error> {
+        self.client.post(self.url(path)).json(body).send().await
+    }
+
+    // Sends a GET request to a gateway path.
+    pub async fn get(&self, path: &str) -> Result<Response, reqwest::Error> {
+        self.client.get(self.url(path)).send().await
+    }
+}
+
+// Builds the test configuration for the given stub URLs.
+//
+// # Arguments
+//
+// * `ollama_url` - Base URL of the Ollama upstream
+// * `panw_url` - Base URL of the PANW scan API
+// * `overrides` - YAML merged key by key over the test configuration
+pub fn test_config(
+    ollama_url: &str,
+    panw_url: &str,
+    overrides: &str,
+) -> Result<Config, ConfigError> {
+    let mut value: serde_yaml::Value = serde_yaml::from_str(
+        &BASE_CONFIG
+            .replace("{ollama}", ollama_url)
+            .replace("{panw}", panw_url),
+    )?;
+    if !overrides.trim().is_empty() {
+        config::merge_values(&mut value, serde_yaml::from_str(overrides)?);
+    }
+    config::config_from_value(value)
+}
@@ -0,0 +1,55 @@
+use crate::config::TlsConfig;
+use axum_server::tls_rustls::RustlsConfig;
+use std::fs;
+use std::io;
+use std::time::{Duration, SystemTime};
+use tracing::{error, info};
+
+// Loads the listener certificate chain and private key from PEM files.
+pub async fn load_rustls_config(tls: &TlsConfig) -> io::Result<RustlsConfig> {
+    RustlsConfig::from_pem_file(&tls.cert_path, &tls.key_path).await
+}
+
+// Returns the latest modification time of the certificate and key files.
+fn last_modified(tls: &TlsConfig) -> Option<SystemTime> {
+    let cert = fs::metadata(&tls.cert_path)
+        .and_then(|m| m.modified())
+        .ok()?;
+    let key = fs::metadata(&tls.key_path)
+        .and_then(|m| m.modified())
+        .ok()?;
+    Some(cert.max(key))
+}
+
+// Watches the certificate and key files and hot-reloads them when they change.
+//
+// Files are polled every `reload_interval_secs`, so renewed certificates (e.g. from
+// cert-manager or certbot) are picked up without restarting the proxy. A failed
+// reload keeps serving the previous certificate.
+pub fn spawn_cert_reloader(rustls_config: RustlsConfig, tls: TlsConfig) {
+    tokio::spawn(async move {
+        let mut interval =
+            tokio::time::interval(Duration::from_secs(tls.reload_interval_secs.max(1)));
+        let mut loaded_at = last_modified(&tls);
+
+        loop {
+            interval.tick().await;
+
+            let modified = last_modified(&tls);
+            if modified.is_none() || modified == loaded_at {
+                continue;
+            }
+
+            match rustls_config
+                .reload_from_pem_file(&tls.cert_path, &tls.key_path)
+                .await
+            {
+                Ok(()) => {
+                    info!("Reloaded TLS certificate from {}", tls.cert_path);
+                    loaded_at = modified;
+                }
+                Err(e) => error!("Failed to reload TLS certificate: {}", e),
+            }
+        }
+    });
+}
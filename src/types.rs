@@ -1,7 +1,7 @@
 use chrono::DateTime;
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{Map, Value};
 
 // Ollama API types
 
@@ -21,7 +21,9 @@ use serde_json::Value;
 // * `stream` - Optional flag to enable streaming responses
 // * `raw` - Optional flag to get raw, unfiltered model output
 // * `format` - Optional output format specification
-// * `options` - Optional model-specific parameters
+// * `options` - Optional model parameters such as temperature or context size
+// * `images` - Optional base64-encoded images for multimodal models
+// * `extra` - Fields not modeled here, passed through unchanged
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GenerateRequest {
     pub model: String,
@@ -37,9 +39,13 @@ pub struct GenerateRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub raw: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub format: Option<String>,
+    pub format: Option<Value>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub options: Option<Value>,
+    pub options: Option<ModelOptions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub images: Option<Vec<String>>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 // Response from an Ollama text generation request.
@@ -54,6 +60,14 @@ pub struct GenerateRequest {
 // * `response` - The generated text content
 // * `context` - Optional context tokens for continuing the conversation
 // * `done` - Indicates whether the generation is complete
+// * `done_reason` - Why generation stopped (e.g. "stop", "length"), final response only
+// * `total_duration` - Time spent on the whole request in nanoseconds, final response only
+// * `load_duration` - Time spent loading the model in nanoseconds
+// * `prompt_eval_count` - Number of tokens in the prompt
+// * `prompt_eval_duration` - Time spent evaluating the prompt in nanoseconds
+// * `eval_count` - Number of tokens generated
+// * `eval_duration` - Time spent generating in nanoseconds
+// * `extra` - Fields not modeled here, passed through unchanged
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GenerateResponse {
     pub model: String,
@@ -62,6 +76,22 @@ pub struct GenerateResponse {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub context: Option<Vec<u32>>,
     pub done: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub done_reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_duration: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub load_duration: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt_eval_count: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt_eval_duration: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub eval_count: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub eval_duration: Option<u64>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 // Request parameters for chat-based interactions with Ollama models.
@@ -75,7 +105,9 @@ pub struct GenerateResponse {
 // * `messages` - Array of conversation messages with roles and content
 // * `stream` - Optional flag to enable streaming responses
 // * `format` - Optional output format specification
-// * `options` - Optional model-specific parameters
+// * `options` - Optional model parameters such as temperature or context size
+// * `tools` - Optional function definitions the model may call
+// * `extra` - Fields not modeled here, passed through unchanged
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatRequest {
     pub model: String,
@@ -83,24 +115,39 @@ pub struct ChatRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub stream: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub format: Option<String>,
+    pub format: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub options: Option<ModelOptions>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub options: Option<Value>,
+    pub tools: Option<Vec<Value>>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 // Represents a single message in a chat conversation.
 //
 // Each message has a role (who is speaking) and content (what is said).
-// Common roles include "system", "user", and "assistant".
+// Common roles include "system", "user", "assistant" and "tool", the latter
+// carrying the result of a tool call back to the model.
 //
 // # Fields
 //
 // * `role` - Identifies the sender of the message (e.g., "user", "assistant")
 // * `content` - The actual text content of the message
+// * `images` - Optional base64-encoded images attached to the message
+// * `tool_calls` - Optional tool invocations requested by the model
+// * `extra` - Fields not modeled here, passed through unchanged
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Message {
     pub role: String,
+    #[serde(default)]
     pub content: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub images: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<Value>>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 // Response from an Ollama chat request.
@@ -113,12 +160,110 @@ pub struct Message {
 // * `created_at` - Timestamp when the response was created
 // * `message` - The model's response as a Message object
 // * `done` - Indicates whether the generation is complete
+// * `done_reason` - Why generation stopped (e.g. "stop", "length"), final response only
+// * `total_duration` - Time spent on the whole request in nanoseconds, final response only
+// * `load_duration` - Time spent loading the model in nanoseconds
+// * `prompt_eval_count` - Number of tokens in the prompt
+// * `prompt_eval_duration` - Time spent evaluating the prompt in nanoseconds
+// * `eval_count` - Number of tokens generated
+// * `eval_duration` - Time spent generating in nanoseconds
+// * `extra` - Fields not modeled here, passed through unchanged
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatResponse {
     pub model: String,
     pub created_at: String,
     pub message: Message,
     pub done: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub done_reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_duration: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub load_duration: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt_eval_count: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt_eval_duration: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub eval_count: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub eval_duration: Option<u64>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+// Model parameters accepted in the `options` field of Ollama requests.
+//
+// Only the commonly used parameters are typed; anything else is kept in `extra`
+// and forwarded unchanged.
+//
+// # Fields
+//
+// * `temperature` - Sampling temperature, higher is more creative
+// * `top_p` - Nucleus sampling probability mass
+// * `top_k` - Number of most likely tokens considered at each step
+// * `num_ctx` - Size of the context window in tokens
+// * `num_predict` - Maximum number of tokens to generate (-1 for unlimited, -2 to fill the context)
+// * `repeat_penalty` - Penalty applied to repeated tokens
+// * `seed` - Random seed for reproducible output
+// * `stop` - Sequences that end generation when produced
+// * `extra` - Fields not modeled here, passed through unchanged
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModelOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_ctx: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_predict: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repeat_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl ModelOptions {
+    // Checks that the parameters are within the ranges Ollama accepts.
+    //
+    // # Returns
+    //
+    // * `Ok(())` - If all set parameters are valid
+    // * `Err(String)` - A description of the first invalid parameter
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(temperature) = self.temperature {
+            if !(0.0..=2.0).contains(&temperature) {
+                return Err(format!(
+                    "temperature must be between 0 and 2, got {}",
+                    temperature
+                ));
+            }
+        }
+        if let Some(top_p) = self.top_p {
+            if !(0.0..=1.0).contains(&top_p) {
+                return Err(format!("top_p must be between 0 and 1, got {}", top_p));
+            }
+        }
+        if self.num_ctx == Some(0) {
+            return Err("num_ctx must be greater than 0".to_string());
+        }
+        if let Some(num_predict) = self.num_predict {
+            if num_predict < -2 {
+                return Err(format!(
+                    "num_predict must be -2, -1 or positive, got {}",
+                    num_predict
+                ));
+            }
+        }
+        Ok(())
+    }
 }
 
 // Request parameters for generating text embeddings with Ollama models.
@@ -130,13 +275,16 @@ pub struct ChatResponse {
 //
 // * `model` - Name of the Ollama embedding model to use
 // * `prompt` - The text to generate embeddings for
-// * `options` - Optional model-specific parameters
+// * `options` - Optional model parameters such as temperature or context size
+// * `extra` - Fields not modeled here, passed through unchanged
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmbeddingsRequest {
     pub model: String,
     pub prompt: String,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub options: Option<Value>,
+    pub options: Option<ModelOptions>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 // Response containing vector embeddings generated by an Ollama model.
@@ -144,9 +292,12 @@ pub struct EmbeddingsRequest {
 // # Fields
 //
 // * `embedding` - Vector of floating-point values representing the text embedding
+// * `extra` - Fields not modeled here, passed through unchanged
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmbeddingsResponse {
     pub embedding: Vec<f32>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 // Response containing a list of available models from the Ollama API.
@@ -154,9 +305,12 @@ pub struct EmbeddingsResponse {
 // # Fields
 //
 // * `models` - Array of ModelInfo objects with details about each available model
+// * `extra` - Fields not modeled here, passed through unchanged
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ListModelsResponse {
     pub models: Vec<ModelInfo>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 // Detailed information about a specific Ollama model.
@@ -170,6 +324,7 @@ pub struct ListModelsResponse {
 // * `size` - Size of the model in bytes
 // * `digest` - Unique hash identifying this version of the model
 // * `details` - Additional technical specifications of the model
+// * `extra` - Fields not modeled here, passed through unchanged
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ModelInfo {
     pub name: String,
@@ -177,6 +332,8 @@ pub struct ModelInfo {
     pub size: u64,
     pub digest: String,
     pub details: ModelDetails,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 // Technical specifications of an Ollama model.
@@ -190,6 +347,7 @@ pub struct ModelInfo {
 // * `families` - All compatible model families
 // * `parameter_size` - Human-readable parameter count (e.g., "7B")
 // * `quantization_level` - Level of precision reduction applied (e.g., "Q4_0")
+// * `extra` - Fields not modeled here, passed through unchanged
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ModelDetails {
     pub format: String,
@@ -197,6 +355,8 @@ pub struct ModelDetails {
     pub families: Vec<String>,
     pub parameter_size: String,
     pub quantization_level: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 // Response containing the Ollama API version information.
@@ -204,9 +364,12 @@ pub struct ModelDetails {
 // # Fields
 //
 // * `version` - Version string of the Ollama API
+// * `extra` - Fields not modeled here, passed through unchanged
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VersionResponse {
     pub version: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 // Request payload for PANW AI Runtime security assessment.
@@ -253,6 +416,27 @@ impl ScanResponse {
             completed_at: None,
         }
     }
+
+    // Returns the names of all detections, e.g. `prompt.injection` or `response.dlp`.
+    pub fn detected_categories(&self) -> Vec<&'static str> {
+        let prompt = &self.prompt_detected;
+        let response = &self.response_detected;
+        [
+            (prompt.url_cats, "prompt.url_cats"),
+            (prompt.dlp, "prompt.dlp"),
+            (prompt.injection, "prompt.injection"),
+            (prompt.toxic_content, "prompt.toxic_content"),
+            (prompt.malicious_code, "prompt.malicious_code"),
+            (response.url_cats, "response.url_cats"),
+            (response.dlp, "response.dlp"),
+            (response.db_security, "response.db_security"),
+            (response.toxic_content, "response.toxic_content"),
+            (response.malicious_code, "response.malicious_code"),
+        ]
+        .into_iter()
+        .filter_map(|(detected, name)| detected.then_some(name))
+        .collect()
+    }
 }
 
 // AI security profile configuration for PANW security scans.
@@ -319,7 +503,7 @@ pub struct Content {
 // * `response_detected` - Security issues found in the response
 // * `created_at` - Optional timestamp when assessment was created
 // * `completed_at` - Optional timestamp when assessment was completed
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ScanResponse {
     #[serde(default)]
     pub report_id: String,
@@ -355,7 +539,7 @@ pub struct ScanResponse {
 // * `injection` - Whether prompt injection attempts were detected
 // * `toxic_content` - Whether toxic or harmful content was detected
 // * `malicious_code` - Whether malicious code was detected
-#[derive(Debug, Clone, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct PromptDetected {
     #[serde(default)]
     pub url_cats: bool,
@@ -381,7 +565,7 @@ pub struct PromptDetected {
 // * `db_security` - Whether database security issues were detected
 // * `toxic_content` - Whether toxic or harmful content was detected
 // * `malicious_code` - Whether malicious code was detected
-#[derive(Debug, Clone, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct ResponseDetected {
     #[serde(default)]
     pub url_cats: bool,